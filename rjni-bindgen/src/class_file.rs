@@ -0,0 +1,514 @@
+//
+//  Class Files
+//
+
+//! Reads the parts of a compiled `.class` file that bindings are generated
+//! from: its name and supertypes, and its fields and methods with their
+//! descriptors, generic signatures, constant values and parameter names.
+
+use std::convert::TryInto;
+
+
+/// The class is public.
+pub const ACC_PUBLIC: u16 = 0x0001;
+
+/// The member is static.
+pub const ACC_STATIC: u16 = 0x0008;
+
+/// The field is final.
+pub const ACC_FINAL: u16 = 0x0010;
+
+/// The method is a bridge generated by the compiler.
+pub const ACC_BRIDGE: u16 = 0x0040;
+
+/// The class is an interface.
+pub const ACC_INTERFACE: u16 = 0x0200;
+
+/// The class or method is abstract.
+pub const ACC_ABSTRACT: u16 = 0x0400;
+
+/// The class or member was generated by the compiler.
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+
+/// The class is an enum.
+pub const ACC_ENUM: u16 = 0x4000;
+
+/// The class is a module descriptor (`module-info.class`).
+pub const ACC_MODULE: u16 = 0x8000;
+
+/// A class read from a class file.
+#[derive(Debug, Clone)]
+pub struct ClassFile {
+	pub access: u16,
+
+	/// The internal name of the class (eg. `com/me/Test`).
+	pub name: String,
+
+	/// The internal name of the superclass, which only `java/lang/Object`
+	/// doesn't have.
+	pub superclass: Option<String>,
+
+	/// The internal names of the interfaces the class implements.
+	pub interfaces: Vec<String>,
+
+	pub fields: Vec<Member>,
+	pub methods: Vec<Member>,
+}
+
+/// A field or method of a class.
+#[derive(Debug, Clone)]
+pub struct Member {
+	pub access: u16,
+	pub name: String,
+
+	/// The JNI type descriptor (eg. `(ILjava/lang/String;)V`).
+	pub descriptor: String,
+
+	/// The generic signature from the `Signature` attribute, if the member's
+	/// type uses generics.
+	pub signature: Option<String>,
+
+	/// The value of a constant field, from its `ConstantValue` attribute.
+	pub constant: Option<Constant>,
+
+	/// The names of a method's parameters, when the class was compiled with
+	/// `-parameters` or `-g`.
+	pub parameters: Option<Vec<String>>,
+}
+
+impl Member {
+	/// Returns true if the member is public, and not generated by the
+	/// compiler.
+	pub fn is_visible(&self) -> bool {
+		self.access & ACC_PUBLIC != 0 && self.access & (ACC_SYNTHETIC | ACC_BRIDGE) == 0
+	}
+
+	/// Returns true if the member is static.
+	pub fn is_static(&self) -> bool {
+		self.access & ACC_STATIC != 0
+	}
+}
+
+/// The value of a constant field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+	Int(i32),
+	Long(i64),
+	Float(f32),
+	Double(f64),
+	Str(String),
+}
+
+/// An entry in a class file's constant pool.
+#[derive(Debug, Clone)]
+enum Entry {
+	/// The unused slot at index 0, or following a long or double.
+	Unused,
+	Utf8(String),
+	Int(i32),
+	Float(f32),
+	Long(i64),
+	Double(f64),
+
+	/// A class, by the index of its name.
+	Class(u16),
+
+	/// A string constant, by the index of its contents.
+	Str(u16),
+
+	/// A method or field reference, dynamic call site, or anything else that
+	/// bindings don't need.
+	Other,
+}
+
+/// Parses a class file.
+pub fn parse(data: &[u8]) -> Result<ClassFile, String> {
+	let mut reader = Reader {
+		data: data,
+		pos: 0,
+	};
+	if reader.u32()? != 0xcafebabe {
+		return Err(String::from("not a class file"));
+	}
+	reader.skip(4)?;
+	let pool = reader.constant_pool()?;
+	let pool = Pool(pool);
+
+	let access = reader.u16()?;
+	let name = pool.class(reader.u16()?)?;
+	let superclass = match reader.u16()? {
+		0 => None,
+		index => Some(pool.class(index)?),
+	};
+	let mut interfaces = Vec::new();
+	for _ in 0..reader.u16()? {
+		interfaces.push(pool.class(reader.u16()?)?);
+	}
+	let fields = reader.members(&pool, false)?;
+	let methods = reader.members(&pool, true)?;
+
+	Ok(ClassFile {
+		access: access,
+		name: name,
+		superclass: superclass,
+		interfaces: interfaces,
+		fields: fields,
+		methods: methods,
+	})
+}
+
+/// The constant pool, indexed from 1.
+struct Pool(Vec<Entry>);
+
+impl Pool {
+	fn get(&self, index: u16) -> Result<&Entry, String> {
+		self.0.get(index as usize).ok_or_else(|| format!("no constant {}", index))
+	}
+
+	fn utf8(&self, index: u16) -> Result<String, String> {
+		match self.get(index)? {
+			Entry::Utf8(text) => Ok(text.clone()),
+			_ => Err(format!("constant {} isn't a string", index)),
+		}
+	}
+
+	fn class(&self, index: u16) -> Result<String, String> {
+		match self.get(index)? {
+			Entry::Class(name) => self.utf8(*name),
+			_ => Err(format!("constant {} isn't a class", index)),
+		}
+	}
+
+	fn constant(&self, index: u16) -> Result<Constant, String> {
+		Ok(match self.get(index)? {
+			Entry::Int(value) => Constant::Int(*value),
+			Entry::Long(value) => Constant::Long(*value),
+			Entry::Float(value) => Constant::Float(*value),
+			Entry::Double(value) => Constant::Double(*value),
+			Entry::Str(text) => Constant::Str(self.utf8(*text)?),
+			_ => return Err(format!("constant {} isn't a constant value", index)),
+		})
+	}
+}
+
+/// Reads big endian values from a class file.
+struct Reader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+		match self.data.get(self.pos..self.pos + len) {
+			Some(bytes) => {
+				self.pos += len;
+				Ok(bytes)
+			},
+			None => Err(String::from("truncated class file")),
+		}
+	}
+
+	fn skip(&mut self, len: usize) -> Result<(), String> {
+		self.bytes(len).map(|_| ())
+	}
+
+	fn u8(&mut self) -> Result<u8, String> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, String> {
+		Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+	}
+
+	fn u32(&mut self) -> Result<u32, String> {
+		Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+	}
+
+	fn u64(&mut self) -> Result<u64, String> {
+		Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+	}
+
+	fn constant_pool(&mut self) -> Result<Vec<Entry>, String> {
+		let count = self.u16()? as usize;
+		let mut pool = vec![Entry::Unused];
+		while pool.len() < count {
+			let tag = self.u8()?;
+			let entry = match tag {
+				1 => {
+					let len = self.u16()? as usize;
+					Entry::Utf8(decode_utf8(self.bytes(len)?))
+				},
+				3 => Entry::Int(self.u32()? as i32),
+				4 => Entry::Float(f32::from_bits(self.u32()?)),
+				5 => Entry::Long(self.u64()? as i64),
+				6 => Entry::Double(f64::from_bits(self.u64()?)),
+				7 => Entry::Class(self.u16()?),
+				8 => Entry::Str(self.u16()?),
+
+				// Method handles
+				15 => {
+					self.skip(3)?;
+					Entry::Other
+				},
+
+				// Method types, modules and packages
+				16 | 19 | 20 => {
+					self.skip(2)?;
+					Entry::Other
+				},
+
+				// Field, method and interface method references, names and
+				// types, and dynamic constants and call sites
+				9 | 10 | 11 | 12 | 17 | 18 => {
+					self.skip(4)?;
+					Entry::Other
+				},
+				tag => return Err(format!("unknown constant pool tag {}", tag)),
+			};
+
+			// Longs and doubles take up two entries
+			let wide = tag == 5 || tag == 6;
+			pool.push(entry);
+			if wide {
+				pool.push(Entry::Unused);
+			}
+		}
+		Ok(pool)
+	}
+
+	fn members(&mut self, pool: &Pool, methods: bool) -> Result<Vec<Member>, String> {
+		let count = self.u16()?;
+		let mut members = Vec::with_capacity(count as usize);
+		for _ in 0..count {
+			let mut member = Member {
+				access: self.u16()?,
+				name: pool.utf8(self.u16()?)?,
+				descriptor: pool.utf8(self.u16()?)?,
+				signature: None,
+				constant: None,
+				parameters: None,
+			};
+
+			// Names from `MethodParameters` are preferred over the local
+			// variable table, which the parameters are found in by their slot
+			let mut locals = Vec::new();
+			for _ in 0..self.u16()? {
+				let name = pool.utf8(self.u16()?)?;
+				let len = self.u32()? as usize;
+				let mut attribute = Reader {
+					data: self.bytes(len)?,
+					pos: 0,
+				};
+				match name.as_str() {
+					"Signature" => member.signature = Some(pool.utf8(attribute.u16()?)?),
+					"ConstantValue" if !methods => {
+						member.constant = Some(pool.constant(attribute.u16()?)?);
+					},
+					"MethodParameters" if methods => {
+						let mut names = Vec::new();
+						for _ in 0..attribute.u8()? {
+							let name = match attribute.u16()? {
+								0 => String::new(),
+								index => pool.utf8(index)?,
+							};
+							attribute.skip(2)?;
+							names.push(name);
+						}
+						member.parameters = Some(names);
+					},
+					"Code" if methods => locals = attribute.local_variables(pool)?,
+					_ => {},
+				}
+			}
+			if member.parameters.is_none() && !locals.is_empty() {
+				member.parameters = parameters_from_locals(&member, &locals);
+			}
+			members.push(member);
+		}
+		Ok(members)
+	}
+
+	/// Reads the variables from the `LocalVariableTable` in a `Code`
+	/// attribute that are live at the start of the method, with their slots.
+	fn local_variables(&mut self, pool: &Pool) -> Result<Vec<(u16, String)>, String> {
+		self.skip(4)?;
+		let code_len = self.u32()? as usize;
+		self.skip(code_len)?;
+		let exceptions = self.u16()? as usize;
+		self.skip(exceptions * 8)?;
+
+		let mut locals = Vec::new();
+		for _ in 0..self.u16()? {
+			let name = pool.utf8(self.u16()?)?;
+			let len = self.u32()? as usize;
+			let mut attribute = Reader {
+				data: self.bytes(len)?,
+				pos: 0,
+			};
+			if name != "LocalVariableTable" {
+				continue;
+			}
+			for _ in 0..attribute.u16()? {
+				let start = attribute.u16()?;
+				attribute.skip(2)?;
+				let name = pool.utf8(attribute.u16()?)?;
+				attribute.skip(2)?;
+				let slot = attribute.u16()?;
+				if start == 0 {
+					locals.push((slot, name));
+				}
+			}
+		}
+		Ok(locals)
+	}
+}
+
+/// Finds a method's parameter names in its local variables, where they take
+/// up the first slots (after `this`, with longs and doubles taking two).
+fn parameters_from_locals(method: &Member, locals: &[(u16, String)]) -> Option<Vec<String>> {
+	let params = method.descriptor[1..].split(')').next().unwrap_or("");
+	let mut slot = if method.is_static() { 0 } else { 1 };
+	let mut names = Vec::new();
+	let mut chars = params.chars().peekable();
+	while let Some(c) = chars.next() {
+		// Skip the rest of an array or class type
+		let mut wide = c == 'J' || c == 'D';
+		let mut c = c;
+		while c == '[' {
+			wide = false;
+			c = chars.next()?;
+		}
+		if c == 'L' {
+			while chars.next()? != ';' {}
+		}
+
+		let name = locals.iter().find(|&&(local, _)| local == slot)?;
+		names.push(name.1.clone());
+		slot += if wide { 2 } else { 1 };
+	}
+	Some(names)
+}
+
+/// Decodes the modified UTF-8 used by class files, which encodes the UTF-16
+/// code units of a string (so characters outside the BMP are surrogate pairs).
+fn decode_utf8(bytes: &[u8]) -> String {
+	let mut units = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let byte = u16::from(bytes[i]);
+		let next = |offset: usize| u16::from(*bytes.get(i + offset).unwrap_or(&0) & 0x3f);
+		if byte & 0x80 == 0 {
+			units.push(byte);
+			i += 1;
+		} else if byte & 0xe0 == 0xc0 {
+			units.push((byte & 0x1f) << 6 | next(1));
+			i += 2;
+		} else {
+			units.push((byte & 0x0f) << 12 | next(1) << 6 | next(2));
+			i += 3;
+		}
+	}
+	String::from_utf16_lossy(&units)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	use crate::fixtures;
+
+	fn member<'a>(members: &'a [Member], name: &str) -> &'a Member {
+		members.iter().find(|member| member.name == name).unwrap()
+	}
+
+	#[test]
+	fn reads_class_hierarchy() {
+		let circle = fixtures::class("com/me/Circle");
+		assert_eq!(circle.name, "com/me/Circle");
+		assert_eq!(circle.superclass.as_deref(), Some("com/me/Base"));
+		assert!(circle.interfaces.is_empty());
+		assert_ne!(circle.access & ACC_PUBLIC, 0);
+
+		let base = fixtures::class("com/me/Base");
+		assert_eq!(base.interfaces, ["com/me/Shape"]);
+		assert_ne!(base.access & ACC_ABSTRACT, 0);
+
+		let shape = fixtures::class("com/me/Shape");
+		assert_ne!(shape.access & ACC_INTERFACE, 0);
+		assert_eq!(shape.superclass.as_deref(), Some("java/lang/Object"));
+
+		let inner = fixtures::class("com/me/Circle$Inner");
+		assert_eq!(inner.name, "com/me/Circle$Inner");
+	}
+
+	#[test]
+	fn reads_constants() {
+		let base = fixtures::class("com/me/Base");
+		let constant = |name| member(&base.fields, name).constant.clone();
+		assert_eq!(constant("MAX"), Some(Constant::Int(10)));
+		assert_eq!(constant("NAME"), Some(Constant::Str(String::from("base \"q\"\n"))));
+		assert_eq!(constant("BIG"), Some(Constant::Long(1234567890123)));
+		assert_eq!(constant("HALF"), Some(Constant::Double(0.5)));
+		assert!(matches!(constant("NAN"), Some(Constant::Float(value)) if value.is_nan()));
+		assert_eq!(constant("C"), Some(Constant::Int('x' as i32)));
+		assert_eq!(constant("YES"), Some(Constant::Int(1)));
+
+		// Only fields initialised with a constant expression have a value
+		assert_eq!(constant("LOCK"), None);
+		assert_eq!(constant("count"), None);
+		assert!(member(&base.fields, "count").is_static());
+	}
+
+	#[test]
+	fn reads_members() {
+		let circle = fixtures::class("com/me/Circle");
+		let radius = member(&circle.fields, "radius");
+		assert_eq!(radius.descriptor, "D");
+		assert_ne!(radius.access & ACC_FINAL, 0);
+		assert!(!radius.is_static());
+
+		let value_of = circle.methods.iter()
+			.filter(|method| method.name == "valueOf")
+			.map(|method| method.descriptor.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(value_of, ["(I)I", "([C)I", "(Ljava/lang/String;)I"]);
+		assert!(!member(&circle.methods, "hidden").is_visible());
+
+		// `get` returns `String`, so a bridge method returning `Object` is generated
+		let bridge = circle.methods.iter()
+			.find(|method| method.name == "get" && method.descriptor == "()Ljava/lang/Object;")
+			.unwrap();
+		assert!(!bridge.is_visible());
+	}
+
+	#[test]
+	fn reads_signatures_and_parameters() {
+		let base = fixtures::class("com/me/Base");
+		assert_eq!(member(&base.methods, "names").signature.as_deref(),
+			Some("()Ljava/util/List<Ljava/lang/String;>;"));
+		assert_eq!(member(&base.fields, "value").signature.as_deref(), Some("TT;"));
+		assert_eq!(member(&base.methods, "get").signature.as_deref(), Some("()TT;"));
+
+		let circle = fixtures::class("com/me/Circle");
+		let constructor = circle.methods.iter()
+			.find(|method| method.name == "<init>" && method.descriptor == "(ILjava/lang/String;)V")
+			.unwrap();
+		assert_eq!(constructor.parameters.as_deref(), Some(&[String::from("r"), String::from("label")][..]));
+		assert_eq!(member(&circle.methods, "type").parameters.as_deref(),
+			Some(&[String::from("self"), String::from("jvm"), String::from("fn")][..]));
+	}
+
+	#[test]
+	fn rejects_invalid_class_files() {
+		assert!(parse(b"").is_err());
+		assert_eq!(parse(b"PK\x03\x04 not a class").unwrap_err(), "not a class file");
+
+		let data = fs::read(fixtures::classes().join("com/me/Circle.class")).unwrap();
+		for len in &[4, 10, 100, data.len() / 2] {
+			assert!(parse(&data[..*len]).is_err(), "parsed {} bytes", len);
+		}
+	}
+}
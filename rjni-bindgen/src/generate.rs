@@ -0,0 +1,786 @@
+//
+//  Code Generation
+//
+
+//! Generates a Rust wrapper struct for each class, with a typed function for
+//! each of its public constructors, methods and fields.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use crate::class_file::{ClassFile, Constant, Member};
+use crate::class_file::{ACC_ABSTRACT, ACC_ENUM, ACC_FINAL, ACC_INTERFACE};
+use crate::types::{binary_name, JavaType, MethodType};
+
+
+/// The box class of each primitive type, which is converted to an `Option`
+/// of the primitive so that it can be null.
+const BOXES: &[(&str, &str)] = &[
+	("java/lang/Boolean", "bool"), ("java/lang/Byte", "i8"), ("java/lang/Character", "char"),
+	("java/lang/Short", "i16"), ("java/lang/Integer", "i32"), ("java/lang/Long", "i64"),
+	("java/lang/Float", "f32"), ("java/lang/Double", "f64"),
+];
+
+/// The collection interfaces that `rjni` wraps, with their number of type
+/// parameters.
+const COLLECTIONS: &[(&str, &str, usize)] = &[
+	("java/util/List", "JList", 1), ("java/util/Set", "JSet", 1), ("java/util/Map", "JMap", 2),
+];
+
+/// The strict and reserved keywords, which can't be used as names.
+const KEYWORDS: &[&str] = &[
+	"as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+	"if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+	"self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+	"where", "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final",
+	"macro", "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "gen",
+];
+
+/// The functions every wrapper has, which methods can't be named.
+const HELPERS: &[&str] = &["class", "from_object", "as_object", "into_object"];
+
+/// Where a type appears, which decides the Rust type it's converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+	/// A parameter, which borrows strings and arrays.
+	Argument,
+
+	/// A returned value, where objects are `Option`s since they can be null.
+	Result,
+
+	/// An array element or type argument.
+	Element,
+}
+
+/// Generates the bindings for the given classes, which can refer to each
+/// other, and to the superclasses and interfaces in `all`.
+pub fn generate(classes: &[&ClassFile], all: &[ClassFile]) -> Result<String, String> {
+	let generator = Generator {
+		names: rust_names(classes),
+		all: all.iter().map(|class| (class.name.as_str(), class)).collect(),
+	};
+	let mut output = String::from(
+		"// Generated by rjni-bindgen. Don't edit this file; regenerate it instead.\n");
+	for class in classes {
+		output.push('\n');
+		generator.class(class, &mut output)?;
+	}
+	Ok(output)
+}
+
+struct Generator<'a> {
+	/// The Rust name of each class bindings are generated for, by internal
+	/// name.
+	names: HashMap<String, String>,
+
+	/// Every class read, by internal name.
+	all: HashMap<&'a str, &'a ClassFile>,
+}
+
+/// A constructor or method to generate a function for.
+struct Function<'a> {
+	member: &'a Member,
+	rust_name: String,
+
+	/// The types from the generic signature, or the descriptor.
+	kind: MethodType,
+}
+
+impl<'a> Generator<'a> {
+	/// Generates the wrapper for a class.
+	fn class(&self, class: &ClassFile, out: &mut String) -> Result<(), String> {
+		let rust = &self.names[&class.name];
+		let java = binary_name(&class.name);
+		let what = if class.access & ACC_INTERFACE != 0 {
+			"interface"
+		} else if class.access & ACC_ENUM != 0 {
+			"enum"
+		} else {
+			"class"
+		};
+
+		writeln!(out, "/// The Java {} `{}`.", what, java).unwrap();
+		writeln!(out, "#[derive(Debug, Clone)]").unwrap();
+		writeln!(out, "#[allow(dead_code, non_camel_case_types, clippy::all)]").unwrap();
+		writeln!(out, "pub struct {}<'a> {{", rust).unwrap();
+		writeln!(out, "\tobject: ::rjni::Object<'a>,").unwrap();
+		writeln!(out, "}}\n").unwrap();
+
+		writeln!(out, "#[allow(dead_code, clippy::all)]").unwrap();
+		writeln!(out, "const _: () = {{").unwrap();
+		writeln!(out, "\tstatic CLASS: ::rjni::ClassCache = ::rjni::ClassCache::new({:?});\n",
+			class.name).unwrap();
+		writeln!(out, "\timpl<'a> {}<'a> {{", rust).unwrap();
+		self.helpers(rust, &java, out);
+
+		let (constructors, methods) = self.functions(class)?;
+		let mut used = methods.iter().chain(&constructors)
+			.map(|function| function.rust_name.clone())
+			.chain(HELPERS.iter().map(|name| name.to_string()))
+			.collect::<HashSet<_>>();
+		for field in class.fields.iter().filter(|field| field.is_visible()) {
+			self.field(field, &mut used, out)?;
+		}
+		for constructor in &constructors {
+			self.constructor(rust, &java, constructor, out);
+		}
+		for method in &methods {
+			self.method(method, out);
+		}
+		writeln!(out, "\t}}\n").unwrap();
+
+		self.conversions(rust, class, out);
+		writeln!(out, "}};").unwrap();
+		Ok(())
+	}
+
+	/// Generates the functions every wrapper has.
+	fn helpers(&self, rust: &str, java: &str, out: &mut String) {
+		write!(out, "\
+\t\t/// Returns the class, loading it if required.
+		pub fn class(jvm: &'a ::rjni::JavaVM) -> ::rjni::Result<::rjni::Class<'a>> {{
+			CLASS.class(jvm)
+		}}
+
+		/// Wraps an object, which must be an instance of `{java}`.
+		pub fn from_object(object: ::rjni::Object<'a>) -> ::rjni::Result<{rust}<'a>> {{
+			// Calling a method on an object of the wrong class is undefined
+			let found = if object.is_null() {{
+				::std::option::Option::Some(::std::string::String::from(\"null\"))
+			}} else if !object.is_instance_of(CLASS.class(object.jvm())?) {{
+				::std::option::Option::Some(object.class_name()?)
+			}} else {{
+				::std::option::Option::None
+			}};
+			match found {{
+				::std::option::Option::Some(found) => ::std::result::Result::Err(
+					::rjni::Error::Conversion(::std::format!(\"expected {java}, found {{}}\", found))),
+				::std::option::Option::None => ::std::result::Result::Ok({rust} {{ object: object }}),
+			}}
+		}}
+
+		/// Returns the wrapped object.
+		pub fn as_object(&self) -> &::rjni::Object<'a> {{
+			&self.object
+		}}
+
+		/// Returns the wrapped object, consuming the wrapper.
+		pub fn into_object(self) -> ::rjni::Object<'a> {{
+			self.object
+		}}
+", rust = rust, java = java).unwrap();
+	}
+
+	/// Collects the public constructors and methods of a class, and names
+	/// them.
+	fn functions<'c>(&self, class: &'c ClassFile)
+			-> Result<(Vec<Function<'c>>, Vec<Function<'c>>), String> {
+		let mut constructors = Vec::new();
+		let mut methods = Vec::new();
+		for member in class.methods.iter().filter(|method| method.is_visible()) {
+			let function = Function {
+				member: member,
+				rust_name: String::new(),
+				kind: method_type(member)?,
+			};
+			if member.name == "<init>" {
+				// Abstract classes can't be instantiated
+				if class.access & (ACC_ABSTRACT | ACC_INTERFACE) == 0 {
+					constructors.push(function);
+				}
+			} else if !member.name.starts_with('<') {
+				methods.push(function);
+			}
+		}
+
+		let mut used = HELPERS.iter().map(|name| name.to_string()).collect();
+		name_overloads(&mut constructors, |_| String::from("new"), &mut used);
+		name_overloads(&mut methods, |method| snake_case(&method.member.name), &mut used);
+		Ok((constructors, methods))
+	}
+
+	/// Generates a function calling a constructor.
+	fn constructor(&self, rust: &str, java: &str, constructor: &Function, out: &mut String) {
+		let simple = java.rsplit('.').next().unwrap_or(java);
+		let (params, args) = self.parameters(constructor, &["jvm"]);
+		writeln!(out, "\n\t\t/// Calls the constructor `public {}({})`.", simple,
+			java_parameters(constructor)).unwrap();
+		writeln!(out, "\t\tpub fn {}(jvm: &'a ::rjni::JavaVM{}) -> ::rjni::Result<{}<'a>> {{",
+			constructor.rust_name, params, rust).unwrap();
+		writeln!(out, "\t\t\tlet object = CLASS.constructor(jvm, {:?})?.call(&CLASS.class(jvm)?, {})?;",
+			constructor.member.descriptor, args).unwrap();
+		writeln!(out, "\t\t\t::std::result::Result::Ok({} {{ object: object }})", rust).unwrap();
+		writeln!(out, "\t\t}}").unwrap();
+	}
+
+	/// Generates a function calling a static or instance method.
+	fn method(&self, method: &Function, out: &mut String) {
+		let member = method.member;
+		let ret = self.rust_type(&method.kind.ret, Position::Result);
+		let mut modifiers = String::from("public ");
+		if member.is_static() {
+			modifiers.push_str("static ");
+		} else if member.access & ACC_ABSTRACT != 0 {
+			modifiers.push_str("abstract ");
+		}
+		writeln!(out, "\n\t\t/// Calls `{}{} {}({})`.", modifiers, method.kind.ret.java_name(),
+			member.name, java_parameters(method)).unwrap();
+
+		if member.is_static() {
+			let (params, args) = self.parameters(method, &["jvm"]);
+			writeln!(out, "\t\tpub fn {}(jvm: &'a ::rjni::JavaVM{}) -> ::rjni::Result<{}> {{",
+				method.rust_name, params, ret).unwrap();
+			writeln!(out, "\t\t\tlet value = CLASS.static_method(jvm, {:?}, {:?})?\n\
+				\t\t\t\t.call(&CLASS.class(jvm)?, {})?;", member.name, member.descriptor,
+				args).unwrap();
+		} else {
+			let (params, args) = self.parameters(method, &["jvm", "self"]);
+			writeln!(out, "\t\tpub fn {}(&self{}) -> ::rjni::Result<{}> {{", method.rust_name,
+				params, ret).unwrap();
+			writeln!(out, "\t\t\tlet jvm = self.object.jvm();").unwrap();
+			writeln!(out, "\t\t\tlet value = CLASS.method(jvm, {:?}, {:?})?.call(&self.object, {})?;",
+				member.name, member.descriptor, args).unwrap();
+		}
+		writeln!(out, "\t\t\t::rjni::FromJava::from_java(value)").unwrap();
+		writeln!(out, "\t\t}}").unwrap();
+	}
+
+	/// Returns the Rust parameters of a function (each preceded by `, `), and
+	/// the expression for the array of their values.
+	fn parameters(&self, function: &Function, reserved: &[&str]) -> (String, String) {
+		let names = parameter_names(function, reserved);
+		let mut params = String::new();
+		let mut args = Vec::new();
+		for (name, kind) in names.iter().zip(&function.kind.params) {
+			write!(params, ", {}: {}", name, self.rust_type(kind, Position::Argument)).unwrap();
+			args.push(format!("::rjni::IntoJava::into_java({}, jvm)?", name));
+		}
+		let args = if args.is_empty() {
+			String::from("&[]")
+		} else {
+			format!("&[{}]", args.join(", "))
+		};
+		(params, args)
+	}
+
+	/// Generates a constant for a constant field, or functions to get and set
+	/// any other field.
+	fn field(&self, field: &Member, used: &mut HashSet<String>, out: &mut String)
+			-> Result<(), String> {
+		let kind = match field.signature {
+			Some(ref signature) => JavaType::parse(signature)
+				.or_else(|_| JavaType::parse(&field.descriptor))?,
+			None => JavaType::parse(&field.descriptor)?,
+		};
+		let modifiers = if field.is_static() { "public static" } else { "public" };
+		let modifiers = if field.access & ACC_FINAL != 0 {
+			format!("{} final", modifiers)
+		} else {
+			modifiers.to_string()
+		};
+		let declaration = format!("{} {} {}", modifiers, kind.java_name(), field.name);
+
+		if let Some(literal) = field.constant.as_ref().and_then(|value| constant(&kind, value)) {
+			let name = unique(constant_case(&field.name), used);
+			writeln!(out, "\n\t\t/// The constant `{}`.", declaration).unwrap();
+			writeln!(out, "\t\tpub const {}: {} = {};", name, literal.0, literal.1).unwrap();
+			return Ok(());
+		}
+
+		let getter = unique(format!("{}_field", snake_case(&field.name)), used);
+		let ret = self.rust_type(&kind, Position::Result);
+		writeln!(out, "\n\t\t/// Gets the field `{}`.", declaration).unwrap();
+		if field.is_static() {
+			writeln!(out, "\t\tpub fn {}(jvm: &'a ::rjni::JavaVM) -> ::rjni::Result<{}> {{", getter,
+				ret).unwrap();
+			writeln!(out, "\t\t\tlet value = CLASS.static_field_with_sig(jvm, {:?}, {:?})?\n\
+				\t\t\t\t.get(&CLASS.class(jvm)?)?;", field.name, field.descriptor).unwrap();
+		} else {
+			writeln!(out, "\t\tpub fn {}(&self) -> ::rjni::Result<{}> {{", getter, ret).unwrap();
+			writeln!(out, "\t\t\tlet jvm = self.object.jvm();").unwrap();
+			writeln!(out, "\t\t\tlet value = CLASS.field_with_sig(jvm, {:?}, {:?})?.get(&self.object)?;",
+				field.name, field.descriptor).unwrap();
+		}
+		writeln!(out, "\t\t\t::rjni::FromJava::from_java(value)").unwrap();
+		writeln!(out, "\t\t}}").unwrap();
+
+		if field.access & ACC_FINAL == 0 {
+			let setter = unique(format!("set_{}_field", snake_case(&field.name)), used);
+			let param = self.rust_type(&kind, Position::Argument);
+			writeln!(out, "\n\t\t/// Sets the field `{}`.", declaration).unwrap();
+			if field.is_static() {
+				writeln!(out, "\t\tpub fn {}(jvm: &'a ::rjni::JavaVM, value: {}) -> ::rjni::Result<()> {{",
+					setter, param).unwrap();
+				writeln!(out, "\t\t\tlet value = ::rjni::IntoJava::into_java(value, jvm)?;").unwrap();
+				writeln!(out, "\t\t\tCLASS.static_field_with_sig(jvm, {:?}, {:?})?\n\
+					\t\t\t\t.set(&CLASS.class(jvm)?, value)", field.name, field.descriptor).unwrap();
+			} else {
+				writeln!(out, "\t\tpub fn {}(&self, value: {}) -> ::rjni::Result<()> {{", setter,
+					param).unwrap();
+				writeln!(out, "\t\t\tlet jvm = self.object.jvm();").unwrap();
+				writeln!(out, "\t\t\tlet value = ::rjni::IntoJava::into_java(value, jvm)?;").unwrap();
+				writeln!(out, "\t\t\tCLASS.field_with_sig(jvm, {:?}, {:?})?.set(&self.object, value)",
+					field.name, field.descriptor).unwrap();
+			}
+			writeln!(out, "\t\t}}").unwrap();
+		}
+		Ok(())
+	}
+
+	/// Generates the conversion traits, and conversions into the wrappers of
+	/// each superclass and interface.
+	fn conversions(&self, rust: &str, class: &ClassFile, out: &mut String) {
+		write!(out, "\
+\timpl<'a> ::rjni::IntoJava<'a> for {rust}<'a> {{
+		fn java_type() -> ::rjni::JavaType {{
+			::rjni::JavaType::Object(::std::string::String::from({name:?}))
+		}}
+
+		fn into_java(self, _: &'a ::rjni::JavaVM) -> ::rjni::Result<::rjni::Value<'a>> {{
+			::std::result::Result::Ok(::rjni::Value::Object(self.object))
+		}}
+	}}
+
+	impl<'a> ::rjni::FromJava<'a> for {rust}<'a> {{
+		fn java_type() -> ::rjni::JavaType {{
+			::rjni::JavaType::Object(::std::string::String::from({name:?}))
+		}}
+
+		fn from_java(value: ::rjni::Value<'a>) -> ::rjni::Result<{rust}<'a>> {{
+			{rust}::from_object(::rjni::FromJava::from_java(value)?)
+		}}
+	}}
+", rust = rust, name = class.name).unwrap();
+
+		for ancestor in self.ancestors(class) {
+			let ancestor = &self.names[&ancestor];
+			write!(out, "
+	impl<'a> ::std::convert::From<{rust}<'a>> for {ancestor}<'a> {{
+		fn from(value: {rust}<'a>) -> {ancestor}<'a> {{
+			{ancestor} {{ object: value.object }}
+		}}
+	}}
+", rust = rust, ancestor = ancestor).unwrap();
+		}
+	}
+
+	/// Returns the superclasses and interfaces of a class that bindings are
+	/// generated for, nearest first.
+	fn ancestors(&self, class: &ClassFile) -> Vec<String> {
+		let mut ancestors = Vec::new();
+		let mut queue = class.superclass.iter().chain(&class.interfaces).collect::<Vec<_>>();
+		let mut seen = HashSet::new();
+		while !queue.is_empty() {
+			let name = queue.remove(0);
+			if !seen.insert(name.as_str()) {
+				continue;
+			}
+			if self.names.contains_key(name) {
+				ancestors.push(name.clone());
+			}
+			if let Some(parent) = self.all.get(name.as_str()) {
+				queue.extend(parent.superclass.iter().chain(&parent.interfaces));
+			}
+		}
+		ancestors
+	}
+
+	/// Returns the Rust type a Java type is converted to.
+	fn rust_type(&self, kind: &JavaType, position: Position) -> String {
+		let object = |name: String| if position == Position::Result {
+			format!("::std::option::Option<{}>", name)
+		} else {
+			name
+		};
+		match kind {
+			JavaType::Boolean => String::from("bool"),
+			JavaType::Byte => String::from("i8"),
+			JavaType::Char => String::from("char"),
+			JavaType::Short => String::from("i16"),
+			JavaType::Int => String::from("i32"),
+			JavaType::Long => String::from("i64"),
+			JavaType::Float => String::from("f32"),
+			JavaType::Double => String::from("f64"),
+			JavaType::Void => String::from("()"),
+			JavaType::Class(name, _) if name == "java/lang/String" => match position {
+				Position::Argument => String::from("&str"),
+				Position::Result => String::from("::std::option::Option<::std::string::String>"),
+				Position::Element => String::from("::std::string::String"),
+			},
+			JavaType::Class(name, _) if BOXES.iter().any(|&(boxed, _)| boxed == name) => {
+				let primitive = BOXES.iter().find(|&&(boxed, _)| boxed == name).unwrap().1;
+				if position == Position::Element {
+					primitive.to_string()
+				} else {
+					format!("::std::option::Option<{}>", primitive)
+				}
+			},
+			JavaType::Class(name, args) if COLLECTIONS.iter().any(|&(java, _, _)| java == name) => {
+				let &(_, wrapper, count) = COLLECTIONS.iter().find(|&&(java, _, _)| java == name).unwrap();
+
+				// Raw types have no type arguments
+				let params = (0..count)
+					.map(|i| match args.get(i) {
+						Some(Some(arg)) if args.len() == count => self.rust_type(arg, Position::Element),
+						_ => String::from("::rjni::Object<'a>"),
+					})
+					.collect::<Vec<_>>();
+				object(format!("::rjni::{}<'a, {}>", wrapper, params.join(", ")))
+			},
+			JavaType::Class(name, _) if self.names.contains_key(name) => {
+				object(format!("{}<'a>", self.names[name]))
+			},
+			JavaType::Class(..) | JavaType::Variable(_) => object(String::from("::rjni::Object<'a>")),
+			JavaType::Array(element) => {
+				let element = self.rust_type(element, Position::Element);
+				match position {
+					Position::Argument => format!("&[{}]", element),
+					Position::Result => format!("::std::option::Option<::std::vec::Vec<{}>>", element),
+					Position::Element => format!("::std::vec::Vec<{}>", element),
+				}
+			},
+		}
+	}
+}
+
+/// Returns the types of a method's parameters and its return type, from its
+/// generic signature if it has one.
+///
+/// The signature leaves out the parameters the compiler adds (eg. the outer
+/// instance passed to an inner class' constructor), in which case the
+/// descriptor is used instead.
+fn method_type(method: &Member) -> Result<MethodType, String> {
+	let erased = MethodType::parse(&method.descriptor)?;
+	let generic = method.signature.as_ref().and_then(|signature| MethodType::parse(signature).ok());
+	Ok(match generic {
+		Some(generic) if generic.params.len() == erased.params.len() => generic,
+		_ => erased,
+	})
+}
+
+/// Names a list of overloaded constructors or methods, so that the one with the
+/// fewest parameters keeps the base name, and the others are named after their
+/// parameters' types (eg. `value_of_char_array`).
+fn name_overloads<F>(functions: &mut [Function], base: F, used: &mut HashSet<String>)
+		where F: Fn(&Function) -> String {
+	let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+	for (i, function) in functions.iter().enumerate() {
+		let name = base(function);
+		match groups.iter_mut().find(|group| group.0 == name) {
+			Some(group) => group.1.push(i),
+			None => groups.push((name, vec![i])),
+		}
+	}
+
+	for (name, mut indices) in groups {
+		indices.sort_by_key(|&i| functions[i].kind.params.len());
+		for (n, i) in indices.into_iter().enumerate() {
+			let function = &mut functions[i];
+			let name = if n == 0 || function.kind.params.is_empty() {
+				name.clone()
+			} else {
+				let types = function.kind.params.iter().map(type_suffix).collect::<Vec<_>>();
+				format!("{}_{}", name, types.join("_"))
+			};
+			function.rust_name = unique(identifier(&name), used);
+		}
+	}
+}
+
+/// Returns a name that hasn't been used yet, by appending a number if
+/// required, and marks it as used.
+fn unique(name: String, used: &mut HashSet<String>) -> String {
+	let mut unique = name.clone();
+	let mut n = 2;
+	while !used.insert(unique.clone()) {
+		unique = format!("{}{}", name, n);
+		n += 1;
+	}
+	unique
+}
+
+/// Returns the names of a function's parameters, renaming any that clash with
+/// the given names used by the generated code.
+fn parameter_names(function: &Function, reserved: &[&str]) -> Vec<String> {
+	let count = function.kind.params.len();
+	let names = match function.member.parameters {
+		Some(ref names) if names.len() == count && names.iter().all(|name| !name.is_empty()) => {
+			names.iter().map(|name| snake_case(name)).collect::<Vec<_>>()
+		},
+		_ => (0..count).map(|i| format!("arg{}", i)).collect(),
+	};
+
+	let mut used = reserved.iter().map(|name| name.to_string()).collect::<HashSet<_>>();
+	names.into_iter().map(|name| unique(identifier(&name), &mut used)).collect()
+}
+
+/// Returns a function's parameters in Java syntax, with their names if known.
+fn java_parameters(function: &Function) -> String {
+	let names = match function.member.parameters {
+		Some(ref names) if names.len() == function.kind.params.len() => names.clone(),
+		_ => Vec::new(),
+	};
+	function.kind.params.iter().enumerate()
+		.map(|(i, kind)| match names.get(i) {
+			Some(name) if !name.is_empty() => format!("{} {}", kind.java_name(), name),
+			_ => kind.java_name(),
+		})
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Returns the part of an overload's name for a parameter's type.
+fn type_suffix(kind: &JavaType) -> String {
+	match kind {
+		JavaType::Class(name, _) => {
+			let simple = name.rsplit(&['/', '$'][..]).next().unwrap_or(name);
+			snake_case(simple)
+		},
+		JavaType::Array(element) => format!("{}_array", type_suffix(element)),
+		JavaType::Variable(name) => snake_case(name),
+		primitive => primitive.java_name(),
+	}
+}
+
+/// Returns the Rust type and literal for the value of a constant field, or
+/// None if it can't be represented (eg. a lone surrogate `char`).
+fn constant(kind: &JavaType, value: &Constant) -> Option<(&'static str, String)> {
+	Some(match (kind, value) {
+		(JavaType::Boolean, Constant::Int(value)) => ("bool", (*value != 0).to_string()),
+		(JavaType::Byte, Constant::Int(value)) => ("i8", value.to_string()),
+		(JavaType::Char, Constant::Int(value)) => {
+			("char", format!("{:?}", ::std::char::from_u32(*value as u32)?))
+		},
+		(JavaType::Short, Constant::Int(value)) => ("i16", value.to_string()),
+		(JavaType::Int, Constant::Int(value)) => ("i32", value.to_string()),
+		(JavaType::Long, Constant::Long(value)) => ("i64", value.to_string()),
+		(JavaType::Float, Constant::Float(value)) => ("f32", float_literal("f32", f64::from(*value))),
+		(JavaType::Double, Constant::Double(value)) => ("f64", float_literal("f64", *value)),
+		(JavaType::Class(..), Constant::Str(value)) => ("&'static str", format!("{:?}", value)),
+		_ => return None,
+	})
+}
+
+/// Returns the literal for a floating point constant.
+fn float_literal(kind: &str, value: f64) -> String {
+	if value.is_nan() {
+		format!("{}::NAN", kind)
+	} else if value.is_infinite() && value > 0.0 {
+		format!("{}::INFINITY", kind)
+	} else if value.is_infinite() {
+		format!("{}::NEG_INFINITY", kind)
+	} else if kind == "f32" {
+		format!("{:?}", value as f32)
+	} else {
+		format!("{:?}", value)
+	}
+}
+
+/// Returns the Rust name for each class, which is its simple name unless two
+/// classes share one, in which case their packages are included.
+fn rust_names(classes: &[&ClassFile]) -> HashMap<String, String> {
+	let simple = |name: &str| {
+		let name = name.rsplit('/').next().unwrap_or(name);
+		name.split('$').map(pascal_case).collect::<String>()
+	};
+	let mut counts = HashMap::new();
+	for class in classes {
+		*counts.entry(simple(&class.name)).or_insert(0) += 1;
+	}
+	classes.iter()
+		.map(|class| {
+			let name = simple(&class.name);
+			let name = if counts[&name] > 1 {
+				class.name.split(&['/', '$'][..]).map(pascal_case).collect()
+			} else {
+				name
+			};
+			(class.name.clone(), identifier(&name))
+		})
+		.collect()
+}
+
+/// Capitalises the first letter of a name, and removes anything that can't be
+/// part of an identifier.
+fn pascal_case(name: &str) -> String {
+	let mut chars = name.chars().filter(|&c| c.is_alphanumeric() || c == '_');
+	match chars.next() {
+		Some(first) => first.to_uppercase().chain(chars).collect(),
+		None => String::new(),
+	}
+}
+
+/// Converts a camel case Java name into a snake case Rust one (eg.
+/// `incrementCurrent` into `increment_current`, and `getURL` into `get_url`).
+fn snake_case(name: &str) -> String {
+	let chars = name.chars().collect::<Vec<_>>();
+	let mut result = String::with_capacity(name.len() + 4);
+	for (i, &c) in chars.iter().enumerate() {
+		// Start a new word at an upper case letter following a lower case one,
+		// or at the last capital in a run of them (the start of the next word)
+		if i > 0 && c.is_uppercase() {
+			let previous = chars[i - 1];
+			let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+			if !previous.is_uppercase() && previous != '_' || previous.is_uppercase() && next_lower {
+				result.push('_');
+			}
+		}
+		if c == '$' {
+			result.push('_');
+		} else {
+			result.extend(c.to_lowercase());
+		}
+	}
+	result
+}
+
+/// Converts a field's name into the screaming snake case used for Rust
+/// constants, unless it already is.
+fn constant_case(name: &str) -> String {
+	identifier(&snake_case(name).to_uppercase())
+}
+
+/// Makes a name usable as an identifier, escaping keywords.
+fn identifier(name: &str) -> String {
+	let name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+		format!("_{}", name)
+	} else {
+		name.to_string()
+	};
+	match name.as_str() {
+		// These can't be raw identifiers
+		"self" | "Self" | "super" | "crate" | "_" => format!("{}_", name),
+		keyword if KEYWORDS.contains(&keyword) => format!("r#{}", name),
+		_ => name,
+	}
+}
+
+
+
+// The bindings for the fixtures are also compiled against `rjni` and called,
+// by `rjni-examples/tests/fixtures.rs`
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{fixtures, Bindgen, Error};
+
+	fn bindings() -> String {
+		Bindgen::new().input(fixtures::classes()).generate().unwrap()
+	}
+
+	/// Returns the generated function with the given signature line, up to
+	/// its closing brace.
+	fn function<'a>(bindings: &'a str, signature: &str) -> &'a str {
+		let start = bindings.find(signature).unwrap_or_else(|| panic!("no `{}`", signature));
+		let end = bindings[start..].find("\n\t\t}\n").unwrap();
+		&bindings[start..start + end]
+	}
+
+	#[test]
+	fn generates_wrappers() {
+		let bindings = bindings();
+		assert!(bindings.starts_with("// Generated by rjni-bindgen."));
+		assert!(bindings.contains("/// The Java class `com.me.Circle`.\n"));
+		assert!(bindings.contains("/// The Java interface `com.me.Shape`.\n"));
+		assert!(bindings.contains("pub struct CircleInner<'a> {"));
+		assert!(bindings.contains("::rjni::ClassCache::new(\"com/me/Circle$Inner\")"));
+
+		// Anonymous classes aren't public
+		assert!(!bindings.contains("Circle$1"));
+
+		// Conversions into every superclass and interface
+		assert!(bindings.contains("impl<'a> ::std::convert::From<Circle<'a>> for Base<'a> {"));
+		assert!(bindings.contains("impl<'a> ::std::convert::From<Circle<'a>> for Shape<'a> {"));
+		assert!(bindings.contains("impl<'a> ::std::convert::From<Base<'a>> for Shape<'a> {"));
+	}
+
+	#[test]
+	fn generates_constants() {
+		let bindings = bindings();
+		assert!(bindings.contains("pub const MAX: i32 = 10;"));
+		assert!(bindings.contains("pub const NAME: &'static str = \"base \\\"q\\\"\\n\";"));
+		assert!(bindings.contains("pub const BIG: i64 = 1234567890123;"));
+		assert!(bindings.contains("pub const HALF: f64 = 0.5;"));
+		assert!(bindings.contains("pub const NAN: f32 = f32::NAN;"));
+		assert!(bindings.contains("pub const C: char = 'x';"));
+		assert!(bindings.contains("pub const YES: bool = true;"));
+	}
+
+	#[test]
+	fn generates_fields() {
+		let bindings = bindings();
+
+		// Static fields are read and written through cached IDs
+		let getter = function(&bindings, "pub fn count_field(jvm: &'a ::rjni::JavaVM) -> ::rjni::Result<i32> {");
+		assert!(getter.contains("CLASS.static_field_with_sig(jvm, \"count\", \"I\")?"));
+		let setter = function(&bindings, "pub fn set_count_field(jvm: &'a ::rjni::JavaVM, value: i32) -> ::rjni::Result<()> {");
+		assert!(setter.contains(".set(&CLASS.class(jvm)?, value)"));
+
+		// Final fields have no setter
+		assert!(bindings.contains("pub fn lock_field(jvm: &'a ::rjni::JavaVM)"));
+		assert!(!bindings.contains("set_lock_field"));
+		assert!(bindings.contains("pub fn radius_field(&self) -> ::rjni::Result<f64> {"));
+		assert!(!bindings.contains("set_radius_field"));
+
+		let setter = function(&bindings, "pub fn set_data_field(&self, value: &[i32]) -> ::rjni::Result<()> {");
+		assert!(setter.contains("CLASS.field_with_sig(jvm, \"data\", \"[I\")?.set(&self.object, value)"));
+		assert!(bindings.contains("pub fn boxed_field(&self) -> ::rjni::Result<::std::option::Option<i32>> {"));
+
+		// Protected fields aren't bound
+		assert!(!bindings.contains("value_field"));
+	}
+
+	#[test]
+	fn generates_methods() {
+		let bindings = bindings();
+		let method = function(&bindings, "pub fn area(&self) -> ::rjni::Result<f64> {");
+		assert!(method.contains("CLASS.method(jvm, \"area\", \"()D\")?.call(&self.object, &[])?"));
+
+		// Generic types become the collection wrappers
+		assert!(bindings.contains("pub fn names(&self) -> ::rjni::Result<::std::option::Option<::rjni::JList<'a, ::std::string::String>>> {"));
+		assert!(bindings.contains("::rjni::JMap<'a, ::std::string::String, ::rjni::JList<'a, i32>>"));
+
+		// Wrapped classes are returned as their wrappers
+		assert!(bindings.contains("pub fn as_shape(&self) -> ::rjni::Result<::std::option::Option<Shape<'a>>> {"));
+
+		// Package-private methods and bridge methods aren't bound
+		assert!(!bindings.contains("fn hidden("));
+		assert_eq!(bindings.matches("\"get\", \"()Ljava/lang/Object;\"").count(), 1);
+	}
+
+	#[test]
+	fn names_overloads_and_keywords() {
+		let bindings = bindings();
+		assert!(bindings.contains("pub fn new(jvm: &'a ::rjni::JavaVM, radius: f64) -> ::rjni::Result<Circle<'a>> {"));
+		assert!(bindings.contains("pub fn new_int_string(jvm: &'a ::rjni::JavaVM, r: i32, label: &str)"));
+		assert!(bindings.contains("pub fn value_of(jvm: &'a ::rjni::JavaVM, x: i32)"));
+		assert!(bindings.contains("pub fn value_of_char_array(jvm: &'a ::rjni::JavaVM, x: &[char])"));
+		assert!(bindings.contains("pub fn value_of_string(jvm: &'a ::rjni::JavaVM, x: &str)"));
+		assert!(bindings.contains("pub fn get_url(&self)"));
+		assert!(bindings.contains("pub fn r#type(&self, self_: i32, jvm2: i32, r#fn: i32)"));
+	}
+
+	#[test]
+	fn selects_classes() {
+		let bindings = Bindgen::new().input(fixtures::classes()).class("com.me.Circle.Inner").generate().unwrap();
+		assert!(bindings.contains("pub struct CircleInner<'a>"));
+		assert!(!bindings.contains("pub struct Circle<'a>"));
+
+		let missing = Bindgen::new().input(fixtures::classes()).class("com.me.Missing").generate();
+		assert!(matches!(missing, Err(Error::ClassNotFound(ref name)) if name == "com.me.Missing"));
+	}
+
+	#[test]
+	fn converts_names() {
+		assert_eq!(snake_case("incrementCurrent"), "increment_current");
+		assert_eq!(snake_case("getURL"), "get_url");
+		assert_eq!(snake_case("URLDecoder"), "url_decoder");
+		assert_eq!(snake_case("access$000"), "access_000");
+		assert_eq!(constant_case("maxValue"), "MAX_VALUE");
+		assert_eq!(constant_case("MAX_VALUE"), "MAX_VALUE");
+		assert_eq!(pascal_case("inner"), "Inner");
+		assert_eq!(identifier("match"), "r#match");
+		assert_eq!(identifier("self"), "self_");
+		assert_eq!(identifier("1st"), "_1st");
+	}
+}
@@ -0,0 +1,290 @@
+//
+//  rjni-bindgen
+//
+
+//! Generates typed Rust bindings for compiled Java classes, which call through
+//! `rjni` with each member's exact descriptor, and cache the IDs they look up.
+//!
+//! Each public class gets a wrapper struct around an `rjni::Object`, with a
+//! function for each public constructor, method and field it declares
+//! (inherited ones are called on the wrapper of the superclass or interface
+//! that declares them, which it converts into with `From`). Instead of:
+//!
+//! ```rust,ignore
+//! let object = class.instantiate(&[Value::Int(5)])?;
+//! object.call::<()>("incrementCurrent", &[])?;
+//! ```
+//!
+//! the generated bindings are called as:
+//!
+//! ```rust,ignore
+//! let test = Test::new(&jvm, 5)?;
+//! test.increment_current()?;
+//! ```
+//!
+//! Bindings can be generated from a build script (after compiling the Java
+//! sources with `rjni-build`):
+//!
+//! ```rust,no_run
+//! use rjni_bindgen::Bindgen;
+//! use rjni_build::{JavaBuild, Version};
+//! use std::{env, path::Path};
+//!
+//! fn main() {
+//! 	let output = match JavaBuild::new("java").version(Version::V18).compile() {
+//! 		Ok(output) => output,
+//! 		Err(err) => panic!("{}", err),
+//! 	};
+//! 	let out = env::var("OUT_DIR").unwrap();
+//! 	let bindings = Bindgen::new()
+//! 		.input(&output.classes)
+//! 		.write_to(Path::new(&out).join("bindings.rs"));
+//! 	if let Err(err) = bindings {
+//! 		panic!("{}", err);
+//! 	}
+//! }
+//! ```
+//!
+//! and then in the crate:
+//!
+//! ```rust,ignore
+//! include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+//! ```
+//!
+//! Or with the `rjni-bindgen` binary:
+//!
+//! ```bash
+//! $ rjni-bindgen -o src/bindings.rs target/app.jar
+//! ```
+//!
+//! # Types
+//!
+//! Java types are converted to the Rust types that implement `rjni`'s
+//! `IntoJava` and `FromJava`:
+//!
+//! * Primitives are converted to their Rust equivalents (eg. `int` to `i32`),
+//!   and their box classes to an `Option` of them (eg. `Integer` to
+//!   `Option<i32>`).
+//! * Parameters borrow strings and arrays (eg. `&str` and `&[i32]`).
+//! * Returned objects, strings and arrays are `Option`s, since they can be
+//!   null.
+//! * `List`, `Set` and `Map` are converted to `JList`, `JSet` and `JMap`, with
+//!   the type arguments from their generic signatures.
+//! * Classes that bindings are generated for are converted to their wrappers,
+//!   which can be converted into the wrappers of their superclasses and
+//!   interfaces with `From`. Any other class is an `rjni::Object`.
+//!
+//! Overloaded methods are told apart by the types of their parameters, so
+//! `valueOf(int)` and `valueOf(char[])` become `value_of` (the overload with
+//! the fewest parameters) and `value_of_char_array`. Parameters are named
+//! after the Java ones when the classes were compiled with `-parameters` or
+//! `-g`.
+
+#![allow(clippy::tabs_in_doc_comments)]
+#![allow(clippy::redundant_field_names)]
+
+mod class_file;
+mod types;
+mod generate;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{error, fmt, fs, io};
+
+use class_file::{ClassFile, ACC_PUBLIC, ACC_SYNTHETIC, ACC_MODULE};
+use rjni_jar::Jar;
+
+
+/// A set of compiled classes to generate bindings for.
+#[derive(Debug, Clone, Default)]
+pub struct Bindgen {
+	inputs: Vec<PathBuf>,
+	classes: Vec<String>,
+}
+
+impl Bindgen {
+	/// Create an empty set of inputs.
+	pub fn new() -> Bindgen {
+		Bindgen::default()
+	}
+
+	/// Add a Jar file, a directory containing the root of a tree of class
+	/// files, or a single class file.
+	pub fn input<P: AsRef<Path>>(mut self, path: P) -> Bindgen {
+		self.inputs.push(path.as_ref().to_owned());
+		self
+	}
+
+	/// Only generate bindings for the given class (eg. `com.me.Test`), which
+	/// can be called more than once. Otherwise, bindings are generated for
+	/// every public class in the inputs.
+	///
+	/// The other classes in the inputs are still read, to find the superclasses
+	/// and interfaces of these ones.
+	pub fn class(mut self, name: &str) -> Bindgen {
+		self.classes.push(name.replace('.', "/"));
+		self
+	}
+
+	/// Generates the bindings, returning their source code.
+	pub fn generate(&self) -> Result<String, Error> {
+		let mut classes = Vec::new();
+		for input in &self.inputs {
+			read_input(input, &mut classes)?;
+		}
+		classes.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let selected = if self.classes.is_empty() {
+			classes.iter().filter(|class| is_bound(class)).collect::<Vec<_>>()
+		} else {
+			// Nested classes are named with `$` internally
+			let wanted = self.classes.iter().collect::<HashSet<_>>();
+			let selected = classes.iter()
+				.filter(|class| wanted.contains(&class.name) || wanted.contains(&class.name.replace('$', "/")))
+				.collect::<Vec<_>>();
+			for name in &self.classes {
+				if !selected.iter().any(|class| &class.name == name || &class.name.replace('$', "/") == name) {
+					return Err(Error::ClassNotFound(name.replace('/', ".")));
+				}
+			}
+			selected
+		};
+		generate::generate(&selected, &classes).map_err(Error::Invalid)
+	}
+
+	/// Generates the bindings, and writes them to a file if they've changed
+	/// (so that a build script doesn't cause everything including them to be
+	/// rebuilt).
+	pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+		let path = path.as_ref();
+		let bindings = self.generate()?;
+		if fs::read_to_string(path).ok().as_ref() != Some(&bindings) {
+			fs::write(path, bindings)?;
+		}
+		Ok(())
+	}
+}
+
+/// Returns true if bindings are generated for a class by default: it must be
+/// public, and not generated by the compiler or describe a module or package.
+fn is_bound(class: &ClassFile) -> bool {
+	class.access & ACC_PUBLIC != 0 && class.access & (ACC_SYNTHETIC | ACC_MODULE) == 0 &&
+		!class.name.ends_with("package-info")
+}
+
+/// Reads the classes in a Jar file, directory, or class file.
+fn read_input(path: &Path, classes: &mut Vec<ClassFile>) -> Result<(), Error> {
+	if path.is_dir() {
+		let mut entries = fs::read_dir(path)?
+			.map(|entry| entry.map(|entry| entry.path()))
+			.collect::<io::Result<Vec<_>>>()?;
+		entries.sort();
+		for entry in entries {
+			if entry.is_dir() || is_class(&entry) {
+				read_input(&entry, classes)?;
+			}
+		}
+		return Ok(());
+	}
+
+	// Name the file, since it might be one of many
+	let in_file = |err: io::Error| io::Error::new(err.kind(), format!("`{}`: {}", path.display(), err));
+	if is_class(path) {
+		let data = fs::read(path).map_err(in_file)?;
+		classes.push(parse_class(&data, &path.display().to_string())?);
+		return Ok(());
+	}
+	let jar = Jar::open(path).map_err(in_file)?;
+	let names = jar.names()
+		.filter(|name| name.ends_with(".class") && !name.starts_with("META-INF/"))
+		.map(String::from)
+		.collect::<Vec<_>>();
+	for name in names {
+		let data = jar.read(&name).map_err(in_file)?.unwrap_or_default();
+		let source = format!("{}!/{}", path.display(), name);
+		classes.push(parse_class(&data, &source)?);
+	}
+	Ok(())
+}
+
+fn parse_class(data: &[u8], source: &str) -> Result<ClassFile, Error> {
+	class_file::parse(data).map_err(|reason| Error::Invalid(format!("`{}`: {}", source, reason)))
+}
+
+fn is_class(path: &Path) -> bool {
+	path.extension().is_some_and(|ext| ext == "class")
+}
+
+
+
+//
+//  Error Handling
+//
+
+/// An error returned when bindings can't be generated.
+#[derive(Debug)]
+pub enum Error {
+	/// A class passed to `Bindgen::class` that isn't in any of the inputs.
+	ClassNotFound(String),
+
+	/// A class file that can't be parsed.
+	Invalid(String),
+
+	/// An error reading the inputs or writing the bindings.
+	Io(io::Error),
+}
+
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Error {
+		Error::Io(err)
+	}
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::ClassNotFound(name) => write!(f, "Class not found: {}", name),
+			Error::Invalid(msg) => write!(f, "Invalid class file: {}", msg),
+			Error::Io(err) => write!(f, "Couldn't generate bindings: {}", err),
+		}
+	}
+}
+
+
+
+#[cfg(test)]
+mod fixtures {
+	//! The classes in `tests/fixtures`, compiled once for every test.
+
+	use std::path::{Path, PathBuf};
+	use std::sync::OnceLock;
+	use std::{env, fs};
+
+	use rjni_build::{JavaBuild, Version};
+
+	use crate::class_file::{self, ClassFile};
+
+	/// Returns the directory the fixtures are compiled into.
+	pub fn classes() -> &'static Path {
+		static CLASSES: OnceLock<PathBuf> = OnceLock::new();
+		CLASSES.get_or_init(|| {
+			let output = JavaBuild::new("tests/fixtures")
+				.version(Version::V18)
+				.arg("-parameters")
+				.out_dir(env::temp_dir().join("rjni-bindgen-tests"))
+				.compile();
+			match output {
+				Ok(output) => output.classes,
+				Err(err) => panic!("{}", err),
+			}
+		})
+	}
+
+	/// Reads a compiled fixture by its internal name (eg. `com/me/Circle`).
+	pub fn class(name: &str) -> ClassFile {
+		let data = fs::read(classes().join(format!("{}.class", name))).unwrap();
+		class_file::parse(&data).unwrap()
+	}
+}
@@ -0,0 +1,74 @@
+//
+//  rjni-bindgen
+//
+
+//! Generates Rust bindings for the classes in Jar files, directories and class
+//! files:
+//!
+//! ```bash
+//! $ rjni-bindgen -o src/bindings.rs --class com.me.Test target/app.jar
+//! ```
+
+use std::{env, fs, process};
+
+use rjni_bindgen::Bindgen;
+
+
+/// The usage message printed for `-h`, or when no inputs are given.
+const USAGE: &str = "\
+Usage: rjni-bindgen [options] <jar, directory or class file>...
+
+Options:
+    -o <file>         write the bindings to a file, rather than stdout
+    --class <name>    only generate bindings for a class (eg. com.me.Test),
+                      which can be given more than once
+    -h, --help        print this message";
+
+fn main() {
+	let mut bindgen = Bindgen::new();
+	let mut output = None;
+	let mut inputs = 0;
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"-h" | "-help" | "--help" => {
+				println!("{}", USAGE);
+				return;
+			},
+			"-o" => output = Some(value(&arg, args.next())),
+			"--class" => bindgen = bindgen.class(&value(&arg, args.next())),
+			_ if arg.starts_with('-') => fail(&format!("unrecognised option `{}`", arg)),
+			_ => {
+				bindgen = bindgen.input(&arg);
+				inputs += 1;
+			},
+		}
+	}
+	if inputs == 0 {
+		eprintln!("{}", USAGE);
+		process::exit(2);
+	}
+
+	let result = bindgen.generate().and_then(|bindings| match output {
+		Some(path) => fs::write(path, bindings).map_err(From::from),
+		None => {
+			print!("{}", bindings);
+			Ok(())
+		},
+	});
+	if let Err(err) = result {
+		eprintln!("rjni-bindgen: {}", err);
+		process::exit(1);
+	}
+}
+
+/// Returns the value following an option, or exits if there isn't one.
+fn value(option: &str, value: Option<String>) -> String {
+	value.unwrap_or_else(|| fail(&format!("`{}` requires a value", option)))
+}
+
+/// Prints an error and the usage message, and exits.
+fn fail(msg: &str) -> ! {
+	eprintln!("rjni-bindgen: {}\n\n{}", msg, USAGE);
+	process::exit(2);
+}
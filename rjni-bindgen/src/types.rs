@@ -0,0 +1,308 @@
+//
+//  Java Types
+//
+
+//! Parses the types in JNI descriptors (eg. `(ILjava/util/List;)V`) and in
+//! generic signatures (eg. `(ILjava/util/List<Ljava/lang/String;>;)V`), which
+//! also name the type arguments of generic classes.
+
+
+/// A Java type, from a descriptor or generic signature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JavaType {
+	Boolean,
+	Byte,
+	Char,
+	Short,
+	Int,
+	Long,
+	Float,
+	Double,
+	Void,
+
+	/// A class by its internal name, with its type arguments if it's generic
+	/// (where `None` is the wildcard `?`).
+	Class(String, Vec<Option<JavaType>>),
+
+	/// An array of the given element type.
+	Array(Box<JavaType>),
+
+	/// A type variable (eg. `T`).
+	Variable(String),
+}
+
+/// The types of a method's parameters and its return type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodType {
+	pub params: Vec<JavaType>,
+	pub ret: JavaType,
+}
+
+impl MethodType {
+	/// Parses a method's descriptor or generic signature, ignoring its type
+	/// parameters and thrown exceptions.
+	pub fn parse(signature: &str) -> Result<MethodType, String> {
+		let mut parser = Parser {
+			text: signature,
+			pos: 0,
+		};
+
+		// Skip the type parameters, whose bounds can contain `<` and `>`
+		if parser.peek() == Some('<') {
+			let mut depth = 0;
+			while let Some(c) = parser.next() {
+				match c {
+					'<' => depth += 1,
+					'>' => depth -= 1,
+					_ => {},
+				}
+				if depth == 0 {
+					break;
+				}
+			}
+		}
+
+		parser.expect('(')?;
+		let mut params = Vec::new();
+		while parser.peek() != Some(')') {
+			params.push(parser.parse_type()?);
+		}
+		parser.expect(')')?;
+		let ret = parser.parse_type()?;
+		Ok(MethodType {
+			params: params,
+			ret: ret,
+		})
+	}
+}
+
+impl JavaType {
+	/// Parses a field's descriptor or generic signature.
+	pub fn parse(signature: &str) -> Result<JavaType, String> {
+		let mut parser = Parser {
+			text: signature,
+			pos: 0,
+		};
+		let kind = parser.parse_type()?;
+		if parser.pos < signature.len() {
+			return Err(format!("unexpected `{}` after the type in `{}`", &signature[parser.pos..],
+				signature));
+		}
+		Ok(kind)
+	}
+
+	/// Returns the type in Java syntax (eg. `java.util.List<java.lang.String>`).
+	pub fn java_name(&self) -> String {
+		match self {
+			JavaType::Boolean => String::from("boolean"),
+			JavaType::Byte => String::from("byte"),
+			JavaType::Char => String::from("char"),
+			JavaType::Short => String::from("short"),
+			JavaType::Int => String::from("int"),
+			JavaType::Long => String::from("long"),
+			JavaType::Float => String::from("float"),
+			JavaType::Double => String::from("double"),
+			JavaType::Void => String::from("void"),
+			JavaType::Class(name, args) if args.is_empty() => binary_name(name),
+			JavaType::Class(name, args) => {
+				let args = args.iter()
+					.map(|arg| arg.as_ref().map_or_else(|| String::from("?"), JavaType::java_name))
+					.collect::<Vec<_>>();
+				format!("{}<{}>", binary_name(name), args.join(", "))
+			},
+			JavaType::Array(element) => format!("{}[]", element.java_name()),
+			JavaType::Variable(name) => name.clone(),
+		}
+	}
+}
+
+/// Converts an internal class name into the binary name used in Java source
+/// (eg. `java/util/Map$Entry` into `java.util.Map.Entry`).
+pub fn binary_name(name: &str) -> String {
+	name.replace(['/', '$'], ".")
+}
+
+struct Parser<'a> {
+	text: &'a str,
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn peek(&self) -> Option<char> {
+		self.text[self.pos..].chars().next()
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek()?;
+		self.pos += c.len_utf8();
+		Some(c)
+	}
+
+	fn expect(&mut self, expected: char) -> Result<(), String> {
+		match self.next() {
+			Some(c) if c == expected => Ok(()),
+			_ => Err(self.error(&format!("expected `{}`", expected))),
+		}
+	}
+
+	fn parse_type(&mut self) -> Result<JavaType, String> {
+		Ok(match self.next() {
+			Some('Z') => JavaType::Boolean,
+			Some('B') => JavaType::Byte,
+			Some('C') => JavaType::Char,
+			Some('S') => JavaType::Short,
+			Some('I') => JavaType::Int,
+			Some('J') => JavaType::Long,
+			Some('F') => JavaType::Float,
+			Some('D') => JavaType::Double,
+			Some('V') => JavaType::Void,
+			Some('[') => JavaType::Array(Box::new(self.parse_type()?)),
+			Some('T') => {
+				let name = self.identifier(&[';'])?;
+				self.expect(';')?;
+				JavaType::Variable(name)
+			},
+			Some('L') => self.parse_class()?,
+			_ => return Err(self.error("expected a type")),
+		})
+	}
+
+	/// Parses a class type after its `L`, up to and including its `;`.
+	fn parse_class(&mut self) -> Result<JavaType, String> {
+		let mut name = self.identifier(&['<', '.', ';'])?;
+		let mut args = Vec::new();
+		loop {
+			match self.next() {
+				Some(';') => return Ok(JavaType::Class(name, args)),
+
+				// The arguments of an outer class don't apply to its inner one
+				Some('<') => args = self.parse_arguments()?,
+				Some('.') => {
+					name.push('$');
+					name.push_str(&self.identifier(&['<', '.', ';'])?);
+					args = Vec::new();
+				},
+				_ => return Err(self.error("unterminated class type")),
+			}
+		}
+	}
+
+	/// Parses type arguments after their `<`, up to and including the `>`.
+	fn parse_arguments(&mut self) -> Result<Vec<Option<JavaType>>, String> {
+		let mut args = Vec::new();
+		loop {
+			match self.peek() {
+				Some('>') => {
+					self.next();
+					return Ok(args);
+				},
+				Some('*') => {
+					self.next();
+					args.push(None);
+				},
+
+				// A bounded wildcard is read and written as its bound
+				Some('+') | Some('-') => {
+					self.next();
+					args.push(Some(self.parse_type()?));
+				},
+				Some(_) => args.push(Some(self.parse_type()?)),
+				None => return Err(self.error("unterminated type arguments")),
+			}
+		}
+	}
+
+	/// Reads an identifier up to (but not including) one of the given
+	/// characters.
+	fn identifier(&mut self, ends: &[char]) -> Result<String, String> {
+		let rest = &self.text[self.pos..];
+		match rest.find(ends) {
+			Some(0) | None => Err(self.error("expected a name")),
+			Some(len) => {
+				self.pos += len;
+				Ok(rest[..len].to_string())
+			},
+		}
+	}
+
+	fn error(&self, msg: &str) -> String {
+		format!("{} at position {} of `{}`", msg, self.pos, self.text)
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn class(name: &str, args: Vec<Option<JavaType>>) -> JavaType {
+		JavaType::Class(String::from(name), args)
+	}
+
+	#[test]
+	fn parses_descriptors() {
+		assert_eq!(JavaType::parse("I").unwrap(), JavaType::Int);
+		assert_eq!(JavaType::parse("[[J").unwrap(),
+			JavaType::Array(Box::new(JavaType::Array(Box::new(JavaType::Long)))));
+		assert_eq!(JavaType::parse("Ljava/lang/String;").unwrap(), class("java/lang/String", vec![]));
+	}
+
+	#[test]
+	fn parses_generic_signatures() {
+		let string = || Some(class("java/lang/String", vec![]));
+		assert_eq!(JavaType::parse("Ljava/util/List<Ljava/lang/String;>;").unwrap(),
+			class("java/util/List", vec![string()]));
+		assert_eq!(JavaType::parse("Ljava/util/Map<TK;Ljava/util/List<*>;>;").unwrap(),
+			class("java/util/Map", vec![Some(JavaType::Variable(String::from("K"))),
+				Some(class("java/util/List", vec![None]))]));
+
+		// Bounded wildcards are their bound
+		assert_eq!(JavaType::parse("Ljava/util/List<+Ljava/lang/String;>;").unwrap(),
+			class("java/util/List", vec![string()]));
+		assert_eq!(JavaType::parse("Ljava/util/List<-Ljava/lang/String;>;").unwrap(),
+			class("java/util/List", vec![string()]));
+
+		// Inner classes of generic classes only keep their own arguments
+		assert_eq!(JavaType::parse("Lcom/me/Outer<TT;>.Inner;").unwrap(), class("com/me/Outer$Inner", vec![]));
+		assert_eq!(JavaType::parse("Lcom/me/Outer<TT;>.Inner<TU;>;").unwrap(),
+			class("com/me/Outer$Inner", vec![Some(JavaType::Variable(String::from("U")))]));
+	}
+
+	#[test]
+	fn parses_method_types() {
+		let method = MethodType::parse("(I[Ljava/lang/String;)V").unwrap();
+		assert_eq!(method.params, [JavaType::Int,
+			JavaType::Array(Box::new(class("java/lang/String", vec![])))]);
+		assert_eq!(method.ret, JavaType::Void);
+
+		// Type parameters (whose bounds contain `<` and `>`) and thrown
+		// exceptions are skipped
+		let method = MethodType::parse(
+			"<E::Ljava/lang/Comparable<TE;>;>(Ljava/util/List<+TE;>;)TE;^Ljava/io/IOException;").unwrap();
+		assert_eq!(method.params, [class("java/util/List", vec![Some(JavaType::Variable(String::from("E")))])]);
+		assert_eq!(method.ret, JavaType::Variable(String::from("E")));
+	}
+
+	#[test]
+	fn rejects_invalid_signatures() {
+		assert!(JavaType::parse("").is_err());
+		assert!(JavaType::parse("Q").is_err());
+		assert!(JavaType::parse("II").is_err());
+		assert!(JavaType::parse("Ljava/lang/String").is_err());
+		assert!(JavaType::parse("L;").is_err());
+		assert!(JavaType::parse("Ljava/util/List<Ljava/lang/String;").is_err());
+		assert!(MethodType::parse("I)V").is_err());
+		assert!(MethodType::parse("(I").is_err());
+		assert!(MethodType::parse("(I)").is_err());
+	}
+
+	#[test]
+	fn java_names() {
+		assert_eq!(JavaType::parse("[I").unwrap().java_name(), "int[]");
+		assert_eq!(JavaType::parse("Ljava/util/Map<Ljava/lang/String;*>;").unwrap().java_name(),
+			"java.util.Map<java.lang.String, ?>");
+		assert_eq!(JavaType::parse("Ljava/util/Map$Entry;").unwrap().java_name(), "java.util.Map.Entry");
+		assert_eq!(binary_name("com/me/Outer$Inner"), "com.me.Outer.Inner");
+	}
+}
@@ -0,0 +1,27 @@
+package com.me;
+
+import java.util.*;
+
+public abstract class Base<T> implements Shape {
+	public static final int MAX = 10;
+	public static final String NAME = "base \"q\"\n";
+	public static final long BIG = 1234567890123L;
+	public static final double HALF = 0.5;
+	public static final float NAN = Float.NaN;
+	public static final char C = 'x';
+	public static final boolean YES = true;
+
+	public static int count;
+	public static final Object LOCK = new Object();
+	protected T value;
+
+	public abstract T get();
+
+	public List<String> names() {
+		return Arrays.asList("a", "b");
+	}
+
+	public Map<String, List<Integer>> map() {
+		return new HashMap<>();
+	}
+}
@@ -0,0 +1,69 @@
+package com.me;
+
+import java.util.*;
+
+public class Circle extends Base<String> {
+	public final double radius;
+	public int[] data = {1, 2};
+	public Integer boxed;
+
+	public Circle(double radius) {
+		this.radius = radius;
+	}
+
+	public Circle(int r, String label) {
+		this((double) r);
+	}
+
+	public double area() {
+		return Math.PI * radius * radius;
+	}
+
+	public String get() {
+		return "circle";
+	}
+
+	public static int valueOf(int x) {
+		return x;
+	}
+
+	public static int valueOf(char[] x) {
+		return x.length;
+	}
+
+	public static int valueOf(String x) {
+		return x.length();
+	}
+
+	public String getURL() {
+		return "";
+	}
+
+	public void type(int self, int jvm, int fn) {}
+
+	public <E extends Comparable<E>> E max(List<? extends E> list) {
+		return list.get(0);
+	}
+
+	public Set<?> wild() {
+		return new HashSet<>();
+	}
+
+	public Shape asShape() {
+		return this;
+	}
+
+	void hidden() {}
+
+	public static class Inner {
+		public int x() {
+			return 3;
+		}
+	}
+
+	public Runnable anon() {
+		return new Runnable() {
+			public void run() {}
+		};
+	}
+}
@@ -0,0 +1,5 @@
+package com.me;
+
+public interface Shape {
+	double area();
+}
@@ -0,0 +1,421 @@
+//
+//  rjni-build
+//
+
+//! Compiles a tree of Java sources from a crate's `build.rs`, so the classes
+//! a crate loads through `rjni` are rebuilt whenever they change, rather than
+//! by running `javac` by hand.
+//!
+//! Add `rjni-build` as a build dependency, and compile the sources in
+//! `build.rs`:
+//!
+//! ```rust,no_run
+//! use rjni_build::{JavaBuild, Version};
+//!
+//! fn main() {
+//! 	let build = JavaBuild::new("java")
+//! 		.version(Version::V18)
+//! 		.jar(true);
+//! 	if let Err(err) = build.compile() {
+//! 		panic!("{}", err);
+//! 	}
+//! }
+//! ```
+//!
+//! The classes are compiled into `OUT_DIR` with the `javac` from `JAVA_HOME`
+//! (or the `PATH`), and the build script reruns when any source changes. Their
+//! location is passed to the crate in environment variables named after the
+//! source directory (here, `java`), which can be used as a path or embedded:
+//!
+//! ```rust,ignore
+//! // The directory of compiled classes, to add to the classpath
+//! const CLASSES: &str = env!("RJNI_JAVA_CLASSES");
+//!
+//! // The Jar file, which is only built when `jar(true)` is set
+//! const JAR: &str = env!("RJNI_JAVA_JAR");
+//! static JAR_BYTES: &[u8] = include_bytes!(env!("RJNI_JAVA_JAR"));
+//! ```
+
+#![allow(clippy::tabs_in_doc_comments)]
+#![allow(clippy::redundant_field_names)]
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, error, fmt, fs, io};
+
+
+/// The JNI version the compiled classes must run on, with the same variants as
+/// `rjni::Version`, which decides the `--release` they're compiled for.
+///
+/// Each JNI version was introduced with the Java release of the same number
+/// (eg. JNI 1.6 with Java 6).
+///
+/// `rjni` can't be a dependency of a build script, so this is a copy of
+/// `rjni::Version`. A test in `rjni` checks the two have the same variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+	V11,
+	V12,
+	V14,
+	V15,
+	V16,
+	V17,
+	V18,
+	V9,
+	V10,
+	V19,
+	V20,
+	V21,
+}
+
+impl Version {
+	/// Returns the Java release that introduced the version.
+	pub fn release(self) -> u32 {
+		match self {
+			Version::V11 => 1,
+			Version::V12 => 2,
+			Version::V14 => 4,
+			Version::V15 => 5,
+			Version::V16 => 6,
+			Version::V17 => 7,
+			Version::V18 => 8,
+			Version::V9 => 9,
+			Version::V10 => 10,
+			Version::V19 => 19,
+			Version::V20 => 20,
+			Version::V21 => 21,
+		}
+	}
+}
+
+
+/// A tree of Java sources to compile.
+#[derive(Debug, Clone)]
+pub struct JavaBuild {
+	sources: PathBuf,
+	name: Option<String>,
+	release: Option<u32>,
+	classpath: Vec<PathBuf>,
+	args: Vec<String>,
+	jar: bool,
+	out_dir: Option<PathBuf>,
+}
+
+/// The compiled classes, which are also passed to the crate in environment
+/// variables.
+#[derive(Debug, Clone)]
+pub struct Output {
+	/// The directory containing the compiled classes, which is passed in
+	/// `RJNI_<NAME>_CLASSES`.
+	pub classes: PathBuf,
+
+	/// The Jar file, if one was built, which is passed in `RJNI_<NAME>_JAR`.
+	pub jar: Option<PathBuf>,
+}
+
+impl JavaBuild {
+	/// Create a build for the Java sources in a directory (relative to the
+	/// crate's root), which contains the root of their packages.
+	pub fn new<P: AsRef<Path>>(sources: P) -> JavaBuild {
+		JavaBuild {
+			sources: sources.as_ref().to_owned(),
+			name: None,
+			release: None,
+			classpath: Vec::new(),
+			args: Vec::new(),
+			jar: false,
+			out_dir: None,
+		}
+	}
+
+	/// Set the name used for the output directory and environment variables,
+	/// which defaults to the name of the source directory.
+	///
+	/// The variables are named in upper case, with anything other than letters
+	/// and digits replaced by `_`.
+	pub fn name(mut self, name: &str) -> JavaBuild {
+		self.name = Some(name.to_string());
+		self
+	}
+
+	/// Compile for the Java release of the JNI version passed to
+	/// `rjni::Options::version`.
+	///
+	/// When `javac` is too new to compile for the release, the oldest release
+	/// it supports is used instead, with a warning.
+	pub fn version(self, version: Version) -> JavaBuild {
+		self.release(version.release())
+	}
+
+	/// Compile for a Java release (eg. `8` or `17`), which otherwise defaults
+	/// to the version of `javac`.
+	pub fn release(mut self, release: u32) -> JavaBuild {
+		self.release = Some(release);
+		self
+	}
+
+	/// Add a directory or Jar file to the classpath the sources are compiled
+	/// against.
+	pub fn classpath<P: AsRef<Path>>(mut self, path: P) -> JavaBuild {
+		self.classpath.push(path.as_ref().to_owned());
+		self
+	}
+
+	/// Pass an extra argument to `javac` (eg. `-Xlint:all`).
+	pub fn arg(mut self, arg: &str) -> JavaBuild {
+		self.args.push(arg.to_string());
+		self
+	}
+
+	/// Set whether the classes are also packaged into a Jar file.
+	pub fn jar(mut self, flag: bool) -> JavaBuild {
+		self.jar = flag;
+		self
+	}
+
+	/// Set the directory to compile into, which otherwise defaults to
+	/// `OUT_DIR`. The classes are put in `rjni-build/<name>/classes` inside it.
+	pub fn out_dir<P: AsRef<Path>>(mut self, dir: P) -> JavaBuild {
+		self.out_dir = Some(dir.as_ref().to_owned());
+		self
+	}
+
+	/// Compile the sources, printing the instructions that tell Cargo when to
+	/// rerun the build script, and set the environment variables for the crate.
+	///
+	/// Unless `out_dir` is set, this must be called from a build script, since
+	/// it uses `OUT_DIR`.
+	pub fn compile(self) -> Result<Output, Error> {
+		let out_dir = match self.out_dir {
+			Some(ref dir) => dir.clone(),
+			None => match env::var_os("OUT_DIR") {
+				Some(dir) => PathBuf::from(dir),
+				None => return Err(Error::Config(String::from(
+					"OUT_DIR isn't set, so `compile` must be called from a build script"))),
+			},
+		};
+		let name = match self.name {
+			Some(ref name) => name.clone(),
+			None => match self.sources.file_name() {
+				Some(name) => name.to_string_lossy().into_owned(),
+				None => return Err(Error::Config(format!(
+					"`{}` has no name to use for its output, so one must be set",
+					self.sources.display()))),
+			},
+		};
+		let prefix = format!("RJNI_{}", variable_name(&name));
+
+		// Rerun when any source changes, or a new one is added
+		let mut sources = Vec::new();
+		find_sources(&self.sources, &mut sources)?;
+		sources.sort();
+		println!("cargo:rerun-if-changed={}", self.sources.display());
+		for source in &sources {
+			println!("cargo:rerun-if-changed={}", source.display());
+		}
+		println!("cargo:rerun-if-env-changed=JAVA_HOME");
+		if sources.is_empty() {
+			return Err(Error::Config(format!(
+				"`{}` doesn't contain any Java sources", self.sources.display())));
+		}
+
+		// Start from an empty directory, so deleted sources leave no classes
+		let dir = out_dir.join("rjni-build").join(&name);
+		let classes = dir.join("classes");
+		if classes.exists() {
+			fs::remove_dir_all(&classes)?;
+		}
+		fs::create_dir_all(&classes)?;
+
+		let javac = tool("javac");
+		let mut command = Command::new(&javac);
+		command.arg("-d").arg(&classes).arg("-encoding").arg("UTF-8");
+		if let Some(release) = self.release {
+			let javac_version = javac_version(&javac)?;
+			let release = match oldest_release(javac_version) {
+				Some(oldest) if release < oldest => {
+					println!("cargo:warning=javac {} can't compile for Java {}, so Java {} is \
+						used instead", javac_version, release, oldest);
+					oldest
+				},
+				_ => release,
+			};
+
+			// `--release` was only added in javac 9
+			if javac_version >= 9 {
+				command.arg("--release").arg(release.to_string());
+			} else {
+				let release = format!("1.{}", release);
+				command.arg("-source").arg(&release).arg("-target").arg(&release);
+			}
+		}
+		if !self.classpath.is_empty() {
+			let classpath = match env::join_paths(&self.classpath) {
+				Ok(classpath) => classpath,
+				Err(err) => return Err(Error::Config(err.to_string())),
+			};
+			command.arg("-classpath").arg(classpath);
+		}
+		command.args(&self.args);
+
+		// List the sources in an argument file, which can't be too long
+		let argfile = dir.join("sources.txt");
+		let listing = sources.iter()
+			.map(|source| quote(&source.to_string_lossy()))
+			.collect::<Vec<_>>()
+			.join("\n");
+		fs::write(&argfile, listing)?;
+		command.arg(format!("@{}", argfile.display()));
+		run(command, &javac)?;
+		println!("cargo:rustc-env={}_CLASSES={}", prefix, classes.display());
+
+		let jar = if self.jar {
+			let jar = dir.join(format!("{}.jar", name));
+			let tool = tool("jar");
+			let mut command = Command::new(&tool);
+			command.arg("cf").arg(&jar).arg("-C").arg(&classes).arg(".");
+			run(command, &tool)?;
+			println!("cargo:rustc-env={}_JAR={}", prefix, jar.display());
+			Some(jar)
+		} else {
+			None
+		};
+
+		Ok(Output {
+			classes: classes,
+			jar: jar,
+		})
+	}
+}
+
+/// Adds the `.java` files in a directory and its subdirectories.
+fn find_sources(dir: &Path, sources: &mut Vec<PathBuf>) -> io::Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			find_sources(&path, sources)?;
+		} else if path.extension().is_some_and(|ext| ext == "java") {
+			sources.push(path);
+		}
+	}
+	Ok(())
+}
+
+/// Converts a name into the form used in environment variables.
+fn variable_name(name: &str) -> String {
+	name.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+		.collect()
+}
+
+/// Quotes an argument for a `javac` argument file.
+fn quote(arg: &str) -> String {
+	format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns the path to a JDK tool, in `JAVA_HOME` if it's set, or otherwise
+/// found on the `PATH`.
+fn tool(name: &str) -> OsString {
+	if let Some(home) = env::var_os("JAVA_HOME") {
+		let path = Path::new(&home).join("bin").join(name);
+		if path.is_file() || path.with_extension("exe").is_file() {
+			return path.into_os_string();
+		}
+	}
+	OsString::from(name)
+}
+
+/// Returns the major version of `javac` (eg. `8` for `javac 1.8.0_392`).
+fn javac_version(javac: &OsString) -> Result<u32, Error> {
+	let output = match Command::new(javac).arg("-version").output() {
+		Ok(output) => output,
+		Err(err) => return Err(Error::NotFound(format!(
+			"couldn't run `{}`: {}", javac.to_string_lossy(), err))),
+	};
+
+	// Older versions print to stderr
+	let text = format!("{}{}", String::from_utf8_lossy(&output.stdout),
+		String::from_utf8_lossy(&output.stderr));
+	let version = text.split_whitespace()
+		.skip_while(|&word| word != "javac")
+		.nth(1)
+		.unwrap_or("");
+	let mut parts = version.split(|c: char| !c.is_ascii_digit());
+	let major = match parts.next().and_then(|part| part.parse().ok()) {
+		Some(1) => parts.next().and_then(|part| part.parse().ok()),
+		major => major,
+	};
+	match major {
+		Some(major) => Ok(major),
+		None => Err(Error::NotFound(format!(
+			"couldn't read the version of `{}` from `{}`", javac.to_string_lossy(), text.trim()))),
+	}
+}
+
+/// Returns the oldest release a version of `javac` can compile for, if known.
+fn oldest_release(javac_version: u32) -> Option<u32> {
+	match javac_version {
+		9..=11 => Some(6),
+		12..=19 => Some(7),
+		20.. => Some(8),
+		_ => None,
+	}
+}
+
+/// Runs a JDK tool, returning an error with its output if it fails.
+fn run(mut command: Command, tool: &OsString) -> Result<(), Error> {
+	let output = match command.output() {
+		Ok(output) => output,
+		Err(err) => return Err(Error::NotFound(format!(
+			"couldn't run `{}` (is a JDK installed, and JAVA_HOME set?): {}",
+			tool.to_string_lossy(), err))),
+	};
+	if output.status.success() {
+		return Ok(());
+	}
+	Err(Error::Failed(format!("`{}` failed with {}:\n{}{}", tool.to_string_lossy(),
+		output.status, String::from_utf8_lossy(&output.stdout),
+		String::from_utf8_lossy(&output.stderr))))
+}
+
+
+
+//
+//  Error Handling
+//
+
+/// An error returned when the sources can't be compiled.
+#[derive(Debug)]
+pub enum Error {
+	/// A build that's set up incorrectly.
+	Config(String),
+
+	/// A JDK tool that couldn't be found or run.
+	NotFound(String),
+
+	/// A JDK tool that failed, with its output (eg. `javac`'s compile errors).
+	Failed(String),
+
+	/// An error reading the sources or writing the output.
+	Io(io::Error),
+}
+
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Error {
+		Error::Io(err)
+	}
+}
+
+impl error::Error for Error {}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Config(msg) => write!(f, "Invalid Java build: {}", msg),
+			Error::NotFound(msg) => write!(f, "JDK not found: {}", msg),
+			Error::Failed(msg) => write!(f, "Java build failed: {}", msg),
+			Error::Io(err) => write!(f, "Java build failed: {}", err),
+		}
+	}
+}
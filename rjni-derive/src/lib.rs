@@ -0,0 +1,583 @@
+//
+//  rjni-derive
+//
+
+//! Derive macros for `rjni`'s `IntoJava` and `FromJava` traits, which copy a
+//! Rust struct into a Java object and back, or map a Rust enum to a Java one.
+//!
+//! Enable the `derive` feature on `rjni` to use them:
+//!
+//! ```rust,ignore
+//! use rjni::{IntoJava, FromJava};
+//!
+//! #[derive(IntoJava, FromJava)]
+//! #[java(class = "com/acme/Order")]
+//! struct Order {
+//! 	// Maps to the Java field `orderId`
+//! 	order_id: i64,
+//!
+//! 	// Uses the `getCustomer` and `setCustomer` methods
+//! 	#[java(getter, setter)]
+//! 	customer: String,
+//!
+//! 	// Maps to the Java field `items`
+//! 	#[java(name = "items")]
+//! 	lines: Vec<String>,
+//!
+//! 	// Isn't copied, and is set to its default value when converting from
+//! 	// Java
+//! 	#[java(skip)]
+//! 	cached_total: Option<f64>,
+//! }
+//! ```
+//!
+//! Each field is mapped to the Java field with the same name in camel case,
+//! and must have a type that implements `IntoJava` or `FromJava`. The field
+//! and method IDs are looked up once, and cached for the life of the program.
+//!
+//! # Attributes
+//!
+//! On the struct:
+//!
+//! * `class = "com/acme/Order"`: the Java class, which is required.
+//! * `accessors`: use getter and setter methods for every field, rather than
+//!   accessing the fields directly.
+//!
+//! On a field:
+//!
+//! * `name = "orderId"`: the name of the Java field, or the property used to
+//!   name its getter and setter.
+//! * `getter` or `getter = "fetchOrderId"`: read the field with a getter,
+//!   named `getOrderId` (or `isOrderId` for a `bool`) unless given.
+//! * `setter` or `setter = "storeOrderId"`: write the field with a setter,
+//!   named `setOrderId` unless given.
+//! * `skip`: don't copy the field. It's set to its default value when
+//!   converting from Java.
+//!
+//! Converting into Java creates an object using the class' public constructor
+//! that takes no arguments.
+//!
+//! # Enums
+//!
+//! A fieldless enum can be mapped to a Java enum class instead:
+//!
+//! ```rust,ignore
+//! #[derive(IntoJava, FromJava)]
+//! #[java(class = "java/util/concurrent/TimeUnit")]
+//! enum TimeUnit {
+//! 	// Maps to the constant `SECONDS`
+//! 	Seconds,
+//!
+//! 	#[java(name = "MILLISECONDS")]
+//! 	Millis,
+//! }
+//! ```
+//!
+//! Each variant is mapped to the constant with the same name in screaming
+//! snake case, unless it's given a `name`. Converting from a constant that
+//! doesn't match any variant returns an error.
+
+#![allow(clippy::tabs_in_doc_comments)]
+#![allow(clippy::redundant_field_names)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr, Type};
+use syn::meta::ParseNestedMeta;
+
+
+/// Derives `rjni::IntoJava` for a struct, copying each field into a new
+/// instance of a Java class, or for a fieldless enum.
+#[proc_macro_derive(IntoJava, attributes(java))]
+pub fn derive_into_java(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	JavaClass::parse(&input)
+		.map(|class| class.expand_into_java())
+		.unwrap_or_else(syn::Error::into_compile_error)
+		.into()
+}
+
+/// Derives `rjni::FromJava` for a struct, copying each field out of an
+/// instance of a Java class, or for a fieldless enum.
+#[proc_macro_derive(FromJava, attributes(java))]
+pub fn derive_from_java(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	JavaClass::parse(&input)
+		.map(|class| class.expand_from_java())
+		.unwrap_or_else(syn::Error::into_compile_error)
+		.into()
+}
+
+
+
+//
+//  Parsing
+//
+
+/// A struct or enum mapped to a Java class.
+struct JavaClass {
+	/// The name of the Rust type.
+	ident: Ident,
+
+	/// The internal name of the Java class (eg. `com/acme/Order`).
+	class: String,
+
+	/// The contents of the Rust type.
+	shape: Shape,
+}
+
+/// The contents of a type mapped to a Java class.
+enum Shape {
+	/// A struct's fields, in declaration order.
+	Struct(Vec<JavaField>),
+
+	/// An enum's variants, each mapped to an enum constant.
+	Enum(Vec<JavaVariant>),
+}
+
+/// A struct field mapped to a Java field or property.
+struct JavaField {
+	/// The name of the Rust field.
+	ident: Ident,
+
+	/// The type of the Rust field.
+	ty: Type,
+
+	/// The name of the Java field, or the property used to name its getter and
+	/// setter.
+	name: String,
+
+	/// The name of the getter to read the field with, if any.
+	getter: Option<String>,
+
+	/// The name of the setter to write the field with, if any.
+	setter: Option<String>,
+
+	/// True if the field isn't copied.
+	skip: bool,
+}
+
+/// An enum variant mapped to a Java enum constant.
+struct JavaVariant {
+	/// The name of the Rust variant.
+	ident: Ident,
+
+	/// The name of the Java constant.
+	name: String,
+}
+
+impl JavaClass {
+	/// Parses a struct or enum and its `java` attributes.
+	fn parse(input: &DeriveInput) -> syn::Result<JavaClass> {
+		if !input.generics.params.is_empty() {
+			return Err(syn::Error::new_spanned(&input.generics,
+				"generic types can't be mapped to a Java class"));
+		}
+
+		// Parse the struct's attributes
+		let mut class = None;
+		let mut accessors = false;
+		for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("java")) {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("class") {
+					let name = meta.value()?.parse::<LitStr>()?.value();
+					class = Some(name.replace('.', "/"));
+					Ok(())
+				} else if meta.path.is_ident("accessors") {
+					accessors = true;
+					Ok(())
+				} else {
+					Err(meta.error("expected `class` or `accessors`"))
+				}
+			})?;
+		}
+		let class = class.ok_or_else(|| syn::Error::new_spanned(&input.ident,
+			"missing the Java class, eg. `#[java(class = \"com/acme/Order\")]`"))?;
+
+		// Parse each field or variant
+		let shape = match input.data {
+			Data::Struct(ref data) => match data.fields {
+				Fields::Named(ref fields) => Shape::Struct(fields.named.iter()
+					.map(|field| JavaField::parse(field, accessors))
+					.collect::<syn::Result<Vec<_>>>()?),
+				_ => return Err(syn::Error::new_spanned(&input.ident,
+					"only structs with named fields can be mapped to a Java class")),
+			},
+			Data::Enum(ref data) => Shape::Enum(data.variants.iter()
+				.map(JavaVariant::parse)
+				.collect::<syn::Result<Vec<_>>>()?),
+			Data::Union(_) => return Err(syn::Error::new_spanned(&input.ident,
+				"unions can't be mapped to a Java class")),
+		};
+
+		Ok(JavaClass {
+			ident: input.ident.clone(),
+			class: class,
+			shape: shape,
+		})
+	}
+}
+
+impl JavaField {
+	/// Parses a field and its `java` attributes.
+	fn parse(field: &syn::Field, accessors: bool) -> syn::Result<JavaField> {
+		let ident = field.ident.clone().unwrap();
+		let mut name = None;
+		let mut getter = None;
+		let mut setter = None;
+		let mut skip = false;
+		if accessors {
+			getter = Some(None);
+			setter = Some(None);
+		}
+
+		for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("java")) {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("name") {
+					name = Some(meta.value()?.parse::<LitStr>()?.value());
+				} else if meta.path.is_ident("getter") {
+					getter = Some(optional_name(&meta)?);
+				} else if meta.path.is_ident("setter") {
+					setter = Some(optional_name(&meta)?);
+				} else if meta.path.is_ident("skip") {
+					skip = true;
+				} else {
+					return Err(meta.error(
+						"expected `name`, `getter`, `setter` or `skip`"));
+				}
+				Ok(())
+			})?;
+		}
+
+		// Getters and setters are named after the property, following the
+		// JavaBeans conventions
+		let name = name.unwrap_or_else(|| camel_case(&ident.to_string()));
+		let property = capitalise(&name);
+		let prefix = if is_bool(&field.ty) { "is" } else { "get" };
+		let getter = getter.map(|getter| {
+			getter.unwrap_or_else(|| format!("{}{}", prefix, property))
+		});
+		let setter = setter.map(|setter| {
+			setter.unwrap_or_else(|| format!("set{}", property))
+		});
+
+		Ok(JavaField {
+			ident: ident,
+			ty: field.ty.clone(),
+			name: name,
+			getter: getter,
+			setter: setter,
+			skip: skip,
+		})
+	}
+}
+
+impl JavaVariant {
+	/// Parses an enum variant and its `java` attributes.
+	fn parse(variant: &syn::Variant) -> syn::Result<JavaVariant> {
+		if !matches!(variant.fields, Fields::Unit) {
+			return Err(syn::Error::new_spanned(&variant.ident,
+				"only fieldless variants can be mapped to an enum constant"));
+		}
+
+		let mut name = None;
+		for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("java")) {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("name") {
+					name = Some(meta.value()?.parse::<LitStr>()?.value());
+					Ok(())
+				} else {
+					Err(meta.error("expected `name`"))
+				}
+			})?;
+		}
+
+		Ok(JavaVariant {
+			ident: variant.ident.clone(),
+			name: name.unwrap_or_else(|| screaming_snake_case(&variant.ident.to_string())),
+		})
+	}
+}
+
+/// Parses the optional method name given to `getter` or `setter`.
+fn optional_name(meta: &ParseNestedMeta) -> syn::Result<Option<String>> {
+	if meta.input.peek(syn::Token![=]) {
+		Ok(Some(meta.value()?.parse::<LitStr>()?.value()))
+	} else {
+		Ok(None)
+	}
+}
+
+/// Converts a snake case Rust name into a camel case Java one (eg. `order_id`
+/// into `orderId`).
+fn camel_case(name: &str) -> String {
+	let name = name.trim_start_matches("r#");
+	let mut result = String::with_capacity(name.len());
+	let mut upper = false;
+	for ch in name.chars() {
+		if ch == '_' && !result.is_empty() {
+			upper = true;
+		} else if upper {
+			result.extend(ch.to_uppercase());
+			upper = false;
+		} else {
+			result.push(ch);
+		}
+	}
+	result
+}
+
+/// Converts a camel case Rust name into the screaming snake case used for
+/// Java constants (eg. `MilliSeconds` into `MILLI_SECONDS`, and `HttpsURL`
+/// into `HTTPS_URL`).
+fn screaming_snake_case(name: &str) -> String {
+	let name = name.trim_start_matches("r#");
+	let chars = name.chars().collect::<Vec<_>>();
+	let mut result = String::with_capacity(name.len() + 4);
+	for (i, &ch) in chars.iter().enumerate() {
+		// Start a new word at an upper case letter following a lower case one,
+		// or at the last capital in a run of them (the start of the next word)
+		if i > 0 && ch.is_uppercase() {
+			let previous = chars[i - 1];
+			let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+			if !previous.is_uppercase() && previous != '_'
+					|| previous.is_uppercase() && next_lower {
+				result.push('_');
+			}
+		}
+		result.extend(ch.to_uppercase());
+	}
+	result
+}
+
+/// Capitalises the first letter of a name.
+fn capitalise(name: &str) -> String {
+	let mut chars = name.chars();
+	match chars.next() {
+		Some(first) => first.to_uppercase().chain(chars).collect(),
+		None => String::new(),
+	}
+}
+
+/// Returns true if a type is `bool`.
+fn is_bool(ty: &Type) -> bool {
+	match ty {
+		Type::Path(path) => path.qself.is_none() && path.path.is_ident("bool"),
+		_ => false,
+	}
+}
+
+
+
+//
+//  Code Generation
+//
+
+impl JavaClass {
+	/// Generates the `IntoJava` implementation.
+	fn expand_into_java(&self) -> TokenStream2 {
+		let ident = &self.ident;
+		let class = &self.class;
+		let body = match self.shape {
+			Shape::Struct(ref fields) => {
+				let writes = fields.iter()
+					.filter(|field| !field.skip)
+					.map(JavaField::write);
+				quote! {
+					let object = CLASS.class(jvm)?.instantiate(&[])?;
+					#(#writes)*
+					::std::result::Result::Ok(::rjni::Value::Object(object))
+				}
+			},
+			Shape::Enum(ref variants) => {
+				let arms = variants.iter().map(|variant| {
+					let name = &variant.name;
+					let variant = &variant.ident;
+					quote! { #ident::#variant => #name }
+				});
+				quote! {
+					let name = match self {
+						#(#arms),*
+					};
+					CLASS.enum_constant(jvm, name).map(::rjni::Value::Object)
+				}
+			},
+		};
+
+		quote! {
+			const _: () = {
+				static CLASS: ::rjni::ClassCache = ::rjni::ClassCache::new(#class);
+
+				impl<'a> ::rjni::IntoJava<'a> for #ident {
+					fn java_type() -> ::rjni::JavaType {
+						::rjni::JavaType::Object(::std::string::String::from(#class))
+					}
+
+					fn into_java(self, jvm: &'a ::rjni::JavaVM)
+							-> ::rjni::Result<::rjni::Value<'a>> {
+						#body
+					}
+				}
+			};
+		}
+	}
+
+	/// Generates the `FromJava` implementation.
+	fn expand_from_java(&self) -> TokenStream2 {
+		let ident = &self.ident;
+		let class = &self.class;
+		let binary_name = class.replace('/', ".");
+		let body = match self.shape {
+			Shape::Struct(ref fields) => {
+				let reads = fields.iter().map(JavaField::read);
+				quote! {
+					::std::result::Result::Ok(#ident {
+						#(#reads),*
+					})
+				}
+			},
+			Shape::Enum(ref variants) => {
+				let arms = variants.iter().map(|variant| {
+					let name = &variant.name;
+					let variant = &variant.ident;
+					quote! { #name => ::std::result::Result::Ok(#ident::#variant) }
+				});
+				quote! {
+					match &object.enum_name()?[..] {
+						#(#arms,)*
+						name => ::std::result::Result::Err(::rjni::Error::Conversion(
+							::std::format!("unknown constant `{}` of {}", name, #binary_name))),
+					}
+				}
+			},
+		};
+
+		quote! {
+			const _: () = {
+				static CLASS: ::rjni::ClassCache = ::rjni::ClassCache::new(#class);
+
+				impl<'a> ::rjni::FromJava<'a> for #ident {
+					fn java_type() -> ::rjni::JavaType {
+						::rjni::JavaType::Object(::std::string::String::from(#class))
+					}
+
+					fn from_java(value: ::rjni::Value<'a>) -> ::rjni::Result<Self> {
+						let object = <::rjni::Object<'a> as ::rjni::FromJava<'a>>::from_java(value)?;
+						let jvm = object.jvm();
+
+						// Accessing an object of the wrong class is undefined
+						let found = if object.is_null() {
+							::std::option::Option::Some(::std::string::String::from("null"))
+						} else if !object.is_instance_of(CLASS.class(jvm)?) {
+							::std::option::Option::Some(object.class_name()?)
+						} else {
+							::std::option::Option::None
+						};
+						if let ::std::option::Option::Some(found) = found {
+							return ::std::result::Result::Err(::rjni::Error::Conversion(
+								::std::format!("expected {}, found {}", #binary_name, found)));
+						}
+
+						#body
+					}
+				}
+			};
+		}
+	}
+}
+
+impl JavaField {
+	/// Generates the statement copying this field into the Java `object`.
+	fn write(&self) -> TokenStream2 {
+		let ident = &self.ident;
+		let ty = &self.ty;
+		let store = match self.setter {
+			Some(ref setter) => quote! {
+				let signature = ::std::format!("({})V", kind.descriptor());
+				CLASS.method(jvm, #setter, &signature)?.call(&object, &[value])?;
+			},
+			None => {
+				let name = &self.name;
+				quote! {
+					CLASS.field_with_sig(jvm, #name, &kind.descriptor())?.set(&object, value)?;
+				}
+			},
+		};
+
+		quote! {
+			{
+				let kind = <#ty as ::rjni::IntoJava<'a>>::java_type();
+				let value = <#ty as ::rjni::IntoJava<'a>>::into_java(self.#ident, jvm)?;
+				#store
+			}
+		}
+	}
+
+	/// Generates the field initialiser reading this field from the Java
+	/// `object`.
+	fn read(&self) -> TokenStream2 {
+		let ident = &self.ident;
+		let ty = &self.ty;
+		if self.skip {
+			return quote! {
+				#ident: ::std::default::Default::default()
+			};
+		}
+
+		let load = match self.getter {
+			Some(ref getter) => quote! {
+				let signature = ::std::format!("(){}", kind.descriptor());
+				CLASS.method(jvm, #getter, &signature)?.call(&object, &[])?
+			},
+			None => {
+				let name = &self.name;
+				quote! {
+					CLASS.field_with_sig(jvm, #name, &kind.descriptor())?.get(&object)?
+				}
+			},
+		};
+
+		quote! {
+			#ident: {
+				let kind = <#ty as ::rjni::FromJava<'a>>::java_type();
+				let value = { #load };
+				<#ty as ::rjni::FromJava<'a>>::from_java(value)?
+			}
+		}
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn camel_case_names() {
+		assert_eq!(camel_case("order_id"), "orderId");
+		assert_eq!(camel_case("name"), "name");
+		assert_eq!(camel_case("http_url_path"), "httpUrlPath");
+		assert_eq!(camel_case("line_2"), "line2");
+		assert_eq!(camel_case("utf8_name"), "utf8Name");
+		assert_eq!(camel_case("_private"), "_private");
+		assert_eq!(camel_case("r#type"), "type");
+	}
+
+	#[test]
+	fn screaming_snake_case_names() {
+		assert_eq!(screaming_snake_case("MilliSeconds"), "MILLI_SECONDS");
+		assert_eq!(screaming_snake_case("Red"), "RED");
+		assert_eq!(screaming_snake_case("A"), "A");
+		assert_eq!(screaming_snake_case("HttpsURL"), "HTTPS_URL");
+		assert_eq!(screaming_snake_case("IOError"), "IO_ERROR");
+		assert_eq!(screaming_snake_case("HTTP"), "HTTP");
+		assert_eq!(screaming_snake_case("Level2"), "LEVEL2");
+		assert_eq!(screaming_snake_case("Utf8Name"), "UTF8_NAME");
+		assert_eq!(screaming_snake_case("Already_Split"), "ALREADY_SPLIT");
+		assert_eq!(screaming_snake_case("r#Type"), "TYPE");
+	}
+}
@@ -0,0 +1,44 @@
+//
+//  Build Script
+//  Compiles the Java classes used by the examples, and generates bindings for
+//  them.
+//
+
+use std::env;
+use std::path::Path;
+
+use rjni_bindgen::Bindgen;
+use rjni_build::{JavaBuild, Version};
+
+fn main() {
+	let out = env::var("OUT_DIR").unwrap();
+
+	// Compile `examples/*.java` into `RJNI_EXAMPLES_CLASSES`, for Java 8, with
+	// `-parameters` so the bindings keep their argument names
+	let build = JavaBuild::new("examples")
+		.version(Version::V18)
+		.arg("-parameters");
+	let examples = match build.compile() {
+		Ok(output) => output,
+		Err(err) => panic!("{}", err),
+	};
+	bindings(Bindgen::new().input(&examples.classes).class("Test"), &out, "test.rs");
+
+	// Check the bindings generated for `rjni-bindgen`'s test fixtures compile,
+	// and work (in `tests/fixtures.rs`)
+	let build = JavaBuild::new("../rjni-bindgen/tests/fixtures")
+		.version(Version::V18)
+		.arg("-parameters");
+	let fixtures = match build.compile() {
+		Ok(output) => output,
+		Err(err) => panic!("{}", err),
+	};
+	bindings(Bindgen::new().input(&fixtures.classes), &out, "fixtures.rs");
+}
+
+/// Generates bindings into a file in the output directory.
+fn bindings(bindgen: Bindgen, out: &str, name: &str) {
+	if let Err(err) = bindgen.write_to(Path::new(out).join(name)) {
+		panic!("{}", err);
+	}
+}
@@ -3,13 +3,11 @@
 //  Test Class
 //
 
-// We compile this class for the version of the JVM we're using, which is
-// specified in the C interface code as JVM_VERSION_1_6 (ie. JVM 1.6).
-//
-// Thus we need to compile this file so it is compatible with this version.
-// We do this by specifying the -source and -target arguments to javac:
-//
-//   javac -source 1.6 -target 1.6 Test.java
+// The `rjni-examples` build script compiles this class with `rjni-build`, for
+// Java 8, into the directory the examples find in `RJNI_EXAMPLES_CLASSES`. So
+// it runs on the JVM versions the examples ask for (`Version::V16` and up), and
+// is rebuilt whenever it changes, along with the bindings `instance.rs` uses,
+// which are generated from it by `rjni-bindgen`.
 
 import java.lang.System;
 
@@ -0,0 +1,41 @@
+//
+//  Derived Conversions
+//  An example that demonstrates copying a Java object's fields into a Rust
+//  struct, using the `derive` feature.
+//
+
+extern crate rjni;
+
+use rjni::{JavaVM, Version, Classpath, Options, Value, FromJava};
+
+/// A snapshot of the state of a `Test` object.
+#[derive(FromJava)]
+#[java(class = "Test")]
+struct Snapshot {
+	/// Read directly from the `current` field.
+	current: i32,
+
+	/// Read from the field again, through the `getCurrent` method.
+	#[java(name = "current", getter)]
+	through_getter: i32,
+}
+
+fn main() {
+	// Use the classes compiled from the examples directory as our classpath
+	let classpath = Classpath::new().add(env!("RJNI_EXAMPLES_CLASSES"));
+
+	// Create the JVM
+	let options = Options::new()
+			.version(Version::V16)
+			.classpath(classpath);
+	let jvm = JavaVM::new(options).unwrap();
+
+	// Create an instance of the `Test` class, and increment its counter
+	let class = jvm.class("Test").unwrap();
+	let object = class.instantiate(&[Value::Int(5)]).unwrap();
+	object.call::<()>("incrementCurrent", &[]).unwrap();
+
+	// Copy the object's state into a Rust struct
+	let snapshot = Snapshot::from_java(Value::Object(object)).unwrap();
+	println!("current: {}, through getter: {}", snapshot.current, snapshot.through_getter);
+}
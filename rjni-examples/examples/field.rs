@@ -7,19 +7,13 @@
 
 extern crate rjni;
 
-use std::path::PathBuf;
-use std::env;
-
 use rjni::{JavaVM, Version, Classpath, Options, Value, Type};
 
 fn main() {
-	// Find the path to the manifest folder, then append the examples directory
-	// to it. This acts as our classpath, where the JVM will look for any .class
-	// files that we want to load.
-	let manifest = env::var("CARGO_MANIFEST_DIR").unwrap_or(".".to_string());
-	let mut path = PathBuf::from(manifest);
-	path.push("examples");
-	let classpath = Classpath::new().add(path);
+	// The build script compiles the Java classes in the examples directory,
+	// and tells us where it put them. This acts as our classpath, where the JVM
+	// will look for any .class files that we want to load.
+	let classpath = Classpath::new().add(env!("RJNI_EXAMPLES_CLASSES"));
 
 	// Create the list of options used to initialise the JVM, specifying the
 	// version number
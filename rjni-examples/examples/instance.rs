@@ -0,0 +1,47 @@
+
+//
+//  Instance
+//  An example that creates an instance of a Java class and calls a method on it.
+//
+
+extern crate rjni;
+
+use rjni::{JavaVM, Version, Classpath, Options};
+
+fn main() {
+	// The build script compiles the Java classes in the examples directory,
+	// and tells us where it put them. This acts as our classpath, where the JVM
+	// will look for any .class files that we want to load.
+	let classpath = Classpath::new().add(env!("RJNI_EXAMPLES_CLASSES"));
+
+	// Create the list of options used to initialise the JVM, specifying the
+	// version number
+	let options = Options::new()
+		.version(Version::V16)
+		.classpath(classpath);
+
+	// Create the Java virtual machine. The argument to this function
+	// is a list of paths that will be combined to create the Java
+	// classpath. The classpath is a list of directories the JVM
+	// will look in when trying to locate a .class or .jar file.
+	let jvm = JavaVM::new(options).unwrap();
+
+	// Create an instance of the `Test` class, through the bindings generated
+	// for it by `rjni-bindgen` (included below). Each binding calls the Java
+	// constructor or method with its exact descriptor, so the constructor
+	// takes a single integer, just like the Java one.
+	let object = Test::new(&jvm, 5).unwrap();
+
+	// Call the method `incrementCurrent` on the object we just created.
+	object.increment_current().unwrap();
+
+	// Call the `getCurrent` method on the object, which returns an `i32`.
+	let value = object.get_current().unwrap();
+
+	// Print the value we just fetched from the object.
+	println!("result: {}", value);
+}
+
+// The bindings for `Test`, which the build script generates from the class it
+// compiles
+include!(concat!(env!("OUT_DIR"), "/test.rs"));
@@ -6,19 +6,13 @@
 
 extern crate rjni;
 
-use std::path::PathBuf;
-use std::env;
-
-use rjni::{JavaVM, Version, Classpath, Options, Value, Type};
+use rjni::{JavaVM, Version, Classpath, Options, Value};
 
 fn main() {
-	// Find the path to the manifest folder, then append the examples directory
-	// to it. This acts as our classpath, where the JVM will look for any .class
-	// files that we want to load.
-	let manifest = env::var("CARGO_MANIFEST_DIR").unwrap_or(".".to_string());
-	let mut path = PathBuf::from(manifest);
-	path.push("examples");
-	let classpath = Classpath::new().add(path);
+	// The build script compiles the Java classes in the examples directory,
+	// and tells us where it put them. This acts as our classpath, where the JVM
+	// will look for any .class files that we want to load.
+	let classpath = Classpath::new().add(env!("RJNI_EXAMPLES_CLASSES"));
 
 	// Create the list of options used to initialise the JVM, specifying the
 	// version number
@@ -37,23 +31,21 @@ fn main() {
 	let class = jvm.class("Test").unwrap();
 
 	// Call a static method.
-	// The first argument is the name of the static, the second is an array
-	// containing the arguments to pass into the static, and the third
-	// is the return type of the static.
-	let value1 = class.call_static(
+	// The first argument is the name of the static, and the second is an
+	// array containing the arguments to pass into the static. The return
+	// type of the static is the Java type of the variable's type.
+	let value1: i32 = class.call_static(
 		"add",
-		&[Value::Int(1), Value::Int(9)],
-		Type::Int
+		&[Value::Int(1), Value::Int(9)]
 	).unwrap();
 
 	// Print the value that was returned by the static method.
 	println!("Result of 1 + 9: {:?}", value1);
 
 	// Call another static method, this time passing in some strings.
-	let value2 = class.call_static(
+	let value2: String = class.call_static(
 		"append",
-		&[Value::Str(String::from("hello"))],
-		Type::Str
+		&[Value::Str(String::from("hello"))]
 	).unwrap();
 
 	// Print the value.
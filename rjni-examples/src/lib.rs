@@ -0,0 +1,14 @@
+//
+//  rjni-examples
+//
+
+//! Examples for `rjni`, which are run with (eg.):
+//!
+//! ```bash
+//! $ cargo run -p rjni-examples --example instance
+//! ```
+//!
+//! They're kept in their own crate so that only they compile the Java classes
+//! in `examples`, rather than every crate that depends on `rjni`. Its build
+//! script also generates bindings for `rjni-bindgen`'s test fixtures, which
+//! `tests/fixtures.rs` checks compile and work.
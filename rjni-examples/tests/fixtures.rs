@@ -0,0 +1,47 @@
+//
+//  Fixture Bindings
+//  Checks the bindings `rjni-bindgen` generates for its test fixtures compile
+//  against `rjni`, and call the methods and fields they're generated for.
+//
+
+use rjni::{Classpath, JavaVM, Options, Version};
+
+include!(concat!(env!("OUT_DIR"), "/fixtures.rs"));
+
+#[test]
+fn calls_fixture_bindings() {
+	let classpath = Classpath::new().add(env!("RJNI_FIXTURES_CLASSES"));
+	let options = Options::new()
+		.version(Version::V18)
+		.classpath(classpath);
+	let jvm = JavaVM::new(options).unwrap();
+
+	// Constructors and overloads
+	let circle = Circle::new(&jvm, 2.0).unwrap();
+	assert_eq!(Circle::new_int_string(&jvm, 3, "label").unwrap().radius_field().unwrap(), 3.0);
+	assert_eq!(Circle::value_of(&jvm, 5).unwrap(), 5);
+	assert_eq!(Circle::value_of_char_array(&jvm, &['a', 'b']).unwrap(), 2);
+	assert_eq!(Circle::value_of_string(&jvm, "four").unwrap(), 4);
+
+	// Methods, including through the superclass and interface wrappers
+	assert_eq!(circle.area().unwrap(), std::f64::consts::PI * 4.0);
+	assert_eq!(circle.get().unwrap().as_deref(), Some("circle"));
+	let base = Base::from(circle.clone());
+	assert_eq!(base.names().unwrap().unwrap().to_vec().unwrap(), ["a", "b"]);
+	assert!(base.map().unwrap().unwrap().to_map().unwrap().is_empty());
+	let shape = circle.as_shape().unwrap().unwrap();
+	assert_eq!(shape.area().unwrap(), circle.area().unwrap());
+	assert_eq!(CircleInner::new(&jvm).unwrap().x().unwrap(), 3);
+
+	// Fields and constants
+	assert_eq!(circle.data_field().unwrap(), Some(vec![1, 2]));
+	circle.set_data_field(&[3, 4, 5]).unwrap();
+	assert_eq!(circle.data_field().unwrap(), Some(vec![3, 4, 5]));
+	assert_eq!(circle.boxed_field().unwrap(), None);
+	circle.set_boxed_field(Some(7)).unwrap();
+	assert_eq!(circle.boxed_field().unwrap(), Some(7));
+	Base::set_count_field(&jvm, 9).unwrap();
+	assert_eq!(Base::count_field(&jvm).unwrap(), 9);
+	assert!(Base::lock_field(&jvm).unwrap().is_some());
+	assert_eq!(Base::MAX, 10);
+}
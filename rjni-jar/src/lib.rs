@@ -0,0 +1,322 @@
+//
+//  rjni-jar
+//
+
+//! Reads Jar (and any Zip) archives without the JVM, for `rjni`, which follows
+//! the libraries listed in their manifests, and `rjni-bindgen`, which reads
+//! the class files in them.
+
+#![allow(clippy::redundant_field_names)]
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::fs::File;
+use std::path::Path;
+
+use miniz_oxide::inflate;
+
+
+/// The signature of the end of central directory record.
+const END_SIGNATURE: u32 = 0x06054b50;
+
+/// The signature of each central directory header.
+const CENTRAL_SIGNATURE: u32 = 0x02014b50;
+
+/// The signature of each local file header.
+const LOCAL_SIGNATURE: u32 = 0x04034b50;
+
+/// A Jar (or any Zip) archive read into memory, which can be searched for
+/// entries without the JVM.
+///
+/// Only what Jar files use is supported: stored and deflated entries, without
+/// Zip64 extensions or encryption.
+pub struct Jar {
+	data: Vec<u8>,
+	entries: Vec<Entry>,
+}
+
+/// An entry listed in an archive's central directory.
+struct Entry {
+	name: String,
+	method: u16,
+	compressed_size: usize,
+	size: usize,
+	offset: usize,
+}
+
+impl Jar {
+	/// Reads an archive, and its list of entries.
+	pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Jar> {
+		let mut data = Vec::new();
+		File::open(path)?.read_to_end(&mut data)?;
+		Jar::from_bytes(data)
+	}
+
+	/// Reads the list of entries in an archive's contents.
+	pub fn from_bytes(data: Vec<u8>) -> io::Result<Jar> {
+		// The end record is followed by a comment of at most 64 KiB
+		let min = data.len().saturating_sub(22 + 0xffff);
+		let end = (min..data.len().saturating_sub(21)).rev()
+			.find(|&pos| u32_at(&data, pos) == Some(END_SIGNATURE));
+		let end = match end {
+			Some(end) => end,
+			None => return Err(invalid("no end of central directory record")),
+		};
+		let count = u16_at(&data, end + 10).unwrap_or(0) as usize;
+		let mut pos = u32_at(&data, end + 16).unwrap_or(0) as usize;
+
+		let mut entries = Vec::with_capacity(count);
+		for _ in 0..count {
+			if u32_at(&data, pos) != Some(CENTRAL_SIGNATURE) {
+				return Err(invalid("malformed central directory"));
+			}
+			let field = |offset: usize| u16_at(&data, pos + offset).unwrap_or(0) as usize;
+			let (name_len, extra_len, comment_len) = (field(28), field(30), field(32));
+			let name = match data.get(pos + 46..pos + 46 + name_len) {
+				Some(name) => String::from_utf8_lossy(name).into_owned(),
+				None => return Err(invalid("truncated central directory")),
+			};
+			entries.push(Entry {
+				name: name,
+				method: field(10) as u16,
+				compressed_size: u32_at(&data, pos + 20).unwrap_or(0) as usize,
+				size: u32_at(&data, pos + 24).unwrap_or(0) as usize,
+				offset: u32_at(&data, pos + 42).unwrap_or(0) as usize,
+			});
+			pos += 46 + name_len + extra_len + comment_len;
+		}
+
+		Ok(Jar {
+			data: data,
+			entries: entries,
+		})
+	}
+
+	/// Returns the name of every entry (eg. `com/me/Test.class`).
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.entries.iter().map(|entry| entry.name.as_str())
+	}
+
+	/// Returns the uncompressed contents of an entry, or None if there's no
+	/// entry with the name.
+	pub fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+		let entry = match self.entries.iter().find(|entry| entry.name == name) {
+			Some(entry) => entry,
+			None => return Ok(None),
+		};
+
+		// The local header's name and extra field can differ from the central
+		// directory's, so its own lengths are used
+		if u32_at(&self.data, entry.offset) != Some(LOCAL_SIGNATURE) {
+			return Err(invalid("malformed local file header"));
+		}
+		let name_len = u16_at(&self.data, entry.offset + 26).unwrap_or(0) as usize;
+		let extra_len = u16_at(&self.data, entry.offset + 28).unwrap_or(0) as usize;
+		let start = entry.offset + 30 + name_len + extra_len;
+		let raw = match self.data.get(start..start + entry.compressed_size) {
+			Some(raw) => raw,
+			None => return Err(invalid("truncated entry")),
+		};
+
+		match entry.method {
+			0 => Ok(Some(raw.to_vec())),
+			8 => match inflate::decompress_to_vec_with_limit(raw, entry.size) {
+				Ok(contents) => Ok(Some(contents)),
+				Err(_) => Err(invalid("corrupt deflated entry")),
+			},
+			method => Err(invalid(&format!("unsupported compression method {}", method))),
+		}
+	}
+
+	/// Returns the main attributes of the archive's manifest
+	/// (`META-INF/MANIFEST.MF`), which are empty if it has none.
+	pub fn manifest(&self) -> io::Result<HashMap<String, String>> {
+		let manifest = self.read("META-INF/MANIFEST.MF")?.unwrap_or_default();
+		Ok(parse_manifest(&String::from_utf8_lossy(&manifest)))
+	}
+}
+
+/// Parses the main section of a manifest, whose lines are `Name: value`, and
+/// continue onto following lines that start with a space.
+fn parse_manifest(manifest: &str) -> HashMap<String, String> {
+	let mut attributes = HashMap::new();
+	let mut current: Option<(String, String)> = None;
+	for line in manifest.lines() {
+		if let Some(continued) = line.strip_prefix(' ') {
+			if let Some((_, ref mut value)) = current {
+				value.push_str(continued);
+			}
+			continue;
+		}
+		attributes.extend(current.take());
+
+		// The main section ends at the first blank line
+		if line.is_empty() {
+			break;
+		}
+		let mut parts = line.splitn(2, ':');
+		if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+			current = Some((name.trim().to_string(), value.trim_start().to_string()));
+		}
+	}
+	attributes.extend(current);
+	attributes
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+	data.get(pos..pos + 2).map(|bytes| u16::from(bytes[0]) | u16::from(bytes[1]) << 8)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+	data.get(pos..pos + 4).map(|bytes| {
+		u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 |
+			u32::from(bytes[3]) << 24
+	})
+}
+
+fn invalid(msg: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, format!("not a valid Jar file: {}", msg))
+}
+
+
+
+/// Builds archives for tests, in this crate and those that read archives with
+/// it.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+	use miniz_oxide::deflate;
+
+	use super::{LOCAL_SIGNATURE, CENTRAL_SIGNATURE, END_SIGNATURE};
+
+	/// Builds a Zip archive from each entry's name and contents, deflating
+	/// the contents of those marked.
+	pub fn zip(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+		fn u16_le(data: &mut Vec<u8>, value: usize) {
+			data.extend_from_slice(&(value as u16).to_le_bytes());
+		}
+		fn u32_le(data: &mut Vec<u8>, value: usize) {
+			data.extend_from_slice(&(value as u32).to_le_bytes());
+		}
+
+		let mut data = Vec::new();
+		let mut central = Vec::new();
+		for &(name, contents, deflated) in entries {
+			let (method, stored) = if deflated {
+				(8, deflate::compress_to_vec(contents, 6))
+			} else {
+				(0, contents.to_vec())
+			};
+			let offset = data.len();
+
+			// The CRC isn't checked, so it's left as 0
+			u32_le(&mut data, LOCAL_SIGNATURE as usize);
+			for &value in &[20, 0, method, 0, 0] {
+				u16_le(&mut data, value);
+			}
+			for &value in &[0, stored.len(), contents.len()] {
+				u32_le(&mut data, value);
+			}
+			u16_le(&mut data, name.len());
+			u16_le(&mut data, 0);
+			data.extend_from_slice(name.as_bytes());
+			data.extend_from_slice(&stored);
+
+			u32_le(&mut central, CENTRAL_SIGNATURE as usize);
+			for &value in &[20, 20, 0, method, 0, 0] {
+				u16_le(&mut central, value);
+			}
+			for &value in &[0, stored.len(), contents.len()] {
+				u32_le(&mut central, value);
+			}
+			for &value in &[name.len(), 0, 0, 0, 0] {
+				u16_le(&mut central, value);
+			}
+			u32_le(&mut central, 0);
+			u32_le(&mut central, offset);
+			central.extend_from_slice(name.as_bytes());
+		}
+
+		let central_offset = data.len();
+		data.extend_from_slice(&central);
+		u32_le(&mut data, END_SIGNATURE as usize);
+		for &value in &[0, 0, entries.len(), entries.len()] {
+			u16_le(&mut data, value);
+		}
+		u32_le(&mut data, central.len());
+		u32_le(&mut data, central_offset);
+		u16_le(&mut data, 0);
+		data
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::zip;
+
+	#[test]
+	fn reads_entries() {
+		let data = zip(&[
+			("a.txt", b"stored", false),
+			("dir/b.txt", b"deflated deflated deflated", true),
+		]);
+		let jar = Jar::from_bytes(data).unwrap();
+		assert_eq!(jar.names().collect::<Vec<_>>(), ["a.txt", "dir/b.txt"]);
+		assert_eq!(jar.read("a.txt").unwrap(), Some(b"stored".to_vec()));
+		assert_eq!(jar.read("dir/b.txt").unwrap(), Some(b"deflated deflated deflated".to_vec()));
+		assert_eq!(jar.read("missing").unwrap(), None);
+	}
+
+	#[test]
+	fn finds_end_record_before_comment() {
+		let mut data = zip(&[("a.txt", b"a", false)]);
+		let len = data.len();
+		data[len - 2] = 7;
+		data.extend_from_slice(b"comment");
+		let jar = Jar::from_bytes(data).unwrap();
+		assert_eq!(jar.read("a.txt").unwrap(), Some(b"a".to_vec()));
+	}
+
+	#[test]
+	fn rejects_invalid_archives() {
+		assert!(Jar::from_bytes(Vec::new()).is_err());
+		assert!(Jar::from_bytes(b"not a zip file at all, just some text".to_vec()).is_err());
+
+		// A central directory pointing past the end of the data
+		let mut data = zip(&[("a.txt", b"a", false)]);
+		let len = data.len();
+		data[len - 6..len - 2].copy_from_slice(&1000u32.to_le_bytes());
+		assert!(Jar::from_bytes(data).is_err());
+
+		// An entry with an unsupported compression method
+		let mut data = zip(&[("a.txt", b"a", false)]);
+		data[8] = 12;
+		let central = data.len() - 22 - 46 - "a.txt".len();
+		data[central + 10] = 12;
+		let jar = Jar::from_bytes(data).unwrap();
+		assert!(jar.read("a.txt").is_err());
+	}
+
+	#[test]
+	fn parses_manifest() {
+		let manifest = "Manifest-Version: 1.0\r\nMain-Class: com.me.Main\r\nClass-Path: lib/a.jar\r\n  lib/b.jar\r\n\r\nName: com/me/\r\nSealed: true\r\n";
+		let attributes = parse_manifest(manifest);
+		assert_eq!(attributes.len(), 3);
+		assert_eq!(attributes["Main-Class"], "com.me.Main");
+		assert_eq!(attributes["Class-Path"], "lib/a.jar lib/b.jar");
+		assert!(!attributes.contains_key("Sealed"));
+	}
+
+	#[test]
+	fn reads_manifest() {
+		let jar = Jar::from_bytes(zip(&[
+			("META-INF/MANIFEST.MF", b"Main-Class: Main\n", true),
+		])).unwrap();
+		assert_eq!(jar.manifest().unwrap()["Main-Class"], "Main");
+
+		let jar = Jar::from_bytes(zip(&[("a.txt", b"a", false)])).unwrap();
+		assert!(jar.manifest().unwrap().is_empty());
+	}
+}
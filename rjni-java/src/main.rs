@@ -0,0 +1,276 @@
+//
+//  rjni-java
+//
+
+//! A launcher for Java applications built on `rjni`, which accepts the common
+//! flags of the `java` command:
+//!
+//! ```bash
+//! $ rjni-java -cp app.jar:libs/dep.jar -Dapp.env=prod -Xmx512m com.acme.Main args...
+//! $ rjni-java -Xmx1g -jar app.jar args...
+//! ```
+//!
+//! Any other option starting with `-` (eg. `-Xss2m` or `-XX:+UseG1GC`) is
+//! passed to the JVM as is, and module options like `--add-opens` take their
+//! value from the next argument, as with `java`. Options only `java` itself
+//! understands (eg. `-version`) are rejected. Like `java`, options are also
+//! read from `@argfiles` and the `JDK_JAVA_OPTIONS` environment variable. When
+//! the application fails, its exception trace and exit status are printed.
+
+#![allow(clippy::redundant_field_names)]
+
+use std::path::PathBuf;
+use std::{env, process};
+
+use rjni::{JavaVM, Options, Classpath, Error};
+
+
+/// The usage message printed for `-help`, or when no main class is given.
+const USAGE: &str = "\
+Usage: rjni-java [options] <main class> [args...]
+       rjni-java [options] -jar <jar file> [args...]
+
+Options:
+    -cp, -classpath, --class-path <path>
+                      directories and Jar files to search for classes,
+                      separated by the platform's path separator
+    -D<name>=<value>  set a system property
+    -Xms<size>        set the initial heap size (eg. 64m)
+    -Xmx<size>        set the maximum heap size (eg. 1g)
+    -p, --module-path <path>
+                      directories of modules
+    --add-modules, --add-opens, --add-exports <value>
+                      module options, as for java
+    -jar <jar file>   run the Main-Class of an executable Jar
+    @<argfile>        read options from a file
+    -h, -help         print this message
+
+Any other option is passed to the JVM.";
+
+/// The application to run.
+enum Target {
+	/// A main class, by its binary name.
+	Class(String),
+
+	/// An executable Jar file.
+	Jar(PathBuf),
+}
+
+/// The parsed command line.
+struct Launch {
+	options: Options,
+	target: Target,
+	args: Vec<String>,
+}
+
+fn main() {
+	let launch = match parse(env::args().skip(1)) {
+		Ok(Some(launch)) => launch,
+		Ok(None) => {
+			println!("{}", USAGE);
+			return;
+		},
+		Err(msg) => {
+			eprintln!("rjni-java: {}\n\n{}", msg, USAGE);
+			process::exit(1);
+		},
+	};
+
+	// The trace of an exception thrown by `main` has already been printed by
+	// its thread's uncaught exception handler
+	let status = match run(launch) {
+		Ok(()) => return,
+		Err(Error::Exit(status)) => status,
+		Err(Error::Exception(info)) => {
+			eprintln!("rjni-java: {}", info.message());
+			1
+		},
+		Err(err) => {
+			eprintln!("rjni-java: {}", err);
+			1
+		},
+	};
+	eprintln!("rjni-java: exited with status {}", status);
+	process::exit(status);
+}
+
+/// Runs the application, until it and the JVM have finished.
+fn run(launch: Launch) -> rjni::Result<()> {
+	match launch.target {
+		Target::Class(class) => JavaVM::run_main(launch.options, &class, &launch.args),
+		Target::Jar(path) => JavaVM::run_jar(launch.options, &path, &launch.args),
+	}
+}
+
+/// Parses the command line arguments (excluding the program name), or returns
+/// None if the usage message was requested.
+fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Launch>, String> {
+	let mut options = Options::default()
+		.ignore_unrecognized_arguments(false)
+		.jdk_java_options()
+		.map_err(|err| err.to_string())?;
+	let mut classpath = None;
+	let mut target = None;
+
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"-cp" | "-classpath" | "--class-path" => {
+				let path = args.next().ok_or_else(|| format!("{} requires a path", arg))?;
+				classpath = Some(path);
+			},
+			"-jar" => {
+				let path = args.next().ok_or("-jar requires a Jar file")?;
+				target = Some(Target::Jar(PathBuf::from(path)));
+				break;
+			},
+			"-h" | "-help" | "--help" | "-?" => return Ok(None),
+			_ if arg.starts_with("--class-path=") => {
+				classpath = Some(arg["--class-path=".len()..].to_string());
+			},
+			_ if arg.starts_with('@') => {
+				options = options.argfile(&arg[1..]).map_err(|err| err.to_string())?;
+			},
+			_ if arg.starts_with("-Xms") => {
+				options = options.initial_heap_size(parse_size(&arg["-Xms".len()..])?);
+			},
+			_ if arg.starts_with("-Xmx") => {
+				options = options.max_heap_size(parse_size(&arg["-Xmx".len()..])?);
+			},
+			_ if arg.starts_with('-') => {
+				// Includes `-D` system properties, and module options that take
+				// the next argument
+				options = options.java_argument(arg, &mut args).map_err(|err| err.to_string())?;
+			},
+			_ => {
+				target = Some(Target::Class(arg));
+				break;
+			},
+		}
+	}
+
+	let target = target.ok_or("no main class or Jar file given")?;
+	if let Target::Class(_) = target {
+		options = options.classpath(build_classpath(classpath));
+	}
+
+	Ok(Some(Launch {
+		options: options,
+		target: target,
+		args: args.collect(),
+	}))
+}
+
+/// Returns the classpath given on the command line, falling back to
+/// `CLASSPATH` and then the current directory like `java`.
+fn build_classpath(classpath: Option<String>) -> Classpath {
+	match classpath {
+		Some(classpath) => env::split_paths(&classpath)
+			.fold(Classpath::new(), |classpath, path| classpath.add(path)),
+		None if env::var_os("CLASSPATH").is_some() => Classpath::from_env(),
+		None => Classpath::new().add("."),
+	}
+}
+
+/// Parses a heap size in bytes, with an optional `k`, `m` or `g` suffix.
+fn parse_size(size: &str) -> Result<usize, String> {
+	let (digits, multiplier) = match size.chars().last() {
+		Some('k') | Some('K') => (&size[..size.len() - 1], 1 << 10),
+		Some('m') | Some('M') => (&size[..size.len() - 1], 1 << 20),
+		Some('g') | Some('G') => (&size[..size.len() - 1], 1 << 30),
+		_ => (size, 1),
+	};
+	digits.parse::<usize>().ok()
+		.and_then(|digits| digits.checked_mul(multiplier))
+		.filter(|&bytes| bytes > 0)
+		.ok_or_else(|| format!("invalid heap size `{}`", size))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rjni_build::JavaBuild;
+
+	fn launch(args: &[&str]) -> Result<Option<Launch>, String> {
+		parse(args.iter().map(|arg| arg.to_string()))
+	}
+
+	#[test]
+	fn jar_ends_options() {
+		match launch(&["-Xmx1g", "-jar", "app.jar", "-Xms1g", "arg"]).unwrap() {
+			Some(Launch { target: Target::Jar(path), args, .. }) => {
+				assert_eq!(path, PathBuf::from("app.jar"));
+				assert_eq!(args, ["-Xms1g", "arg"]);
+			},
+			_ => panic!("expected a Jar"),
+		}
+		assert!(launch(&["-jar"]).is_err());
+	}
+
+	#[test]
+	fn module_options_take_next_argument() {
+		let args = ["--add-opens", "java.base/java.lang=ALL-UNNAMED", "com.me.Main", "arg"];
+		match launch(&args).unwrap() {
+			Some(Launch { target: Target::Class(class), args, .. }) => {
+				assert_eq!(class, "com.me.Main");
+				assert_eq!(args, ["arg"]);
+			},
+			_ => panic!("expected a main class"),
+		}
+		assert!(launch(&["--add-opens"]).is_err());
+	}
+
+	#[test]
+	fn rejects_launcher_options() {
+		assert!(launch(&["-version"]).is_err());
+		assert!(launch(&["-version", "com.me.Main"]).is_err());
+		assert!(launch(&["-Xmx1g"]).is_err());
+		assert!(launch(&["-help"]).unwrap().is_none());
+	}
+
+	#[test]
+	fn falls_back_to_classpath_variable() {
+		let entries = |classpath: Option<&str>| {
+			build_classpath(classpath.map(String::from)).entries()
+		};
+		env::set_var("CLASSPATH", "lib");
+		assert_eq!(entries(Some("a")), [PathBuf::from("a")]);
+		assert_eq!(entries(None), [PathBuf::from("lib")]);
+		env::remove_var("CLASSPATH");
+		assert_eq!(entries(None), [PathBuf::from(".")]);
+	}
+
+	#[test]
+	fn parses_sizes() {
+		assert_eq!(parse_size("1g"), Ok(1 << 30));
+		assert_eq!(parse_size("512M"), Ok(512 << 20));
+		assert_eq!(parse_size("64k"), Ok(64 << 10));
+		assert_eq!(parse_size("1024"), Ok(1024));
+		assert!(parse_size("0").is_err());
+		assert!(parse_size("0m").is_err());
+		assert!(parse_size("").is_err());
+		assert!(parse_size("g").is_err());
+		assert!(parse_size("1.5g").is_err());
+		assert!(parse_size("99999999999999999999").is_err());
+		assert!(parse_size(&format!("{}g", usize::MAX >> 20)).is_err());
+	}
+
+	#[test]
+	fn runs_main_class() {
+		let output = JavaBuild::new("tests/fixtures")
+			.out_dir(env::temp_dir().join("rjni-java-tests"))
+			.compile()
+			.unwrap();
+		let classes = output.classes.to_str().unwrap();
+
+		// The fixture exits with its argument plus `rjni.offset`, so this checks
+		// the classpath, system property and arguments all reached it
+		let launch = launch(&["-cp", classes, "-Drjni.offset=2", "Exit", "5"]).unwrap().unwrap();
+		match run(launch) {
+			Err(Error::Exit(status)) => assert_eq!(status, 7),
+			result => panic!("unexpected result: {:?}", result),
+		}
+	}
+}
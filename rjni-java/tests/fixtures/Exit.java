@@ -0,0 +1,7 @@
+// Exits with the status given as its argument, plus the `rjni.offset` system
+// property
+public class Exit {
+	public static void main(String[] args) {
+		System.exit(Integer.parseInt(args[0]) + Integer.getInteger("rjni.offset", 0));
+	}
+}
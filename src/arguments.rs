@@ -0,0 +1,387 @@
+//
+//  Option Sources
+//
+
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs};
+
+use {Options, Error, Result};
+
+
+/// Where an option given to the JVM came from, which is reported when the JVM
+/// rejects it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+	/// Set on the `Options` in code.
+	Code,
+
+	/// Read from an argument file.
+	Argfile(PathBuf),
+
+	/// Read from an environment variable (eg. `JDK_JAVA_OPTIONS`).
+	Environment(&'static str),
+
+	/// Given on the command line of a launcher, through
+	/// `Options::java_argument`.
+	CommandLine,
+}
+
+impl fmt::Display for Origin {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&Origin::Code => write!(f, "set in code"),
+			&Origin::Argfile(ref path) => write!(f, "from `@{}`", path.display()),
+			&Origin::Environment(name) => write!(f, "from {}", name),
+			&Origin::CommandLine => write!(f, "on the command line"),
+		}
+	}
+}
+
+/// Options that take their value as the next argument for the `java` launcher,
+/// but must be joined to it with a `=` for the JVM.
+const MODULE_OPTIONS: &[&str] = &[
+	"--module-path", "--upgrade-module-path", "--add-modules", "--limit-modules",
+	"--add-reads", "--add-exports", "--add-opens", "--patch-module",
+	"--enable-native-access",
+];
+
+/// Options only the `java` launcher understands, which select the application
+/// to run, or don't run one at all.
+const LAUNCHER_OPTIONS: &[&str] = &[
+	"-jar", "-m", "--module", "--source", "-version", "--version", "-showversion",
+	"--show-version", "-h", "-help", "--help", "-?", "--dry-run",
+];
+
+impl Options {
+	/// Add the options in an argument file, in the format accepted by the
+	/// `java` launcher's `@argfiles`.
+	///
+	/// Arguments are separated by whitespace, and can be quoted with `"` or
+	/// `'` to include whitespace. Within quotes, `\` escapes the next
+	/// character, and a `\` at the end of a line continues the argument on
+	/// the next one. A `#` starts a comment that runs to the end of the line.
+	///
+	/// The file can contain JVM options, and `-cp` or `--class-path` (which
+	/// replaces the classpath set in code), but not the main class or `-jar`.
+	pub fn argfile<P: AsRef<Path>>(self, path: P) -> Result<Options> {
+		let path = path.as_ref();
+		let text = match fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(err) => return Err(Error::InvalidOption(format!(
+				"couldn't read `@{}`: {}", path.display(), err))),
+		};
+		let origin = Origin::Argfile(path.to_owned());
+		match tokenize(&text) {
+			Ok(args) => self.add_arguments(args, origin),
+			Err(msg) => Err(Error::InvalidOption(format!("{} {}", msg, origin))),
+		}
+	}
+
+	/// Add the options in the `JDK_JAVA_OPTIONS` environment variable, which
+	/// the `java` launcher (but not the JVM itself) reads, if it's set.
+	///
+	/// The variable is parsed like an argument file, and can refer to argument
+	/// files with `@<path>`.
+	///
+	/// The `JAVA_TOOL_OPTIONS` environment variable doesn't need adding, since
+	/// the JVM always reads it itself. When the JVM rejects an option, either
+	/// is reported as the source.
+	pub fn jdk_java_options(self) -> Result<Options> {
+		let name = "JDK_JAVA_OPTIONS";
+		let value = match env::var(name) {
+			Ok(value) => value,
+			Err(_) => return Ok(self),
+		};
+		let origin = Origin::Environment(name);
+		let args = match tokenize(&value) {
+			Ok(args) => args,
+			Err(msg) => return Err(Error::InvalidOption(format!("{} {}", msg, origin))),
+		};
+
+		let mut options = self;
+		let mut pending = Vec::new();
+		for arg in args {
+			match arg.strip_prefix('@') {
+				Some(path) => {
+					options = options.add_arguments(pending.split_off(0), origin.clone())?
+						.argfile(path)?;
+				},
+				None => pending.push(arg),
+			}
+		}
+		options.add_arguments(pending, origin)
+	}
+
+	/// Add an argument given to the `java` launcher before the main class as
+	/// a JVM option, for a launcher built on `JavaVM::run_main`.
+	///
+	/// Options that take their value as the next argument (eg. `--add-opens`
+	/// or `-p`) take it from `rest`. Options only the `java` launcher
+	/// understands (eg. `-version` or `-jar`) are rejected, as are arguments
+	/// that aren't options.
+	pub fn java_argument<I>(self, arg: String, rest: &mut I) -> Result<Options>
+			where I: Iterator<Item = String> {
+		self.add_argument(arg, rest, &Origin::CommandLine)
+	}
+
+	/// Adds arguments for the `java` launcher as JVM options.
+	fn add_arguments(mut self, args: Vec<String>, origin: Origin) -> Result<Options> {
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			self = self.add_argument(arg, &mut args, &origin)?;
+		}
+		Ok(self)
+	}
+
+	/// Adds an argument for the `java` launcher as a JVM option, taking its
+	/// value from the following arguments if it needs one.
+	fn add_argument<I>(mut self, arg: String, rest: &mut I, origin: &Origin) -> Result<Options>
+			where I: Iterator<Item = String> {
+		let name = arg.split('=').next().unwrap_or("");
+		let mut value = |name: &str| match rest.next() {
+			Some(value) => Ok(value),
+			None => Err(Error::InvalidOption(format!(
+				"`{}` {} requires a value", name, origin))),
+		};
+
+		let option = match arg.as_str() {
+			"-cp" | "-classpath" | "--class-path" => {
+				format!("-Djava.class.path={}", value(&arg)?)
+			},
+			"-p" => format!("--module-path={}", value(&arg)?),
+			_ if MODULE_OPTIONS.contains(&arg.as_str()) => {
+				format!("{}={}", arg, value(&arg)?)
+			},
+			_ if arg.starts_with("--class-path=") => {
+				format!("-Djava.class.path={}", &arg["--class-path=".len()..])
+			},
+			_ if LAUNCHER_OPTIONS.contains(&name) => {
+				return Err(Error::InvalidOption(format!(
+					"`{}` {} is only understood by the java launcher", arg, origin)));
+			},
+			_ if arg.starts_with('-') => arg,
+			_ => {
+				return Err(Error::InvalidOption(format!(
+					"`{}` {} isn't an option, and a main class can't be given", arg,
+					origin)));
+			},
+		};
+		self.custom.push((option, origin.clone()));
+		Ok(self)
+	}
+}
+
+/// Splits text into arguments, following the rules for the `java` launcher's
+/// argument files.
+fn tokenize(text: &str) -> ::std::result::Result<Vec<String>, String> {
+	let mut args = Vec::new();
+	let mut arg: Option<String> = None;
+	let mut chars = text.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'#' => {
+				while chars.peek().is_some_and(|&c| c != '\n' && c != '\r') {
+					chars.next();
+				}
+			},
+			'"' | '\'' => {
+				let quote = c;
+				let arg = arg.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some(c) if c == quote => break,
+						Some('\\') => match chars.next() {
+							Some('n') => arg.push('\n'),
+							Some('r') => arg.push('\r'),
+							Some('t') => arg.push('\t'),
+							Some('f') => arg.push('\x0c'),
+
+							// Continue on the next line, without its indentation
+							Some('\n') | Some('\r') => {
+								while chars.peek().is_some_and(|c| c.is_whitespace()) {
+									chars.next();
+								}
+							},
+							Some(c) => arg.push(c),
+							None => return Err(String::from("unterminated quote")),
+						},
+						Some('\n') | Some('\r') | None => {
+							return Err(String::from("unterminated quote"));
+						},
+						Some(c) => arg.push(c),
+					}
+				}
+			},
+			_ if c.is_whitespace() => {
+				if let Some(arg) = arg.take() {
+					args.push(arg);
+				}
+			},
+			_ => arg.get_or_insert_with(String::new).push(c),
+		}
+	}
+	args.extend(arg);
+	Ok(args)
+}
+
+/// Returns the options the JVM reads from the `JAVA_TOOL_OPTIONS` environment
+/// variable, before any others.
+pub fn java_tool_options() -> Vec<String> {
+	env::var("JAVA_TOOL_OPTIONS").ok()
+		.and_then(|value| tokenize(&value).ok())
+		.unwrap_or_default()
+}
+
+/// Finds the option the JVM rejected in the messages it printed, returning the
+/// error to report.
+pub fn blame(options: &[(String, Origin)], output: &[String]) -> Option<Error> {
+	// Prefer an option mentioned in full over one only mentioned by its name
+	// (so `-ea` is blamed rather than `-ea:com.me...` when both are given),
+	// and then the longest
+	for line in output {
+		let rejected = options.iter()
+			.filter_map(|&(ref option, ref origin)| {
+				mentions(line, option).map(|mention| ((mention, option.len()), option, origin))
+			})
+			.max_by_key(|&(rank, _, _)| rank)
+			.map(|(_, option, origin)| (option, origin));
+		if let Some((option, origin)) = rejected {
+			return Some(Error::InvalidOption(format!(
+				"`{}` {} was rejected: {}", option, origin, line.trim())));
+		}
+	}
+
+	// The JVM reads `JAVA_TOOL_OPTIONS` before it can print through a hook
+	let tool_options = java_tool_options();
+	if tool_options.is_empty() {
+		None
+	} else {
+		Some(Error::InvalidOption(format!(
+			"the JVM rejected an option, possibly one from JAVA_TOOL_OPTIONS (`{}`)",
+			tool_options.join(" "))))
+	}
+}
+
+/// How a message from the JVM refers to an option, in order of preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Mention {
+	/// By the part before its value (eg. `-Xlog` for `-Xlog:bogus`), or for
+	/// `-XX` options, by name (eg. `Unrecognized VM option 'Bogus'`).
+	Name,
+
+	/// As a whole word.
+	Full,
+}
+
+/// Returns how a message from the JVM refers to an option, if it does.
+fn mentions(line: &str, option: &str) -> Option<Mention> {
+	let words = line.split(|c: char| c.is_whitespace() || "'\"`,".contains(c))
+		.map(|word| word.trim_end_matches(&[':', '.'][..]))
+		.filter(|word| !word.is_empty())
+		.collect::<Vec<_>>();
+	if words.contains(&option) {
+		return Some(Mention::Full);
+	}
+	let by_prefix = words.iter().any(|word| {
+		let value = option.strip_prefix(word).and_then(|rest| rest.chars().next());
+		word.starts_with('-') && (value == Some(':') || value == Some('='))
+	});
+	if by_prefix {
+		return Some(Mention::Name);
+	}
+
+	let flag = option.strip_prefix("-XX:")?.trim_start_matches(&['+', '-'][..]);
+	let name = flag.split('=').next().unwrap_or(flag);
+	if !name.is_empty() && words.iter().any(|word| *word == name || word.starts_with(&format!("{}=", name))) {
+		Some(Mention::Name)
+	} else {
+		None
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tokens(text: &str) -> Vec<String> {
+		tokenize(text).unwrap()
+	}
+
+	fn rejected(options: &[&str], line: &str) -> Option<String> {
+		let options = options.iter()
+			.map(|option| (option.to_string(), Origin::Code))
+			.collect::<Vec<_>>();
+		match blame(&options, &[line.to_string()]) {
+			Some(Error::InvalidOption(msg)) => Some(msg),
+			_ => None,
+		}
+	}
+
+	#[test]
+	fn tokenize_whitespace() {
+		assert_eq!(tokens("  -Xmx1g\t-ea\n\n-Dfoo=bar  "), ["-Xmx1g", "-ea", "-Dfoo=bar"]);
+		assert!(tokens("").is_empty());
+	}
+
+	#[test]
+	fn tokenize_quotes() {
+		assert_eq!(tokens("-Dname=\"a b\" 'c d'"), ["-Dname=a b", "c d"]);
+		assert_eq!(tokens("\"it's\" 'say \"hi\"'"), ["it's", "say \"hi\""]);
+		assert_eq!(tokens("-Dempty=\"\""), ["-Dempty="]);
+		assert_eq!(tokenize("\"open"), Err(String::from("unterminated quote")));
+		assert_eq!(tokenize("'split\nline'"), Err(String::from("unterminated quote")));
+	}
+
+	#[test]
+	fn tokenize_escapes() {
+		assert_eq!(tokens("\"a\\tb\\nc\\\\d\\\"e\""), ["a\tb\nc\\d\"e"]);
+
+		// Backslashes are only escapes inside quotes
+		assert_eq!(tokens("C:\\path"), ["C:\\path"]);
+	}
+
+	#[test]
+	fn tokenize_continuation() {
+		assert_eq!(tokens("\"-Dlong=first\\\n    second\""), ["-Dlong=firstsecond"]);
+		assert_eq!(tokens("\"-Dlong=first\\\r\n\tsecond\""), ["-Dlong=firstsecond"]);
+	}
+
+	#[test]
+	fn tokenize_comments() {
+		assert_eq!(tokens("# a comment\n-ea # trailing\n-esa"), ["-ea", "-esa"]);
+		assert_eq!(tokens("\"#not a comment\""), ["#not a comment"]);
+	}
+
+	#[test]
+	fn mentions_whole_words() {
+		assert_eq!(mentions("Unrecognized option: -ea", "-ea"), Some(Mention::Full));
+		assert_eq!(mentions("Invalid maximum heap size: -Xmx1z", "-Xmx1z"), Some(Mention::Full));
+		assert_eq!(mentions("Unrecognized option: -ea:foo", "-ea"), None);
+		assert_eq!(mentions("-XX:+UseSerialGC -ea:foo", "-ea"), None);
+		assert_eq!(mentions("Unrecognized option: -eager", "-ea"), None);
+	}
+
+	#[test]
+	fn mentions_by_name() {
+		assert_eq!(mentions("Invalid -Xlog option", "-Xlog:bogus"), Some(Mention::Name));
+		assert_eq!(mentions("Unrecognized VM option 'Bogus'", "-XX:+Bogus"), Some(Mention::Name));
+		assert_eq!(mentions("Improperly specified VM option 'Size=x'", "-XX:Size=x"),
+			Some(Mention::Name));
+		assert_eq!(mentions("Unrecognized VM option 'BogusFlag'", "-XX:+Bogus"), None);
+	}
+
+	#[test]
+	fn blame_prefers_full_mentions() {
+		let msg = rejected(&["-ea:com.me...", "-ea", "-Xmx1g"], "Unrecognized option: -ea").unwrap();
+		assert!(msg.starts_with("`-ea` set in code was rejected"), "{}", msg);
+
+		let msg = rejected(&["-Xlog:gc", "-Xlog:bogus"], "Invalid -Xlog option '-Xlog:bogus'").unwrap();
+		assert!(msg.starts_with("`-Xlog:bogus` set in code"), "{}", msg);
+
+		let msg = rejected(&["-XX:+UseSerialGC", "-XX:+Bogus"], "Unrecognized VM option 'Bogus'").unwrap();
+		assert!(msg.starts_with("`-XX:+Bogus` set in code"), "{}", msg);
+	}
+}
@@ -0,0 +1,193 @@
+//
+//  Boxing
+//
+
+use dynamic::is_assignable;
+use {JavaVM, Class, Value, JavaType, MethodSignature, ClassCache};
+use Result;
+
+
+/// A primitive type's box class, and the method used to unbox it.
+struct BoxClass {
+	primitive: JavaType,
+	class: ClassCache,
+	unbox: &'static str,
+}
+
+/// Each primitive type's box class, which caches the IDs of the `valueOf` and
+/// unboxing methods.
+static BOX_CLASSES: [BoxClass; 8] = [
+	BoxClass {
+		primitive: JavaType::Boolean,
+		class: ClassCache::new("java/lang/Boolean"),
+		unbox: "booleanValue",
+	},
+	BoxClass {
+		primitive: JavaType::Byte,
+		class: ClassCache::new("java/lang/Byte"),
+		unbox: "byteValue",
+	},
+	BoxClass {
+		primitive: JavaType::Char,
+		class: ClassCache::new("java/lang/Character"),
+		unbox: "charValue",
+	},
+	BoxClass {
+		primitive: JavaType::Short,
+		class: ClassCache::new("java/lang/Short"),
+		unbox: "shortValue",
+	},
+	BoxClass {
+		primitive: JavaType::Int,
+		class: ClassCache::new("java/lang/Integer"),
+		unbox: "intValue",
+	},
+	BoxClass {
+		primitive: JavaType::Long,
+		class: ClassCache::new("java/lang/Long"),
+		unbox: "longValue",
+	},
+	BoxClass {
+		primitive: JavaType::Float,
+		class: ClassCache::new("java/lang/Float"),
+		unbox: "floatValue",
+	},
+	BoxClass {
+		primitive: JavaType::Double,
+		class: ClassCache::new("java/lang/Double"),
+		unbox: "doubleValue",
+	},
+];
+
+impl<'a> Value<'a> {
+	/// Boxes a primitive value into an instance of its box class (eg.
+	/// `Value::Int` into a `java.lang.Integer`).
+	///
+	/// Strings and objects are returned unchanged.
+	pub fn boxed(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		let kind = match primitive_type(&self) {
+			Some(kind) => kind,
+			None => return Ok(self),
+		};
+		let box_class = find_box(&kind);
+		let signature = format!("({})L{};", kind.descriptor(), box_class.class.name);
+		let value_of = box_class.class.static_method(jvm, "valueOf", &signature)?;
+		value_of.call(&box_class.class.class(jvm)?, &[self])
+	}
+
+	/// Unboxes an instance of a box class into its primitive value (eg. a
+	/// `java.lang.Integer` into `Value::Int`).
+	///
+	/// Primitives, strings, null and other objects are returned unchanged.
+	pub fn unboxed(self) -> Result<Value<'a>> {
+		let box_class = match box_class_of(&self)? {
+			Some(box_class) => box_class,
+			None => return Ok(self),
+		};
+		let obj = self.as_object();
+		let signature = format!("(){}", box_class.primitive.descriptor());
+		let unbox = box_class.class.method(obj.jvm, box_class.unbox, &signature)?;
+		unbox.call(&obj, &[])
+	}
+}
+
+/// Returns the box class for the given primitive type.
+fn find_box(kind: &JavaType) -> &'static BoxClass {
+	BOX_CLASSES.iter()
+		.find(|box_class| &box_class.primitive == kind)
+		.unwrap()
+}
+
+/// Returns the box class the given value is an instance of, or None if it
+/// isn't a non-null object of a box class.
+fn box_class_of(value: &Value) -> Result<Option<&'static BoxClass>> {
+	if let &Value::Object(ref obj) = value {
+		if obj.is_null() {
+			return Ok(None);
+		}
+		for box_class in BOX_CLASSES.iter() {
+			if obj.is_instance_of(box_class.class.class(obj.jvm)?) {
+				return Ok(Some(box_class));
+			}
+		}
+	}
+	Ok(None)
+}
+
+/// Returns the name of the box class for the given primitive type (eg.
+/// `java/lang/Integer`).
+pub fn box_class_name(kind: &JavaType) -> &'static str {
+	find_box(kind).class.name
+}
+
+/// Returns the box class for the given primitive type.
+pub fn box_class<'a>(jvm: &'a JavaVM, kind: &JavaType) -> Result<Class<'a>> {
+	find_box(kind).class.class(jvm)
+}
+
+/// Returns the primitive type the given value unboxes to, or None if it isn't
+/// an instance of a box class.
+pub fn unboxed_type(value: &Value) -> Result<Option<JavaType>> {
+	Ok(box_class_of(value)?.map(|box_class| box_class.primitive.clone()))
+}
+
+/// Returns true if the class with the given internal name is a primitive
+/// type's box class.
+pub fn is_box_class(name: &str) -> bool {
+	BOX_CLASSES.iter().any(|box_class| box_class.class.name == name)
+}
+
+/// Returns the type of a primitive value, or None if it's a reference.
+pub fn primitive_type(value: &Value) -> Option<JavaType> {
+	match value {
+		&Value::Boolean(_) => Some(JavaType::Boolean),
+		&Value::Byte(_) => Some(JavaType::Byte),
+		&Value::Char(_) => Some(JavaType::Char),
+		&Value::Short(_) => Some(JavaType::Short),
+		&Value::Int(_) => Some(JavaType::Int),
+		&Value::Long(_) => Some(JavaType::Long),
+		&Value::Float(_) => Some(JavaType::Float),
+		&Value::Double(_) => Some(JavaType::Double),
+		_ => None,
+	}
+}
+
+/// Boxes primitive arguments passed to reference parameters, and unboxes
+/// objects passed to primitive parameters, for a call with an explicit
+/// signature.
+///
+/// A primitive is only boxed if its box class can be assigned to the parameter
+/// (eg. an `int` can be passed as a `Number`), and an object is only unboxed
+/// to exactly the parameter's type. Any other argument is left as is, for
+/// `MethodSignature::validate` to reject.
+pub fn box_arguments<'a>(jvm: &'a JavaVM, signature: &MethodSignature,
+                         args: &[Value<'a>]) -> Result<Vec<Value<'a>>> {
+	if args.len() != signature.arg_count() {
+		return Ok(args.to_vec());
+	}
+
+	let mut result = Vec::with_capacity(args.len());
+	for (param, arg) in signature.args().iter().zip(args) {
+		let converted = match (primitive_type(arg), param) {
+			(_, &JavaType::Array(_)) => None,
+			(Some(ref kind), &JavaType::Object(ref name)) => {
+				let param_class = jvm.class(name)?;
+				if is_assignable(jvm, box_class(jvm, kind)?.raw, param_class.raw) {
+					Some(arg.clone().boxed(jvm)?)
+				} else {
+					None
+				}
+			},
+			(None, _) if param.is_primitive() => {
+				if unboxed_type(arg)?.as_ref() == Some(param) {
+					Some(arg.clone().unboxed()?)
+				} else {
+					None
+				}
+			},
+			_ => None,
+		};
+		result.push(converted.unwrap_or_else(|| arg.clone()));
+	}
+	Ok(result)
+}
@@ -0,0 +1,401 @@
+//
+//  Classpath Resolution
+//
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs};
+
+use rjni_jar::Jar;
+use Classpath;
+
+
+/// A problem with an entry on a classpath, found by `Classpath::validate`.
+///
+/// The JVM silently ignores such entries, so a missing class is often the
+/// first sign of one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClasspathIssue {
+	/// An entry that doesn't exist, which was either added directly, or listed
+	/// in the `Class-Path` of a Jar's manifest.
+	Missing {
+		path: PathBuf,
+		referenced_by: Option<PathBuf>,
+	},
+
+	/// An entry that exists but can't be read, or a Jar file that isn't a
+	/// valid archive.
+	Unreadable {
+		path: PathBuf,
+		reason: String,
+	},
+}
+
+impl fmt::Display for ClasspathIssue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&ClasspathIssue::Missing { ref path, referenced_by: Some(ref jar) } => {
+				write!(f, "`{}` (in the Class-Path of `{}`) doesn't exist", path.display(),
+					jar.display())
+			},
+			&ClasspathIssue::Missing { ref path, referenced_by: None } => {
+				write!(f, "`{}` doesn't exist", path.display())
+			},
+			&ClasspathIssue::Unreadable { ref path, ref reason } => {
+				write!(f, "`{}` can't be read: {}", path.display(), reason)
+			},
+		}
+	}
+}
+
+impl Classpath {
+	/// Create a classpath from the `CLASSPATH` environment variable, or an
+	/// empty one if it isn't set.
+	///
+	/// As with the `java` launcher, an empty entry refers to the current
+	/// directory, and entries can be wildcards (eg. `lib/*`).
+	pub fn from_env() -> Classpath {
+		match env::var_os("CLASSPATH") {
+			Some(paths) => env::split_paths(&paths).fold(Classpath::new(), |classpath, path| {
+				if path.as_os_str().is_empty() {
+					classpath.add(".")
+				} else {
+					classpath.add(path)
+				}
+			}),
+			None => Classpath::new(),
+		}
+	}
+
+	/// Add every Jar file in a directory, like the wildcard `dir/*`.
+	///
+	/// The directory is listed when the JVM is created, and its Jar files are
+	/// added in order of their names.
+	pub fn add_dir_jars<T: AsRef<Path>>(self, dir: T) -> Classpath {
+		self.add(dir.as_ref().join("*"))
+	}
+
+	/// Set whether the entries listed in the `Class-Path` attribute of each Jar
+	/// file's manifest are added after it (recursively), rather than only
+	/// being followed by the JVM's class loader.
+	///
+	/// This makes the libraries a Jar depends on visible to `entries` and
+	/// `validate`, and lets them be deduplicated.
+	pub fn follow_manifests(mut self, flag: bool) -> Classpath {
+		self.follow_manifests = flag;
+		self
+	}
+
+	/// Returns the entries passed to the JVM, after expanding wildcards,
+	/// following manifests (if enabled), and removing duplicates.
+	///
+	/// Entries that don't exist are kept, since they may be created before
+	/// classes are loaded from them.
+	pub fn entries(&self) -> Vec<PathBuf> {
+		self.resolve(false).0
+	}
+
+	/// Checks every entry exists and can be read, including those listed in
+	/// Jar manifests when `follow_manifests` is set, and returns the problems
+	/// found.
+	pub fn validate(&self) -> Vec<ClasspathIssue> {
+		self.resolve(true).1
+	}
+
+	/// Expands, follows and deduplicates the entries, and checks each one can
+	/// be read if `check` is set.
+	fn resolve(&self, check: bool) -> (Vec<PathBuf>, Vec<ClasspathIssue>) {
+		let mut resolution = Resolution {
+			follow_manifests: self.follow_manifests,
+			check: check,
+			entries: Vec::new(),
+			issues: Vec::new(),
+			seen: HashSet::new(),
+		};
+		for path in &self.paths {
+			if path.file_name().is_some_and(|name| name == "*") {
+				let dir = path.parent().unwrap_or_else(|| Path::new(""));
+				resolution.add_wildcard(dir);
+			} else {
+				resolution.add(path, None);
+			}
+		}
+		(resolution.entries, resolution.issues)
+	}
+}
+
+/// The state of resolving a classpath's entries.
+struct Resolution {
+	follow_manifests: bool,
+
+	/// Whether each entry is read, to check it's readable.
+	check: bool,
+
+	entries: Vec<PathBuf>,
+	issues: Vec<ClasspathIssue>,
+
+	/// The canonical path of each entry added.
+	seen: HashSet<PathBuf>,
+}
+
+impl Resolution {
+	/// Adds the Jar files in a directory, in order of their names.
+	fn add_wildcard(&mut self, dir: &Path) {
+		// Like `java`, `*` on its own refers to the current directory
+		let listed = if dir.as_os_str().is_empty() {
+			fs::read_dir(".")
+		} else {
+			fs::read_dir(dir)
+		};
+		let listing = match listed {
+			Ok(listing) => listing,
+			Err(err) => {
+				self.issue(dir, None, err.to_string());
+				return;
+			},
+		};
+
+		let mut jars = listing
+			.filter_map(|entry| entry.ok())
+			.map(|entry| entry.path())
+			.filter(|path| path.is_file() && is_jar(path))
+			.collect::<Vec<_>>();
+		jars.sort();
+		for jar in jars {
+			self.add(&jar, None);
+		}
+	}
+
+	/// Adds an entry, unless it's already been added, followed by the entries
+	/// in its manifest.
+	fn add(&mut self, path: &Path, referenced_by: Option<&Path>) {
+		let canonical = match fs::canonicalize(path) {
+			Ok(canonical) => canonical,
+			Err(err) => {
+				if self.seen.insert(path.to_path_buf()) {
+					self.issue(path, referenced_by, err.to_string());
+					self.entries.push(path.to_path_buf());
+				}
+				return;
+			},
+		};
+		if !self.seen.insert(canonical.clone()) {
+			return;
+		}
+		self.entries.push(path.to_path_buf());
+
+		if canonical.is_dir() {
+			if self.check {
+				if let Err(err) = fs::read_dir(&canonical) {
+					self.issue(path, referenced_by, err.to_string());
+				}
+			}
+			return;
+		}
+		if !self.check && !self.follow_manifests {
+			return;
+		}
+
+		// Reading the archive also checks it's valid
+		let manifest = match Jar::open(&canonical).and_then(|jar| jar.manifest()) {
+			Ok(manifest) => manifest,
+			Err(err) => {
+				self.issue(path, referenced_by, err.to_string());
+				return;
+			},
+		};
+		if !self.follow_manifests {
+			return;
+		}
+		let class_path = match manifest.get("Class-Path") {
+			Some(class_path) => class_path.clone(),
+			None => return,
+		};
+
+		// Entries are space separated URLs, relative to the Jar's directory
+		let base = path.parent().unwrap_or_else(|| Path::new(""));
+		for url in class_path.split_whitespace() {
+			if let Some(entry) = url_path(url) {
+				self.add(&base.join(entry), Some(path));
+			}
+		}
+	}
+
+	/// Records a problem with an entry, which is reported as missing if it
+	/// doesn't exist.
+	fn issue(&mut self, path: &Path, referenced_by: Option<&Path>, reason: String) {
+		let issue = if path.exists() {
+			ClasspathIssue::Unreadable {
+				path: path.to_path_buf(),
+				reason: reason,
+			}
+		} else {
+			ClasspathIssue::Missing {
+				path: path.to_path_buf(),
+				referenced_by: referenced_by.map(Path::to_path_buf),
+			}
+		};
+		if !self.issues.contains(&issue) {
+			self.issues.push(issue);
+		}
+	}
+}
+
+/// Returns true if a path has a `.jar` extension, in any case.
+fn is_jar(path: &Path) -> bool {
+	path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("jar"))
+}
+
+/// Converts a relative or `file:` URL from a `Class-Path` attribute into a
+/// path, decoding escapes like `%20`, or returns None for other schemes.
+fn url_path(url: &str) -> Option<PathBuf> {
+	let path = match url.strip_prefix("file:") {
+		Some(path) => path,
+		None if url.contains("://") => return None,
+		None => url,
+	};
+
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let escaped = bytes.get(i + 1..i + 3)
+			.and_then(|hex| ::std::str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match (bytes[i], escaped) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 3;
+			},
+			(byte, _) => {
+				decoded.push(byte);
+				i += 1;
+			},
+		}
+	}
+	Some(PathBuf::from(String::from_utf8_lossy(&decoded).into_owned()))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::process;
+	use rjni_jar::testing::zip;
+
+	/// A directory that's removed once the test finishes.
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new(name: &str) -> TempDir {
+			let path = env::temp_dir().join(format!("rjni-classpath-{}-{}", process::id(), name));
+			let _ = fs::remove_dir_all(&path);
+			fs::create_dir_all(&path).unwrap();
+			TempDir(path)
+		}
+
+		/// Writes a Jar file, with the given `Class-Path` if there is one.
+		fn jar(&self, name: &str, class_path: Option<&str>) -> PathBuf {
+			let path = self.0.join(name);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			let manifest = match class_path {
+				Some(class_path) => format!("Manifest-Version: 1.0\nClass-Path: {}\n", class_path),
+				None => String::from("Manifest-Version: 1.0\n"),
+			};
+			fs::write(&path, zip(&[("META-INF/MANIFEST.MF", manifest.as_bytes(), true)])).unwrap();
+			path
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	#[test]
+	fn expands_wildcards() {
+		let dir = TempDir::new("wildcard");
+		let b = dir.jar("b.jar", None);
+		let a = dir.jar("a.JAR", None);
+		fs::write(dir.0.join("notes.txt"), "").unwrap();
+		fs::create_dir(dir.0.join("sub.jar")).unwrap();
+
+		let entries = Classpath::new().add_dir_jars(&dir.0).entries();
+		assert_eq!(entries, [a, b]);
+	}
+
+	#[test]
+	fn follows_manifests() {
+		let dir = TempDir::new("manifests");
+		let lib = dir.jar("lib/lib.jar", None);
+		let spaced = dir.jar("lib/with space.jar", None);
+		let app = dir.jar("app.jar", Some("lib/lib.jar lib/with%20space.jar http://example.com/x.jar"));
+
+		let classpath = Classpath::new().add(&app);
+		assert_eq!(classpath.entries(), vec![app.clone()]);
+		assert_eq!(classpath.follow_manifests(true).entries(), [app, lib, spaced]);
+	}
+
+	#[test]
+	fn removes_duplicates() {
+		let dir = TempDir::new("duplicates");
+		let lib = dir.jar("lib.jar", None);
+		let app = dir.jar("app.jar", Some("lib.jar ./lib.jar"));
+
+		let entries = Classpath::new()
+			.add(&lib)
+			.add(&app)
+			.add(dir.0.join(".").join("app.jar"))
+			.follow_manifests(true)
+			.entries();
+		assert_eq!(entries, [lib, app]);
+
+		// Manifests that refer to each other don't loop forever
+		let cycle = TempDir::new("cycle");
+		let a = cycle.jar("a.jar", Some("b.jar"));
+		let b = cycle.jar("b.jar", Some("a.jar"));
+		assert_eq!(Classpath::new().add(&a).follow_manifests(true).entries(), [a, b]);
+	}
+
+	#[test]
+	fn reports_issues() {
+		let dir = TempDir::new("issues");
+		let app = dir.jar("app.jar", Some("missing.jar"));
+		let broken = dir.0.join("broken.jar");
+		fs::write(&broken, "not a zip").unwrap();
+		let absent = dir.0.join("absent");
+
+		let issues = Classpath::new()
+			.add(&app)
+			.add(&broken)
+			.add(&absent)
+			.follow_manifests(true)
+			.validate();
+		assert_eq!(issues.len(), 3);
+		assert_eq!(issues[0], ClasspathIssue::Missing {
+			path: dir.0.join("missing.jar"),
+			referenced_by: Some(app),
+		});
+		assert!(matches!(issues[1], ClasspathIssue::Unreadable { ref path, .. } if *path == broken));
+		assert_eq!(issues[2], ClasspathIssue::Missing {
+			path: absent,
+			referenced_by: None,
+		});
+	}
+
+	#[test]
+	fn decodes_urls() {
+		assert_eq!(url_path("lib/a.jar"), Some(PathBuf::from("lib/a.jar")));
+		assert_eq!(url_path("lib/with%20space.jar"), Some(PathBuf::from("lib/with space.jar")));
+		assert_eq!(url_path("file:/opt/lib/a%2Bb.jar"), Some(PathBuf::from("/opt/lib/a+b.jar")));
+		assert_eq!(url_path("%E2%82%AC.jar"), Some(PathBuf::from("\u{20ac}.jar")));
+
+		// Malformed escapes are kept as they are
+		assert_eq!(url_path("100%.jar"), Some(PathBuf::from("100%.jar")));
+		assert_eq!(url_path("a%zz.jar"), Some(PathBuf::from("a%zz.jar")));
+
+		assert_eq!(url_path("http://example.com/a.jar"), None);
+	}
+}
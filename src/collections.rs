@@ -0,0 +1,652 @@
+//
+//  Collections
+//
+
+//! Typed wrappers around `java.util` collections.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use convert::mismatch;
+use dispatch::from_jobject;
+use boxing::is_box_class;
+use {JavaVM, Object, ClassCache, Type, Value, JavaType, IntoJava, FromJava};
+use {Error, Result, with_local_frame, with_local_frame_value, delete_local_ref};
+
+
+static COLLECTION: ClassCache = ClassCache::new("java/util/Collection");
+static LIST: ClassCache = ClassCache::new("java/util/List");
+static SET: ClassCache = ClassCache::new("java/util/Set");
+static MAP: ClassCache = ClassCache::new("java/util/Map");
+static MAP_ENTRY: ClassCache = ClassCache::new("java/util/Map$Entry");
+static ITERATOR: ClassCache = ClassCache::new("java/util/Iterator");
+static ARRAY_LIST: ClassCache = ClassCache::new("java/util/ArrayList");
+static HASH_SET: ClassCache = ClassCache::new("java/util/HashSet");
+static HASH_MAP: ClassCache = ClassCache::new("java/util/HashMap");
+static STRING: ClassCache = ClassCache::new("java/lang/String");
+
+
+/// A `java.util.List` holding elements of type `T`.
+///
+/// Elements are converted through `IntoJava` and `FromJava` as they're added
+/// and read, with primitives boxed (eg. a `JList<i32>` is a
+/// `List<Integer>`). The element type isn't checked until an element is read.
+///
+/// The local references created while adding an element are deleted once
+/// it's added, as is an element's reference once it's read into a type that
+/// copies it (eg. a `String`), so large collections can be converted.
+#[derive(Debug, Clone)]
+pub struct JList<'a, T> {
+	object: Object<'a>,
+	marker: PhantomData<T>,
+}
+
+impl<'a, T> JList<'a, T> {
+	/// Create a new, empty `java.util.ArrayList`.
+	pub fn new(jvm: &'a JavaVM) -> Result<JList<'a, T>> {
+		let object = ARRAY_LIST.class(jvm)?.instantiate(&[])?;
+		Ok(JList::wrap(object))
+	}
+
+	/// Wraps an object, which must be a non-null instance of
+	/// `java.util.List`.
+	pub fn from_object(object: Object<'a>) -> Result<JList<'a, T>> {
+		check_instance(&object, &LIST)?;
+		Ok(JList::wrap(object))
+	}
+
+	fn wrap(object: Object<'a>) -> JList<'a, T> {
+		JList {
+			object: object,
+			marker: PhantomData,
+		}
+	}
+
+	/// Returns the underlying list object.
+	pub fn as_object(&self) -> &Object<'a> {
+		&self.object
+	}
+
+	/// Returns the underlying list object.
+	pub fn into_object(self) -> Object<'a> {
+		self.object
+	}
+
+	/// Returns the number of elements in the list.
+	pub fn len(&self) -> Result<usize> {
+		size(&self.object)
+	}
+
+	/// Returns true if the list has no elements.
+	pub fn is_empty(&self) -> Result<bool> {
+		Ok(self.len()? == 0)
+	}
+
+	/// Removes every element from the list.
+	pub fn clear(&self) -> Result<()> {
+		let method = COLLECTION.method(self.object.jvm, "clear", "()V")?;
+		method.call(&self.object, &[])?;
+		Ok(())
+	}
+}
+
+impl<'a, T: IntoJava<'a>> JList<'a, T> {
+	/// Create a new `java.util.ArrayList` holding the given values.
+	pub fn from_vec(jvm: &'a JavaVM, values: Vec<T>) -> Result<JList<'a, T>> {
+		let list = JList::new(jvm)?;
+		for value in values {
+			list.add(value)?;
+		}
+		Ok(list)
+	}
+
+	/// Appends an element to the end of the list.
+	pub fn add(&self, value: T) -> Result<()> {
+		let jvm = self.object.jvm;
+		let method = COLLECTION.method(jvm, "add", "(Ljava/lang/Object;)Z")?;
+		with_local_frame(jvm, || {
+			method.call(&self.object, &[to_element(jvm, value)?])?;
+			Ok(())
+		})
+	}
+
+	/// Inserts an element at the given index, shifting the elements after it.
+	pub fn insert(&self, index: usize, value: T) -> Result<()> {
+		let jvm = self.object.jvm;
+		let method = LIST.method(jvm, "add", "(ILjava/lang/Object;)V")?;
+		with_local_frame(jvm, || {
+			method.call(&self.object, &[java_index(index)?, to_element(jvm, value)?])?;
+			Ok(())
+		})
+	}
+
+	/// Replaces the element at the given index.
+	pub fn set(&self, index: usize, value: T) -> Result<()> {
+		let jvm = self.object.jvm;
+		let method = LIST.method(jvm, "set", "(ILjava/lang/Object;)Ljava/lang/Object;")?;
+		with_local_frame(jvm, || {
+			method.call(&self.object, &[java_index(index)?, to_element(jvm, value)?])?;
+			Ok(())
+		})
+	}
+}
+
+impl<'a, T: FromJava<'a>> JList<'a, T> {
+	/// Returns the element at the given index.
+	pub fn get(&self, index: usize) -> Result<T> {
+		let method = LIST.method(self.object.jvm, "get", "(I)Ljava/lang/Object;")?;
+		from_element(method.call(&self.object, &[java_index(index)?])?)
+	}
+
+	/// Removes and returns the element at the given index.
+	pub fn remove(&self, index: usize) -> Result<T> {
+		let method = LIST.method(self.object.jvm, "remove", "(I)Ljava/lang/Object;")?;
+		from_element(method.call(&self.object, &[java_index(index)?])?)
+	}
+
+	/// Returns an iterator over the list's elements.
+	pub fn iter(&self) -> Result<JIterator<'a, T>> {
+		iterator(&self.object)
+	}
+
+	/// Copies the list's elements into a vector.
+	pub fn to_vec(&self) -> Result<Vec<T>> {
+		self.iter()?.collect()
+	}
+}
+
+
+/// A `java.util.Set` holding elements of type `T`.
+///
+/// Elements are converted in the same way as a `JList`'s.
+#[derive(Debug, Clone)]
+pub struct JSet<'a, T> {
+	object: Object<'a>,
+	marker: PhantomData<T>,
+}
+
+impl<'a, T> JSet<'a, T> {
+	/// Create a new, empty `java.util.HashSet`.
+	pub fn new(jvm: &'a JavaVM) -> Result<JSet<'a, T>> {
+		let object = HASH_SET.class(jvm)?.instantiate(&[])?;
+		Ok(JSet::wrap(object))
+	}
+
+	/// Wraps an object, which must be a non-null instance of `java.util.Set`.
+	pub fn from_object(object: Object<'a>) -> Result<JSet<'a, T>> {
+		check_instance(&object, &SET)?;
+		Ok(JSet::wrap(object))
+	}
+
+	fn wrap(object: Object<'a>) -> JSet<'a, T> {
+		JSet {
+			object: object,
+			marker: PhantomData,
+		}
+	}
+
+	/// Returns the underlying set object.
+	pub fn as_object(&self) -> &Object<'a> {
+		&self.object
+	}
+
+	/// Returns the underlying set object.
+	pub fn into_object(self) -> Object<'a> {
+		self.object
+	}
+
+	/// Returns the number of elements in the set.
+	pub fn len(&self) -> Result<usize> {
+		size(&self.object)
+	}
+
+	/// Returns true if the set has no elements.
+	pub fn is_empty(&self) -> Result<bool> {
+		Ok(self.len()? == 0)
+	}
+
+	/// Removes every element from the set.
+	pub fn clear(&self) -> Result<()> {
+		let method = COLLECTION.method(self.object.jvm, "clear", "()V")?;
+		method.call(&self.object, &[])?;
+		Ok(())
+	}
+
+	/// Returns true if the set contains the given value.
+	pub fn contains<V: IntoJava<'a>>(&self, value: V) -> Result<bool> {
+		let jvm = self.object.jvm;
+		let method = COLLECTION.method(jvm, "contains", "(Ljava/lang/Object;)Z")?;
+		with_local_frame(jvm, || {
+			bool::from_java(method.call(&self.object, &[to_element(jvm, value)?])?)
+		})
+	}
+
+	/// Removes the given value from the set, returning true if it was
+	/// present.
+	pub fn remove<V: IntoJava<'a>>(&self, value: V) -> Result<bool> {
+		let jvm = self.object.jvm;
+		let method = COLLECTION.method(jvm, "remove", "(Ljava/lang/Object;)Z")?;
+		with_local_frame(jvm, || {
+			bool::from_java(method.call(&self.object, &[to_element(jvm, value)?])?)
+		})
+	}
+}
+
+impl<'a, T: IntoJava<'a>> JSet<'a, T> {
+	/// Create a new `java.util.HashSet` holding the given values.
+	pub fn from_set(jvm: &'a JavaVM, values: HashSet<T>) -> Result<JSet<'a, T>> {
+		let set = JSet::new(jvm)?;
+		for value in values {
+			set.add(value)?;
+		}
+		Ok(set)
+	}
+
+	/// Adds an element to the set, returning true if it wasn't already
+	/// present.
+	pub fn add(&self, value: T) -> Result<bool> {
+		let jvm = self.object.jvm;
+		let method = COLLECTION.method(jvm, "add", "(Ljava/lang/Object;)Z")?;
+		with_local_frame(jvm, || {
+			bool::from_java(method.call(&self.object, &[to_element(jvm, value)?])?)
+		})
+	}
+}
+
+impl<'a, T: FromJava<'a>> JSet<'a, T> {
+	/// Returns an iterator over the set's elements.
+	pub fn iter(&self) -> Result<JIterator<'a, T>> {
+		iterator(&self.object)
+	}
+
+	/// Copies the set's elements into a Rust set.
+	pub fn to_set(&self) -> Result<HashSet<T>> where T: Eq + Hash {
+		self.iter()?.collect()
+	}
+}
+
+
+/// A `java.util.Map` from keys of type `K` to values of type `V`.
+///
+/// Keys and values are converted in the same way as a `JList`'s elements.
+#[derive(Debug, Clone)]
+pub struct JMap<'a, K, V> {
+	object: Object<'a>,
+	marker: PhantomData<(K, V)>,
+}
+
+impl<'a, K, V> JMap<'a, K, V> {
+	/// Create a new, empty `java.util.HashMap`.
+	pub fn new(jvm: &'a JavaVM) -> Result<JMap<'a, K, V>> {
+		let object = HASH_MAP.class(jvm)?.instantiate(&[])?;
+		Ok(JMap::wrap(object))
+	}
+
+	/// Wraps an object, which must be a non-null instance of `java.util.Map`.
+	pub fn from_object(object: Object<'a>) -> Result<JMap<'a, K, V>> {
+		check_instance(&object, &MAP)?;
+		Ok(JMap::wrap(object))
+	}
+
+	fn wrap(object: Object<'a>) -> JMap<'a, K, V> {
+		JMap {
+			object: object,
+			marker: PhantomData,
+		}
+	}
+
+	/// Returns the underlying map object.
+	pub fn as_object(&self) -> &Object<'a> {
+		&self.object
+	}
+
+	/// Returns the underlying map object.
+	pub fn into_object(self) -> Object<'a> {
+		self.object
+	}
+
+	/// Returns the number of entries in the map.
+	pub fn len(&self) -> Result<usize> {
+		let method = MAP.method(self.object.jvm, "size", "()I")?;
+		Ok(i32::from_java(method.call(&self.object, &[])?)? as usize)
+	}
+
+	/// Returns true if the map has no entries.
+	pub fn is_empty(&self) -> Result<bool> {
+		Ok(self.len()? == 0)
+	}
+
+	/// Removes every entry from the map.
+	pub fn clear(&self) -> Result<()> {
+		let method = MAP.method(self.object.jvm, "clear", "()V")?;
+		method.call(&self.object, &[])?;
+		Ok(())
+	}
+
+	/// Returns true if the map has an entry for the given key.
+	pub fn contains_key<Q: IntoJava<'a>>(&self, key: Q) -> Result<bool> {
+		let jvm = self.object.jvm;
+		let method = MAP.method(jvm, "containsKey", "(Ljava/lang/Object;)Z")?;
+		with_local_frame(jvm, || {
+			bool::from_java(method.call(&self.object, &[to_element(jvm, key)?])?)
+		})
+	}
+
+	/// Returns the value of the map's `keySet`, `values` or `entrySet` method,
+	/// with the given signature.
+	fn view(&self, name: &str, signature: &str) -> Result<Object<'a>> {
+		let method = MAP.method(self.object.jvm, name, signature)?;
+		Object::from_java(method.call(&self.object, &[])?)
+	}
+}
+
+impl<'a, K: IntoJava<'a>, V: IntoJava<'a>> JMap<'a, K, V> {
+	/// Create a new `java.util.HashMap` holding the given entries.
+	pub fn from_map(jvm: &'a JavaVM, entries: HashMap<K, V>)
+			-> Result<JMap<'a, K, V>> {
+		let map = JMap::new(jvm)?;
+		for (key, value) in entries {
+			map.put(key, value)?;
+		}
+		Ok(map)
+	}
+
+	/// Associates a value with a key, replacing any previous value.
+	pub fn put(&self, key: K, value: V) -> Result<()> {
+		let jvm = self.object.jvm;
+		let signature = "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;";
+		let method = MAP.method(jvm, "put", signature)?;
+		with_local_frame(jvm, || {
+			method.call(&self.object, &[to_element(jvm, key)?, to_element(jvm, value)?])?;
+			Ok(())
+		})
+	}
+}
+
+impl<'a, K: FromJava<'a>, V: FromJava<'a>> JMap<'a, K, V> {
+	/// Returns the value associated with the given key, or None if there
+	/// isn't one (or it's null).
+	pub fn get<Q: IntoJava<'a>>(&self, key: Q) -> Result<Option<V>> {
+		let jvm = self.object.jvm;
+		let method = MAP.method(jvm, "get", "(Ljava/lang/Object;)Ljava/lang/Object;")?;
+		from_element(with_local_frame_value(jvm, || {
+			method.call(&self.object, &[to_element(jvm, key)?])
+		})?)
+	}
+
+	/// Removes the entry for the given key, returning its value if there was
+	/// one.
+	pub fn remove<Q: IntoJava<'a>>(&self, key: Q) -> Result<Option<V>> {
+		let jvm = self.object.jvm;
+		let method = MAP.method(jvm, "remove", "(Ljava/lang/Object;)Ljava/lang/Object;")?;
+		from_element(with_local_frame_value(jvm, || {
+			method.call(&self.object, &[to_element(jvm, key)?])
+		})?)
+	}
+
+	/// Returns an iterator over the map's keys.
+	pub fn keys(&self) -> Result<JIterator<'a, K>> {
+		iterator(&self.view("keySet", "()Ljava/util/Set;")?)
+	}
+
+	/// Returns an iterator over the map's values.
+	pub fn values(&self) -> Result<JIterator<'a, V>> {
+		iterator(&self.view("values", "()Ljava/util/Collection;")?)
+	}
+
+	/// Returns an iterator over the map's entries, as key-value pairs.
+	pub fn iter(&self) -> Result<JEntries<'a, K, V>> {
+		Ok(JEntries {
+			entries: iterator(&self.view("entrySet", "()Ljava/util/Set;")?)?,
+			marker: PhantomData,
+		})
+	}
+
+	/// Copies the map's entries into a Rust map.
+	pub fn to_map(&self) -> Result<HashMap<K, V>> where K: Eq + Hash {
+		self.iter()?.collect()
+	}
+}
+
+
+/// An iterator over a `java.util.Iterator`, converting each element into `T`.
+///
+/// Each step calls into the JVM, so yields a `Result`. The iterator stops
+/// after the first error.
+#[derive(Debug)]
+pub struct JIterator<'a, T> {
+	iterator: Object<'a>,
+	done: bool,
+	marker: PhantomData<T>,
+}
+
+impl<'a, T> JIterator<'a, T> {
+	/// Wraps an object, which must be a non-null instance of
+	/// `java.util.Iterator`.
+	pub fn from_object(object: Object<'a>) -> Result<JIterator<'a, T>> {
+		check_instance(&object, &ITERATOR)?;
+		Ok(JIterator {
+			iterator: object,
+			done: false,
+			marker: PhantomData,
+		})
+	}
+}
+
+impl<'a, T: FromJava<'a>> JIterator<'a, T> {
+	/// Returns the next element, or None if there are no more.
+	fn advance(&self) -> Result<Option<T>> {
+		let jvm = self.iterator.jvm;
+		let has_next = ITERATOR.method(jvm, "hasNext", "()Z")?;
+		if !bool::from_java(has_next.call(&self.iterator, &[])?)? {
+			return Ok(None);
+		}
+		let next = ITERATOR.method(jvm, "next", "()Ljava/lang/Object;")?;
+		from_element(next.call(&self.iterator, &[])?).map(Some)
+	}
+}
+
+impl<'a, T: FromJava<'a>> Iterator for JIterator<'a, T> {
+	type Item = Result<T>;
+
+	fn next(&mut self) -> Option<Result<T>> {
+		if self.done {
+			return None;
+		}
+		let result = self.advance();
+		if !matches!(result, Ok(Some(_))) {
+			self.done = true;
+		}
+		result.transpose()
+	}
+}
+
+
+/// An iterator over the entries of a `JMap`.
+#[derive(Debug)]
+pub struct JEntries<'a, K, V> {
+	entries: JIterator<'a, Object<'a>>,
+	marker: PhantomData<(K, V)>,
+}
+
+impl<'a, K: FromJava<'a>, V: FromJava<'a>> Iterator for JEntries<'a, K, V> {
+	type Item = Result<(K, V)>;
+
+	fn next(&mut self) -> Option<Result<(K, V)>> {
+		let entry = match self.entries.next()? {
+			Ok(entry) => entry,
+			Err(err) => return Some(Err(err)),
+		};
+		let result = (|| {
+			let jvm = entry.jvm;
+			let key = MAP_ENTRY.method(jvm, "getKey", "()Ljava/lang/Object;")?;
+			let value = MAP_ENTRY.method(jvm, "getValue", "()Ljava/lang/Object;")?;
+			Ok((from_element(key.call(&entry, &[])?)?,
+				from_element(value.call(&entry, &[])?)?))
+		})();
+		delete_local_ref(&entry);
+		if result.is_err() {
+			self.entries.done = true;
+		}
+		Some(result)
+	}
+}
+
+
+
+//
+//  Conversions
+//
+
+/// Implements the conversion traits for a collection wrapper, which is
+/// passed as the given interface.
+macro_rules! wrapper {
+	($wrapper:ident <$($param:ident),*>, $interface:expr) => {
+		impl<'a, $($param),*> IntoJava<'a> for $wrapper<'a, $($param),*> {
+			fn java_type() -> JavaType {
+				JavaType::Object(String::from($interface))
+			}
+
+			fn into_java(self, _: &'a JavaVM) -> Result<Value<'a>> {
+				Ok(Value::Object(self.object))
+			}
+		}
+
+		impl<'a, $($param),*> FromJava<'a> for $wrapper<'a, $($param),*> {
+			fn java_type() -> JavaType {
+				JavaType::Object(String::from($interface))
+			}
+
+			fn from_java(value: Value<'a>) -> Result<$wrapper<'a, $($param),*>> {
+				$wrapper::from_object(Object::from_java(value)?)
+			}
+		}
+	};
+}
+
+wrapper!(JList<T>, "java/util/List");
+wrapper!(JSet<T>, "java/util/Set");
+wrapper!(JMap<K, V>, "java/util/Map");
+
+impl<'a, T: IntoJava<'a>> IntoJava<'a> for HashSet<T> {
+	fn java_type() -> JavaType {
+		JavaType::Object(String::from("java/util/Set"))
+	}
+
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		JSet::from_set(jvm, self)?.into_java(jvm)
+	}
+}
+
+impl<'a, T: FromJava<'a> + Eq + Hash> FromJava<'a> for HashSet<T> {
+	fn java_type() -> JavaType {
+		JavaType::Object(String::from("java/util/Set"))
+	}
+
+	fn from_java(value: Value<'a>) -> Result<HashSet<T>> {
+		JSet::from_java(value)?.to_set()
+	}
+}
+
+impl<'a, K: IntoJava<'a>, V: IntoJava<'a>> IntoJava<'a> for HashMap<K, V> {
+	fn java_type() -> JavaType {
+		JavaType::Object(String::from("java/util/Map"))
+	}
+
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		JMap::from_map(jvm, self)?.into_java(jvm)
+	}
+}
+
+impl<'a, K: FromJava<'a> + Eq + Hash, V: FromJava<'a>> FromJava<'a> for HashMap<K, V> {
+	fn java_type() -> JavaType {
+		JavaType::Object(String::from("java/util/Map"))
+	}
+
+	fn from_java(value: Value<'a>) -> Result<HashMap<K, V>> {
+		JMap::from_java(value)?.to_map()
+	}
+}
+
+
+/// Returns true if the object is a `java.util.Collection`, whose elements
+/// can be read into a vector.
+pub fn is_collection(object: &Object) -> Result<bool> {
+	Ok(!object.is_null() && object.is_instance_of(COLLECTION.class(object.jvm)?))
+}
+
+/// Reads every element of a `java.util.Collection` into a vector.
+pub fn collection_to_vec<'a, T: FromJava<'a>>(object: &Object<'a>) -> Result<Vec<T>> {
+	iterator(object)?.collect()
+}
+
+/// Returns an iterator over a `java.util.Collection`'s elements.
+fn iterator<'a, T>(collection: &Object<'a>) -> Result<JIterator<'a, T>> {
+	let method = COLLECTION.method(collection.jvm, "iterator", "()Ljava/util/Iterator;")?;
+	JIterator::from_object(Object::from_java(method.call(collection, &[])?)?)
+}
+
+/// Returns the number of elements in a `java.util.Collection`.
+fn size(collection: &Object) -> Result<usize> {
+	let method = COLLECTION.method(collection.jvm, "size", "()I")?;
+	Ok(i32::from_java(method.call(collection, &[])?)? as usize)
+}
+
+/// Returns an error if the object isn't a non-null instance of the cached
+/// interface, since calling its methods on any other object is undefined.
+fn check_instance(object: &Object, interface: &ClassCache) -> Result<()> {
+	if !object.is_null() && object.is_instance_of(interface.class(object.jvm)?) {
+		Ok(())
+	} else {
+		let expected = JavaType::Object(String::from(interface.name));
+		Err(mismatch(&Value::Object(object.clone()), &expected))
+	}
+}
+
+/// Converts a value into an element of a collection, which must be an object.
+fn to_element<'a, T: IntoJava<'a>>(jvm: &'a JavaVM, value: T) -> Result<Value<'a>> {
+	value.into_java(jvm)?.boxed(jvm)
+}
+
+/// Converts an element of a collection (or array) into a Rust type.
+///
+/// Elements are always returned as objects, so strings are read as `String`s
+/// if that's what `T` expects. The element must be a new local reference,
+/// which is deleted if `T` copies it into Rust.
+pub fn from_element<'a, T: FromJava<'a>>(value: Value<'a>) -> Result<T> {
+	let obj = match value {
+		Value::Object(ref obj) if copies::<T>() => obj.clone(),
+		value => return T::from_java(value),
+	};
+	// Nothing created while copying the element is needed afterwards either
+	let result = with_local_frame(obj.jvm, || {
+		if let Type::Str = T::java_type().value_type() {
+			if !obj.is_null() && obj.is_instance_of(STRING.class(obj.jvm)?) {
+				return T::from_java(unsafe { from_jobject(obj.jvm, obj.raw, &Type::Str) });
+			}
+		}
+		T::from_java(value)
+	});
+	delete_local_ref(&obj);
+	result
+}
+
+/// Returns true if converting an object into `T` copies it into Rust (as a
+/// string, boxed primitive, or array of them), so `T` holds no references.
+fn copies<'a, T: FromJava<'a>>() -> bool {
+	match T::java_type().base_type() {
+		&JavaType::Object(ref name) => name == "java/lang/String" || is_box_class(name),
+		_ => true,
+	}
+}
+
+/// Converts an index into a Java `int`.
+fn java_index<'a>(index: usize) -> Result<Value<'a>> {
+	match i32::try_from(index) {
+		Ok(index) => Ok(Value::Int(index)),
+		Err(_) => Err(Error::Conversion(format!(
+			"index {} is too large for a Java collection", index))),
+	}
+}
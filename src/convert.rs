@@ -0,0 +1,530 @@
+//
+//  Conversions
+//
+
+//! Conversions between Rust types and Java values.
+
+use std::convert::TryFrom;
+use std::ptr;
+
+use ffi;
+use dispatch::{from_jchar, to_jchar};
+use boxing::box_class_name;
+use collections::{is_collection, collection_to_vec, from_element};
+use {JavaVM, Object, Value, JavaType, internal_name};
+use {Error, Result, with_local_frame};
+
+
+/// A Rust type that can be converted into a Java value, to pass as an argument
+/// to a method or store in a field.
+///
+/// This is implemented for the primitive types, `String`, `&str`, `Object`,
+/// and `Option`s, `Vec`s, slices and arrays of those. Implement it for your
+/// own types to pass them across the boundary too.
+pub trait IntoJava<'a> {
+	/// Returns the Java type of the converted value.
+	fn java_type() -> JavaType;
+
+	/// Converts this into a Java value.
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>>;
+}
+
+/// A Rust type that can be created from a Java value, returned from a method
+/// or read from a field.
+///
+/// This is implemented for the same types as `IntoJava`, and `()` for methods
+/// that don't return anything.
+pub trait FromJava<'a>: Sized {
+	/// Returns the Java type of the values this is created from.
+	fn java_type() -> JavaType;
+
+	/// Creates this from a Java value.
+	fn from_java(value: Value<'a>) -> Result<Self>;
+}
+
+
+
+//
+//  Primitives
+//
+
+/// Implements the conversion traits for a primitive type, which is stored in
+/// the given `Value` variant.
+macro_rules! primitive {
+	($kind:ty, $variant:ident, $java:expr) => {
+		impl<'a> IntoJava<'a> for $kind {
+			fn java_type() -> JavaType {
+				$java
+			}
+
+			fn into_java(self, _: &'a JavaVM) -> Result<Value<'a>> {
+				Ok(Value::$variant(self))
+			}
+		}
+
+		impl<'a> FromJava<'a> for $kind {
+			fn java_type() -> JavaType {
+				$java
+			}
+
+			fn from_java(value: Value<'a>) -> Result<$kind> {
+				match value.unboxed()? {
+					Value::$variant(value) => Ok(value),
+					value => Err(mismatch(&value, &$java)),
+				}
+			}
+		}
+	};
+}
+
+primitive!(bool, Boolean, JavaType::Boolean);
+primitive!(i8, Byte, JavaType::Byte);
+primitive!(char, Char, JavaType::Char);
+primitive!(i16, Short, JavaType::Short);
+primitive!(i32, Int, JavaType::Int);
+primitive!(i64, Long, JavaType::Long);
+primitive!(f32, Float, JavaType::Float);
+primitive!(f64, Double, JavaType::Double);
+
+impl<'a> FromJava<'a> for () {
+	fn java_type() -> JavaType {
+		JavaType::Void
+	}
+
+	fn from_java(value: Value<'a>) -> Result<()> {
+		match value {
+			Value::Void => Ok(()),
+			value => Err(mismatch(&value, &JavaType::Void)),
+		}
+	}
+}
+
+
+
+//
+//  Objects
+//
+
+/// Returns the type of a `java.lang.String`.
+fn string_type() -> JavaType {
+	JavaType::Object(String::from("java/lang/String"))
+}
+
+/// Returns the type of a `java.lang.Object`.
+fn object_type() -> JavaType {
+	JavaType::Object(String::from("java/lang/Object"))
+}
+
+impl<'a> IntoJava<'a> for String {
+	fn java_type() -> JavaType {
+		string_type()
+	}
+
+	fn into_java(self, _: &'a JavaVM) -> Result<Value<'a>> {
+		Ok(Value::Str(self))
+	}
+}
+
+impl<'a, 's> IntoJava<'a> for &'s str {
+	fn java_type() -> JavaType {
+		string_type()
+	}
+
+	fn into_java(self, _: &'a JavaVM) -> Result<Value<'a>> {
+		Ok(Value::Str(String::from(self)))
+	}
+}
+
+impl<'a> FromJava<'a> for String {
+	fn java_type() -> JavaType {
+		string_type()
+	}
+
+	fn from_java(value: Value<'a>) -> Result<String> {
+		match value {
+			Value::Str(value) => Ok(value),
+			value => Err(mismatch(&value, &string_type())),
+		}
+	}
+}
+
+impl<'a> IntoJava<'a> for Object<'a> {
+	fn java_type() -> JavaType {
+		object_type()
+	}
+
+	fn into_java(self, _: &'a JavaVM) -> Result<Value<'a>> {
+		Ok(Value::Object(self))
+	}
+}
+
+impl<'a> FromJava<'a> for Object<'a> {
+	fn java_type() -> JavaType {
+		object_type()
+	}
+
+	fn from_java(value: Value<'a>) -> Result<Object<'a>> {
+		match value {
+			Value::Object(obj) => Ok(obj),
+			value => Err(mismatch(&value, &object_type())),
+		}
+	}
+}
+
+// `None` is converted to and from null, so primitives are boxed (eg. an
+// `Option<i32>` is a `java.lang.Integer`)
+impl<'a, T: IntoJava<'a>> IntoJava<'a> for Option<T> {
+	fn java_type() -> JavaType {
+		boxed_type(<T as IntoJava>::java_type())
+	}
+
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		match self {
+			Some(value) => value.into_java(jvm)?.boxed(jvm),
+			None => Ok(Value::Object(Object {
+				jvm: jvm,
+				raw: ptr::null_mut(),
+			})),
+		}
+	}
+}
+
+impl<'a, T: FromJava<'a>> FromJava<'a> for Option<T> {
+	fn java_type() -> JavaType {
+		boxed_type(<T as FromJava>::java_type())
+	}
+
+	fn from_java(value: Value<'a>) -> Result<Option<T>> {
+		match value {
+			Value::Object(ref obj) if obj.is_null() => Ok(None),
+			value => T::from_java(value).map(Some),
+		}
+	}
+}
+
+/// Returns the box class for a primitive type, so that it can be null.
+fn boxed_type(kind: JavaType) -> JavaType {
+	if kind.is_primitive() && kind != JavaType::Void {
+		JavaType::Object(String::from(box_class_name(&kind)))
+	} else {
+		kind
+	}
+}
+
+/// Returns an error for a value that isn't of the expected type.
+pub(crate) fn mismatch(value: &Value, expected: &JavaType) -> Error {
+	let found = match value {
+		&Value::Boolean(_) => String::from("boolean"),
+		&Value::Byte(_) => String::from("byte"),
+		&Value::Char(_) => String::from("char"),
+		&Value::Short(_) => String::from("short"),
+		&Value::Int(_) => String::from("int"),
+		&Value::Long(_) => String::from("long"),
+		&Value::Float(_) => String::from("float"),
+		&Value::Double(_) => String::from("double"),
+		&Value::Str(_) => string_type().class_name(),
+		&Value::Void => String::from("void"),
+		&Value::Object(ref obj) if obj.is_null() => String::from("null"),
+		&Value::Object(ref obj) => obj.class_name()
+			.unwrap_or_else(|_| String::from("an object")),
+	};
+	Error::Conversion(format!("expected {}, found {}", expected.class_name(), found))
+}
+
+
+
+//
+//  Arrays
+//
+
+impl<'a, T: IntoJava<'a>> IntoJava<'a> for Vec<T> {
+	fn java_type() -> JavaType {
+		JavaType::array(<T as IntoJava>::java_type(), 1)
+	}
+
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		new_array(jvm, self).map(Value::Object)
+	}
+}
+
+impl<'a, 's, T: IntoJava<'a> + Clone> IntoJava<'a> for &'s [T] {
+	fn java_type() -> JavaType {
+		JavaType::array(<T as IntoJava>::java_type(), 1)
+	}
+
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		self.to_vec().into_java(jvm)
+	}
+}
+
+impl<'a, T: IntoJava<'a>, const N: usize> IntoJava<'a> for [T; N] {
+	fn java_type() -> JavaType {
+		JavaType::array(<T as IntoJava>::java_type(), 1)
+	}
+
+	fn into_java(self, jvm: &'a JavaVM) -> Result<Value<'a>> {
+		Vec::from(self).into_java(jvm)
+	}
+}
+
+impl<'a, T: FromJava<'a>> FromJava<'a> for Vec<T> {
+	fn java_type() -> JavaType {
+		JavaType::array(<T as FromJava>::java_type(), 1)
+	}
+
+	fn from_java(value: Value<'a>) -> Result<Vec<T>> {
+		let array = match value {
+			Value::Object(obj) => obj,
+			value => return Err(mismatch(&value, &Self::java_type())),
+		};
+		if array.is_null() {
+			return Err(mismatch(&Value::Object(array), &Self::java_type()));
+		}
+
+		// Lists and other collections are read through their iterator
+		if is_collection(&array)? {
+			return collection_to_vec(&array);
+		}
+
+		array_values(&array)
+	}
+}
+
+impl<'a, T: FromJava<'a>, const N: usize> FromJava<'a> for [T; N] {
+	fn java_type() -> JavaType {
+		JavaType::array(<T as FromJava>::java_type(), 1)
+	}
+
+	fn from_java(value: Value<'a>) -> Result<[T; N]> {
+		let elements = Vec::<T>::from_java(value)?;
+		let length = elements.len();
+		<[T; N]>::try_from(elements).map_err(|_| Error::Conversion(format!(
+			"expected an array of length {}, found length {}", N, length)))
+	}
+}
+
+
+/// A primitive type that can be stored in a Java array.
+trait ArrayElement: Copy + Default {
+	/// Creates a new array of this type with the given length.
+	unsafe fn new_array(env: *mut ffi::JNIEnv, length: ffi::jsize) -> ffi::jarray;
+
+	/// Copies a region of an array into a buffer.
+	unsafe fn get_region(env: *mut ffi::JNIEnv, array: ffi::jarray,
+	                     length: ffi::jsize, buffer: *mut Self);
+
+	/// Copies a buffer into a region of an array.
+	unsafe fn set_region(env: *mut ffi::JNIEnv, array: ffi::jarray,
+	                     length: ffi::jsize, buffer: *const Self);
+}
+
+/// Implements `ArrayElement` using the given family of JNI functions.
+macro_rules! array_element {
+	($kind:ty, $new:ident, $get:ident, $set:ident) => {
+		impl ArrayElement for $kind {
+			unsafe fn new_array(env: *mut ffi::JNIEnv, length: ffi::jsize)
+					-> ffi::jarray {
+				((**env).$new)(env, length)
+			}
+
+			unsafe fn get_region(env: *mut ffi::JNIEnv, array: ffi::jarray,
+			                     length: ffi::jsize, buffer: *mut $kind) {
+				((**env).$get)(env, array, 0, length, buffer)
+			}
+
+			unsafe fn set_region(env: *mut ffi::JNIEnv, array: ffi::jarray,
+			                     length: ffi::jsize, buffer: *const $kind) {
+				((**env).$set)(env, array, 0, length, buffer)
+			}
+		}
+	};
+}
+
+array_element!(ffi::jboolean, NewBooleanArray, GetBooleanArrayRegion, SetBooleanArrayRegion);
+array_element!(i8, NewByteArray, GetByteArrayRegion, SetByteArrayRegion);
+array_element!(u16, NewCharArray, GetCharArrayRegion, SetCharArrayRegion);
+array_element!(i16, NewShortArray, GetShortArrayRegion, SetShortArrayRegion);
+array_element!(i32, NewIntArray, GetIntArrayRegion, SetIntArrayRegion);
+array_element!(i64, NewLongArray, GetLongArrayRegion, SetLongArrayRegion);
+array_element!(f32, NewFloatArray, GetFloatArrayRegion, SetFloatArrayRegion);
+array_element!(f64, NewDoubleArray, GetDoubleArrayRegion, SetDoubleArrayRegion);
+
+/// Copies every element of a primitive array into a vector.
+unsafe fn read_elements<T: ArrayElement>(env: *mut ffi::JNIEnv,
+                                         array: ffi::jarray, length: ffi::jsize)
+		-> Vec<T> {
+	let mut elements = vec![T::default(); length as usize];
+	T::get_region(env, array, length, elements.as_mut_ptr());
+	elements
+}
+
+/// Creates a primitive array holding the given elements.
+unsafe fn write_elements<T: ArrayElement>(env: *mut ffi::JNIEnv, elements: &[T])
+		-> ffi::jarray {
+	let length = elements.len() as ffi::jsize;
+	let array = T::new_array(env, length);
+	if !array.is_null() {
+		T::set_region(env, array, length, elements.as_ptr());
+	}
+	array
+}
+
+/// Takes the contents of each value, which must all be the given variant.
+macro_rules! unpack {
+	($values:expr, $element:expr, $variant:ident) => {
+		$values.into_iter().map(|value| match value {
+			Value::$variant(value) => Ok(value),
+			value => Err(mismatch(&value, $element)),
+		}).collect::<Result<Vec<_>>>()
+	};
+}
+
+/// Creates a Java array holding the given values.
+fn new_array<'a, T: IntoJava<'a>>(jvm: &'a JavaVM, elements: Vec<T>)
+		-> Result<Object<'a>> {
+	let env = jvm.env;
+	let element = &<T as IntoJava>::java_type();
+
+	// Java arrays are indexed by an `int`
+	let length = match ffi::jsize::try_from(elements.len()) {
+		Ok(length) => length,
+		Err(_) => return Err(Error::Conversion(format!(
+			"{} elements is too many for a Java array", elements.len()))),
+	};
+
+	if let &JavaType::Object(_) | &JavaType::Array(_) = element {
+		return new_object_array(jvm, element, length, elements);
+	}
+	let values = elements.into_iter()
+		.map(|value| value.into_java(jvm))
+		.collect::<Result<Vec<_>>>()?;
+
+	let raw = unsafe {
+		match element {
+			&JavaType::Boolean => {
+				let elements = unpack!(values, element, Boolean)?.into_iter()
+					.map(|value| if value { ffi::JNI_TRUE } else { ffi::JNI_FALSE })
+					.collect::<Vec<_>>();
+				write_elements(env, &elements)
+			},
+			&JavaType::Byte => write_elements(env, &unpack!(values, element, Byte)?),
+			&JavaType::Char => {
+				let elements = unpack!(values, element, Char)?.into_iter()
+					.map(to_jchar)
+					.collect::<Result<Vec<_>>>()?;
+				write_elements(env, &elements)
+			},
+			&JavaType::Short => write_elements(env, &unpack!(values, element, Short)?),
+			&JavaType::Int => write_elements(env, &unpack!(values, element, Int)?),
+			&JavaType::Long => write_elements(env, &unpack!(values, element, Long)?),
+			&JavaType::Float => write_elements(env, &unpack!(values, element, Float)?),
+			&JavaType::Double => write_elements(env, &unpack!(values, element, Double)?),
+			&JavaType::Void => {
+				return Err(Error::Conversion(String::from("an array can't hold void")));
+			},
+			&JavaType::Object(_) | &JavaType::Array(_) => unreachable!(),
+		}
+	};
+
+	if jvm.has_exception() {
+		Err(Error::from_exception(jvm))
+	} else {
+		Ok(Object {
+			jvm: jvm,
+			raw: raw,
+		})
+	}
+}
+
+/// Creates a Java array of objects with the given element type, holding the
+/// given values.
+///
+/// Each value is converted just before it's stored, in its own local reference
+/// frame, so the references created while converting it are deleted once it's
+/// in the array.
+fn new_object_array<'a, T: IntoJava<'a>>(jvm: &'a JavaVM, element: &JavaType,
+                                         length: ffi::jsize, elements: Vec<T>)
+		-> Result<Object<'a>> {
+	// Create an array of the element's class
+	let env = jvm.env;
+	let class = jvm.class(&internal_name(&element.class_name()))?;
+	let array = unsafe { ((**env).NewObjectArray)(env, length, class.raw, ptr::null_mut()) };
+	if array.is_null() {
+		return Err(Error::from_exception(jvm));
+	}
+
+	// Store each element, which the JVM checks is an instance of the element's
+	// class
+	for (index, value) in elements.into_iter().enumerate() {
+		with_local_frame(jvm, || {
+			let value = value.into_java(jvm)?;
+			let obj = match &value {
+				&Value::Str(_) | &Value::Object(_) => unsafe { value.to_jvalue(jvm)?.l },
+				value => return Err(mismatch(value, element)),
+			};
+			unsafe { ((**env).SetObjectArrayElement)(env, array, index as ffi::jsize, obj) };
+			if jvm.has_exception() {
+				return Err(Error::from_exception(jvm));
+			}
+			Ok(())
+		})?;
+	}
+
+	Ok(Object {
+		jvm: jvm,
+		raw: array,
+	})
+}
+
+/// Reads every element of a Java array, converting each into `T`.
+///
+/// Objects are converted as they're read, so only one element's reference is
+/// held at a time if `T` copies it into Rust.
+fn array_values<'a, T: FromJava<'a>>(array: &Object<'a>) -> Result<Vec<T>> {
+	let jvm = array.jvm;
+	let env = jvm.env;
+	let element = &<T as FromJava>::java_type();
+
+	// Reading an array as the wrong type is undefined, so check its class
+	let kind = JavaType::array(element.clone(), 1);
+	if let &JavaType::Void = element {
+		return Err(Error::Conversion(String::from("an array can't hold void")));
+	}
+	let class = jvm.class(&internal_name(&kind.class_name()))?;
+	if !array.is_instance_of(class) {
+		return Err(mismatch(&Value::Object(array.clone()), &kind));
+	}
+
+	let values: Vec<Value> = unsafe {
+		let length = ((**env).GetArrayLength)(env, array.raw);
+		match element {
+			&JavaType::Boolean => read_elements::<ffi::jboolean>(env, array.raw, length)
+				.into_iter()
+				.map(|value| Value::Boolean(value == ffi::JNI_TRUE))
+				.collect(),
+			&JavaType::Byte => read_elements(env, array.raw, length)
+				.into_iter().map(Value::Byte).collect(),
+			&JavaType::Char => read_elements(env, array.raw, length)
+				.into_iter().map(|value| Value::Char(from_jchar(value))).collect(),
+			&JavaType::Short => read_elements(env, array.raw, length)
+				.into_iter().map(Value::Short).collect(),
+			&JavaType::Int => read_elements(env, array.raw, length)
+				.into_iter().map(Value::Int).collect(),
+			&JavaType::Long => read_elements(env, array.raw, length)
+				.into_iter().map(Value::Long).collect(),
+			&JavaType::Float => read_elements(env, array.raw, length)
+				.into_iter().map(Value::Float).collect(),
+			&JavaType::Double => read_elements(env, array.raw, length)
+				.into_iter().map(Value::Double).collect(),
+			&JavaType::Void => unreachable!(),
+			&JavaType::Object(_) | &JavaType::Array(_) => {
+				return (0..length).map(|index| from_element(Value::Object(Object {
+					jvm: jvm,
+					raw: ((**env).GetObjectArrayElement)(env, array.raw, index),
+				}))).collect();
+			},
+		}
+	};
+
+	values.into_iter().map(T::from_java).collect()
+}
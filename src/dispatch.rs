@@ -0,0 +1,331 @@
+//
+//  Typed Dispatch
+//
+
+//! Calls the JNI function matching the type of a method or field.
+//!
+//! Each primitive type has its own family of JNI functions for calling methods
+//! and accessing fields, which return or take that type directly. Calling the
+//! function for the exact type means values are passed where the platform's
+//! calling convention expects them (eg. floats in floating point registers).
+
+use ffi;
+use {JavaVM, Object, Value, Type, Error, Result};
+use {to_jvalues, convert_string};
+
+
+/// A primitive Java type, with its own family of JNI functions for calling
+/// methods and accessing fields.
+///
+/// This trait is sealed. It's implemented for `bool`, `i8`, `u16` (a Java
+/// `char`, which is a UTF-16 code unit), `i16`, `i32`, `i64`, `f32` and `f64`.
+pub trait JavaPrimitive: private::Sealed {
+	/// The Java type corresponding to this Rust type.
+	const TYPE: Type;
+}
+
+mod private {
+	/// Implemented for each type with a `Dispatch` implementation that's a
+	/// primitive, so `JavaPrimitive` can't be implemented outside this crate.
+	pub trait Sealed: super::Dispatch {}
+}
+
+/// What a method is called on, or a field is accessed through.
+#[derive(Clone, Copy)]
+pub enum Receiver {
+	/// An instance method or field on an object.
+	Instance(ffi::jobject),
+
+	/// A particular class' implementation of a method, called on an object.
+	Nonvirtual(ffi::jobject, ffi::jclass),
+
+	/// A static method or field on a class.
+	Static(ffi::jclass),
+}
+
+/// The JNI functions for calling methods returning a type, and accessing
+/// fields of that type.
+///
+/// Implemented for the JNI representation of each Java type. A `char` is
+/// dispatched as a `u16` (a UTF-16 code unit), and converted to and from a
+/// Rust `char` by `from_jchar` and `to_jchar`.
+pub trait Dispatch: Sized {
+	/// Calls the method with the given ID.
+	unsafe fn call(env: *mut ffi::JNIEnv, receiver: Receiver,
+	               id: ffi::jmethodID, args: *const ffi::jvalue) -> Self;
+
+	/// Reads the field with the given ID.
+	unsafe fn get(env: *mut ffi::JNIEnv, receiver: Receiver,
+	              id: ffi::jfieldID) -> Self;
+
+	/// Writes the field with the given ID.
+	unsafe fn set(env: *mut ffi::JNIEnv, receiver: Receiver,
+	              id: ffi::jfieldID, value: Self);
+}
+
+/// Implements `Dispatch` for a type using the given family of JNI functions.
+macro_rules! dispatch {
+	($kind:ty, $call:ident, $nonvirtual:ident, $static_call:ident,
+	 $get:ident, $set:ident, $get_static:ident, $set_static:ident) => {
+		impl Dispatch for $kind {
+			unsafe fn call(env: *mut ffi::JNIEnv, receiver: Receiver,
+			               id: ffi::jmethodID, args: *const ffi::jvalue) -> $kind {
+				match receiver {
+					Receiver::Instance(obj) =>
+						((**env).$call)(env, obj, id, args),
+					Receiver::Nonvirtual(obj, class) =>
+						((**env).$nonvirtual)(env, obj, class, id, args),
+					Receiver::Static(class) =>
+						((**env).$static_call)(env, class, id, args),
+				}
+			}
+
+			unsafe fn get(env: *mut ffi::JNIEnv, receiver: Receiver,
+			              id: ffi::jfieldID) -> $kind {
+				match receiver {
+					Receiver::Instance(obj) | Receiver::Nonvirtual(obj, _) =>
+						((**env).$get)(env, obj, id),
+					Receiver::Static(class) =>
+						((**env).$get_static)(env, class, id),
+				}
+			}
+
+			unsafe fn set(env: *mut ffi::JNIEnv, receiver: Receiver,
+			              id: ffi::jfieldID, value: $kind) {
+				match receiver {
+					Receiver::Instance(obj) | Receiver::Nonvirtual(obj, _) =>
+						((**env).$set)(env, obj, id, value),
+					Receiver::Static(class) =>
+						((**env).$set_static)(env, class, id, value),
+				}
+			}
+		}
+	};
+}
+
+dispatch!(ffi::jobject, CallObjectMethodA, CallNonvirtualObjectMethodA,
+	CallStaticObjectMethodA, GetObjectField, SetObjectField,
+	GetStaticObjectField, SetStaticObjectField);
+dispatch!(ffi::jboolean, CallBooleanMethodA, CallNonvirtualBooleanMethodA,
+	CallStaticBooleanMethodA, GetBooleanField, SetBooleanField,
+	GetStaticBooleanField, SetStaticBooleanField);
+dispatch!(i8, CallByteMethodA, CallNonvirtualByteMethodA,
+	CallStaticByteMethodA, GetByteField, SetByteField,
+	GetStaticByteField, SetStaticByteField);
+dispatch!(u16, CallCharMethodA, CallNonvirtualCharMethodA,
+	CallStaticCharMethodA, GetCharField, SetCharField,
+	GetStaticCharField, SetStaticCharField);
+dispatch!(i16, CallShortMethodA, CallNonvirtualShortMethodA,
+	CallStaticShortMethodA, GetShortField, SetShortField,
+	GetStaticShortField, SetStaticShortField);
+dispatch!(i32, CallIntMethodA, CallNonvirtualIntMethodA,
+	CallStaticIntMethodA, GetIntField, SetIntField,
+	GetStaticIntField, SetStaticIntField);
+dispatch!(i64, CallLongMethodA, CallNonvirtualLongMethodA,
+	CallStaticLongMethodA, GetLongField, SetLongField,
+	GetStaticLongField, SetStaticLongField);
+dispatch!(f32, CallFloatMethodA, CallNonvirtualFloatMethodA,
+	CallStaticFloatMethodA, GetFloatField, SetFloatField,
+	GetStaticFloatField, SetStaticFloatField);
+dispatch!(f64, CallDoubleMethodA, CallNonvirtualDoubleMethodA,
+	CallStaticDoubleMethodA, GetDoubleField, SetDoubleField,
+	GetStaticDoubleField, SetStaticDoubleField);
+
+// A `jboolean` is a byte, which is only true if it's exactly `JNI_TRUE`
+impl Dispatch for bool {
+	unsafe fn call(env: *mut ffi::JNIEnv, receiver: Receiver,
+	               id: ffi::jmethodID, args: *const ffi::jvalue) -> bool {
+		<ffi::jboolean as Dispatch>::call(env, receiver, id, args) == ffi::JNI_TRUE
+	}
+
+	unsafe fn get(env: *mut ffi::JNIEnv, receiver: Receiver,
+	              id: ffi::jfieldID) -> bool {
+		<ffi::jboolean as Dispatch>::get(env, receiver, id) == ffi::JNI_TRUE
+	}
+
+	unsafe fn set(env: *mut ffi::JNIEnv, receiver: Receiver,
+	              id: ffi::jfieldID, value: bool) {
+		let value = if value { ffi::JNI_TRUE } else { ffi::JNI_FALSE };
+		<ffi::jboolean as Dispatch>::set(env, receiver, id, value)
+	}
+}
+
+/// Implements `JavaPrimitive` for a type, as the given Java type.
+macro_rules! primitive {
+	($kind:ty, $java:expr) => {
+		impl private::Sealed for $kind {}
+
+		impl JavaPrimitive for $kind {
+			const TYPE: Type = $java;
+		}
+	};
+}
+
+primitive!(bool, Type::Boolean);
+primitive!(i8, Type::Byte);
+primitive!(u16, Type::Char);
+primitive!(i16, Type::Short);
+primitive!(i32, Type::Int);
+primitive!(i64, Type::Long);
+primitive!(f32, Type::Float);
+primitive!(f64, Type::Double);
+
+
+/// Calls the method with the given ID, using the JNI function for its return
+/// type.
+///
+/// The method ID must belong to the receiver's class (or one of its
+/// superclasses), and have the given return type.
+pub unsafe fn call<'a>(jvm: &'a JavaVM, receiver: Receiver, id: ffi::jmethodID,
+                       args: &[Value], return_type: &Type) -> Result<Value<'a>> {
+	let env = jvm.env;
+
+	// Convert the list of arguments into an array of jvalues
+	let java_args = to_jvalues(args, jvm)?;
+	let args = java_args.as_ptr();
+
+	// Call the method
+	let result = match return_type {
+		&Type::Boolean => Value::Boolean(bool::call(env, receiver, id, args)),
+		&Type::Byte => Value::Byte(i8::call(env, receiver, id, args)),
+		&Type::Char => Value::Char(from_jchar(u16::call(env, receiver, id, args))),
+		&Type::Short => Value::Short(i16::call(env, receiver, id, args)),
+		&Type::Int => Value::Int(i32::call(env, receiver, id, args)),
+		&Type::Long => Value::Long(i64::call(env, receiver, id, args)),
+		&Type::Float => Value::Float(f32::call(env, receiver, id, args)),
+		&Type::Double => Value::Double(f64::call(env, receiver, id, args)),
+		&Type::Str | &Type::Object(_) => {
+			let obj = <ffi::jobject as Dispatch>::call(env, receiver, id, args);
+			check_exception(jvm)?;
+			from_jobject(jvm, obj, return_type)
+		},
+		&Type::Void => {
+			call_void(env, receiver, id, args);
+			Value::Void
+		},
+	};
+
+	check_exception(jvm)?;
+	Ok(result)
+}
+
+/// Calls a method with the given ID that doesn't return anything.
+unsafe fn call_void(env: *mut ffi::JNIEnv, receiver: Receiver,
+                    id: ffi::jmethodID, args: *const ffi::jvalue) {
+	match receiver {
+		Receiver::Instance(obj) =>
+			((**env).CallVoidMethodA)(env, obj, id, args),
+		Receiver::Nonvirtual(obj, class) =>
+			((**env).CallNonvirtualVoidMethodA)(env, obj, class, id, args),
+		Receiver::Static(class) =>
+			((**env).CallStaticVoidMethodA)(env, class, id, args),
+	}
+}
+
+/// Reads the field with the given ID, using the JNI function for its type.
+///
+/// The field ID must belong to the receiver's class (or one of its
+/// superclasses), and have the given type.
+pub unsafe fn get_field<'a>(jvm: &'a JavaVM, receiver: Receiver,
+                            id: ffi::jfieldID, kind: &Type) -> Result<Value<'a>> {
+	let env = jvm.env;
+
+	// Get the contents of the field
+	let result = match kind {
+		&Type::Boolean => Value::Boolean(bool::get(env, receiver, id)),
+		&Type::Byte => Value::Byte(i8::get(env, receiver, id)),
+		&Type::Char => Value::Char(from_jchar(u16::get(env, receiver, id))),
+		&Type::Short => Value::Short(i16::get(env, receiver, id)),
+		&Type::Int => Value::Int(i32::get(env, receiver, id)),
+		&Type::Long => Value::Long(i64::get(env, receiver, id)),
+		&Type::Float => Value::Float(f32::get(env, receiver, id)),
+		&Type::Double => Value::Double(f64::get(env, receiver, id)),
+		&Type::Str | &Type::Object(_) => {
+			let obj = <ffi::jobject as Dispatch>::get(env, receiver, id);
+			check_exception(jvm)?;
+			from_jobject(jvm, obj, kind)
+		},
+		&Type::Void => return Err(Error::ArgumentMismatch(
+			String::from("a field can't have type void"))),
+	};
+
+	check_exception(jvm)?;
+	Ok(result)
+}
+
+/// Writes a value to the field with the given ID, using the JNI function for
+/// the value's type.
+///
+/// The field ID must belong to the receiver's class (or one of its
+/// superclasses), and have the value's type.
+pub unsafe fn set_field(jvm: &JavaVM, receiver: Receiver, id: ffi::jfieldID,
+                        value: &Value) -> Result<()> {
+	let env = jvm.env;
+
+	// Set the contents of the field
+	match value {
+		&Value::Boolean(v) => bool::set(env, receiver, id, v),
+		&Value::Byte(v) => i8::set(env, receiver, id, v),
+		&Value::Char(v) => u16::set(env, receiver, id, to_jchar(v)?),
+		&Value::Short(v) => i16::set(env, receiver, id, v),
+		&Value::Int(v) => i32::set(env, receiver, id, v),
+		&Value::Long(v) => i64::set(env, receiver, id, v),
+		&Value::Float(v) => f32::set(env, receiver, id, v),
+		&Value::Double(v) => f64::set(env, receiver, id, v),
+		&Value::Str(_) | &Value::Object(_) => {
+			let java_value = value.to_jvalue(jvm)?;
+			<ffi::jobject as Dispatch>::set(env, receiver, id, java_value.l);
+		},
+		&Value::Void => return Err(Error::ArgumentMismatch(
+			String::from("can't store void in a field"))),
+	}
+
+	check_exception(jvm)
+}
+
+/// Converts a Java character into a Rust one.
+///
+/// A lone surrogate (half of a UTF-16 surrogate pair) isn't a valid Rust
+/// character, so it's replaced with U+FFFD.
+pub fn from_jchar(value: ffi::jchar) -> char {
+	char::from_u32(value as u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Converts a Rust character into a Java one, which must fit in a single
+/// UTF-16 code unit.
+pub fn to_jchar(value: char) -> Result<ffi::jchar> {
+	let code = value as u32;
+	if code > 0xFFFF {
+		Err(Error::ArgumentMismatch(format!(
+			"`{}` doesn't fit in a Java char", value.escape_unicode())))
+	} else {
+		Ok(code as ffi::jchar)
+	}
+}
+
+/// Converts an object returned from the JNI into a value of the given type.
+///
+/// A null string has no contents to read, so it's left as a null object.
+pub unsafe fn from_jobject<'a>(jvm: &'a JavaVM, obj: ffi::jobject, kind: &Type)
+		-> Value<'a> {
+	if let (&Type::Str, false) = (kind, obj.is_null()) {
+		// Allocate a new string object and read from the Java string
+		let mut result = String::new();
+		convert_string(jvm, obj as ffi::jstring, &mut result);
+		Value::Str(result)
+	} else {
+		Value::Object(Object {
+			jvm: jvm,
+			raw: obj,
+		})
+	}
+}
+
+/// Returns the pending exception as an error, if there is one.
+fn check_exception(jvm: &JavaVM) -> Result<()> {
+	if jvm.has_exception() {
+		Err(Error::from_exception(jvm))
+	} else {
+		Ok(())
+	}
+}
@@ -0,0 +1,514 @@
+
+//
+//  Overload Resolution
+//
+
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use ffi;
+use signature::class_type;
+use boxing::{box_class, unboxed_type, primitive_type};
+use {JavaVM, Class, Object, Value, Type, JavaType, MethodSignature, GlobalRef};
+use {Error, Result, with_local_frame};
+
+
+/// The `static` bit in the modifiers returned by `Method.getModifiers`.
+const STATIC_MODIFIER: i32 = 0x0008;
+
+/// The overloads chosen for previous dynamic calls.
+///
+/// Classes from different class loaders can share a name, so each key holds
+/// the overloads chosen for every class with the method and argument types.
+static RESOLVED: Mutex<Option<HashMap<CacheKey, Vec<Resolved>>>> = Mutex::new(None);
+
+/// Identifies a dynamic call by the method being called, and the types of the
+/// arguments passed to it.
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+	name: String,
+	is_static: bool,
+
+	/// The runtime type of each argument, or `None` for a null object.
+	args: Vec<Option<JavaType>>,
+}
+
+/// The overload chosen for a dynamic call.
+#[derive(Clone)]
+struct Resolved {
+	id: ffi::jmethodID,
+	signature: MethodSignature,
+
+	/// Keeps the class loaded, so that the method ID remains valid.
+	class: Arc<GlobalRef>,
+}
+
+unsafe impl Send for Resolved {}
+
+
+/// A public method considered as a candidate for a dynamic call.
+struct Candidate {
+	id: ffi::jmethodID,
+	signature: MethodSignature,
+
+	/// The class of each of the method's parameters.
+	params: Vec<ffi::jclass>,
+}
+
+
+impl<'a> Object<'a> {
+	/// Call a public method on this object, choosing between its overloads
+	/// using the values of the arguments, in the same way the Java compiler
+	/// would.
+	///
+	/// Candidate methods are listed through `Class.getMethods`. An overload is
+	/// applicable if each argument can be passed through primitive widening
+	/// (eg. `int` to `long`) or a reference conversion (eg. `String` to
+	/// `Object`), or failing that, through boxing and unboxing (eg. `int` to
+	/// `Integer`). The most specific applicable overload is called, and the
+	/// arguments are converted to its parameter types. Variable arity methods
+	/// are only considered with an explicit array argument.
+	///
+	/// The choice is cached for the class and the types of the arguments, so
+	/// reflection is only used the first time a method is called with each
+	/// combination of argument types.
+	pub fn call_dynamic(&self, name: &str, args: &[Value]) -> Result<Value<'a>> {
+		let class = self.class();
+		let resolved = resolve(&class, name, args, false)?;
+		let args = coerce(self.jvm, &resolved.signature, args)?;
+		self.invoke(resolved.id, &args, &resolved.signature.return_type().value_type())
+	}
+}
+
+impl<'a> Class<'a> {
+	/// Call a public static method on this class, choosing between its
+	/// overloads using the values of the arguments.
+	///
+	/// Overloads are chosen in the same way as `Object::call_dynamic`.
+	pub fn call_static_dynamic(&self, name: &str, args: &[Value])
+			-> Result<Value<'a>> {
+		let resolved = resolve(self, name, args, true)?;
+		let args = coerce(self.jvm, &resolved.signature, args)?;
+		self.invoke_static(resolved.id, &args, &resolved.signature.return_type().value_type())
+	}
+}
+
+
+/// Chooses the overload to call for the given arguments, using the cached
+/// choice if there is one.
+fn resolve(class: &Class, name: &str, args: &[Value], is_static: bool)
+		-> Result<Resolved> {
+	let jvm = class.jvm;
+	let arg_types = with_local_frame(jvm, || {
+		args.iter().map(value_type).collect::<Result<Vec<_>>>()
+	})?;
+	let key = CacheKey {
+		name: name.to_string(),
+		is_static: is_static,
+		args: arg_types,
+	};
+
+	// Check the cache before falling back to reflection
+	{
+		let cache = RESOLVED.lock().unwrap_or_else(|err| err.into_inner());
+		let entries = cache.as_ref().and_then(|cache| cache.get(&key));
+		let env = jvm.env;
+		let cached = entries.into_iter().flatten().find(|resolved| unsafe {
+			((**env).IsSameObject)(env, resolved.class.raw, class.raw) == ffi::JNI_TRUE
+		});
+		if let Some(resolved) = cached {
+			return Ok(resolved.clone());
+		}
+	}
+
+	// Every local reference created during reflection can be discarded once
+	// we've chosen a method
+	let (id, signature) = with_local_frame(jvm, || choose(class, name, args, is_static))?;
+	let resolved = Resolved {
+		id: id,
+		signature: signature,
+		class: Arc::new(class.global()),
+	};
+	let mut cache = RESOLVED.lock().unwrap_or_else(|err| err.into_inner());
+	cache.get_or_insert_with(HashMap::new).entry(key).or_default().push(resolved.clone());
+	Ok(resolved)
+}
+
+/// Lists the candidate methods through reflection, and picks the most
+/// specific one applicable to the given arguments.
+fn choose(class: &Class, name: &str, args: &[Value], is_static: bool)
+		-> Result<(ffi::jmethodID, MethodSignature)> {
+	let candidates = candidates(class, name, args.len(), is_static)?;
+
+	// Follow the Java compiler's phases, only allowing boxing conversions if
+	// no method is applicable without them
+	for &boxing in &[false, true] {
+		let mut applicable = Vec::new();
+		for candidate in &candidates {
+			if is_applicable(class.jvm, candidate, args, boxing)? {
+				applicable.push(candidate);
+			}
+		}
+		if applicable.is_empty() {
+			continue;
+		}
+
+		// Find the method that's more specific than every other applicable
+		// method
+		for &m1 in &applicable {
+			let most_specific = applicable.iter().all(|&m2| {
+				ptr::eq(m1, m2) || is_more_specific(class.jvm, m1, m2)
+			});
+			if most_specific {
+				return Ok((m1.id, m1.signature.clone()));
+			}
+		}
+
+		let overloads: Vec<_> = applicable.iter()
+			.map(|candidate| candidate.signature.descriptor())
+			.collect();
+		return Err(Error::ArgumentMismatch(format!(
+			"ambiguous call to `{}`, which could refer to any of `{}`",
+			name, overloads.join("`, `"))));
+	}
+
+	Err(Error::ArgumentMismatch(format!(
+		"no public overload of `{}` accepts the arguments {:?}", name, args)))
+}
+
+/// Returns every public method on the class with the given name and number of
+/// parameters.
+fn candidates(class: &Class, name: &str, arg_count: usize, is_static: bool)
+		-> Result<Vec<Candidate>> {
+	let jvm = class.jvm;
+	let env = jvm.env;
+	let class_obj = Object {
+		jvm: jvm,
+		raw: class.raw,
+	};
+
+	let methods = class_obj.call_with_sig("getMethods", "()[Ljava/lang/reflect/Method;", &[])?
+		.as_object();
+	let mut candidates = Vec::new();
+	for method in object_array(jvm, methods.raw) {
+		// Filter by name, modifiers, and number of parameters first, to avoid
+		// unnecessary reflection
+		if method.call::<String>("getName", &[])? != name {
+			continue;
+		}
+		let modifiers = method.call::<i32>("getModifiers", &[])?;
+		if (modifiers & STATIC_MODIFIER != 0) != is_static {
+			continue;
+		}
+		if method.call::<bool>("isBridge", &[])? {
+			continue;
+		}
+		let param_array = method.call_with_sig("getParameterTypes", "()[Ljava/lang/Class;", &[])?
+			.as_object();
+		let params = object_array(jvm, param_array.raw);
+		if params.len() != arg_count {
+			continue;
+		}
+
+		// Build the method's signature from the types of its parameters
+		let mut types = Vec::with_capacity(params.len());
+		for param in &params {
+			types.push(JavaType::from_class_name(&param.call::<String>("getName", &[])?)?);
+		}
+		let return_class = method.call_with_sig("getReturnType", "()Ljava/lang/Class;", &[])?
+			.as_object();
+		let return_type = JavaType::from_class_name(&return_class.call::<String>("getName", &[])?)?;
+
+		candidates.push(Candidate {
+			id: unsafe { ((**env).FromReflectedMethod)(env, method.raw) },
+			signature: MethodSignature::new(types, return_type),
+			params: params.iter().map(|param| param.raw).collect(),
+		});
+	}
+	Ok(candidates)
+}
+
+/// Returns true if the given arguments can be passed to the candidate method,
+/// optionally allowing boxing and unboxing conversions.
+fn is_applicable(jvm: &JavaVM, candidate: &Candidate, args: &[Value], boxing: bool)
+		-> Result<bool> {
+	let params = candidate.signature.args().iter().zip(&candidate.params);
+	for ((param, &param_class), arg) in params.zip(args) {
+		let accepted = match (primitive_type(arg), param.is_primitive()) {
+			// Primitive widening conversions
+			(Some(ref kind), true) => widens(kind, param),
+
+			// Boxing, then a widening reference conversion
+			(Some(ref kind), false) => {
+				boxing && is_assignable(jvm, box_class(jvm, kind)?.raw, param_class)
+			},
+
+			// Unboxing, then a primitive widening conversion
+			(None, true) => {
+				if !boxing {
+					false
+				} else {
+					match unboxed_type(arg)? {
+						Some(kind) => widens(&kind, param),
+						None => false,
+					}
+				}
+			},
+
+			// Widening reference conversions
+			(None, false) => match arg {
+				&Value::Str(_) => {
+					is_assignable(jvm, find_class(jvm, "java/lang/String")?, param_class)
+				},
+				&Value::Object(ref obj) => is_instance(jvm, obj.raw, param_class),
+				_ => false,
+			},
+		};
+		if !accepted {
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
+/// Returns true if each of the first method's parameters is a subtype of the
+/// second method's corresponding parameter.
+fn is_more_specific(jvm: &JavaVM, m1: &Candidate, m2: &Candidate) -> bool {
+	let params1 = m1.signature.args().iter().zip(&m1.params);
+	let params2 = m2.signature.args().iter().zip(&m2.params);
+	params1.zip(params2).all(|((t1, &c1), (t2, &c2))| {
+		match (t1.is_primitive(), t2.is_primitive()) {
+			(true, true) => widens(t1, t2),
+			(false, false) => is_assignable(jvm, c1, c2),
+			_ => false,
+		}
+	})
+}
+
+
+/// Converts each argument into the type of the corresponding parameter in the
+/// chosen signature.
+fn coerce<'a>(jvm: &'a JavaVM, signature: &MethodSignature, args: &[Value<'a>])
+		-> Result<Vec<Value<'a>>> {
+	let mut result = Vec::with_capacity(args.len());
+	for (param, arg) in signature.args().iter().zip(args) {
+		result.push(match (primitive_type(arg), param.is_primitive()) {
+			(Some(_), true) => widen(arg, param),
+			(Some(_), false) => arg.clone().boxed(jvm)?,
+			(None, true) => {
+				let unboxed = arg.clone().unboxed()?;
+				widen(&unboxed, param)
+			},
+			(None, false) => arg.clone(),
+		});
+	}
+	Ok(result)
+}
+
+/// Converts a primitive value into a wider primitive type. The conversion must
+/// be permitted by `widens`.
+fn widen<'a>(value: &Value<'a>, to: &JavaType) -> Value<'a> {
+	// Extract the value as both an integer and a float, so each wider type can
+	// pick whichever is exact
+	let (int, float) = match value {
+		&Value::Byte(v) => (v as i64, v as f64),
+		&Value::Short(v) => (v as i64, v as f64),
+		&Value::Char(v) => (v as i64, v as u32 as f64),
+		&Value::Int(v) => (v as i64, v as f64),
+		&Value::Long(v) => (v, v as f64),
+		&Value::Float(v) => (0, v as f64),
+		_ => return value.clone(),
+	};
+
+	match to {
+		&JavaType::Short => Value::Short(int as i16),
+		&JavaType::Int => Value::Int(int as i32),
+		&JavaType::Long => Value::Long(int),
+		&JavaType::Float => match value {
+			&Value::Float(v) => Value::Float(v),
+			&Value::Long(v) => Value::Float(v as f32),
+			_ => Value::Float(float as f32),
+		},
+		&JavaType::Double => Value::Double(float),
+		_ => value.clone(),
+	}
+}
+
+/// Returns true if a primitive of the given type can be passed as a parameter
+/// of the other through identity or a widening primitive conversion.
+fn widens(from: &JavaType, to: &JavaType) -> bool {
+	if from == to {
+		return true;
+	}
+	match from {
+		&JavaType::Byte => matches!(to, &JavaType::Short | &JavaType::Int
+			| &JavaType::Long | &JavaType::Float | &JavaType::Double),
+		&JavaType::Short | &JavaType::Char => matches!(to, &JavaType::Int
+			| &JavaType::Long | &JavaType::Float | &JavaType::Double),
+		&JavaType::Int => matches!(to, &JavaType::Long | &JavaType::Float
+			| &JavaType::Double),
+		&JavaType::Long => matches!(to, &JavaType::Float | &JavaType::Double),
+		&JavaType::Float => to == &JavaType::Double,
+		_ => false,
+	}
+}
+
+
+/// Returns the runtime type of the given argument, or None for a null object.
+fn value_type(value: &Value) -> Result<Option<JavaType>> {
+	match value {
+		&Value::Void => Err(Error::ArgumentMismatch(
+			String::from("void can't be passed as an argument"))),
+		&Value::Str(_) => Ok(Some(JavaType::from(&Type::Str))),
+		&Value::Object(ref obj) if obj.raw.is_null() => Ok(None),
+		&Value::Object(ref obj) => class_type(obj).map(Some),
+		_ => Ok(primitive_type(value)),
+	}
+}
+
+
+/// Returns the elements of a Java object array.
+fn object_array<'a>(jvm: &'a JavaVM, array: ffi::jobjectArray) -> Vec<Object<'a>> {
+	let env = jvm.env;
+	let len = unsafe { ((**env).GetArrayLength)(env, array) };
+	(0..len).map(|i| Object {
+		jvm: jvm,
+		raw: unsafe { ((**env).GetObjectArrayElement)(env, array, i) },
+	}).collect()
+}
+
+/// Loads the class with the given name.
+fn find_class(jvm: &JavaVM, name: &str) -> Result<ffi::jclass> {
+	Ok(jvm.class(name)?.raw)
+}
+
+/// Returns true if an object of the class `sub` can be assigned to a variable
+/// of the class `sup`.
+pub fn is_assignable(jvm: &JavaVM, sub: ffi::jclass, sup: ffi::jclass) -> bool {
+	let env = jvm.env;
+	unsafe { ((**env).IsAssignableFrom)(env, sub, sup) == ffi::JNI_TRUE }
+}
+
+/// Returns true if the object is an instance of the class. Null objects are an
+/// instance of every class.
+fn is_instance(jvm: &JavaVM, obj: ffi::jobject, class: ffi::jclass) -> bool {
+	let env = jvm.env;
+	unsafe { ((**env).IsInstanceOf)(env, obj, class) == ffi::JNI_TRUE }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tests::jvm;
+
+	#[test]
+	fn widening_order() {
+		let order = [JavaType::Byte, JavaType::Short, JavaType::Int, JavaType::Long,
+			JavaType::Float, JavaType::Double];
+		for (i, from) in order.iter().enumerate() {
+			for (j, to) in order.iter().enumerate() {
+				assert_eq!(widens(from, to), i <= j, "{} to {}", from, to);
+			}
+		}
+
+		// A char only widens to an int or wider, and nothing widens to a char
+		assert!(widens(&JavaType::Char, &JavaType::Int));
+		assert!(!widens(&JavaType::Char, &JavaType::Short));
+		assert!(!widens(&JavaType::Byte, &JavaType::Char));
+		assert!(!widens(&JavaType::Short, &JavaType::Char));
+		assert!(!widens(&JavaType::Boolean, &JavaType::Int));
+		assert!(!widens(&JavaType::Int, &JavaType::Boolean));
+	}
+
+	#[test]
+	fn widen_values() {
+		assert_eq!(widen(&Value::Byte(-3), &JavaType::Short).as_short(), -3);
+		assert_eq!(widen(&Value::Char('a'), &JavaType::Int).as_int(), 97);
+		assert_eq!(widen(&Value::Int(-7), &JavaType::Long).as_long(), -7);
+		assert_eq!(widen(&Value::Long(1 << 40), &JavaType::Float).as_float(),
+			(1u64 << 40) as f32);
+		assert_eq!(widen(&Value::Float(1.5), &JavaType::Double).as_double(), 1.5);
+		assert_eq!(widen(&Value::Int(4), &JavaType::Int).as_int(), 4);
+	}
+
+	#[test]
+	fn coerces_arguments() {
+		let jvm = jvm();
+		let signature = MethodSignature::parse("(JLjava/lang/Object;I)V").unwrap();
+		let boxed = Value::Int(9).boxed(&jvm).unwrap();
+		let args = coerce(&jvm, &signature, &[Value::Short(2), Value::Int(5), boxed]).unwrap();
+		assert_eq!(args[0].clone().as_long(), 2);
+		assert_eq!(args[1].clone().unboxed().unwrap().as_int(), 5);
+		assert_eq!(args[2].clone().as_int(), 9);
+	}
+
+	#[test]
+	fn chooses_most_specific_primitive() {
+		let jvm = jvm();
+		let string = jvm.class("java/lang/String").unwrap();
+		let value_of = |arg| string.call_static_dynamic("valueOf", &[arg]).unwrap().as_str();
+
+		// `valueOf(char)` is more specific than `valueOf(int)`, which is more
+		// specific than `valueOf(long)`
+		assert_eq!(value_of(Value::Char('a')), "a");
+		assert_eq!(value_of(Value::Short(5)), "5");
+
+		let math = jvm.class("java/lang/Math").unwrap();
+		let max = math.call_static_dynamic("max", &[Value::Int(3), Value::Long(4)]).unwrap();
+		assert_eq!(max.as_long(), 4);
+		let max = math.call_static_dynamic("max", &[Value::Int(3), Value::Float(2.5)]).unwrap();
+		assert_eq!(max.as_float(), 3.0);
+	}
+
+	#[test]
+	fn boxes_only_in_second_phase() {
+		let jvm = jvm();
+		let list = jvm.class("java/util/ArrayList").unwrap().instantiate(&[]).unwrap();
+
+		// Only `add(Object)` is applicable, by boxing the int
+		for &value in &[10, 0] {
+			list.call_dynamic("add", &[Value::Int(value)]).unwrap();
+		}
+
+		// `remove(int)` is applicable without boxing, so it's chosen over
+		// `remove(Object)` and removes the element at index 0
+		let removed = list.call_dynamic("remove", &[Value::Int(0)]).unwrap();
+		assert_eq!(removed.unboxed().unwrap().as_int(), 10);
+		let size = list.call_dynamic("size", &[]).unwrap();
+		assert_eq!(size.as_int(), 1);
+	}
+
+	#[test]
+	fn rejects_ambiguous_calls() {
+		let jvm = jvm();
+		let builder = jvm.class("java/lang/StringBuilder").unwrap().instantiate(&[]).unwrap();
+
+		// A null is applicable to `append(String)` and `append(char[])`, and
+		// neither is more specific than the other
+		let null = Value::Object(Object {
+			jvm: &jvm,
+			raw: ptr::null_mut(),
+		});
+		match builder.call_dynamic("append", &[null]) {
+			Err(Error::ArgumentMismatch(message)) => assert!(message.starts_with("ambiguous call")),
+			other => panic!("expected an ambiguous call, got {:?}", other),
+		}
+
+		// A string is only applicable to the reference overloads, of which
+		// `append(String)` is the most specific
+		builder.call_dynamic("append", &[Value::Str(String::from("hi"))]).unwrap();
+		let result = builder.call_dynamic("toString", &[]).unwrap();
+		assert_eq!(result.as_str(), "hi");
+	}
+
+	#[test]
+	fn rejects_inapplicable_calls() {
+		let jvm = jvm();
+		let math = jvm.class("java/lang/Math").unwrap();
+		let result = math.call_static_dynamic("abs", &[Value::Str(String::from("1"))]);
+		assert!(matches!(result, Err(Error::ArgumentMismatch(_))));
+	}
+}
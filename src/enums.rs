@@ -0,0 +1,70 @@
+//
+//  Enums
+//
+
+use {JavaVM, Object, ClassCache, Type, Value, FromJava};
+use {Error, Result};
+
+
+/// The `java.lang.Enum` class, which declares `name` and `ordinal`.
+static ENUM: ClassCache = ClassCache::new("java/lang/Enum");
+
+impl ClassCache {
+	/// Returns the constant with the given name, for a cache of an enum class.
+	///
+	/// Enum constants are singletons, so each one is read from its static
+	/// field once and kept alive by the cache.
+	pub fn enum_constant<'a>(&self, jvm: &'a JavaVM, name: &str)
+			-> Result<Object<'a>> {
+		self.with(jvm, |cached| {
+			if let Some(constant) = cached.constants.get(name) {
+				return Ok(constant.as_object(jvm));
+			}
+
+			// Each constant is a static field of the enum's own type
+			let class = cached.class.as_class(jvm);
+			let constant = match class.static_field(name, Type::Object(self.name))? {
+				Value::Object(obj) => obj,
+				_ => unreachable!(),
+			};
+			if constant.is_null() {
+				return Err(Error::Conversion(format!(
+					"`{}` isn't an initialised enum constant", name)));
+			}
+
+			cached.constants.insert(name.to_string(), constant.global());
+			Ok(constant)
+		})
+	}
+}
+
+impl<'a> Object<'a> {
+	/// Returns the name of this enum constant, as declared in Java.
+	///
+	/// The object must be an instance of an enum class.
+	pub fn enum_name(&self) -> Result<String> {
+		self.check_enum()?;
+		let method = ENUM.method(self.jvm, "name", "()Ljava/lang/String;")?;
+		String::from_java(method.call(self, &[])?)
+	}
+
+	/// Returns the position of this enum constant in its enum's declaration,
+	/// starting from 0.
+	///
+	/// The object must be an instance of an enum class.
+	pub fn enum_ordinal(&self) -> Result<i32> {
+		self.check_enum()?;
+		let method = ENUM.method(self.jvm, "ordinal", "()I")?;
+		i32::from_java(method.call(self, &[])?)
+	}
+
+	/// Returns an error if this object isn't an enum constant, since calling
+	/// `Enum`'s methods on any other object is undefined.
+	fn check_enum(&self) -> Result<()> {
+		if !self.is_null() && self.is_instance_of(ENUM.class(self.jvm)?) {
+			Ok(())
+		} else {
+			Err(Error::Conversion(String::from("expected an enum constant")))
+		}
+	}
+}
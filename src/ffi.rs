@@ -11,9 +11,13 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(dead_code)]
+#![allow(clippy::enum_variant_names)]
 
 use libc;
-use std::{mem, ptr};
+use std::ptr;
+
+#[cfg(feature = "dynamic")]
+use loader;
 
 pub const JNI_FALSE: jboolean = 0;
 pub const JNI_TRUE: jboolean = 1;
@@ -31,6 +35,11 @@ pub enum JNIVersion {
 	JNI_VERSION_1_6 = 0x00010006,
 	JNI_VERSION_1_7 = 0x00010007,
 	JNI_VERSION_1_8 = 0x00010008,
+	JNI_VERSION_9   = 0x00090000,
+	JNI_VERSION_10  = 0x000a0000,
+	JNI_VERSION_19  = 0x00130000,
+	JNI_VERSION_20  = 0x00140000,
+	JNI_VERSION_21  = 0x00150000,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -80,7 +89,7 @@ pub struct JavaVMAttachArgs {
 
 pub type jvoid = libc::c_void;
 pub type jboolean = libc::c_uchar;
-pub type jbyte = libc::c_char;
+pub type jbyte = libc::c_schar;
 pub type jchar = libc::c_ushort;
 pub type jshort = libc::c_short;
 pub type jint = libc::c_int;
@@ -109,26 +118,19 @@ pub type jfieldID = *mut libc::c_void;
 pub type jmethodID = *mut libc::c_void;
 
 pub enum Empty {}
-pub type MethodFn = extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jvalue;
-pub type GetFieldFn = extern "C" fn(env: *mut JNIEnv, obj: jobject, fieldID: jfieldID) -> jvalue;
-pub type SetFieldFn = extern "C" fn(env: *mut JNIEnv, obj: jobject, fieldID: jfieldID, val: jvalue);
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy)]
 #[repr(C)]
-pub struct jvalue {
-	pub data: u64,
-}
-
-impl jvalue {
-	pub fn z(&self) -> jboolean { unsafe { mem::transmute(self.data as u8) } }
-	pub fn b(&self) -> jbyte { unsafe { mem::transmute(self.data as u8) } }
-	pub fn c(&self) -> jchar { unsafe { mem::transmute(self.data as u16) } }
-	pub fn s(&self) -> jshort { unsafe { mem::transmute(self.data as u16) } }
-	pub fn i(&self) -> jint { unsafe { mem::transmute(self.data as u32) } }
-	pub fn j(&self) -> jlong { unsafe { mem::transmute(self.data) } }
-	pub fn f(&self) -> jfloat { unsafe { mem::transmute(self.data as u32) } }
-	pub fn d(&self) -> jdouble { unsafe { mem::transmute(self.data) } }
-	pub fn l(&self) -> jobject { unsafe { mem::transmute(self.data) } }
+pub union jvalue {
+	pub z: jboolean,
+	pub b: jbyte,
+	pub c: jchar,
+	pub s: jshort,
+	pub i: jint,
+	pub j: jlong,
+	pub f: jfloat,
+	pub d: jdouble,
+	pub l: jobject,
 }
 
 #[derive(Clone, Copy)]
@@ -150,9 +152,9 @@ pub enum JNIReleaseArrayElementsMode {
 
 #[repr(C)]
 pub struct JNINativeMethod {
-	name: *mut libc::c_char,
-	signature: *mut libc::c_char,
-	fnPtr: *mut jvoid,
+	pub name: *mut libc::c_char,
+	pub signature: *mut libc::c_char,
+	pub fnPtr: *mut jvoid,
 }
 
 #[repr(C)]
@@ -184,7 +186,7 @@ pub struct JNINativeInterface {
 	// field in memory, its important that the two versions of the struct
 	// correspond.
 
-	pub GetVersion: extern "C" fn(env: *mut JNIEnv) -> JNIVersion,
+	pub GetVersion: extern "C" fn(env: *mut JNIEnv) -> jint,
 
 	pub DefineClass: extern "C" fn(env: *mut JNIEnv, name: *const libc::c_char, loader: jobject, buf: *const jbyte, len: jsize) -> jclass,
 	pub FindClass:   extern "C" fn(env: *mut JNIEnv, name: *const libc::c_char) -> jclass,
@@ -257,36 +259,36 @@ pub struct JNINativeInterface {
 	pub CallVoidMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty),
 	pub CallVoidMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue),
 
-	pub CallNonvirtualObjectMethod:   extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jobject,
-	pub CallNonvirtualObjectMethodV:  extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jobject,
-	pub CallNonvirtualObjectMethodA:  extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jobject,
-	pub CallNonvirtualBooleanMethod:  extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jboolean,
-	pub CallNonvirtualBooleanMethodV: extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jboolean,
-	pub CallNonvirtualBooleanMethodA: extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jboolean,
-	pub CallNonvirtualByteMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jbyte,
-	pub CallNonvirtualByteMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jbyte,
-	pub CallNonvirtualByteMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jbyte,
-	pub CallNonvirtualCharMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jchar,
-	pub CallNonvirtualCharMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jchar,
-	pub CallNonvirtualCharMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jchar,
-	pub CallNonvirtualShortMethod:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jshort,
-	pub CallNonvirtualShortMethodV:   extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jshort,
-	pub CallNonvirtualShortMethodA:   extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jshort,
-	pub CallNonvirtualIntMethod:      extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jint,
-	pub CallNonvirtualIntMethodV:     extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jint,
-	pub CallNonvirtualIntMethodA:     extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jint,
-	pub CallNonvirtualLongMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jlong,
-	pub CallNonvirtualLongMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jlong,
-	pub CallNonvirtualLongMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jlong,
-	pub CallNonvirtualFloatMethod:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jfloat,
-	pub CallNonvirtualFloatMethodV:   extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jfloat,
-	pub CallNonvirtualFloatMethodA:   extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jfloat,
-	pub CallNonvirtualDoubleMethod:   extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...) -> jdouble,
-	pub CallNonvirtualDoubleMethodV:  extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty) -> jdouble,
-	pub CallNonvirtualDoubleMethodA:  extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue) -> jdouble,
-	pub CallNonvirtualVoidMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, ...),
-	pub CallNonvirtualVoidMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: Empty),
-	pub CallNonvirtualVoidMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, methodID: jmethodID, args: *const jvalue),
+	pub CallNonvirtualObjectMethod:   extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jobject,
+	pub CallNonvirtualObjectMethodV:  extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jobject,
+	pub CallNonvirtualObjectMethodA:  extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jobject,
+	pub CallNonvirtualBooleanMethod:  extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jboolean,
+	pub CallNonvirtualBooleanMethodV: extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jboolean,
+	pub CallNonvirtualBooleanMethodA: extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jboolean,
+	pub CallNonvirtualByteMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jbyte,
+	pub CallNonvirtualByteMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jbyte,
+	pub CallNonvirtualByteMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jbyte,
+	pub CallNonvirtualCharMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jchar,
+	pub CallNonvirtualCharMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jchar,
+	pub CallNonvirtualCharMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jchar,
+	pub CallNonvirtualShortMethod:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jshort,
+	pub CallNonvirtualShortMethodV:   extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jshort,
+	pub CallNonvirtualShortMethodA:   extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jshort,
+	pub CallNonvirtualIntMethod:      extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jint,
+	pub CallNonvirtualIntMethodV:     extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jint,
+	pub CallNonvirtualIntMethodA:     extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jint,
+	pub CallNonvirtualLongMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jlong,
+	pub CallNonvirtualLongMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jlong,
+	pub CallNonvirtualLongMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jlong,
+	pub CallNonvirtualFloatMethod:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jfloat,
+	pub CallNonvirtualFloatMethodV:   extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jfloat,
+	pub CallNonvirtualFloatMethodA:   extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jfloat,
+	pub CallNonvirtualDoubleMethod:   extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...) -> jdouble,
+	pub CallNonvirtualDoubleMethodV:  extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty) -> jdouble,
+	pub CallNonvirtualDoubleMethodA:  extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue) -> jdouble,
+	pub CallNonvirtualVoidMethod:     extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, ...),
+	pub CallNonvirtualVoidMethodV:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: Empty),
+	pub CallNonvirtualVoidMethodA:    extern "C" fn(env: *mut JNIEnv, obj: jobject, class: jclass, methodID: jmethodID, args: *const jvalue),
 
 	pub GetFieldID: extern "C" fn(env: *mut JNIEnv, class: jclass, name: *const libc::c_char, sig: *const ::libc::c_char) -> jfieldID,
 
@@ -453,10 +455,17 @@ pub struct JNINativeInterface {
 	pub GetDirectBufferCapacity: extern "C" fn(env: *mut JNIEnv, buf: jobject) -> jlong,
 
 	pub GetObjectRefType: extern "C" fn(env: *mut JNIEnv, obj: jobject) -> jobjectRefType,
+
+	// Only present from JNI_VERSION_9
+	pub GetModule: extern "C" fn(env: *mut JNIEnv, clazz: jclass) -> jobject,
+
+	// Only present from JNI_VERSION_21
+	pub IsVirtualThread: extern "C" fn(env: *mut JNIEnv, obj: jobject) -> jboolean,
 }
 
 // Link to the JavaVM framework on OSX, and to the jvm library on everything
-// else
+// else, unless the library is loaded at runtime
+#[cfg(not(feature = "dynamic"))]
 #[cfg_attr(target_os = "macos", link(name = "JavaVM", kind = "framework"))]
 #[cfg_attr(not(target_os = "macos"), link(name = "jvm"))]
 extern "C" {
@@ -464,3 +473,29 @@ extern "C" {
 	pub fn JNI_GetDefaultJavaVMInitArgs(args: *mut JavaVMInitArgs) -> JNIError;
 	pub fn JNI_GetCreatedJavaVMs(vm: *mut *mut JavaVM, bufLen: jsize, nVMs: *mut jsize) -> JNIError;
 }
+
+// With the `dynamic` feature, forward to the functions resolved from the
+// loaded library, which fail with JNI_ERR if it can't be found
+#[cfg(feature = "dynamic")]
+pub unsafe fn JNI_CreateJavaVM(vm: *mut *mut JavaVM, env: *mut *mut JNIEnv, args: *mut JavaVMInitArgs) -> JNIError {
+	match loader::library() {
+		Ok(library) => (library.create_java_vm)(vm, env, args),
+		Err(_) => JNIError::JNI_ERR,
+	}
+}
+
+#[cfg(feature = "dynamic")]
+pub unsafe fn JNI_GetDefaultJavaVMInitArgs(args: *mut JavaVMInitArgs) -> JNIError {
+	match loader::library() {
+		Ok(library) => (library.get_default_java_vm_init_args)(args),
+		Err(_) => JNIError::JNI_ERR,
+	}
+}
+
+#[cfg(feature = "dynamic")]
+pub unsafe fn JNI_GetCreatedJavaVMs(vm: *mut *mut JavaVM, bufLen: jsize, nVMs: *mut jsize) -> JNIError {
+	match loader::library() {
+		Ok(library) => (library.get_created_java_vms)(vm, bufLen, nVMs),
+		Err(_) => JNIError::JNI_ERR,
+	}
+}
@@ -0,0 +1,427 @@
+//
+//  Futures
+//
+
+use std::{fmt, panic, ptr};
+use std::cell::Cell;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use ffi;
+use collections::from_element;
+use native::{NativeClass, Native};
+use launcher;
+use {JavaVM, Object, ClassCache, GlobalRef, Value, JavaType, IntoJava, FromJava};
+use {Error, Result, with_local_frame};
+
+
+static COMPLETION_STAGE: ClassCache = ClassCache::new("java/util/concurrent/CompletionStage");
+static COMPLETABLE_FUTURE: ClassCache = ClassCache::new("java/util/concurrent/CompletableFuture");
+static COMPLETION_EXCEPTION: ClassCache = ClassCache::new("java/util/concurrent/CompletionException");
+static RUNTIME_EXCEPTION: ClassCache = ClassCache::new("java/lang/RuntimeException");
+
+/// The `BiConsumer` passed to `whenComplete`, which wakes the Rust future
+/// waiting on a Java one.
+static CALLBACK: NativeClass = NativeClass::new(
+	"rjni/FutureCallback",
+	"java/lang/Object",
+	&["java/util/function/BiConsumer"],
+	&[
+		Native {
+			name: "accept",
+			signature: "(Ljava/lang/Object;Ljava/lang/Object;)V",
+			function: accept as *mut ffi::jvoid,
+		},
+	],
+);
+
+
+
+//
+//  Java Futures
+//
+
+/// A Java `CompletableFuture` (or any other `CompletionStage`), awaited as a
+/// Rust future that resolves to its result converted into `T`.
+///
+/// The first poll registers a callback with `whenComplete`, which wakes the
+/// task from whichever Java thread completes the future, so no thread is
+/// blocked while waiting. Exceptions the future completes with are returned as
+/// errors.
+///
+/// Like other objects, a `JFuture` can only be used on the thread that owns
+/// its `JavaVM`, so it can be awaited by any executor that runs futures on the
+/// current thread. Dropping a `JFuture` before the Java future completes
+/// frees the state its callback holds.
+#[derive(Debug)]
+pub struct JFuture<'a, T> {
+	future: Object<'a>,
+	shared: Option<Arc<Mutex<Shared>>>,
+
+	/// The callback registered with `whenComplete`, once it's been registered.
+	callback: Option<Object<'a>>,
+
+	marker: PhantomData<fn() -> T>,
+}
+
+/// The state shared between a `JFuture` and its callback.
+#[derive(Debug, Default)]
+struct Shared {
+	outcome: Option<Outcome>,
+	waker: Option<Waker>,
+}
+
+/// How a Java future completed.
+#[derive(Debug)]
+enum Outcome {
+	/// The future's result, or None for null.
+	Value(Option<GlobalRef>),
+
+	/// The exception the future completed with.
+	Exception(GlobalRef),
+}
+
+impl<'a, T> JFuture<'a, T> {
+	/// Wraps an object, which must be a non-null instance of
+	/// `java.util.concurrent.CompletionStage`.
+	pub fn new(future: Object<'a>) -> Result<JFuture<'a, T>> {
+		if future.is_null() || !future.is_instance_of(COMPLETION_STAGE.class(future.jvm)?) {
+			return Err(Error::Conversion(String::from(
+				"expected an instance of java.util.concurrent.CompletionStage")));
+		}
+		Ok(JFuture {
+			future: future,
+			shared: None,
+			callback: None,
+			marker: PhantomData,
+		})
+	}
+
+	/// Returns the underlying future object.
+	pub fn as_object(&self) -> &Object<'a> {
+		&self.future
+	}
+
+	/// Returns the underlying future object.
+	pub fn into_object(self) -> Object<'a> {
+		self.future.clone()
+	}
+
+	/// Registers a callback to be run when the Java future completes, returning
+	/// the state it shares with the callback.
+	fn register(&mut self, waker: &Waker) -> Result<Arc<Mutex<Shared>>> {
+		let jvm = self.future.jvm;
+		let shared = Arc::new(Mutex::new(Shared {
+			outcome: None,
+			waker: Some(waker.clone()),
+		}));
+
+		// The callback is run immediately if the future has already completed
+		let callback = CALLBACK.instantiate(jvm, shared.clone())?;
+		self.callback = Some(callback.clone());
+		let signature = "(Ljava/util/function/BiConsumer;)Ljava/util/concurrent/CompletionStage;";
+		let method = COMPLETION_STAGE.method(jvm, "whenComplete", signature)?;
+		method.call(&self.future, &[Value::Object(callback)])?;
+
+		self.shared = Some(shared.clone());
+		Ok(shared)
+	}
+}
+
+impl<'a, T> Drop for JFuture<'a, T> {
+	fn drop(&mut self) {
+		// The callback only frees its state when it's run, which may be never
+		if let Some(ref callback) = self.callback {
+			unsafe { CALLBACK.release::<Arc<Mutex<Shared>>>(callback.jvm.env, callback.raw) };
+		}
+	}
+}
+
+impl<'a, T: FromJava<'a>> Future for JFuture<'a, T> {
+	type Output = Result<T>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<T>> {
+		let this = self.get_mut();
+		let shared = match this.shared {
+			Some(ref shared) => shared.clone(),
+			None => match this.register(cx.waker()) {
+				Ok(shared) => shared,
+				Err(err) => return Poll::Ready(Err(err)),
+			},
+		};
+
+		// Wait for the callback, making sure it wakes the latest task
+		let outcome = {
+			let mut shared = shared.lock().unwrap_or_else(|err| err.into_inner());
+			match shared.outcome.take() {
+				Some(outcome) => outcome,
+				None => {
+					let stale = !shared.waker.as_ref()
+						.is_some_and(|waker| waker.will_wake(cx.waker()));
+					if stale {
+						shared.waker = Some(cx.waker().clone());
+					}
+					return Poll::Pending;
+				},
+			}
+		};
+
+		let jvm = this.future.jvm;
+		Poll::Ready(match outcome {
+			Outcome::Value(value) => from_element(Value::Object(local_ref(jvm, value.as_ref()))),
+			Outcome::Exception(exception) => Err(exception_error(local_ref(jvm, Some(&exception)))),
+		})
+	}
+}
+
+/// Creates a local reference to a global one, so it remains valid after the
+/// global reference is dropped.
+fn local_ref<'a>(jvm: &'a JavaVM, global: Option<&GlobalRef>) -> Object<'a> {
+	let env = jvm.env;
+	Object {
+		jvm: jvm,
+		raw: match global {
+			Some(global) => unsafe { ((**env).NewLocalRef)(env, global.raw) },
+			None => ::std::ptr::null_mut(),
+		},
+	}
+}
+
+/// Returns the error for an exception a future completed with, unwrapping
+/// the `CompletionException` dependent stages wrap their cause in.
+fn exception_error(exception: Object) -> Error {
+	let jvm = exception.jvm;
+	let wrapped = COMPLETION_EXCEPTION.class(jvm)
+		.is_ok_and(|class| exception.is_instance_of(class));
+	if wrapped {
+		let cause = exception.call_with_sig("getCause", "()Ljava/lang/Throwable;", &[])
+			.and_then(Object::from_java);
+		if let Ok(cause) = cause {
+			if !cause.is_null() {
+				return Error::from_throwable(&cause);
+			}
+		}
+	}
+	Error::from_throwable(&exception)
+}
+
+/// Implements `BiConsumer.accept(Object, Object)` for the callback passed to
+/// `whenComplete`, recording the outcome and waking the task.
+extern "C" fn accept(env: *mut ffi::JNIEnv, this: ffi::jobject, value: ffi::jobject,
+                     exception: ffi::jobject) {
+	unsafe {
+		// The callback is only run once, so takes its shared state
+		let shared = match CALLBACK.take::<Arc<Mutex<Shared>>>(env, this) {
+			Some(shared) => shared,
+			None => return,
+		};
+		let outcome = match global_ref(env, exception) {
+			Some(exception) => Outcome::Exception(exception),
+			None => Outcome::Value(global_ref(env, value)),
+		};
+		let waker = {
+			let mut shared = shared.lock().unwrap_or_else(|err| err.into_inner());
+			shared.outcome = Some(outcome);
+			shared.waker.take()
+		};
+
+		// Unwinding into the JVM is undefined
+		if let Some(waker) = waker {
+			let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| waker.wake()));
+		}
+	}
+}
+
+/// Creates a global reference to an object from a native method, or None if
+/// it's null.
+unsafe fn global_ref(env: *mut ffi::JNIEnv, raw: ffi::jobject) -> Option<GlobalRef> {
+	if raw.is_null() {
+		return None;
+	}
+	let mut vm = ::std::ptr::null_mut();
+	if ((**env).GetJavaVM)(env, &mut vm) != ffi::JNIError::JNI_OK {
+		return None;
+	}
+	Some(GlobalRef {
+		vm: vm,
+		raw: ((**env).NewGlobalRef)(env, raw),
+	})
+}
+
+
+impl<'a, T> IntoJava<'a> for JFuture<'a, T> {
+	fn java_type() -> JavaType {
+		JavaType::Object(String::from("java/util/concurrent/CompletableFuture"))
+	}
+
+	fn into_java(self, _: &'a JavaVM) -> Result<Value<'a>> {
+		Ok(Value::Object(self.future.clone()))
+	}
+}
+
+impl<'a, T> FromJava<'a> for JFuture<'a, T> {
+	fn java_type() -> JavaType {
+		JavaType::Object(String::from("java/util/concurrent/CompletableFuture"))
+	}
+
+	fn from_java(value: Value<'a>) -> Result<JFuture<'a, T>> {
+		JFuture::new(Object::from_java(value)?)
+	}
+}
+
+
+
+//
+//  Rust Futures
+//
+
+impl JavaVM {
+	/// Create a Java `CompletableFuture` that completes with the output of a
+	/// Rust future.
+	///
+	/// Returns the Java future, and a `Completion` that drives the Rust future
+	/// and completes the Java one with its result. No particular runtime is
+	/// assumed, so the completion must be spawned on (or polled by) an
+	/// executor of your choosing. An `Ok` result is converted through
+	/// `IntoJava`, and an `Err` completes the Java future exceptionally with a
+	/// `RuntimeException` holding its message.
+	///
+	/// If the Java future is completed or cancelled first, the completion
+	/// stops polling the Rust future.
+	pub fn completable_future<'a, F, T, E>(&'a self, future: F)
+			-> Result<(Object<'a>, Completion<F>)>
+			where F: Future<Output = ::std::result::Result<T, E>>,
+			      T: for<'b> IntoJava<'b>,
+			      E: fmt::Display {
+		let target = COMPLETABLE_FUTURE.class(self)?.instantiate(&[])?;
+		let completion = Completion {
+			future: Box::pin(future),
+			target: target.global(),
+		};
+		Ok((target, completion))
+	}
+}
+
+/// A future that drives a Rust future and completes a Java
+/// `CompletableFuture` with its output, created by
+/// `JavaVM::completable_future`.
+///
+/// The completion can be polled from any thread. The first poll on a thread
+/// attaches it to the JVM as a daemon thread, which stays attached until it
+/// exits, so an executor's threads are only attached once. It resolves to an
+/// error if the result couldn't be passed to Java.
+pub struct Completion<F> {
+	future: Pin<Box<F>>,
+	target: GlobalRef,
+}
+
+impl<F, T, E> Future for Completion<F>
+		where F: Future<Output = ::std::result::Result<T, E>>,
+		      T: for<'b> IntoJava<'b>,
+		      E: fmt::Display {
+	type Output = Result<()>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+		let jvm = match attach(self.target.vm) {
+			Ok(jvm) => jvm,
+			Err(err) => return Poll::Ready(Err(err)),
+		};
+
+		// The thread can stay attached for good, so the local references made
+		// here are freed by a local frame. The Rust future is polled outside
+		// one, since it may keep references it makes between polls.
+		let target = self.target.as_object(&jvm);
+		let done = with_local_frame(&jvm, || {
+			COMPLETABLE_FUTURE.method(&jvm, "isDone", "()Z")
+				.and_then(|method| method.call(&target, &[]))
+				.and_then(bool::from_java)
+		});
+
+		// Stop early if there's no longer anything to complete
+		match done {
+			Ok(false) => {},
+			Ok(true) => return Poll::Ready(Ok(())),
+			Err(err) => return Poll::Ready(Err(err)),
+		}
+
+		let output = match self.future.as_mut().poll(cx) {
+			Poll::Ready(output) => output,
+			Poll::Pending => return Poll::Pending,
+		};
+		Poll::Ready(with_local_frame(&jvm, || complete(&jvm, &target, output)))
+	}
+}
+
+/// Detaches the current thread from the JVM when it exits, if it was attached
+/// by `attach` and the JVM hasn't been destroyed since.
+struct Attachment(Cell<*mut ffi::JavaVM>);
+
+impl Drop for Attachment {
+	fn drop(&mut self) {
+		let vm = self.0.get();
+		let destroyed = launcher::DESTROYED.read().unwrap_or_else(|err| err.into_inner());
+		if !vm.is_null() && !*destroyed {
+			unsafe { ((**vm).DetachCurrentThread)(vm) };
+		}
+	}
+}
+
+thread_local! {
+	static ATTACHMENT: Attachment = const { Attachment(Cell::new(ptr::null_mut())) };
+}
+
+/// Returns the JVM for the current thread, attaching it as a daemon thread
+/// until it exits if it isn't attached already.
+pub(crate) fn attach(vm: *mut ffi::JavaVM) -> Result<JavaVM> {
+	let destroyed = launcher::DESTROYED.read().unwrap_or_else(|err| err.into_inner());
+	if *destroyed {
+		return Err(Error::from_ffi(ffi::JNIError::JNI_EDETACHED));
+	}
+	let mut env = ptr::null_mut();
+	unsafe {
+		let mut status = ((**vm).GetEnv)(vm, &mut env, ffi::JNIVersion::JNI_VERSION_1_2);
+		if status == ffi::JNIError::JNI_EDETACHED {
+			status = ((**vm).AttachCurrentThreadAsDaemon)(vm, &mut env, ptr::null_mut());
+			if status == ffi::JNIError::JNI_OK {
+				ATTACHMENT.with(|attachment| attachment.0.set(vm));
+			}
+		}
+		if status != ffi::JNIError::JNI_OK {
+			return Err(Error::from_ffi(status));
+		}
+	}
+	Ok(JavaVM {
+		vm: vm,
+		env: env,
+	})
+}
+
+/// Completes a `CompletableFuture` with the output of a Rust future.
+fn complete<'a, T, E>(jvm: &'a JavaVM, target: &Object<'a>,
+                      output: ::std::result::Result<T, E>) -> Result<()>
+		where T: IntoJava<'a>, E: fmt::Display {
+	let value = output.map_err(|err| err.to_string())
+		.and_then(|value| {
+			value.into_java(jvm)
+				.and_then(|value| value.boxed(jvm))
+				.map_err(|err| err.to_string())
+		});
+
+	match value {
+		Ok(value) => {
+			let method = COMPLETABLE_FUTURE.method(jvm, "complete", "(Ljava/lang/Object;)Z")?;
+			method.call(target, &[value])?;
+		},
+		Err(message) => {
+			let exception = RUNTIME_EXCEPTION.class(jvm)?
+				.instantiate_with_sig("(Ljava/lang/String;)V", &[Value::Str(message)])?;
+			let signature = "(Ljava/lang/Throwable;)Z";
+			let method = COMPLETABLE_FUTURE.method(jvm, "completeExceptionally", signature)?;
+			method.call(target, &[Value::Object(exception)])?;
+		},
+	}
+	Ok(())
+}
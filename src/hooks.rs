@@ -0,0 +1,217 @@
+//
+//  JVM Hooks
+//
+
+use std::ffi::CStr;
+use std::sync::{Arc, Mutex};
+use std::{panic, ptr, thread};
+
+use libc;
+use ffi;
+use launcher;
+
+
+/// A callback passed each line the JVM prints.
+pub type OutputHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// A callback passed the status given to `System.exit`.
+pub type ExitHook = Arc<dyn Fn(i32) + Send + Sync>;
+
+/// A callback run before the JVM aborts the process.
+pub type AbortHook = Arc<dyn Fn() + Send + Sync>;
+
+/// The callbacks set in the options of the JVM, which are global since the
+/// JVM's hooks are plain function pointers, and only one JVM can be created
+/// per process.
+static HOOKS: Mutex<Hooks> = Mutex::new(Hooks {
+	output: None,
+	exit: None,
+	abort: None,
+	line: String::new(),
+	capturing: false,
+	captured: Vec::new(),
+});
+
+struct Hooks {
+	output: Option<OutputHook>,
+	exit: Option<ExitHook>,
+	abort: Option<AbortHook>,
+
+	/// The JVM's output since the last newline.
+	line: String,
+
+	/// Whether lines of output are being kept in `captured`, while the JVM is
+	/// created.
+	capturing: bool,
+	captured: Vec<String>,
+}
+
+extern "C" {
+	// The `va_list` is only passed through, so it's treated as a pointer, which
+	// is how it's passed on every supported platform
+	#[cfg(not(windows))]
+	fn vasprintf(buf: *mut *mut libc::c_char, format: *const libc::c_char,
+		args: *mut libc::c_void) -> libc::c_int;
+	#[cfg(windows)]
+	fn _vscprintf(format: *const libc::c_char, args: *mut libc::c_void) -> libc::c_int;
+	#[cfg(windows)]
+	fn vsnprintf(buf: *mut libc::c_char, size: libc::size_t, format: *const libc::c_char,
+		args: *mut libc::c_void) -> libc::c_int;
+}
+
+/// Formats the arguments to `vfprintf` into a heap allocated string, which is
+/// freed with `libc::free`, returning its length (or a negative error).
+#[cfg(not(windows))]
+unsafe fn vformat(buf: &mut *mut libc::c_char, format: *const libc::c_char,
+                 args: *mut libc::c_void) -> libc::c_int {
+	// Sizes the buffer from the formatted length, like `va_copy` followed by
+	// two calls to `vsnprintf`
+	vasprintf(buf, format, args)
+}
+
+/// Formats the arguments to `vfprintf` into a heap allocated string, which is
+/// freed with `libc::free`, returning its length (or a negative error).
+#[cfg(windows)]
+unsafe fn vformat(buf: &mut *mut libc::c_char, format: *const libc::c_char,
+                 args: *mut libc::c_void) -> libc::c_int {
+	// A `va_list` is a plain pointer passed by value here, so the arguments can
+	// be read twice without copying it
+	let len = _vscprintf(format, args);
+	if len < 0 {
+		return len;
+	}
+	*buf = libc::malloc(len as usize + 1) as *mut libc::c_char;
+	if (*buf).is_null() {
+		return -1;
+	}
+	vsnprintf(*buf, len as usize + 1, format, args)
+}
+
+/// Replaces the callbacks, before a JVM is created with them.
+pub fn install(output: Option<OutputHook>, exit: Option<ExitHook>, abort: Option<AbortHook>) {
+	let mut hooks = HOOKS.lock().unwrap_or_else(|err| err.into_inner());
+	hooks.output = output;
+	hooks.exit = exit;
+	hooks.abort = abort;
+	hooks.line.clear();
+}
+
+/// Starts keeping each line the JVM prints, as well as passing it on.
+pub fn start_capture() {
+	let mut hooks = HOOKS.lock().unwrap_or_else(|err| err.into_inner());
+	hooks.capturing = true;
+	hooks.captured.clear();
+}
+
+/// Stops keeping the JVM's output, returning the lines kept since
+/// `start_capture` (including any unfinished line).
+pub fn stop_capture() -> Vec<String> {
+	let mut hooks = HOOKS.lock().unwrap_or_else(|err| err.into_inner());
+	hooks.capturing = false;
+	let mut captured = hooks.captured.split_off(0);
+	if !hooks.line.is_empty() {
+		captured.push(hooks.line.clone());
+	}
+
+	// Without a callback, the unfinished line has already been printed
+	if hooks.output.is_none() {
+		hooks.line.clear();
+	}
+	captured
+}
+
+/// Called by the JVM instead of `vfprintf` for everything it prints to stdout
+/// or stderr (eg. warnings from `-Xcheck:jni`), which is passed to the output
+/// callback line by line, or otherwise printed as normal.
+///
+/// This is only installed when there's an output callback, or the output is
+/// needed to report which option the JVM rejected.
+pub unsafe extern "C" fn vfprintf_hook(file: *mut libc::FILE, format: *const libc::c_char,
+                                       args: *mut libc::c_void) -> ffi::jint {
+	let mut buf = ptr::null_mut();
+	let len = vformat(&mut buf, format, args);
+	if len < 0 {
+		return len;
+	}
+	let text = CStr::from_ptr(buf).to_string_lossy().into_owned();
+	let len = print(file, buf, &text, len);
+	libc::free(buf as *mut libc::c_void);
+	len
+}
+
+/// Prints what the JVM formatted, or passes it to the output callback line by
+/// line.
+unsafe fn print(file: *mut libc::FILE, buf: *const libc::c_char, text: &str,
+                len: libc::c_int) -> libc::c_int {
+	// Take each complete line, holding onto the rest until it's finished
+	let (hook, lines) = {
+		let mut hooks = HOOKS.lock().unwrap_or_else(|err| err.into_inner());
+		if hooks.output.is_none() {
+			libc::fputs(buf, file);
+			if !hooks.capturing {
+				return len;
+			}
+		}
+		hooks.line.push_str(text);
+		let end = match hooks.line.rfind('\n') {
+			Some(end) => end,
+			None => return len,
+		};
+		let lines = hooks.line[..end].split('\n').map(String::from).collect::<Vec<_>>();
+		hooks.line.drain(..=end);
+		if hooks.capturing {
+			hooks.captured.extend(lines.iter().cloned());
+		}
+		(hooks.output.clone(), lines)
+	};
+	if let Some(hook) = hook {
+		for line in lines {
+			let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| hook(&line)));
+		}
+	}
+	len
+}
+
+/// Passes any unfinished line of output to the output callback.
+fn flush_output() {
+	let (hook, line) = {
+		let mut hooks = HOOKS.lock().unwrap_or_else(|err| err.into_inner());
+		let line = hooks.line.split_off(0);
+		(hooks.output.clone(), line)
+	};
+	if let (Some(hook), false) = (hook, line.is_empty()) {
+		let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| hook(&line)));
+	}
+}
+
+/// Called by the JVM when `System.exit` (or `Runtime.halt`) is called, after
+/// it has stopped running Java code.
+///
+/// This is only installed when there's an exit callback, or the application
+/// is started by `JavaVM::run_main`, in which case the exiting thread blocks
+/// forever rather than returning and letting the JVM terminate the process.
+pub extern "C" fn exit_hook(code: ffi::jint) {
+	flush_output();
+	let hook = HOOKS.lock().unwrap_or_else(|err| err.into_inner()).exit.clone();
+	if let Some(ref hook) = hook {
+		let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| hook(code)));
+	}
+
+	let launched = launcher::exited(code);
+	if hook.is_none() && !launched {
+		return;
+	}
+	loop {
+		thread::park();
+	}
+}
+
+/// Called by the JVM before it aborts the process (eg. after a fatal error),
+/// which it does once this returns.
+pub extern "C" fn abort_hook() {
+	flush_output();
+	let hook = HOOKS.lock().unwrap_or_else(|err| err.into_inner()).abort.clone();
+	if let Some(hook) = hook {
+		let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| hook()));
+	}
+}
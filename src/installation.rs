@@ -0,0 +1,468 @@
+//
+//  Java Installations
+//
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::cmp::Reverse;
+use std::{env, fmt, fs};
+
+use {Error, Result};
+
+
+/// A JDK or JRE installed on this machine, described by the `release` file in
+/// its home directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaInstallation {
+	home: PathBuf,
+	library: PathBuf,
+	version: String,
+	major_version: u32,
+	properties: HashMap<String, String>,
+}
+
+/// How the JVM library to load is chosen, with the `dynamic` feature.
+#[derive(Debug, Clone)]
+pub enum Selection {
+	/// A specific installation.
+	Installation(JavaInstallation),
+
+	/// The installation `JavaInstallation::find` picks for a requirement.
+	Version(VersionRequirement),
+}
+
+impl JavaInstallation {
+	/// Reads the installation in the given Java home directory (eg.
+	/// `/usr/lib/jvm/java-17-openjdk-amd64`).
+	///
+	/// The directory must contain a `release` file with a `JAVA_VERSION`, and
+	/// a JVM library. A JDK 8 `jre` directory is resolved to the JDK containing
+	/// it.
+	pub fn from_home<P: AsRef<Path>>(home: P) -> Result<JavaInstallation> {
+		let mut home = match fs::canonicalize(home.as_ref()) {
+			Ok(home) => home,
+			Err(err) => return Err(Error::JvmNotFound(format!(
+				"`{}`: {}", home.as_ref().display(), err))),
+		};
+
+		// The `java.home` of JDK 8 is its `jre` directory
+		if !home.join("release").is_file() && home.ends_with("jre") {
+			if let Some(jdk) = home.parent().filter(|jdk| jdk.join("release").is_file()) {
+				home = jdk.to_path_buf();
+			}
+		}
+
+		let properties = match fs::read_to_string(home.join("release")) {
+			Ok(release) => parse_release(&release),
+			Err(err) => return Err(Error::JvmNotFound(format!(
+				"no release file in `{}`: {}", home.display(), err))),
+		};
+		let version = match properties.get("JAVA_VERSION") {
+			Some(version) => version.clone(),
+			None => return Err(Error::JvmNotFound(format!(
+				"no JAVA_VERSION in the release file in `{}`", home.display()))),
+		};
+		let major_version = match major_version(&version) {
+			Some(major_version) => major_version,
+			None => return Err(Error::JvmNotFound(format!(
+				"unrecognised JAVA_VERSION `{}` in `{}`", version, home.display()))),
+		};
+		let library = match library_paths(&home).into_iter().find(|path| path.is_file()) {
+			Some(library) => library,
+			None => return Err(Error::JvmNotFound(format!(
+				"no JVM library in `{}`", home.display()))),
+		};
+
+		Ok(JavaInstallation {
+			home: home,
+			library: library,
+			version: version,
+			major_version: major_version,
+			properties: properties,
+		})
+	}
+
+	/// Returns every installation found in `JAVA_HOME`, the `java.home` of the
+	/// `java` command on the `PATH`, SDKMAN, and the platform's common install
+	/// directories (eg. `/usr/lib/jvm/*`).
+	///
+	/// Installations are ordered from the newest version to the oldest, and
+	/// each is only listed once, even if found in several places.
+	pub fn find_all() -> Vec<JavaInstallation> {
+		installations(preferred_homes())
+	}
+
+	/// Picks an installation that satisfies a version requirement, and can be
+	/// loaded by this process.
+	///
+	/// The choice is deterministic: `JAVA_HOME` is preferred if it satisfies
+	/// the requirement, then the `java` command on the `PATH`, and otherwise
+	/// the newest satisfying installation found by `find_all`.
+	pub fn find(requirement: &VersionRequirement) -> Result<JavaInstallation> {
+		let usable = |installation: &JavaInstallation| {
+			installation.is_compatible() && requirement.matches(installation.major_version)
+		};
+
+		// Running `java` to find its home is slow, so it's only done once
+		let preferred = preferred_homes();
+		let installation = preferred.iter()
+			.filter_map(|home| JavaInstallation::from_home(home).ok())
+			.find(&usable);
+		if let Some(installation) = installation {
+			return Ok(installation);
+		}
+		match installations(preferred).into_iter().find(&usable) {
+			Some(installation) => Ok(installation),
+			None => Err(Error::JvmNotFound(format!(
+				"no Java installation matching {} in JAVA_HOME, the PATH, or the \
+				common install directories", requirement))),
+		}
+	}
+
+	/// Returns the installation containing the given JVM library, if it's in
+	/// one.
+	pub fn of_library(library: &Path) -> Option<JavaInstallation> {
+		let library = fs::canonicalize(library).ok()?;
+		library.ancestors()
+			.skip(2)
+			.take(4)
+			.filter_map(|home| JavaInstallation::from_home(home).ok())
+			.find(|installation| {
+				fs::canonicalize(&installation.library).ok().as_ref() == Some(&library)
+			})
+	}
+
+	/// Returns the installation's home directory.
+	pub fn home(&self) -> &Path {
+		&self.home
+	}
+
+	/// Returns the path to the installation's JVM library (eg.
+	/// `lib/server/libjvm.so`).
+	pub fn library_path(&self) -> &Path {
+		&self.library
+	}
+
+	/// Returns the full Java version (eg. `17.0.8` or `1.8.0_382`).
+	pub fn version(&self) -> &str {
+		&self.version
+	}
+
+	/// Returns the major Java version (eg. 17, or 8 for `1.8.0_382`).
+	pub fn major_version(&self) -> u32 {
+		self.major_version
+	}
+
+	/// Returns the vendor that built the installation (eg. `Eclipse Adoptium`),
+	/// if known.
+	pub fn vendor(&self) -> Option<&str> {
+		self.property("IMPLEMENTOR")
+	}
+
+	/// Returns the CPU architecture the installation was built for (eg.
+	/// `x86_64` or `aarch64`), if known.
+	pub fn architecture(&self) -> Option<&str> {
+		self.property("OS_ARCH")
+	}
+
+	/// Returns a property from the `release` file (eg. `JAVA_RUNTIME_VERSION`).
+	pub fn property(&self, name: &str) -> Option<&str> {
+		self.properties.get(name).map(|value| value.as_str())
+	}
+
+	/// Returns true if the installation was built for this process's CPU
+	/// architecture, or its architecture isn't known.
+	pub fn is_compatible(&self) -> bool {
+		self.architecture().is_none_or(|arch| {
+			normalize_arch(arch) == normalize_arch(env::consts::ARCH)
+		})
+	}
+}
+
+/// Returns the installations in the preferred homes and the common install
+/// directories, as `JavaInstallation::find_all` does.
+fn installations(preferred: Vec<PathBuf>) -> Vec<JavaInstallation> {
+	let mut installations: Vec<JavaInstallation> = Vec::new();
+	for home in preferred.into_iter().chain(installed_homes()) {
+		if let Ok(installation) = JavaInstallation::from_home(&home) {
+			if !installations.iter().any(|found| found.home == installation.home) {
+				installations.push(installation);
+			}
+		}
+	}
+
+	// The sort is stable, so equal versions stay in the order found
+	installations.sort_by_key(|installation| Reverse(installation.major_version));
+	installations
+}
+
+/// Parses the `KEY="value"` lines of a `release` file.
+fn parse_release(release: &str) -> HashMap<String, String> {
+	release.lines()
+		.map(|line| line.trim())
+		.filter(|line| !line.starts_with('#'))
+		.filter_map(|line| {
+			let mut parts = line.splitn(2, '=');
+			let key = parts.next()?.trim();
+			let value = parts.next()?.trim().trim_matches('"');
+			Some((key.to_string(), value.to_string()))
+		})
+		.collect()
+}
+
+/// Returns the major version of a Java version string, which starts with `1.`
+/// before Java 9.
+fn major_version(version: &str) -> Option<u32> {
+	let mut numbers = version.split(|c: char| !c.is_ascii_digit());
+	let first = numbers.next()?.parse().ok()?;
+	if first == 1 {
+		numbers.next()?.parse().ok()
+	} else {
+		Some(first)
+	}
+}
+
+/// Maps the different names for each CPU architecture onto one.
+fn normalize_arch(arch: &str) -> &str {
+	match arch {
+		"amd64" | "x86_64" => "x86_64",
+		"x86" | "i386" | "i486" | "i586" | "i686" => "x86",
+		"arm64" | "aarch64" => "aarch64",
+		arch => arch,
+	}
+}
+
+
+/// A requirement on the major version of a Java installation, like "17 or
+/// newer".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRequirement {
+	min: u32,
+	max: u32,
+}
+
+impl VersionRequirement {
+	/// Matches any version.
+	pub fn any() -> VersionRequirement {
+		VersionRequirement::between(0, u32::MAX)
+	}
+
+	/// Matches the given major version or newer.
+	pub fn at_least(version: u32) -> VersionRequirement {
+		VersionRequirement::between(version, u32::MAX)
+	}
+
+	/// Matches the given major version or older.
+	pub fn at_most(version: u32) -> VersionRequirement {
+		VersionRequirement::between(0, version)
+	}
+
+	/// Matches exactly the given major version.
+	pub fn exactly(version: u32) -> VersionRequirement {
+		VersionRequirement::between(version, version)
+	}
+
+	/// Matches major versions between `min` and `max`, inclusive.
+	pub fn between(min: u32, max: u32) -> VersionRequirement {
+		VersionRequirement {
+			min: min,
+			max: max,
+		}
+	}
+
+	/// Returns true if the given major version satisfies the requirement.
+	pub fn matches(&self, major_version: u32) -> bool {
+		self.min <= major_version && major_version <= self.max
+	}
+}
+
+impl fmt::Display for VersionRequirement {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match (self.min, self.max) {
+			(0, u32::MAX) => write!(f, "any version"),
+			(min, u32::MAX) => write!(f, "Java {} or newer", min),
+			(0, max) => write!(f, "Java {} or older", max),
+			(min, max) if min == max => write!(f, "Java {}", min),
+			(min, max) => write!(f, "Java {} to {}", min, max),
+		}
+	}
+}
+
+
+
+//
+//  Discovery
+//
+
+#[cfg(target_os = "macos")]
+const LIBRARY_NAME: &str = "libjvm.dylib";
+#[cfg(all(unix, not(target_os = "macos")))]
+const LIBRARY_NAME: &str = "libjvm.so";
+#[cfg(windows)]
+const LIBRARY_NAME: &str = "jvm.dll";
+
+/// The directory containing the `server` and `client` directories.
+#[cfg(not(windows))]
+const LIBRARY_DIR: &str = "lib";
+#[cfg(windows)]
+const LIBRARY_DIR: &str = "bin";
+
+/// The directories Java is commonly installed in.
+#[cfg(target_os = "macos")]
+const INSTALL_DIRS: &[&str] = &[
+	"/Library/Java/JavaVirtualMachines",
+	"/opt/homebrew/opt",
+	"/usr/local/opt",
+];
+#[cfg(all(unix, not(target_os = "macos")))]
+const INSTALL_DIRS: &[&str] = &["/usr/lib/jvm", "/usr/lib64/jvm", "/usr/java", "/opt/java"];
+#[cfg(windows)]
+const INSTALL_DIRS: &[&str] = &[
+	"C:\\Program Files\\Java",
+	"C:\\Program Files\\Eclipse Adoptium",
+	"C:\\Program Files\\Microsoft",
+	"C:\\Program Files\\Zulu",
+];
+
+/// The path from an installed JDK's directory to its Java home.
+#[cfg(target_os = "macos")]
+const HOME_SUFFIX: &str = "Contents/Home";
+#[cfg(not(target_os = "macos"))]
+const HOME_SUFFIX: &str = "";
+
+/// Returns `JAVA_HOME`, and the `java.home` of the `java` command on the
+/// `PATH`, which are preferred over other installations.
+fn preferred_homes() -> Vec<PathBuf> {
+	let mut homes = Vec::new();
+	if let Some(home) = env::var_os("JAVA_HOME") {
+		homes.push(PathBuf::from(home));
+	}
+	if let Some(home) = java_home_from_path() {
+		homes.push(home);
+	}
+	homes
+}
+
+/// Returns the `java.home` system property reported by the `java` command on
+/// the `PATH`, if there is one.
+fn java_home_from_path() -> Option<PathBuf> {
+	let output = Command::new("java")
+		.args(["-XshowSettings:properties", "-version"])
+		.output()
+		.ok()?;
+	let settings = String::from_utf8_lossy(&output.stderr);
+	settings.lines()
+		.filter_map(|line| line.trim().strip_prefix("java.home = "))
+		.next()
+		.map(PathBuf::from)
+}
+
+/// Returns the possible Java home directories in the platform's common install
+/// directories, and those managed by SDKMAN and IntelliJ.
+fn installed_homes() -> Vec<PathBuf> {
+	let mut roots = INSTALL_DIRS.iter().map(PathBuf::from).collect::<Vec<_>>();
+	let sdkman = env::var_os("SDKMAN_DIR")
+		.map(PathBuf::from)
+		.or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".sdkman")));
+	if let Some(sdkman) = sdkman {
+		roots.push(sdkman.join("candidates").join("java"));
+	}
+	if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+		roots.push(Path::new(&home).join(".jdks"));
+	}
+
+	let mut homes = Vec::new();
+	for root in roots {
+		if let Ok(entries) = fs::read_dir(root) {
+			homes.extend(entries
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.path().join(HOME_SUFFIX)));
+		}
+	}
+	homes
+}
+
+/// Returns the locations the JVM library might be in, relative to a Java home
+/// directory, for both JDK 9+ and older layouts.
+fn library_paths(home: &Path) -> Vec<PathBuf> {
+	let arch = match env::consts::ARCH {
+		"x86_64" => "amd64",
+		"x86" => "i386",
+		arch => arch,
+	};
+	let mut paths = Vec::new();
+	for base in &["", "jre"] {
+		for dir in &[LIBRARY_DIR.to_string(), format!("lib/{}", arch)] {
+			for kind in &["server", "client"] {
+				paths.push(home.join(base).join(dir).join(kind).join(LIBRARY_NAME));
+			}
+		}
+	}
+	paths
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_release_files() {
+		let release = parse_release("# A comment\nJAVA_VERSION=\"17.0.8\"\n\n  \
+			IMPLEMENTOR = \"Eclipse Adoptium\"\nMODULES=\"java.base java.sql\"\nBROKEN\n\
+			URL=\"a=b\"\n");
+		assert_eq!(release.len(), 4);
+		assert_eq!(release["JAVA_VERSION"], "17.0.8");
+		assert_eq!(release["IMPLEMENTOR"], "Eclipse Adoptium");
+		assert_eq!(release["MODULES"], "java.base java.sql");
+		assert_eq!(release["URL"], "a=b");
+	}
+
+	#[test]
+	fn major_versions() {
+		assert_eq!(major_version("1.8.0_382"), Some(8));
+		assert_eq!(major_version("17.0.8"), Some(17));
+		assert_eq!(major_version("21"), Some(21));
+		assert_eq!(major_version("22-ea"), Some(22));
+		assert_eq!(major_version("1"), None);
+		assert_eq!(major_version("abc"), None);
+		assert_eq!(major_version(""), None);
+	}
+
+	#[test]
+	fn version_requirements() {
+		assert!(VersionRequirement::any().matches(0));
+		assert!(VersionRequirement::any().matches(u32::MAX));
+		assert!(VersionRequirement::at_least(17).matches(17));
+		assert!(VersionRequirement::at_least(17).matches(21));
+		assert!(!VersionRequirement::at_least(17).matches(11));
+		assert!(VersionRequirement::at_most(11).matches(8));
+		assert!(!VersionRequirement::at_most(11).matches(17));
+		assert!(VersionRequirement::exactly(17).matches(17));
+		assert!(!VersionRequirement::exactly(17).matches(18));
+		assert!(VersionRequirement::between(11, 17).matches(11));
+		assert!(VersionRequirement::between(11, 17).matches(17));
+		assert!(!VersionRequirement::between(11, 17).matches(21));
+	}
+
+	#[test]
+	fn display_version_requirements() {
+		assert_eq!(VersionRequirement::any().to_string(), "any version");
+		assert_eq!(VersionRequirement::at_least(17).to_string(), "Java 17 or newer");
+		assert_eq!(VersionRequirement::at_most(11).to_string(), "Java 11 or older");
+		assert_eq!(VersionRequirement::exactly(21).to_string(), "Java 21");
+		assert_eq!(VersionRequirement::between(11, 17).to_string(), "Java 11 to 17");
+	}
+
+	#[test]
+	fn normalizes_architectures() {
+		assert_eq!(normalize_arch("amd64"), normalize_arch("x86_64"));
+		assert_eq!(normalize_arch("i686"), "x86");
+		assert_eq!(normalize_arch("arm64"), "aarch64");
+		assert_eq!(normalize_arch("aarch64"), "aarch64");
+		assert_eq!(normalize_arch("riscv64"), "riscv64");
+		assert_ne!(normalize_arch("x86"), normalize_arch("x86_64"));
+	}
+}
@@ -0,0 +1,235 @@
+//
+//  Launcher
+//
+
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::{Mutex, Condvar, RwLock};
+use std::{ptr, thread};
+
+use ffi;
+use rjni_jar::Jar;
+use signature::internal_name;
+use {JavaVM, Options, Classpath, Class, Object, IntoJava, FromJava};
+use {Error, Result};
+
+
+/// The state shared between `JavaVM::run_main` and the JVM's exit hook.
+static LAUNCH: Mutex<Launch> = Mutex::new(Launch {
+	waiting: false,
+	outcome: None,
+});
+
+/// Signalled when an outcome is recorded in `LAUNCH`.
+static FINISHED: Condvar = Condvar::new();
+
+/// Set once `run_main` destroys the JVM, after which nothing can call into it.
+/// A process can only create one JVM, so this applies to every handle.
+///
+/// Code that calls into the JVM without a `JavaVM` handle (eg. to detach a
+/// thread as it exits) holds the read lock while it does, so the JVM can't be
+/// destroyed underneath it.
+pub static DESTROYED: RwLock<bool> = RwLock::new(false);
+
+struct Launch {
+	/// Whether a thread is waiting in `run_main`, in which case `System.exit`
+	/// shouldn't terminate the process.
+	waiting: bool,
+
+	/// How the application finished, once it has.
+	outcome: Option<Outcome>,
+}
+
+/// How a Java application finished.
+enum Outcome {
+	/// `main` returned (or threw an exception), and the JVM was destroyed.
+	Returned(Result<()>),
+
+	/// `System.exit` was called with the given status code.
+	Exited(i32),
+}
+
+impl JavaVM {
+	/// Creates a JVM and runs a Java application's
+	/// `public static void main(String[])` method, like the `java` launcher.
+	///
+	/// The class can be given by its binary (`com.me.Main`) or internal
+	/// (`com/me/Main`) name, and is loaded by the system class loader. As with
+	/// the `java` launcher, `main` runs on a new thread named `main` (whose
+	/// context class loader is the main class's loader), and the JVM is
+	/// destroyed once it and all other non-daemon threads have finished.
+	///
+	/// If `main` throws an exception, it's passed to the thread's uncaught
+	/// exception handler (which prints its stack trace by default) and
+	/// returned. If the application calls `System.exit` with a non-zero
+	/// status, `Error::Exit` is returned. After `System.exit` the JVM is
+	/// halted but never destroyed, so nothing else may use it.
+	///
+	/// The current thread is detached from the JVM once it's created, so this
+	/// can't be called from a native method.
+	pub fn run_main(mut options: Options, class: &str, args: &[String]) -> Result<()> {
+		options.launching = true;
+		let jvm = JavaVM::new(options)?;
+		let name = internal_name(class);
+		jvm.launch(args, move |jvm| jvm.class(&name))
+	}
+
+	/// Creates a JVM and runs an executable Jar file, like `java -jar`.
+	///
+	/// The `Main-Class` is read from the Jar's manifest, and the classpath is
+	/// replaced with the Jar followed by the libraries listed in its
+	/// `Class-Path` (relative to the Jar's location), so the application is
+	/// visible to the system class loader and in `java.class.path`. Otherwise
+	/// this behaves like `run_main`.
+	pub fn run_jar<P: AsRef<Path>>(options: Options, path: P, args: &[String]) -> Result<()> {
+		let path = path.as_ref();
+		let manifest = match Jar::open(path).and_then(|jar| jar.manifest()) {
+			Ok(manifest) => manifest,
+			Err(err) => return Err(Error::Launch(format!(
+				"couldn't read `{}`: {}", path.display(), err))),
+		};
+
+		// Attribute names aren't case sensitive
+		let main_class = manifest.iter()
+			.find(|&(name, _)| name.eq_ignore_ascii_case("Main-Class"))
+			.map(|(_, value)| value.trim());
+		let main_class = match main_class {
+			Some(main_class) if !main_class.is_empty() => main_class,
+			_ => return Err(Error::Launch(format!(
+				"no Main-Class manifest attribute in `{}`", path.display()))),
+		};
+		let classpath = Classpath::new().add(path).follow_manifests(true);
+		JavaVM::run_main(options.classpath(classpath), main_class, args)
+	}
+
+	/// Runs the main method of the class returned by `find_main` on a new
+	/// thread, and waits for the application to finish.
+	fn launch<F>(self, args: &[String], find_main: F) -> Result<()>
+			where F: for<'a> FnOnce(&'a JavaVM) -> Result<Class<'a>> + Send + 'static {
+		let vm = self.vm;
+
+		// Otherwise the JVM would wait for this thread when it's destroyed
+		let status = unsafe { ((**vm).DetachCurrentThread)(vm) };
+		if status != ffi::JNIError::JNI_OK {
+			return Err(Error::from_ffi(status));
+		}
+
+		{
+			let mut launch = LAUNCH.lock().unwrap_or_else(|err| err.into_inner());
+			launch.waiting = true;
+			launch.outcome = None;
+		}
+
+		// Raw pointers aren't `Send`, so the pointer is passed as an address
+		let vm_addr = vm as usize;
+		let args = args.to_vec();
+		let spawned = thread::Builder::new()
+			.name(String::from("main"))
+			.spawn(move || {
+				let result = run(vm_addr as *mut ffi::JavaVM, args, find_main);
+				finish(Outcome::Returned(result));
+			});
+		if let Err(err) = spawned {
+			return Err(Error::Launch(format!("failed to start main thread: {}", err)));
+		}
+
+		// Wait for either `main` to return, or `System.exit`
+		let mut launch = LAUNCH.lock().unwrap_or_else(|err| err.into_inner());
+		while launch.outcome.is_none() {
+			launch = FINISHED.wait(launch).unwrap_or_else(|err| err.into_inner());
+		}
+		launch.waiting = false;
+		match launch.outcome.take().unwrap() {
+			Outcome::Returned(result) => result,
+			Outcome::Exited(0) => Ok(()),
+			Outcome::Exited(code) => Err(Error::Exit(code)),
+		}
+	}
+}
+
+/// Attaches the current thread to the JVM as `main`, runs the application's
+/// main method, then destroys the JVM.
+fn run<F>(vm: *mut ffi::JavaVM, args: Vec<String>, find_main: F) -> Result<()>
+		where F: for<'a> FnOnce(&'a JavaVM) -> Result<Class<'a>> {
+	unsafe {
+		let name = CString::new("main").unwrap();
+		let mut attach_args = ffi::JavaVMAttachArgs {
+			version: ffi::JNIVersion::JNI_VERSION_1_2,
+			name: name.as_ptr(),
+			group: ptr::null_mut(),
+		};
+		let mut env = ptr::null_mut();
+		let status = ((**vm).AttachCurrentThread)(vm, &mut env, &mut attach_args);
+		if status != ffi::JNIError::JNI_OK {
+			return Err(Error::from_ffi(status));
+		}
+
+		let jvm = JavaVM {
+			vm: vm,
+			env: env,
+		};
+		let result = find_main(&jvm).and_then(|class| {
+			set_context_loader(&jvm, &class)?;
+			let main = class.static_method("main", "([Ljava/lang/String;)V")?;
+			let args = [args.into_java(&jvm)?.to_jvalue(&jvm)?];
+			((**env).CallStaticVoidMethodA)(env, class.raw, main.id, args.as_ptr());
+			if !jvm.has_exception() {
+				return Ok(());
+			}
+
+			// Rethrow the exception once it's been described, so detaching the
+			// thread passes it to the uncaught exception handler, which prints
+			// its stack trace like the `java` launcher
+			let exception = jvm.exception_obj();
+			jvm.clear_exception();
+			let err = Error::from_throwable(&exception);
+			((**env).Throw)(env, exception.raw);
+			Err(err)
+		});
+
+		// Waits for every other non-daemon thread to finish
+		((**vm).DetachCurrentThread)(vm);
+		*DESTROYED.write().unwrap_or_else(|err| err.into_inner()) = true;
+		let status = ((**vm).DestroyJavaVM)(vm);
+		if result.is_ok() && status != ffi::JNIError::JNI_OK {
+			return Err(Error::from_ffi(status));
+		}
+		result
+	}
+}
+
+/// Sets the current thread's context class loader to the one that loaded the
+/// main class, as threads attached through the JNI don't have one.
+fn set_context_loader(jvm: &JavaVM, class: &Class) -> Result<()> {
+	let class_obj = Object {
+		jvm: jvm,
+		raw: class.raw,
+	};
+	let loader = class_obj.call_with_sig("getClassLoader", "()Ljava/lang/ClassLoader;", &[])?;
+	let thread = Object::from_java(jvm.class("java/lang/Thread")?
+		.call_static_with_sig("currentThread", "()Ljava/lang/Thread;", &[])?)?;
+	thread.call_with_sig("setContextClassLoader", "(Ljava/lang/ClassLoader;)V", &[loader])?;
+	Ok(())
+}
+
+/// Records how the application finished, and wakes the thread waiting in
+/// `run_main`.
+fn finish(outcome: Outcome) {
+	let mut launch = LAUNCH.lock().unwrap_or_else(|err| err.into_inner());
+	if launch.outcome.is_none() {
+		launch.outcome = Some(outcome);
+	}
+	FINISHED.notify_all();
+}
+
+/// Records the status passed to `System.exit` if the application was started
+/// by `run_main`, and returns whether it was, in which case the exiting thread
+/// mustn't return and let the JVM terminate the process.
+pub fn exited(code: ffi::jint) -> bool {
+	let waiting = LAUNCH.lock().unwrap_or_else(|err| err.into_inner()).waiting;
+	if waiting {
+		finish(Outcome::Exited(code));
+	}
+	waiting
+}
+
@@ -27,23 +27,22 @@
 //! (ie.  the directory containing the root of your Java code) to the classpath,
 //! and specify the correct JVM version:
 //!
-//! ```rust
-//! use rjni::{Jvm, Version, Classpath, Options};
+//! ```rust,no_run
+//! use rjni::{JavaVM, Version, Classpath, Options};
 //!
 //! fn main() {
 //! 	// Create a custom classpath, pointing to the directory containing the
 //! 	// root of your Java code
-//! 	let mut classpath = Classpath::new();
-//! 	classpath.add(&Path::new("/path/to/project"));
+//! 	let classpath = Classpath::new().add("/path/to/project");
 //!
 //! 	// Create a series of configuration options for the JVM, specifying the
 //! 	// version of the JVM we want to use (1.6), and our custom classpath
-//! 	let mut options = Options::new();
-//! 	options.version(Version::V16);
-//! 	options.classpath(classpath);
+//! 	let options = Options::new()
+//! 		.version(Version::V16)
+//! 		.classpath(classpath);
 //!
 //! 	// Create the JVM with these options
-//! 	let jvm = Jvm::new(options).unwrap();
+//! 	let jvm = JavaVM::new(options).unwrap();
 //!
 //! 	// Get the `com.me.Test` class using the JVM
 //! 	let class = jvm.class("com/me/Test").unwrap();
@@ -53,14 +52,73 @@
 //! ```
 
 #![allow(dead_code)]
+#![allow(clippy::tabs_in_doc_comments)]
+#![allow(clippy::match_ref_pats)]
+#![allow(clippy::needless_borrowed_reference)]
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::needless_lifetimes)]
+#![allow(clippy::wrong_self_convention)]
+#![allow(clippy::should_implement_trait)]
 
 extern crate libc;
+extern crate rjni_jar;
+
+#[cfg(feature = "derive")]
+extern crate rjni_derive;
+
+#[cfg(test)]
+extern crate rjni_build;
 
 mod ffi;
+mod signature;
+mod dynamic;
+mod dispatch;
+mod boxing;
+mod convert;
+mod enums;
+mod collections;
+mod native;
+mod streams;
+mod futures;
+mod launcher;
+mod hooks;
+mod arguments;
+mod classpath;
+mod xml;
+mod maven;
+mod installation;
+
+#[cfg(feature = "dynamic")]
+mod loader;
+
+pub use signature::{JavaType, MethodSignature, DescriptorError, internal_name, binary_name};
+pub use dispatch::JavaPrimitive;
+pub use convert::{IntoJava, FromJava};
+pub use collections::{JList, JSet, JMap, JIterator, JEntries};
+pub use streams::{JavaInputStream, JavaOutputStream};
+pub use futures::{JFuture, Completion};
+pub use installation::{JavaInstallation, VersionRequirement};
+pub use classpath::ClasspathIssue;
+
+#[cfg(feature = "dynamic")]
+pub use loader::load_jvm;
+
+/// Derive macros for `IntoJava` and `FromJava`, which map a struct to a Java
+/// class.
+#[cfg(feature = "derive")]
+pub use rjni_derive::{IntoJava, FromJava};
 
 use std::path::{PathBuf, Path};
 use std::ffi::{CString, CStr};
-use std::{mem, ptr, error, fmt, env, char};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::{ptr, error, fmt, env};
+
+use dispatch::Receiver;
+use signature::arg_types;
+use hooks::{OutputHook, ExitHook, AbortHook};
+use arguments::Origin;
+use installation::Selection;
 
 
 /// All possible versions of the JVM.
@@ -69,7 +127,8 @@ use std::{mem, ptr, error, fmt, env, char};
 /// version" error may be triggered upon creating the JVM.
 ///
 /// The integer values of these versions correspond to the FFI version numbers
-/// required by the JVM.
+/// required by the JVM. From Java 9 onwards, the JNI version only changes in
+/// the Java releases that add to it (eg. `V21` for Java 21 to 23).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Version {
 	V11 = 0x00010001,
@@ -79,43 +138,161 @@ pub enum Version {
 	V16 = 0x00010006,
 	V17 = 0x00010007,
 	V18 = 0x00010008,
+	V9 = 0x00090000,
+	V10 = 0x000a0000,
+	V19 = 0x00130000,
+	V20 = 0x00140000,
+	V21 = 0x00150000,
+}
+
+impl Version {
+	/// Every version, from oldest to newest.
+	const ALL: [Version; 12] = [
+		Version::V11, Version::V12, Version::V14, Version::V15, Version::V16,
+		Version::V17, Version::V18, Version::V9, Version::V10, Version::V19,
+		Version::V20, Version::V21,
+	];
+
+	/// Returns the equivalent FFI version number.
+	fn to_ffi(self) -> ffi::JNIVersion {
+		match self {
+			Version::V11 => ffi::JNIVersion::JNI_VERSION_1_1,
+			Version::V12 => ffi::JNIVersion::JNI_VERSION_1_2,
+			Version::V14 => ffi::JNIVersion::JNI_VERSION_1_4,
+			Version::V15 => ffi::JNIVersion::JNI_VERSION_1_5,
+			Version::V16 => ffi::JNIVersion::JNI_VERSION_1_6,
+			Version::V17 => ffi::JNIVersion::JNI_VERSION_1_7,
+			Version::V18 => ffi::JNIVersion::JNI_VERSION_1_8,
+			Version::V9 => ffi::JNIVersion::JNI_VERSION_9,
+			Version::V10 => ffi::JNIVersion::JNI_VERSION_10,
+			Version::V19 => ffi::JNIVersion::JNI_VERSION_19,
+			Version::V20 => ffi::JNIVersion::JNI_VERSION_20,
+			Version::V21 => ffi::JNIVersion::JNI_VERSION_21,
+		}
+	}
+
+	/// Converts an FFI version number into its equivalent version.
+	fn from_ffi(version: ffi::JNIVersion) -> Version {
+		match version {
+			ffi::JNIVersion::JNI_VERSION_1_1 => Version::V11,
+			ffi::JNIVersion::JNI_VERSION_1_2 => Version::V12,
+			ffi::JNIVersion::JNI_VERSION_1_4 => Version::V14,
+			ffi::JNIVersion::JNI_VERSION_1_5 => Version::V15,
+			ffi::JNIVersion::JNI_VERSION_1_6 => Version::V16,
+			ffi::JNIVersion::JNI_VERSION_1_7 => Version::V17,
+			ffi::JNIVersion::JNI_VERSION_1_8 => Version::V18,
+			ffi::JNIVersion::JNI_VERSION_9 => Version::V9,
+			ffi::JNIVersion::JNI_VERSION_10 => Version::V10,
+			ffi::JNIVersion::JNI_VERSION_19 => Version::V19,
+			ffi::JNIVersion::JNI_VERSION_20 => Version::V20,
+			ffi::JNIVersion::JNI_VERSION_21 => Version::V21,
+		}
+	}
+
+	/// Converts the version number returned by `GetVersion` into the newest
+	/// version it supports, since a newer JVM can return a number added after
+	/// this crate was written.
+	fn from_raw(version: ffi::jint) -> Version {
+		*Version::ALL.iter()
+			.rev()
+			.find(|known| **known as ffi::jint <= version)
+			.unwrap_or(&Version::V11)
+	}
 }
 
 
 /// Initialisation options required upon creation of the JVM.
 pub struct Options {
-	version: Version,
+	/// The version to request, or None for the latest supported one.
+	version: Option<Version>,
+	installation: Option<Selection>,
 	classpath: Classpath,
 	initial_heap_size: usize,
 	max_heap_size: usize,
+	thread_stack_size: usize,
+	properties: Vec<(String, String)>,
+	library_path: Vec<PathBuf>,
+	garbage_collector: Option<GarbageCollector>,
+	check_jni: bool,
+	verbose: Vec<Verbose>,
+	enable_assertions: bool,
+	module_path: Vec<PathBuf>,
+	add_opens: Vec<ModuleAccess>,
+	add_exports: Vec<ModuleAccess>,
+	java_agents: Vec<(PathBuf, Option<String>)>,
 	ignore_unrecognised: bool,
-	custom: Vec<String>,
+	custom: Vec<(String, Origin)>,
+	output_hook: Option<OutputHook>,
+	exit_hook: Option<ExitHook>,
+	abort_hook: Option<AbortHook>,
+
+	/// Whether the JVM is created to run an application with
+	/// `JavaVM::run_main`.
+	launching: bool,
 
 	/// This is required in order to preserve the existence of the heap
 	/// allocated CString instance, to prevent it from being dropped while a
 	/// pointer to its contents is used in the JavaVMInitArgs list.
 	option_strings: Vec<CString>,
 	options: Vec<ffi::JavaVMOption>,
+
+	/// Each option passed to the JVM (except the hooks), and where it came
+	/// from, to report which one the JVM rejected.
+	sources: Vec<(String, Origin)>,
 }
 
 impl Options {
 	/// Create an empty set of options.
 	pub fn new() -> Options {
 		Options {
-			version: Version::V11,
+			version: Some(Version::V11),
+			installation: None,
 			classpath: Classpath::new(),
 			initial_heap_size: 0,
 			max_heap_size: 0,
+			thread_stack_size: 0,
+			properties: Vec::new(),
+			library_path: Vec::new(),
+			garbage_collector: None,
+			check_jni: false,
+			verbose: Vec::new(),
+			enable_assertions: false,
+			module_path: Vec::new(),
+			add_opens: Vec::new(),
+			add_exports: Vec::new(),
+			java_agents: Vec::new(),
 			ignore_unrecognised: true,
 			custom: Vec::new(),
+			output_hook: None,
+			exit_hook: None,
+			abort_hook: None,
+			launching: false,
 			option_strings: Vec::new(),
 			options: Vec::new(),
+			sources: Vec::new(),
 		}
 	}
 
 	/// Set the JVM version to use.
 	pub fn version(mut self, version: Version) -> Options {
-		self.version = version;
+		self.version = Some(version);
+		self
+	}
+
+	/// Load the JVM library from the given Java installation, with the
+	/// `dynamic` feature.
+	#[cfg(feature = "dynamic")]
+	pub fn installation(mut self, installation: JavaInstallation) -> Options {
+		self.installation = Some(Selection::Installation(installation));
+		self
+	}
+
+	/// Load the JVM library from the installation `JavaInstallation::find`
+	/// picks for a version requirement (eg. Java 17 or newer), with the
+	/// `dynamic` feature.
+	#[cfg(feature = "dynamic")]
+	pub fn java_version(mut self, requirement: VersionRequirement) -> Options {
+		self.installation = Some(Selection::Version(requirement));
 		self
 	}
 
@@ -142,6 +319,98 @@ impl Options {
 		self
 	}
 
+	/// Set the stack size for each thread in bytes (ie. `-Xss`).
+	///
+	/// Call this with a size of 0 to unset any previously set value.
+	pub fn thread_stack_size(mut self, size: usize) -> Options {
+		self.thread_stack_size = size;
+		self
+	}
+
+	/// Set a system property (ie. `-D<key>=<value>`), replacing any previously
+	/// set value for the same key.
+	///
+	/// The key can't be empty or contain a `=`, which is reported when the JVM
+	/// is created.
+	pub fn property<K: ToString, V: ToString>(mut self, key: K, value: V) -> Options {
+		let key = key.to_string();
+		let value = value.to_string();
+		match self.properties.iter_mut().find(|property| property.0 == key) {
+			Some(property) => property.1 = value,
+			None => self.properties.push((key, value)),
+		}
+		self
+	}
+
+	/// Add a directory to search for native libraries loaded with
+	/// `System.loadLibrary` (ie. `-Djava.library.path`).
+	pub fn library_path<P: AsRef<Path>>(mut self, path: P) -> Options {
+		self.library_path.push(path.as_ref().to_owned());
+		self
+	}
+
+	/// Set the garbage collector the JVM uses (eg. `-XX:+UseG1GC`).
+	pub fn garbage_collector(mut self, collector: GarbageCollector) -> Options {
+		self.garbage_collector = Some(collector);
+		self
+	}
+
+	/// Set whether the JVM performs additional checks on calls to JNI functions
+	/// (ie. `-Xcheck:jni`), which is useful when debugging native code.
+	pub fn check_jni(mut self, flag: bool) -> Options {
+		self.check_jni = flag;
+		self
+	}
+
+	/// Enable verbose output from a part of the JVM (eg. `-verbose:gc`).
+	pub fn verbose(mut self, kind: Verbose) -> Options {
+		if !self.verbose.contains(&kind) {
+			self.verbose.push(kind);
+		}
+		self
+	}
+
+	/// Set whether Java `assert` statements are checked (ie. `-ea`).
+	pub fn enable_assertions(mut self, flag: bool) -> Options {
+		self.enable_assertions = flag;
+		self
+	}
+
+	/// Add a directory or modular Jar file to the module path (ie.
+	/// `--module-path`).
+	///
+	/// This requires version 9 or newer.
+	pub fn module_path<P: AsRef<Path>>(mut self, path: P) -> Options {
+		self.module_path.push(path.as_ref().to_owned());
+		self
+	}
+
+	/// Open a module's package to another module for deep reflection (ie.
+	/// `--add-opens=java.base/java.lang=ALL-UNNAMED`).
+	///
+	/// The target module is `ALL-UNNAMED` for code on the classpath. This
+	/// requires version 9 or newer.
+	pub fn add_opens(mut self, module: &str, package: &str, target: &str) -> Options {
+		self.add_opens.push(ModuleAccess::new(module, package, target));
+		self
+	}
+
+	/// Export a module's package to another module, even though the module
+	/// doesn't export it (ie. `--add-exports`).
+	///
+	/// This requires version 9 or newer.
+	pub fn add_exports(mut self, module: &str, package: &str, target: &str) -> Options {
+		self.add_exports.push(ModuleAccess::new(module, package, target));
+		self
+	}
+
+	/// Load a Java agent from a Jar file, passing it the given options (ie.
+	/// `-javaagent:<path>=<options>`).
+	pub fn java_agent<P: AsRef<Path>>(mut self, path: P, options: Option<&str>) -> Options {
+		self.java_agents.push((path.as_ref().to_owned(), options.map(String::from)));
+		self
+	}
+
 	/// Set whether the JVM should ignore unrecognised arguments, or trigger an
 	/// exception when one is provided.
 	pub fn ignore_unrecognized_arguments(mut self, flag: bool) -> Options {
@@ -152,11 +421,42 @@ impl Options {
 	/// Adds a custom, string based option (like passing in a command line
 	/// argument to the `java` process).
 	pub fn custom<T: ToString>(mut self, arg: T) -> Options {
-		self.custom.push(arg.to_string());
+		self.custom.push((arg.to_string(), Origin::Code));
 		self
 	}
 
-	/// Builds the underlying list of options.
+	/// Pass everything the JVM itself prints to stdout or stderr (eg. warnings
+	/// from `-Xcheck:jni`, or why it failed to start) to a callback instead,
+	/// one line at a time without the trailing newline.
+	///
+	/// This doesn't include output from Java code, like `System.out`.
+	pub fn on_vm_output<F: Fn(&str) + Send + Sync + 'static>(mut self, callback: F) -> Options {
+		self.output_hook = Some(Arc::new(callback));
+		self
+	}
+
+	/// Call a callback with the status passed to `System.exit` (or
+	/// `Runtime.halt`), once the JVM has stopped running Java code.
+	///
+	/// Rather than terminating the process once the callback returns, the
+	/// exiting thread is blocked forever, so the rest of the process keeps
+	/// running. The JVM is halted though, so nothing else (including dropping
+	/// a `GlobalRef`) may use it. Call `std::process::exit` in the callback to
+	/// exit instead.
+	pub fn on_exit<F: Fn(i32) + Send + Sync + 'static>(mut self, callback: F) -> Options {
+		self.exit_hook = Some(Arc::new(callback));
+		self
+	}
+
+	/// Call a callback when the JVM aborts the process after a fatal error,
+	/// just before it does.
+	pub fn on_abort<F: Fn() + Send + Sync + 'static>(mut self, callback: F) -> Options {
+		self.abort_hook = Some(Arc::new(callback));
+		self
+	}
+
+	/// Builds the underlying list of options, or returns an error if an option
+	/// is invalid, or requires a newer version than the one requested.
 	///
 	/// This function is marked unsafe since we use unsafe pointers with regards
 	/// to the FFI struct. The caller must ensure that the lifetime of the
@@ -165,45 +465,151 @@ impl Options {
 	/// This function must take a mutable pointer to `self`, rather than consume
 	/// self, since the Options struct must outlive the returned JavaVMInitArgs
 	/// struct.
-	unsafe fn build(&mut self) -> ffi::JavaVMInitArgs {
-		// Don't bother specifying heap size configurations if they're equal to
-		// 0, as this is the marker value we used
+	unsafe fn build(&mut self) -> Result<ffi::JavaVMInitArgs> {
+		// Only query the latest version now, since the JVM library might not
+		// have been loaded when the options were created
+		let version = match self.version {
+			Some(version) => version,
+			None => latest_jvm_version()
+				.map_or(*Version::ALL.last().unwrap(), |args| Version::from_ffi(args.version)),
+		};
+
+		// The hooks come first, so the JVM uses them while parsing the other
+		// options. The exit hook is also needed for `JavaVM::run_main` to report
+		// calls to `System.exit`, and the output hook for `JavaVM::new` to report
+		// which argument file or environment variable a rejected option came from
+		hooks::install(self.output_hook.clone(), self.exit_hook.clone(),
+			self.abort_hook.clone());
+		if self.exit_hook.is_some() || self.launching {
+			self.add_hook("exit", hooks::exit_hook as *const libc::c_void)?;
+		}
+		let from_files = self.custom.iter().any(|&(_, ref origin)| *origin != Origin::Code);
+		if self.output_hook.is_some() || from_files {
+			self.add_hook("vfprintf", hooks::vfprintf_hook as *const libc::c_void)?;
+		}
+		if self.abort_hook.is_some() {
+			self.add_hook("abort", hooks::abort_hook as *const libc::c_void)?;
+		}
+
+		// Don't bother specifying size configurations if they're equal to 0,
+		// as this is the marker value we used
 		if self.initial_heap_size > 0 {
 			let option = format!("-Xms{}", self.initial_heap_size);
-			self.add_option(option);
+			self.add_option(option)?;
 		}
 
 		if self.max_heap_size > 0 {
 			let option = format!("-Xmx{}", self.max_heap_size);
-			self.add_option(option);
+			self.add_option(option)?;
+		}
+
+		if self.thread_stack_size > 0 {
+			let option = format!("-Xss{}", self.thread_stack_size);
+			self.add_option(option)?;
+		}
+
+		// Wildcards are expanded here, since only the `java` launcher (and not
+		// the JVM) understands them
+		let classpath = format!("-Djava.class.path={}", self.classpath.build()?);
+		self.add_option(classpath)?;
+
+		// The JVM splits each property at its first `=`, so only the value may
+		// contain one
+		for (key, value) in self.properties.clone() {
+			if key.is_empty() || key.contains('=') {
+				return Err(Error::InvalidOption(format!(
+					"system property name `{}` is empty or contains `=`", key)));
+			}
+			self.add_option(format!("-D{}={}", key, value))?;
+		}
+
+		if !self.library_path.is_empty() {
+			let option = format!("-Djava.library.path={}", join_paths(&self.library_path)?);
+			self.add_option(option)?;
+		}
+
+		if let Some(collector) = self.garbage_collector {
+			require_version(version, collector.version(), collector.option())?;
+			if collector.experimental() {
+				self.add_option(String::from("-XX:+UnlockExperimentalVMOptions"))?;
+			}
+			self.add_option(String::from(collector.option()))?;
+		}
+
+		if self.check_jni {
+			self.add_option(String::from("-Xcheck:jni"))?;
+		}
+
+		for kind in self.verbose.clone() {
+			self.add_option(String::from(kind.option()))?;
+		}
+
+		if self.enable_assertions {
+			self.add_option(String::from("-ea"))?;
+		}
+
+		// Modules were added in Java 9, so earlier JVMs don't know these options
+		if !self.module_path.is_empty() {
+			require_version(version, Version::V9, "--module-path")?;
+			let option = format!("--module-path={}", join_paths(&self.module_path)?);
+			self.add_option(option)?;
+		}
+
+		for access in self.add_opens.clone() {
+			require_version(version, Version::V9, "--add-opens")?;
+			self.add_option(format!("--add-opens={}", access.build()?))?;
+		}
+
+		for access in self.add_exports.clone() {
+			require_version(version, Version::V9, "--add-exports")?;
+			self.add_option(format!("--add-exports={}", access.build()?))?;
 		}
 
-		// Construct the classpath from a single string, so we only have a
-		// single heap allocation (and potentially some future reallocations if
-		// the classpath.build function requires it)
-		let mut classpath = String::from("-Djava.class.path=");
-		self.classpath.build(&mut classpath);
-		self.add_option(classpath);
+		for (path, options) in self.java_agents.clone() {
+			let mut option = format!("-javaagent:{}", path_str(&path)?);
+			if let Some(options) = options {
+				option.push('=');
+				option.push_str(&options);
+			}
+			self.add_option(option)?;
+		}
 
-		// Pop each custom option off the list until no more are there, so we
-		// don't have to call .clone() on each item in the list and waste heap
-		// memory on duplicating a bunch of strings
-		while let Some(option) = self.custom.pop() {
-			self.add_option(option);
+		// Take the custom options in order, since the JVM uses the last of
+		// several values for the same option, without cloning each string
+		for (option, origin) in self.custom.split_off(0) {
+			self.add_option_from(option, origin)?;
 		}
 
-		ffi::JavaVMInitArgs {
-			version: mem::transmute(self.version),
+		Ok(ffi::JavaVMInitArgs {
+			version: version.to_ffi(),
 			nOptions: self.options.len() as ffi::jint,
 			options: self.options.as_mut_ptr(),
 			ignoreUnrecognized: self.ignore_unrecognised as ffi::jboolean,
-		}
+		})
+	}
+
+	/// Adds an option set in code to the list of FFI options, used when we're
+	/// constructing the final options list.
+	fn add_option(&mut self, option: String) -> Result<()> {
+		self.add_option_from(option, Origin::Code)
+	}
+
+	/// Adds an option to the list of FFI options, recording where it came
+	/// from.
+	fn add_option_from(&mut self, option: String, origin: Origin) -> Result<()> {
+		self.push_option(option.clone())?;
+		self.sources.push((option, origin));
+		Ok(())
 	}
 
-	/// Adds an option to the list of FFI options, used when we're constructing
-	/// the final options list.
-	fn add_option(&mut self, option: String) {
-		let cstr = CString::new(option).unwrap();
+	/// Adds a string to the list of FFI options.
+	fn push_option(&mut self, option: String) -> Result<()> {
+		let cstr = match CString::new(option) {
+			Ok(cstr) => cstr,
+			Err(err) => return Err(Error::InvalidOption(format!(
+				"`{}` contains a nul byte",
+				String::from_utf8_lossy(&err.into_vec()).replace('\0', "\\0")))),
+		};
 		self.options.push(ffi::JavaVMOption {
 			optionString: cstr.as_ptr(),
 			extraInfo: ptr::null(),
@@ -213,6 +619,15 @@ impl Options {
 		// lives for at least as long as the pointer we just created using
 		// .as_ptr() above
 		self.option_strings.push(cstr);
+		Ok(())
+	}
+
+	/// Adds one of the JVM's hooks (eg. `exit`), which is passed a function
+	/// pointer as the option's extra information.
+	fn add_hook(&mut self, name: &str, hook: *const libc::c_void) -> Result<()> {
+		self.push_option(name.to_string())?;
+		self.options.last_mut().unwrap().extraInfo = hook;
+		Ok(())
 	}
 }
 
@@ -221,23 +636,165 @@ impl Default for Options {
 	/// for the JVM.
 	///
 	/// Automatically selects the most recently supported version of the JVM on
-	/// this system.
+	/// this system, when the JVM is created.
 	fn default() -> Options {
-		// Extract the information from the set of default arguments
-		let args = latest_jvm_version().expect("No supported JNI version");
-		let version = unsafe { mem::transmute(args.version) };
-		let ignore_unrecognised = args.ignoreUnrecognized == ffi::JNI_TRUE;
 		Options {
-			version: version,
-			classpath: Classpath::new(),
-			initial_heap_size: 0,
-			max_heap_size: 0,
-			ignore_unrecognised: ignore_unrecognised,
-			custom: Vec::new(),
+			version: None,
+			..Options::new()
+		}
+	}
+}
 
-			option_strings: Vec::new(),
-			options: Vec::new(),
+/// A garbage collector the JVM can use.
+///
+/// The newer collectors require `Version::V10`, which is the JNI version of
+/// every release from Java 10 to 18, so the version can't tell which of them
+/// the JVM is. Collectors that were experimental in any of those releases are
+/// always unlocked with `-XX:+UnlockExperimentalVMOptions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GarbageCollector {
+	/// The single threaded collector (`-XX:+UseSerialGC`).
+	Serial,
+
+	/// The throughput collector (`-XX:+UseParallelGC`).
+	Parallel,
+
+	/// The Garbage-First collector (`-XX:+UseG1GC`), the default from Java 9.
+	G1,
+
+	/// The low latency Z collector (`-XX:+UseZGC`), from Java 11, which was
+	/// experimental until Java 15.
+	Z,
+
+	/// The low pause Shenandoah collector (`-XX:+UseShenandoahGC`), from Java
+	/// 12, which was experimental until Java 15 and isn't included in every
+	/// build of the JVM.
+	Shenandoah,
+
+	/// The experimental collector that never reclaims memory
+	/// (`-XX:+UseEpsilonGC`), from Java 11.
+	Epsilon,
+}
+
+impl GarbageCollector {
+	/// Returns the option that selects the collector.
+	fn option(self) -> &'static str {
+		match self {
+			GarbageCollector::Serial => "-XX:+UseSerialGC",
+			GarbageCollector::Parallel => "-XX:+UseParallelGC",
+			GarbageCollector::G1 => "-XX:+UseG1GC",
+			GarbageCollector::Z => "-XX:+UseZGC",
+			GarbageCollector::Shenandoah => "-XX:+UseShenandoahGC",
+			GarbageCollector::Epsilon => "-XX:+UseEpsilonGC",
+		}
+	}
+
+	/// Returns the oldest version of the JVM with the collector.
+	fn version(self) -> Version {
+		match self {
+			GarbageCollector::Serial | GarbageCollector::Parallel => Version::V11,
+			GarbageCollector::G1 => Version::V17,
+			GarbageCollector::Z | GarbageCollector::Shenandoah |
+				GarbageCollector::Epsilon => Version::V10,
+		}
+	}
+
+	/// Returns true if the collector is experimental in any of the releases
+	/// its version allows.
+	fn experimental(self) -> bool {
+		matches!(self, GarbageCollector::Z | GarbageCollector::Shenandoah |
+			GarbageCollector::Epsilon)
+	}
+}
+
+/// A part of the JVM that can print verbose output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbose {
+	/// Garbage collections (`-verbose:gc`).
+	Gc,
+
+	/// Loaded classes (`-verbose:class`).
+	Class,
+
+	/// Native methods and other JNI activity (`-verbose:jni`).
+	Jni,
+}
+
+impl Verbose {
+	/// Returns the option that enables the output.
+	fn option(self) -> &'static str {
+		match self {
+			Verbose::Gc => "-verbose:gc",
+			Verbose::Class => "-verbose:class",
+			Verbose::Jni => "-verbose:jni",
+		}
+	}
+}
+
+/// Access to a module's package granted to another module, by
+/// `--add-opens` or `--add-exports`.
+#[derive(Debug, Clone)]
+struct ModuleAccess {
+	module: String,
+	package: String,
+	target: String,
+}
+
+impl ModuleAccess {
+	fn new(module: &str, package: &str, target: &str) -> ModuleAccess {
+		ModuleAccess {
+			module: module.to_string(),
+			package: package.to_string(),
+			target: target.to_string(),
+		}
+	}
+
+	/// Returns the option's value, `<module>/<package>=<target>`, checking
+	/// that the parts don't contain the separators.
+	fn build(&self) -> Result<String> {
+		for part in &[&self.module, &self.package, &self.target] {
+			if part.is_empty() || part.contains(&['/', '=', ','][..]) {
+				return Err(Error::InvalidOption(format!(
+					"module access `{}/{}={}` has an empty part, or one containing `/`, `=` \
+					or `,`", self.module, self.package, self.target)));
+			}
 		}
+		Ok(format!("{}/{}={}", self.module, self.package, self.target))
+	}
+}
+
+/// Returns an error if the requested version is older than the one an option
+/// requires.
+fn require_version(version: Version, required: Version, option: &str) -> Result<()> {
+	if version >= required {
+		Ok(())
+	} else {
+		Err(Error::InvalidOption(format!(
+			"`{}` requires version {:?} or newer, but {:?} was requested", option, required,
+			version)))
+	}
+}
+
+/// Joins a list of paths with the platform's path separator, checking none of
+/// them contain it.
+fn join_paths(paths: &[PathBuf]) -> Result<String> {
+	let joined = match env::join_paths(paths) {
+		Ok(joined) => joined,
+		Err(err) => return Err(Error::InvalidOption(err.to_string())),
+	};
+	match joined.into_string() {
+		Ok(joined) => Ok(joined),
+		Err(joined) => Err(Error::InvalidOption(format!(
+			"path `{}` isn't valid unicode", joined.to_string_lossy()))),
+	}
+}
+
+/// Converts a path into a string, for use in an option.
+fn path_str(path: &Path) -> Result<&str> {
+	match path.to_str() {
+		Some(path) => Ok(path),
+		None => Err(Error::InvalidOption(format!(
+			"path `{}` isn't valid unicode", path.display()))),
 	}
 }
 
@@ -251,10 +808,10 @@ fn latest_jvm_version() -> Option<ffi::JavaVMInitArgs> {
 	//
 	// We use this in order to determine the most recently supported JVM
 	// version by iterating in reverse order over the versions.
-	for version in (Version::V11 as u32 .. Version::V18 as u32).rev() {
+	for version in Version::ALL.iter().rev() {
 		// Create a default arguments struct with the pre-specified version
 		let mut args = ffi::JavaVMInitArgs {
-			version: unsafe { mem::transmute(version) },
+			version: version.to_ffi(),
 			nOptions: 0,
 			options: ptr::null_mut(),
 			ignoreUnrecognized: ffi::JNI_TRUE,
@@ -274,11 +831,24 @@ fn latest_jvm_version() -> Option<ffi::JavaVMInitArgs> {
 }
 
 
-/// A structured list of filesystem directories which the JVM will search when
-/// looking for a class to load.
-#[derive(Debug)]
+/// A structured list of filesystem directories and Jar files which the JVM
+/// will search when looking for a class to load.
+#[derive(Debug, Clone)]
 pub struct Classpath {
 	paths: Vec<PathBuf>,
+	follow_manifests: bool,
+
+	/// The local Maven repository, if not the default.
+	maven_repository: Option<PathBuf>,
+
+	/// The artifacts added from Maven, without their versions.
+	maven_artifacts: Vec<String>,
+}
+
+impl Default for Classpath {
+	fn default() -> Classpath {
+		Classpath::new()
+	}
 }
 
 impl Classpath {
@@ -286,6 +856,9 @@ impl Classpath {
 	pub fn new() -> Classpath {
 		Classpath {
 			paths: Vec::new(),
+			follow_manifests: false,
+			maven_repository: None,
+			maven_artifacts: Vec::new(),
 		}
 	}
 
@@ -298,26 +871,18 @@ impl Classpath {
 	/// `/thing`, then you should add the path `/thing` to the classpath. If
 	/// you've added the `Test.class` file to a Jar file at `/thing/myjar.jar`,
 	/// then you should add the path `/thing/myjar.jar` to the classpath.
+	///
+	/// Like the `java` launcher, a path whose last component is `*` (eg.
+	/// `/thing/lib/*`) adds every Jar file in that directory.
 	pub fn add<T: AsRef<Path>>(mut self, path: T) -> Classpath {
 		self.paths.push(path.as_ref().to_owned());
 		self
 	}
 
-	/// Builds and returns the underlying classpath string.
-	fn build(&self, string: &mut String) {
-		// Iterate over each path
-		for path in &self.paths {
-			let converted_path = path.to_str().unwrap();
-			string.push_str(converted_path);
-
-			// The Java classpath separator is different depending on the
-			// platform. On Windows it's `;`, on Unix it's `:`
-			if env::consts::FAMILY == "windows" {
-				string.push(';');
-			} else {
-				string.push(':');
-			}
-		}
+	/// Builds and returns the underlying classpath string, separated by the
+	/// platform's path separator (`;` on Windows, `:` everywhere else).
+	fn build(&self) -> Result<String> {
+		join_paths(&self.entries())
 	}
 }
 
@@ -334,14 +899,22 @@ pub struct JavaVM {
 impl JavaVM {
 	/// Create a new virtual machine from the given set of options.
 	pub fn new(mut options: Options) -> Result<JavaVM> {
+		// Load the selected JVM library, reporting a missing Java installation
+		// rather than a generic JNI error
+		#[cfg(feature = "dynamic")]
+		loader::load(options.installation.as_ref())?;
+
 		unsafe {
 			// Construct the FFI options struct
-			let mut args = options.build();
+			let mut args = options.build()?;
 
-			// Create the JVM
+			// Create the JVM, keeping what it prints to find any option it
+			// rejects
 			let mut vm = ptr::null_mut();
 			let mut env = ptr::null_mut();
+			hooks::start_capture();
 			let status = ffi::JNI_CreateJavaVM(&mut vm, &mut env, &mut args);
+			let output = hooks::stop_capture();
 
 			// Check for an error
 			if status == ffi::JNIError::JNI_OK {
@@ -349,6 +922,9 @@ impl JavaVM {
 					vm: vm,
 					env: env,
 				})
+			} else if status == ffi::JNIError::JNI_EINVAL || status == ffi::JNIError::JNI_ERR {
+				Err(arguments::blame(&options.sources, &output)
+					.unwrap_or_else(|| Error::from_ffi(status)))
 			} else {
 				Err(Error::from_ffi(status))
 			}
@@ -390,6 +966,23 @@ impl JavaVM {
 		}
 	}
 
+	/// Returns the JNI version supported by the JVM, which determines the JNI
+	/// functions that can be used (eg. `Class::module` requires `V9`).
+	pub fn version(&self) -> Version {
+		Version::from_raw(unsafe { ((**self.env).GetVersion)(self.env) })
+	}
+
+	/// Returns an unsupported version error if the JVM doesn't support at
+	/// least the given JNI version, which must be checked before using a JNI
+	/// function added in it.
+	fn require_version(&self, version: Version) -> Result<()> {
+		if self.version() >= version {
+			Ok(())
+		} else {
+			Err(Error::UnsupportedVersion)
+		}
+	}
+
 	/// Returns true when an exception has occurred.
 	fn has_exception(&self) -> bool {
 		unsafe { ((**self.env).ExceptionCheck)(self.env) == ffi::JNI_TRUE }
@@ -401,7 +994,7 @@ impl JavaVM {
 	}
 
 	/// Get the throwable instance of the most recently occurred exception.
-	fn exception_obj(&self) -> Object {
+	fn exception_obj(&self) -> Object<'_> {
 		Object {
 			jvm: self,
 			raw: unsafe { ((**self.env).ExceptionOccurred)(self.env) },
@@ -425,7 +1018,7 @@ impl JavaVM {
 ///
 /// The class has a prescribed lifetime, since it cannot outlive the JVM that
 /// created it.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Class<'a> {
 	jvm: &'a JavaVM,
 	raw: ffi::jclass,
@@ -441,36 +1034,65 @@ impl<'a> Class<'a> {
 		}
 	}
 
+	/// Returns the `java.lang.Module` this class is a member of.
+	///
+	/// This requires JNI version 9 or newer, and returns an unsupported version
+	/// error otherwise.
+	pub fn module(&self) -> Result<Object<'a>> {
+		self.jvm.require_version(Version::V9)?;
+		let env = self.jvm.env;
+		Ok(Object {
+			jvm: self.jvm,
+			raw: unsafe { ((**env).GetModule)(env, self.raw) },
+		})
+	}
+
+	/// Create a global reference to this class, which keeps the class loaded
+	/// and remains valid until it is dropped.
+	pub fn global(&self) -> GlobalRef {
+		GlobalRef::new(self.jvm, self.raw)
+	}
+
 	/// Create an instance of this class.
 	///
 	/// The provided arguments are for the object's constructor. The correct
 	/// overloaded constructor is chosen based on the types of the arguments.
 	pub fn instantiate(&self, args: &[Value]) -> Result<Object<'a>> {
-		let env = self.jvm.env;
+		let signature = MethodSignature::from_values(args, &Type::Void)?;
+		self.construct(&signature, args)
+	}
 
-		// Get the constructor method ID
-		let name = CString::new("<init>").unwrap();
-		let signature = CString::new(function_signature(args, &Type::Void)).unwrap();
-		let id = unsafe {
-			((**env).GetMethodID)(
-				env,
-				self.raw,
-				name.as_ptr(),
-				signature.as_ptr(),
-			)
-		};
+	/// Create an instance of this class, using the constructor with the given
+	/// JNI type signature (eg. `(Ljava/lang/Object;)V`).
+	///
+	/// The arguments are checked against the signature before the constructor
+	/// is called, boxing and unboxing them as in `Object::call_with_sig`.
+	pub fn instantiate_with_sig(&self, signature: &str, args: &[Value])
+			-> Result<Object<'a>> {
+		let signature = MethodSignature::parse(signature)?;
+		let args = &check_arguments(self.jvm, &signature, args)?;
+		self.construct(&signature, args)
+	}
 
-		// Check the constructor exists
-		if id == 0 as ffi::jmethodID {
+	/// Calls the constructor with the given signature to create an instance of
+	/// this class.
+	fn construct(&self, signature: &MethodSignature, args: &[Value])
+			-> Result<Object<'a>> {
+		// Get the constructor method ID and check it exists
+		let id = self.method_id("<init>", signature);
+		if id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
+		self.new_object(id, args)
+	}
 
-		// Convert the list of arguments into an array of jvalues
-		let mut java_args = Vec::with_capacity(args.len());
-		for arg in args {
-			java_args.push(arg.to_jvalue(self.jvm));
+	/// Creates an instance of this class with the constructor with the given
+	/// ID.
+	fn new_object(&self, id: ffi::jmethodID, args: &[Value]) -> Result<Object<'a>> {
+		let env = self.jvm.env;
 
-		}
+		// Convert the list of arguments into an array of jvalues
+		let java_args = to_jvalues(args, self.jvm)?;
 
 		// Call the constructor and instantiate the object
 		let obj = unsafe {
@@ -488,18 +1110,114 @@ impl<'a> Class<'a> {
 		}
 	}
 
-	/// Returns the ID for a method with the given name, arguments, and return
-	/// type.
-	fn method_id(&self, name: &str, args: &[Value], return_type: &Type)
-			-> ffi::jmethodID {
-		let env = self.jvm.env;
+	/// Look up an instance method on this class by its name and JNI type
+	/// signature (eg. `(ILjava/lang/String;)V`).
+	///
+	/// The returned handle holds the resolved method ID, so it can be called
+	/// repeatedly on instances of this class without looking the method up
+	/// again.
+	pub fn method(&self, name: &str, signature: &str) -> Result<Method> {
+		let signature = MethodSignature::parse(signature)?;
+		let id = self.method_id(name, &signature);
+		if id.is_null() {
+			return Err(Error::from_exception(self.jvm));
+		}
+		Ok(Method {
+			id: id,
+			class: Arc::new(self.global()),
+			signature: signature,
+		})
+	}
+
+	/// Look up a constructor on this class by its JNI type signature (eg.
+	/// `(I)V`).
+	///
+	/// Like `method`, the returned handle holds the resolved method ID.
+	pub fn constructor(&self, signature: &str) -> Result<Constructor> {
+		let signature = MethodSignature::parse(signature)?;
+		let id = self.method_id("<init>", &signature);
+		if id.is_null() {
+			return Err(Error::from_exception(self.jvm));
+		}
+		Ok(Constructor {
+			id: id,
+			class: Arc::new(self.global()),
+			signature: signature,
+		})
+	}
+
+	/// Look up a static method on this class by its name and JNI type
+	/// signature.
+	///
+	/// Like `method`, the returned handle holds the resolved method ID.
+	pub fn static_method(&self, name: &str, signature: &str)
+			-> Result<StaticMethod> {
+		let signature = MethodSignature::parse(signature)?;
+		let id = self.static_method_id(name, &signature);
+		if id.is_null() {
+			return Err(Error::from_exception(self.jvm));
+		}
+		Ok(StaticMethod {
+			id: id,
+			class: Arc::new(self.global()),
+			signature: signature,
+		})
+	}
+
+	/// Look up an instance field on this class by its name and type.
+	///
+	/// The returned handle holds the resolved field ID, so the field can be
+	/// read and written repeatedly without looking it up again.
+	pub fn field(&self, name: &str, kind: Type) -> Result<Field> {
+		let id = self.field_id(name, &kind)?;
+		if id.is_null() {
+			return Err(Error::from_exception(self.jvm));
+		}
+		Ok(Field {
+			id: id,
+			class: Arc::new(self.global()),
+			kind: kind,
+		})
+	}
+
+	/// Look up an instance field on this class by its name and JNI type
+	/// descriptor (eg. `[Ljava/lang/String;`).
+	pub fn field_with_sig(&self, name: &str, descriptor: &str) -> Result<Field> {
+		let kind = JavaType::parse(descriptor)?;
+		let id = self.field_id(name, &kind)?;
+		if id.is_null() {
+			return Err(Error::from_exception(self.jvm));
+		}
+		Ok(Field {
+			id: id,
+			class: Arc::new(self.global()),
+			kind: kind.value_type(),
+		})
+	}
 
-		// Get the function signature from the arguments and return type
-		let fn_sig = function_signature(args, &return_type);
-		let signature = CString::new(fn_sig).unwrap();
+	/// Look up a static field on this class by its name and JNI type
+	/// descriptor.
+	///
+	/// Like `field`, the returned handle holds the resolved field ID.
+	pub fn static_field_with_sig(&self, name: &str, descriptor: &str) -> Result<StaticField> {
+		let kind = JavaType::parse(descriptor)?;
+		let id = self.static_field_id(name, &kind)?;
+		if id.is_null() {
+			return Err(Error::from_exception(self.jvm));
+		}
+		Ok(StaticField {
+			id: id,
+			class: Arc::new(self.global()),
+			kind: kind.value_type(),
+		})
+	}
 
-		// Convert the name of the method into a useful form
+	/// Returns the ID for a method with the given name and signature.
+	fn method_id(&self, name: &str, signature: &MethodSignature)
+			-> ffi::jmethodID {
+		let env = self.jvm.env;
 		let name = CString::new(name).unwrap();
+		let signature = CString::new(signature.descriptor()).unwrap();
 
 		// Call the FFI function
 		unsafe {
@@ -512,18 +1230,13 @@ impl<'a> Class<'a> {
 		}
 	}
 
-	/// Returns the ID for a static method on this class with the given name,
-	/// arguments, and return type.
-	fn static_method_id(&self, name: &str, args: &[Value], return_type: &Type)
+	/// Returns the ID for a static method on this class with the given name and
+	/// signature.
+	fn static_method_id(&self, name: &str, signature: &MethodSignature)
 			-> ffi::jmethodID {
 		let env = self.jvm.env;
-
-		// Get the function signature from the arguments and return type
-		let sig = function_signature(args, &return_type);
-		let signature = CString::new(sig).unwrap();
-
-		// Convert the name of the method into a useful form
 		let name = CString::new(name).unwrap();
+		let signature = CString::new(signature.descriptor()).unwrap();
 
 		// Call the FFI function
 		unsafe {
@@ -536,130 +1249,141 @@ impl<'a> Class<'a> {
 		}
 	}
 
-	/// Call a static method on this class.
+	/// Call a static method on this class, converting its result into a Rust
+	/// type.
 	///
-	/// If the function doesn't return a value (ie. a void return type), then
-	/// Value::Void is returned.
+	/// The function's signature is determined by the types of each argument
+	/// and the Java type of `R`, in the same way as `Object::call`.
 	///
 	/// Value::Void should not be passed as an argument, and will generate an
-	/// exception.
-	pub fn call_static(&self, name: &str, args: &[Value], return_type: Type)
-	                   -> Result<Value> {
-		let env = self.jvm.env;
+	/// error.
+	pub fn call_static<R: FromJava<'a>>(&self, name: &str, args: &[Value]) -> Result<R> {
+		let signature = MethodSignature::new(arg_types(args)?, R::java_type());
+		R::from_java(self.call_static_sig(name, &signature, args)?)
+	}
+
+	/// Call a static method on this class with the given JNI type signature
+	/// (eg. `(Ljava/lang/Object;I)V`).
+	///
+	/// The arguments are checked against the signature before the method is
+	/// called, boxing and unboxing them as in `Object::call_with_sig`.
+	pub fn call_static_with_sig(&self, name: &str, signature: &str, args: &[Value])
+			-> Result<Value<'a>> {
+		let signature = MethodSignature::parse(signature)?;
+		let args = &check_arguments(self.jvm, &signature, args)?;
+		self.call_static_sig(name, &signature, args)
+	}
 
+	/// Looks up and calls a static method with the given signature.
+	fn call_static_sig(&self, name: &str, signature: &MethodSignature,
+	                   args: &[Value]) -> Result<Value<'a>> {
 		// Get the method ID and check it exists
-		let method_id = self.static_method_id(name, args, &return_type);
-		if method_id == 0 as ffi::jmethodID {
+		let method_id = self.static_method_id(name, signature);
+		if method_id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
 
-		// Convert the list of arguments into an array of jvalues
-		let mut java_args = Vec::with_capacity(args.len());
-		for arg in args {
-			java_args.push(arg.to_jvalue(self.jvm));
-		}
-
-		// Call the method
-		let result = unsafe {
-			let base: *const ffi::MethodFn = mem::transmute(&(**env).CallStaticObjectMethodA);
-			let offset = return_type.offset() * 3;
-			let fn_ptr = base.offset(offset as isize);
-			(*fn_ptr)(env, self.raw, method_id, java_args.as_ptr())
-		};
+		self.invoke_static(method_id, args, &signature.return_type().value_type())
+	}
 
-		// Convert the result into a value
-		if self.jvm.has_exception() {
-			Err(Error::from_exception(self.jvm))
-		} else {
-			Ok(Value::from_jvalue(result, &return_type, self.jvm))
-		}
+	/// Calls the static method with the given ID on this class.
+	fn invoke_static(&self, method_id: ffi::jmethodID, args: &[Value],
+	                 return_type: &Type) -> Result<Value<'a>> {
+		let receiver = Receiver::Static(self.raw);
+		unsafe { dispatch::call(self.jvm, receiver, method_id, args, return_type) }
 	}
 
 	/// Returns the ID for a field with the given name and type.
-	fn field_id<T: Signature>(&self, name: &str, kind: &T) -> ffi::jfieldID {
+	fn field_id<T: Signature>(&self, name: &str, kind: &T) -> Result<ffi::jfieldID> {
 		let env = self.jvm.env;
 		let name = CString::new(name).unwrap();
-		let signature = CString::new(kind.signature()).unwrap();
-		unsafe {
+		let signature = CString::new(kind.signature()?).unwrap();
+		Ok(unsafe {
 			((**env).GetFieldID)(
 				env,
 				self.raw,
 				name.as_ptr(),
 				signature.as_ptr(),
 			)
-		}
+		})
 	}
 
 	/// Returns the ID for a static field on this class with the given name and
 	/// type.
-	fn static_field_id<T: Signature>(&self, name: &str, kind: &T) -> ffi::jfieldID {
+	fn static_field_id<T: Signature>(&self, name: &str, kind: &T) -> Result<ffi::jfieldID> {
 		let env = self.jvm.env;
 		let name = CString::new(name).unwrap();
-		let signature = CString::new(kind.signature()).unwrap();
-		unsafe {
+		let signature = CString::new(kind.signature()?).unwrap();
+		Ok(unsafe {
 			((**env).GetStaticFieldID)(
 				env,
 				self.raw,
 				name.as_ptr(),
 				signature.as_ptr(),
 			)
+		})
+	}
+
+	/// Returns the declared type of a field on this class or one of its
+	/// superclasses, found through reflection. Null has no class of its own,
+	/// so this is the type used to look up a field being set to null.
+	fn declared_field_type(&self, name: &str) -> Result<JavaType> {
+		let mut class = Object {
+			jvm: self.jvm,
+			raw: self.raw,
+		};
+		let mut not_found = None;
+		loop {
+			let field = class.call_with_sig("getDeclaredField",
+				"(Ljava/lang/String;)Ljava/lang/reflect/Field;", &[Value::Str(name.to_string())]);
+			match field {
+				Ok(field) => {
+					let kind = field.as_object()
+						.call_with_sig("getType", "()Ljava/lang/Class;", &[])?.as_object();
+					return JavaType::from_class_name(&kind.call::<String>("getName", &[])?);
+				},
+				Err(err) => {
+					not_found.get_or_insert(err);
+				},
+			}
+			let superclass = class.call_with_sig("getSuperclass", "()Ljava/lang/Class;", &[])?
+				.as_object();
+			if superclass.is_null() {
+				return Err(not_found.unwrap());
+			}
+			class = superclass;
 		}
 	}
 
 	/// Get the value of a static field on this class.
-	pub fn static_field(&self, name: &str, kind: Type) -> Result<Value> {
-		let env = self.jvm.env;
-
+	pub fn static_field(&self, name: &str, kind: Type) -> Result<Value<'a>> {
 		// Get the field ID and check it exists
-		let field_id = self.static_field_id(name, &kind);
-		if field_id == 0 as ffi::jfieldID {
+		let field_id = self.static_field_id(name, &kind)?;
+		if field_id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
 
 		// Get the contents of the field
-		let result = unsafe {
-			let base: *const ffi::GetFieldFn = mem::transmute(&(**env).GetStaticObjectField);
-			let offset = kind.offset();
-			let fn_ptr = base.offset(offset as isize);
-			(*fn_ptr)(env, self.raw, field_id)
-		};
-
-		// Convert the result into a value
-		if self.jvm.has_exception() {
-			Err(Error::from_exception(self.jvm))
-		} else {
-			Ok(Value::from_jvalue(result, &kind, self.jvm))
-		}
-
+		let receiver = Receiver::Static(self.raw);
+		unsafe { dispatch::get_field(self.jvm, receiver, field_id, &kind) }
 	}
 
 	/// Set the value of a static field on this class.
 	pub fn set_static_field(&self, name: &str, value: Value) -> Result<()> {
-		let env = self.jvm.env;
-
 		// Get the field ID and check it exists
-		let field_id = self.static_field_id(name, &value);
-		if field_id == 0 as ffi::jfieldID {
+		let field_id = match &value {
+			&Value::Object(ref obj) if obj.is_null() => {
+				self.static_field_id(name, &self.declared_field_type(name)?)?
+			},
+			_ => self.static_field_id(name, &value)?,
+		};
+		if field_id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
 
-		// Convert the value into a useable form
-		let java_value = value.to_jvalue(self.jvm);
-
 		// Set the contents of the field
-		unsafe {
-			let base: *const ffi::SetFieldFn = mem::transmute(&(**env).SetStaticObjectField);
-			let offset = value.offset();
-			let fn_ptr = base.offset(offset as isize);
-			(*fn_ptr)(env, self.raw, field_id, java_value);
-		}
-
-		// Convert the result into a value
-		if self.jvm.has_exception() {
-			Err(Error::from_exception(self.jvm))
-		} else {
-			Ok(())
-		}
+		let receiver = Receiver::Static(self.raw);
+		unsafe { dispatch::set_field(self.jvm, receiver, field_id, &value) }
 	}
 }
 
@@ -669,7 +1393,7 @@ impl<'a> Class<'a> {
 ///
 /// The object has a prescribed lifetime, since it cannot outlive the JVM that
 /// created it.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Object<'a> {
 	jvm: &'a JavaVM,
 	raw: ffi::jobject,
@@ -680,10 +1404,20 @@ impl<'a> Object<'a> {
 	/// of as a string.
 	pub fn class_name(&self) -> Result<String> {
 		// Get the corresponding class object
-		let class_obj = self.call("getClass", &[], Type::Object("java/lang/Class"))?.as_object();
+		let class_obj = self.call_with_sig("getClass", "()Ljava/lang/Class;", &[])?.as_object();
 
 		// Call the `getName` method on the class object
-		Ok(class_obj.call("getName", &[], Type::Str)?.as_str())
+		class_obj.call("getName", &[])
+	}
+
+	/// Returns the JVM that created this object.
+	pub fn jvm(&self) -> &'a JavaVM {
+		self.jvm
+	}
+
+	/// Returns true if this is a null reference.
+	pub fn is_null(&self) -> bool {
+		self.raw.is_null()
 	}
 
 	/// Returns the class that this object is an instance of.
@@ -695,6 +1429,12 @@ impl<'a> Object<'a> {
 		}
 	}
 
+	/// Create a global reference to this object, which remains valid until it
+	/// is dropped.
+	pub fn global(&self) -> GlobalRef {
+		GlobalRef::new(self.jvm, self.raw)
+	}
+
 	/// Returns true if this object is an instance of the given class.
 	///
 	/// Both this object and the given class must have been created by the same
@@ -706,106 +1446,672 @@ impl<'a> Object<'a> {
 		}
 	}
 
-	/// Call a method on this object.
+	/// Returns true if this object is a virtual thread.
+	///
+	/// This requires JNI version 21 or newer, and returns an unsupported
+	/// version error otherwise.
+	pub fn is_virtual_thread(&self) -> Result<bool> {
+		self.jvm.require_version(Version::V21)?;
+		let env = self.jvm.env;
+		Ok(unsafe { ((**env).IsVirtualThread)(env, self.raw) == ffi::JNI_TRUE })
+	}
+
+	/// Call a method on this object, converting its result into a Rust type.
 	///
 	/// The function's signature is determined by the types of each argument
-	/// and the given return type. If the signature doesn't match any valid
-	/// function with the given name, then an exception is generated (with no
-	/// stack trace).
+	/// and the Java type of `R` (eg. `()` for a void method). If the signature
+	/// doesn't match any valid function with the given name, then an exception
+	/// is generated (with no stack trace).
 	///
-	/// If the function doesn't return a value (ie. a void return type), then
-	/// Value::Void is returned.
+	/// Since each object argument contributes its runtime class to the
+	/// signature, this can't call methods that declare a parameter as a
+	/// supertype of the argument's class, or that return an object of some
+	/// other class than `R`'s. Use `call_with_sig` for those.
 	///
 	/// Value::Void should not be passed as an argument, and will generate an
-	/// exception.
-	pub fn call(&self, name: &str, args: &[Value], return_type: Type)
-			-> Result<Value> {
-		let env = self.jvm.env;
+	/// error.
+	pub fn call<R: FromJava<'a>>(&self, name: &str, args: &[Value]) -> Result<R> {
+		let signature = MethodSignature::new(arg_types(args)?, R::java_type());
+		R::from_java(self.call_sig(name, &signature, args)?)
+	}
+
+	/// Call a method on this object with the given JNI type signature (eg.
+	/// `(Ljava/lang/Object;I)V`).
+	///
+	/// The arguments are checked against the signature before the method is
+	/// called, and the type of the returned value is determined by the
+	/// signature. Primitives passed to reference parameters are boxed (eg.
+	/// `Value::Int` into a `java.lang.Integer` for an `Object` parameter), and
+	/// box objects passed to primitive parameters are unboxed.
+	pub fn call_with_sig(&self, name: &str, signature: &str, args: &[Value])
+			-> Result<Value<'a>> {
+		let signature = MethodSignature::parse(signature)?;
+		let args = &check_arguments(self.jvm, &signature, args)?;
+		self.call_sig(name, &signature, args)
+	}
 
+	/// Looks up and calls a method with the given signature.
+	fn call_sig(&self, name: &str, signature: &MethodSignature, args: &[Value])
+			-> Result<Value<'a>> {
 		// Get the method ID and check it exists
 		let class = self.class();
-		let method_id = class.method_id(name, args, &return_type);
-		if method_id == 0 as ffi::jmethodID {
+		let method_id = class.method_id(name, signature);
+		if method_id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
 
-		// Convert the list of arguments into an array of jvalues
-		let mut java_args = Vec::with_capacity(args.len());
-		for arg in args {
-			java_args.push(arg.to_jvalue(self.jvm));
+		self.invoke(method_id, args, &signature.return_type().value_type())
+	}
+
+	/// Calls the method with the given ID on this object.
+	fn invoke(&self, method_id: ffi::jmethodID, args: &[Value],
+	          return_type: &Type) -> Result<Value<'a>> {
+		let receiver = Receiver::Instance(self.raw);
+		unsafe { dispatch::call(self.jvm, receiver, method_id, args, return_type) }
+	}
+
+	/// Call a method on this object, dispatching to the implementation in the
+	/// given class rather than the one chosen by the object's runtime class.
+	///
+	/// This is the equivalent of `super.method()` in Java. The class must be
+	/// this object's class or one of its superclasses, and its implementation
+	/// is called even if a subclass overrides the method.
+	///
+	/// The method's signature is determined in the same way as `call`.
+	pub fn call_nonvirtual<R: FromJava<'a>>(&self, class: &Class, name: &str, args: &[Value])
+			-> Result<R> {
+		let signature = MethodSignature::new(arg_types(args)?, R::java_type());
+		R::from_java(self.call_nonvirtual_sig(class, name, &signature, args)?)
+	}
+
+	/// Call the implementation of a method in the given class on this object,
+	/// with the given JNI type signature.
+	///
+	/// The arguments are checked against the signature before the method is
+	/// called, boxing and unboxing them as in `Object::call_with_sig`.
+	pub fn call_nonvirtual_with_sig(&self, class: &Class, name: &str,
+	                                signature: &str, args: &[Value])
+			-> Result<Value<'a>> {
+		let signature = MethodSignature::parse(signature)?;
+		let args = &check_arguments(self.jvm, &signature, args)?;
+		self.call_nonvirtual_sig(class, name, &signature, args)
+	}
+
+	/// Looks up a method with the given signature on the given class, and
+	/// calls that class' implementation of it.
+	fn call_nonvirtual_sig(&self, class: &Class, name: &str,
+	                       signature: &MethodSignature, args: &[Value])
+			-> Result<Value<'a>> {
+		// Get the method ID from the class whose implementation we want
+		let method_id = class.method_id(name, signature);
+		if method_id.is_null() {
+			return Err(Error::from_exception(self.jvm));
 		}
 
-		// Call the method
-		let result = unsafe {
-			let base: *const ffi::MethodFn = mem::transmute(&(**env).CallObjectMethodA);
-			let offset = return_type.offset() * 3;
-			let fn_ptr = base.offset(offset as isize);
-			(*fn_ptr)(env, self.raw, method_id, java_args.as_ptr())
-		};
+		self.invoke_nonvirtual(class, method_id, args, &signature.return_type().value_type())
+	}
 
-		// Convert the result into a value
-		if self.jvm.has_exception() {
-			Err(Error::from_exception(self.jvm))
-		} else {
-			Ok(Value::from_jvalue(result, &return_type, self.jvm))
+	/// Calls the given class' implementation of the method with the given ID
+	/// on this object.
+	fn invoke_nonvirtual(&self, class: &Class, method_id: ffi::jmethodID,
+	                     args: &[Value], return_type: &Type) -> Result<Value<'a>> {
+		// The JNI doesn't check the object is an instance of the class, and
+		// the behaviour is undefined if it isn't
+		if !self.is_instance_of(class.clone()) {
+			return Err(Error::ArgumentMismatch(String::from(
+				"object isn't an instance of the class for a non-virtual call")));
 		}
+
+		// Call the method
+		let receiver = Receiver::Nonvirtual(self.raw, class.raw);
+		unsafe { dispatch::call(self.jvm, receiver, method_id, args, return_type) }
 	}
 
 	/// Get the value of a public field on this object.
-	pub fn field(&self, name: &str, kind: Type) -> Result<Value> {
-		let env = self.jvm.env;
-
+	pub fn field(&self, name: &str, kind: Type) -> Result<Value<'a>> {
 		// Get the field ID and check it exists
 		let class = self.class();
-		let field_id = class.field_id(name, &kind);
-		if field_id == 0 as ffi::jfieldID {
+		let field_id = class.field_id(name, &kind)?;
+		if field_id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
 
-		// Get the contents of the field
-		let result = unsafe {
-			let base: *const ffi::GetFieldFn = mem::transmute(&(**env).GetObjectField);
-			let offset = kind.offset();
-			let fn_ptr = base.offset(offset as isize);
-			(*fn_ptr)(env, self.raw, field_id)
-		};
+		self.get_field(field_id, &kind)
+	}
 
-		// Convert the result into a value
-		if self.jvm.has_exception() {
-			Err(Error::from_exception(self.jvm))
-		} else {
-			Ok(Value::from_jvalue(result, &kind, self.jvm))
-		}
+	/// Reads the field with the given ID on this object.
+	fn get_field(&self, field_id: ffi::jfieldID, kind: &Type)
+			-> Result<Value<'a>> {
+		let receiver = Receiver::Instance(self.raw);
+		unsafe { dispatch::get_field(self.jvm, receiver, field_id, kind) }
 	}
 
 	/// Set the value of a public field on this object.
 	pub fn set_field(&self, name: &str, value: Value) -> Result<()> {
-		let env = self.jvm.env;
-
 		// Get the field ID and check it exists
 		let class = self.class();
-		let field_id = class.field_id(name, &value);
-		if field_id == 0 as ffi::jfieldID {
+		let field_id = match &value {
+			&Value::Object(ref obj) if obj.is_null() => {
+				class.field_id(name, &class.declared_field_type(name)?)?
+			},
+			_ => class.field_id(name, &value)?,
+		};
+		if field_id.is_null() {
 			return Err(Error::from_exception(self.jvm));
 		}
 
-		// Convert the value into a useable form
-		let java_value = value.to_jvalue(self.jvm);
+		self.put_field(field_id, &value)
+	}
 
-		// Set the contents of the field
-		unsafe {
-			let base: *const ffi::SetFieldFn = mem::transmute(&(**env).SetObjectField);
-			let offset = value.offset();
-			let fn_ptr = base.offset(offset as isize);
-			(*fn_ptr)(env, self.raw, field_id, java_value);
+	/// Writes the field with the given ID on this object.
+	fn put_field(&self, field_id: ffi::jfieldID, value: &Value) -> Result<()> {
+		let receiver = Receiver::Instance(self.raw);
+		unsafe { dispatch::set_field(self.jvm, receiver, field_id, value) }
+	}
+}
+
+
+//
+//  Methods and Fields
+//
+
+/// An instance method with a resolved ID.
+///
+/// Method IDs remain valid for as long as the class that declares them is
+/// loaded, so a handle can be stored (eg. in a `ClassCache`) and called
+/// repeatedly on any instance of the class.
+#[derive(Debug, Clone)]
+pub struct Method {
+	id: ffi::jmethodID,
+	class: Arc<GlobalRef>,
+	signature: MethodSignature,
+}
+
+unsafe impl Send for Method {}
+unsafe impl Sync for Method {}
+
+impl Method {
+	/// Call this method on the given object, which must be an instance of the
+	/// class the method was looked up on.
+	///
+	/// The arguments are checked against the method's signature, boxing and
+	/// unboxing them as in `Object::call_with_sig`.
+	pub fn call<'a>(&self, object: &Object<'a>, args: &[Value<'a>])
+			-> Result<Value<'a>> {
+		check_instance(object, &self.class)?;
+		let args = &check_arguments(object.jvm, &self.signature, args)?;
+		object.invoke(self.id, args, &self.signature.return_type().value_type())
+	}
+
+	/// Call the implementation of this method in the given class on the given
+	/// object, like `Object::call_nonvirtual`.
+	///
+	/// The method must have been looked up on the given class or one of its
+	/// superclasses.
+	pub fn call_nonvirtual<'a>(&self, object: &Object<'a>, class: &Class,
+	                           args: &[Value<'a>]) -> Result<Value<'a>> {
+		check_subclass(class, &self.class)?;
+		let args = &check_arguments(object.jvm, &self.signature, args)?;
+		object.invoke_nonvirtual(class, self.id, args, &self.signature.return_type().value_type())
+	}
+}
+
+
+/// A static method with a resolved ID.
+#[derive(Debug, Clone)]
+pub struct StaticMethod {
+	id: ffi::jmethodID,
+	class: Arc<GlobalRef>,
+	signature: MethodSignature,
+}
+
+unsafe impl Send for StaticMethod {}
+unsafe impl Sync for StaticMethod {}
+
+impl StaticMethod {
+	/// Call this method on the given class, which must be the class the
+	/// method was looked up on or one of its subclasses.
+	///
+	/// The arguments are checked as in `Method::call`.
+	pub fn call<'a>(&self, class: &Class<'a>, args: &[Value<'a>])
+			-> Result<Value<'a>> {
+		check_subclass(class, &self.class)?;
+		let args = &check_arguments(class.jvm, &self.signature, args)?;
+		class.invoke_static(self.id, args, &self.signature.return_type().value_type())
+	}
+}
+
+
+/// A constructor with a resolved ID.
+#[derive(Debug, Clone)]
+pub struct Constructor {
+	id: ffi::jmethodID,
+	class: Arc<GlobalRef>,
+	signature: MethodSignature,
+}
+
+unsafe impl Send for Constructor {}
+unsafe impl Sync for Constructor {}
+
+impl Constructor {
+	/// Create an instance of the given class with this constructor, which
+	/// must be the class the constructor was looked up on.
+	///
+	/// The arguments are checked as in `Method::call`.
+	pub fn call<'a>(&self, class: &Class<'a>, args: &[Value<'a>]) -> Result<Object<'a>> {
+		// Constructors aren't inherited, so the class must be the same one
+		let env = class.jvm.env;
+		let same = unsafe { ((**env).IsSameObject)(env, class.raw, self.class.raw) };
+		if same != ffi::JNI_TRUE {
+			return Err(Error::ArgumentMismatch(String::from(
+				"constructor called on a class other than the one it was looked up on")));
 		}
+		let args = &check_arguments(class.jvm, &self.signature, args)?;
+		class.new_object(self.id, args)
+	}
+}
 
-		// Convert the result into a value
-		if self.jvm.has_exception() {
-			Err(Error::from_exception(self.jvm))
-		} else {
+
+/// An instance field with a resolved ID.
+#[derive(Debug, Clone)]
+pub struct Field {
+	id: ffi::jfieldID,
+	class: Arc<GlobalRef>,
+	kind: Type,
+}
+
+unsafe impl Send for Field {}
+unsafe impl Sync for Field {}
+
+impl Field {
+	/// Get the value of this field on the given object, which must be an
+	/// instance of the class the field was looked up on.
+	pub fn get<'a>(&self, object: &Object<'a>) -> Result<Value<'a>> {
+		check_instance(object, &self.class)?;
+		object.get_field(self.id, &self.kind)
+	}
+
+	/// Set the value of this field on the given object, which must be an
+	/// instance of the class the field was looked up on.
+	pub fn set(&self, object: &Object, value: Value) -> Result<()> {
+		check_instance(object, &self.class)?;
+		check_value(object.jvm, &self.kind, &value)?;
+		object.put_field(self.id, &value)
+	}
+}
+
+/// A static field with a resolved ID.
+#[derive(Debug, Clone)]
+pub struct StaticField {
+	id: ffi::jfieldID,
+	class: Arc<GlobalRef>,
+	kind: Type,
+}
+
+unsafe impl Send for StaticField {}
+unsafe impl Sync for StaticField {}
+
+impl StaticField {
+	/// Get the value of this field, through the class it was looked up on or
+	/// one of its subclasses.
+	pub fn get<'a>(&self, class: &Class<'a>) -> Result<Value<'a>> {
+		check_subclass(class, &self.class)?;
+		let receiver = Receiver::Static(class.raw);
+		unsafe { dispatch::get_field(class.jvm, receiver, self.id, &self.kind) }
+	}
+
+	/// Set the value of this field, through the class it was looked up on or
+	/// one of its subclasses.
+	pub fn set(&self, class: &Class, value: Value) -> Result<()> {
+		check_subclass(class, &self.class)?;
+		check_value(class.jvm, &self.kind, &value)?;
+		let receiver = Receiver::Static(class.raw);
+		unsafe { dispatch::set_field(class.jvm, receiver, self.id, &value) }
+	}
+}
+
+/// Returns an error unless the object is a non-null instance of the class a
+/// method or field was looked up on, since the JNI doesn't check it, and the
+/// behaviour is undefined if it isn't.
+fn check_instance(object: &Object, class: &GlobalRef) -> Result<()> {
+	if object.is_null() || !object.is_instance_of(class.as_class(object.jvm)) {
+		return Err(Error::ArgumentMismatch(String::from(
+			"object isn't an instance of the class the member was looked up on")));
+	}
+	Ok(())
+}
+
+/// Returns an error unless the class is (or extends) the class a method or
+/// static field was looked up on.
+fn check_subclass(class: &Class, declaring: &GlobalRef) -> Result<()> {
+	if !dynamic::is_assignable(class.jvm, class.raw, declaring.raw) {
+		return Err(Error::ArgumentMismatch(String::from(
+			"class doesn't extend the class the member was looked up on")));
+	}
+	Ok(())
+}
+
+/// Returns an error unless the value can be stored in a field of the given
+/// type, including the class of an object.
+fn check_value(jvm: &JavaVM, kind: &Type, value: &Value) -> Result<()> {
+	let kind = JavaType::from(kind);
+	if !kind.accepts(value) || !kind.accepts_class(jvm, value)? {
+		return Err(Error::ArgumentMismatch(format!(
+			"can't store {:?} in a field of type {}", value, kind)));
+	}
+	Ok(())
+}
+
+/// Boxes or unboxes the arguments to a method as its signature requires, and
+/// checks they match it, including the class of each object.
+fn check_arguments<'a>(jvm: &'a JavaVM, signature: &MethodSignature, args: &[Value<'a>])
+		-> Result<Vec<Value<'a>>> {
+	let args = boxing::box_arguments(jvm, signature, args)?;
+	signature.validate(&args)?;
+	signature.check_classes(jvm, &args)?;
+	Ok(args)
+}
+
+
+/// A lazily populated cache for a single class and the IDs of its methods and
+/// fields, designed to be stored in a `static`.
+///
+/// The class is kept alive by a global reference, so the cached IDs remain
+/// valid for as long as the cache is.
+///
+/// ```rust,no_run
+/// # use rjni::{JavaVM, ClassCache, Options, Value};
+/// static INTEGER: ClassCache = ClassCache::new("java/lang/Integer");
+///
+/// # let jvm = JavaVM::new(Options::new()).unwrap();
+/// let value_of = INTEGER.static_method(&jvm, "valueOf", "(I)Ljava/lang/Integer;").unwrap();
+/// let class = INTEGER.class(&jvm).unwrap();
+/// let boxed = value_of.call(&class, &[Value::Int(5)]).unwrap();
+/// ```
+pub struct ClassCache {
+	name: &'static str,
+	state: Mutex<Option<CachedClass>>,
+}
+
+/// The contents of a populated class cache, keyed by member name and
+/// signature.
+struct CachedClass {
+	class: GlobalRef,
+	constructors: HashMap<String, Constructor>,
+	methods: HashMap<(String, String), Method>,
+	static_methods: HashMap<(String, String), StaticMethod>,
+	fields: HashMap<(String, String), Field>,
+	static_fields: HashMap<(String, String), StaticField>,
+	constants: HashMap<String, GlobalRef>,
+}
+
+impl ClassCache {
+	/// Create an empty cache for the class with the given fully qualified
+	/// name (eg. `java/lang/Integer`).
+	pub const fn new(name: &'static str) -> ClassCache {
+		ClassCache {
+			name: name,
+			state: Mutex::new(None),
+		}
+	}
+
+	/// Returns the cached class, loading it if required.
+	pub fn class<'a>(&self, jvm: &'a JavaVM) -> Result<Class<'a>> {
+		self.with(jvm, |cached| Ok(cached.class.as_class(jvm)))
+	}
+
+	/// Returns the cached constructor with the given signature, looking it up
+	/// if required.
+	pub fn constructor(&self, jvm: &JavaVM, signature: &str) -> Result<Constructor> {
+		self.with(jvm, |cached| {
+			if let Some(constructor) = cached.constructors.get(signature) {
+				return Ok(constructor.clone());
+			}
+			let constructor = cached.class.as_class(jvm).constructor(signature)?;
+			cached.constructors.insert(signature.to_string(), constructor.clone());
+			Ok(constructor)
+		})
+	}
+
+	/// Returns the cached instance method with the given name and signature,
+	/// looking it up if required.
+	pub fn method(&self, jvm: &JavaVM, name: &str, signature: &str)
+			-> Result<Method> {
+		self.with(jvm, |cached| {
+			let key = (name.to_string(), signature.to_string());
+			if let Some(method) = cached.methods.get(&key) {
+				return Ok(method.clone());
+			}
+			let method = cached.class.as_class(jvm).method(name, signature)?;
+			cached.methods.insert(key, method.clone());
+			Ok(method)
+		})
+	}
+
+	/// Returns the cached static method with the given name and signature,
+	/// looking it up if required.
+	pub fn static_method(&self, jvm: &JavaVM, name: &str, signature: &str)
+			-> Result<StaticMethod> {
+		self.with(jvm, |cached| {
+			let key = (name.to_string(), signature.to_string());
+			if let Some(method) = cached.static_methods.get(&key) {
+				return Ok(method.clone());
+			}
+			let method = cached.class.as_class(jvm).static_method(name, signature)?;
+			cached.static_methods.insert(key, method.clone());
+			Ok(method)
+		})
+	}
+
+	/// Returns the cached instance field with the given name and type,
+	/// looking it up if required.
+	pub fn field(&self, jvm: &JavaVM, name: &str, kind: Type) -> Result<Field> {
+		self.with(jvm, |cached| {
+			let key = (name.to_string(), kind.signature()?);
+			if let Some(field) = cached.fields.get(&key) {
+				return Ok(field.clone());
+			}
+			let field = cached.class.as_class(jvm).field(name, kind)?;
+			cached.fields.insert(key, field.clone());
+			Ok(field)
+		})
+	}
+
+	/// Returns the cached instance field with the given name and JNI type
+	/// descriptor, looking it up if required.
+	pub fn field_with_sig(&self, jvm: &JavaVM, name: &str, descriptor: &str)
+			-> Result<Field> {
+		self.with(jvm, |cached| {
+			let key = (name.to_string(), descriptor.to_string());
+			if let Some(field) = cached.fields.get(&key) {
+				return Ok(field.clone());
+			}
+			let field = cached.class.as_class(jvm).field_with_sig(name, descriptor)?;
+			cached.fields.insert(key, field.clone());
+			Ok(field)
+		})
+	}
+
+	/// Returns the cached static field with the given name and JNI type
+	/// descriptor, looking it up if required.
+	pub fn static_field_with_sig(&self, jvm: &JavaVM, name: &str, descriptor: &str)
+			-> Result<StaticField> {
+		self.with(jvm, |cached| {
+			let key = (name.to_string(), descriptor.to_string());
+			if let Some(field) = cached.static_fields.get(&key) {
+				return Ok(field.clone());
+			}
+			let field = cached.class.as_class(jvm).static_field_with_sig(name, descriptor)?;
+			cached.static_fields.insert(key, field.clone());
+			Ok(field)
+		})
+	}
+
+	/// Runs the given function on the populated cache, loading the class
+	/// first if this is the first time the cache has been used.
+	fn with<T, F>(&self, jvm: &JavaVM, f: F) -> Result<T>
+			where F: FnOnce(&mut CachedClass) -> Result<T> {
+		let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+		if state.is_none() {
+			let class = jvm.class(self.name)?;
+			*state = Some(CachedClass {
+				class: class.global(),
+				constructors: HashMap::new(),
+				methods: HashMap::new(),
+				static_methods: HashMap::new(),
+				fields: HashMap::new(),
+				static_fields: HashMap::new(),
+				constants: HashMap::new(),
+			});
+		}
+		f(state.as_mut().unwrap())
+	}
+}
+
+
+
+//
+//  Global References
+//
+
+/// A global reference to a Java object or class.
+///
+/// Unlike `Object` and `Class`, a global reference isn't tied to the lifetime
+/// of a `JavaVM` instance, and can be stored and shared between threads. The
+/// referenced object won't be garbage collected until the reference is
+/// dropped.
+#[derive(Debug)]
+pub struct GlobalRef {
+	vm: *mut ffi::JavaVM,
+	raw: ffi::jobject,
+}
+
+unsafe impl Send for GlobalRef {}
+unsafe impl Sync for GlobalRef {}
+
+impl GlobalRef {
+	/// Create a new global reference to the given object.
+	fn new(jvm: &JavaVM, raw: ffi::jobject) -> GlobalRef {
+		let env = jvm.env;
+		GlobalRef {
+			vm: jvm.vm,
+			raw: unsafe { ((**env).NewGlobalRef)(env, raw) },
+		}
+	}
+
+	/// Returns the referenced object.
+	///
+	/// The JVM must be the one that created this reference.
+	pub fn as_object<'a>(&self, jvm: &'a JavaVM) -> Object<'a> {
+		Object {
+			jvm: jvm,
+			raw: self.raw,
+		}
+	}
+
+	/// Returns the referenced class. The reference must have been created from
+	/// a class.
+	///
+	/// The JVM must be the one that created this reference.
+	pub fn as_class<'a>(&self, jvm: &'a JavaVM) -> Class<'a> {
+		Class {
+			jvm: jvm,
+			raw: self.raw,
+		}
+	}
+}
+
+impl Drop for GlobalRef {
+	fn drop(&mut self) {
+		// The reference might be dropped on a thread other than the one that
+		// created it
+		let raw = self.raw;
+		let _ = with_attached(self.vm, |jvm| {
+			unsafe { ((**jvm.env).DeleteGlobalRef)(jvm.env, raw) };
 			Ok(())
+		});
+	}
+}
+
+/// Runs a function in a new local reference frame, which deletes every local
+/// reference the function creates once it returns. The result mustn't hold
+/// any of them.
+fn with_local_frame<T, F>(jvm: &JavaVM, f: F) -> Result<T>
+		where F: FnOnce() -> Result<T> {
+	let env = jvm.env;
+	unsafe {
+		if ((**env).PushLocalFrame)(env, 16) != ffi::JNIError::JNI_OK {
+			return Err(Error::from_exception(jvm));
+		}
+	}
+	let result = f();
+	unsafe {
+		((**env).PopLocalFrame)(env, ptr::null_mut());
+	}
+	result
+}
+
+/// Runs a function in a new local reference frame like `with_local_frame`,
+/// except that an object it returns is kept, as a new local reference in the
+/// enclosing frame.
+fn with_local_frame_value<'a, F>(jvm: &'a JavaVM, f: F) -> Result<Value<'a>>
+		where F: FnOnce() -> Result<Value<'a>> {
+	let env = jvm.env;
+	unsafe {
+		if ((**env).PushLocalFrame)(env, 16) != ffi::JNIError::JNI_OK {
+			return Err(Error::from_exception(jvm));
+		}
+	}
+	let result = f();
+	let kept = match result {
+		Ok(Value::Object(ref obj)) => obj.raw,
+		_ => ptr::null_mut(),
+	};
+	let raw = unsafe { ((**env).PopLocalFrame)(env, kept) };
+	result.map(|value| match value {
+		Value::Object(_) => Value::Object(Object {
+			jvm: jvm,
+			raw: raw,
+		}),
+		value => value,
+	})
+}
+
+/// Deletes a local reference to an object that's no longer used.
+fn delete_local_ref(obj: &Object) {
+	if !obj.is_null() {
+		let env = obj.jvm.env;
+		unsafe { ((**env).DeleteLocalRef)(env, obj.raw) };
+	}
+}
+
+/// Runs a function with a handle to the given JVM for the current thread,
+/// attaching the thread to the JVM temporarily if it isn't already.
+fn with_attached<T, F>(vm: *mut ffi::JavaVM, f: F) -> Result<T>
+		where F: FnOnce(&JavaVM) -> Result<T> {
+	unsafe {
+		let mut env = ptr::null_mut();
+		let mut attached = false;
+		let status = ((**vm).GetEnv)(vm, &mut env, ffi::JNIVersion::JNI_VERSION_1_2);
+		if status == ffi::JNIError::JNI_EDETACHED {
+			let status = ((**vm).AttachCurrentThread)(vm, &mut env, ptr::null_mut());
+			if status != ffi::JNIError::JNI_OK {
+				return Err(Error::from_ffi(status));
+			}
+			attached = true;
+		} else if status != ffi::JNIError::JNI_OK {
+			return Err(Error::from_ffi(status));
+		}
+
+		let jvm = JavaVM {
+			vm: vm,
+			env: env,
+		};
+		let result = f(&jvm);
+		if attached {
+			((**vm).DetachCurrentThread)(vm);
 		}
+		result
 	}
 }
 
@@ -817,8 +2123,9 @@ impl<'a> Object<'a> {
 
 /// Implemented by both `Type` and `Value`.
 trait Signature {
-	/// Returns the identifying type signature for this value.
-	fn signature(&self) -> String;
+	/// Returns the identifying type signature for this value, or an error if
+	/// it's a null object, whose type can't be inferred.
+	fn signature(&self) -> Result<String>;
 }
 
 /// The type of a Java value returned from a method.
@@ -836,60 +2143,23 @@ pub enum Type {
 	Void,
 
 	/// The argument specifies the fully qualified class name of the object,
-	/// eg. `java/lang/String`, using `/` to separate packages.
+	/// eg. `java/lang/String`, using `/` to separate packages. Array classes
+	/// are named by their descriptor, eg. `[I`.
 	///
 	/// This should be known at compile time, hence the static lifetime on the
 	/// string.
 	Object(&'static str),
 }
 
-impl Type {
-	/// Returns the function signature component for this value.
-	fn static_signature(&self) -> &'static str {
-		match self {
-			&Type::Boolean => "Z",
-			&Type::Byte => "B",
-			&Type::Char => "C",
-			&Type::Short => "S",
-			&Type::Int => "I",
-			&Type::Long => "J",
-			&Type::Float => "F",
-			&Type::Double => "D",
-			&Type::Str => "Ljava/lang/String;",
-			&Type::Void => "V",
-			// The object type is handled properly in the calling function
-			&Type::Object(_) => "L",
-		}
-	}
-
-	/// Returns the integer offset of the corresponding method call function
-	/// within the JNIEnv struct.
-	fn offset(&self) -> usize {
-		match self {
-			// Use the `CallObjectMethod` for both objects and strings
-			&Type::Object(_) => 0,
-			&Type::Str => 0,
-			&Type::Boolean => 1,
-			&Type::Byte => 2,
-			&Type::Char => 3,
-			&Type::Short => 4,
-			&Type::Int => 5,
-			&Type::Long => 6,
-			&Type::Float => 7,
-			&Type::Double => 8,
-			&Type::Void => 9,
-		}
+impl Signature for Type {
+	fn signature(&self) -> Result<String> {
+		Ok(JavaType::from(self).descriptor())
 	}
 }
 
-impl Signature for Type {
-	fn signature(&self) -> String {
-		let mut result = String::from(self.static_signature());
-		if let &Type::Object(class_name) = self {
-			result.push_str(class_name);
-			result.push(';');
-		}
-		result
+impl Signature for JavaType {
+	fn signature(&self) -> Result<String> {
+		Ok(self.descriptor())
 	}
 }
 
@@ -912,7 +2182,7 @@ macro_rules! expand {
 ///
 /// The value has a prescribed lifetime, since it cannot outlive the JVM that
 /// created it.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value<'a> {
 	Boolean(bool),
 	Byte(i8),
@@ -946,84 +2216,34 @@ impl<'a> Value<'a> {
 		}
 	}
 
-	/// Returns the integer offset of the corresponding method call function
-	/// within the JNIEnv struct.
-	fn offset(&self) -> usize {
-		match self {
-			// Use the `CallObjectMethod` for both objects and strings
-			&Value::Object(_) => 0,
-			&Value::Str(_) => 0,
-			&Value::Boolean(_) => 1,
-			&Value::Byte(_) => 2,
-			&Value::Char(_) => 3,
-			&Value::Short(_) => 4,
-			&Value::Int(_) => 5,
-			&Value::Long(_) => 6,
-			&Value::Float(_) => 7,
-			&Value::Double(_) => 8,
-			&Value::Void => 9,
-		}
-	}
-
 	/// Converts the value into a Java value suitable to pass as an argument to
 	/// an FFI call.
-	fn to_jvalue(&self, jvm: &JavaVM) -> ffi::jvalue {
-		let data = unsafe {
-			match self {
-				&Value::Boolean(v) => mem::transmute(v as u64),
-				&Value::Byte(v) => mem::transmute(v as u64),
-				&Value::Char(v) => mem::transmute(v as u64),
-				&Value::Short(v) => mem::transmute(v as u64),
-				&Value::Int(v) => mem::transmute(v as u64),
-				&Value::Long(v) => mem::transmute(v as u64),
-				&Value::Float(v) => mem::transmute(v as u64),
-				&Value::Double(v) => mem::transmute(v as u64),
-				&Value::Object(ref v) => mem::transmute(v.raw as u64),
-				// TODO: Don't panic, return an exception
-				&Value::Void => panic!("Can't pass void to a function"),
-				&Value::Str(ref v) => {
-					// TODO: Possible memory leak? Where do we dealloc this?
-					// Does the GC do it for us? I assume so...
-					let env = jvm.env;
-					let cstr = CString::new(v.clone()).unwrap();
-					let java_str = ((**env).NewStringUTF)(env, cstr.as_ptr());
-					mem::transmute(java_str as u64)
-				},
-			}
+	fn to_jvalue(&self, jvm: &JavaVM) -> Result<ffi::jvalue> {
+		let value = match self {
+			&Value::Boolean(v) => ffi::jvalue {
+				z: if v { ffi::JNI_TRUE } else { ffi::JNI_FALSE },
+			},
+			&Value::Byte(v) => ffi::jvalue { b: v },
+			&Value::Char(v) => ffi::jvalue { c: dispatch::to_jchar(v)? },
+			&Value::Short(v) => ffi::jvalue { s: v },
+			&Value::Int(v) => ffi::jvalue { i: v },
+			&Value::Long(v) => ffi::jvalue { j: v },
+			&Value::Float(v) => ffi::jvalue { f: v },
+			&Value::Double(v) => ffi::jvalue { d: v },
+			&Value::Object(ref v) => ffi::jvalue { l: v.raw },
+			&Value::Void => return Err(Error::ArgumentMismatch(
+				String::from("can't pass void as an argument"))),
+			&Value::Str(ref v) => {
+				// TODO: Possible memory leak? Where do we dealloc this?
+				// Does the GC do it for us? I assume so...
+				let env = jvm.env;
+				let cstr = CString::new(v.clone()).unwrap();
+				let java_str = unsafe { ((**env).NewStringUTF)(env, cstr.as_ptr()) };
+				ffi::jvalue { l: java_str }
+			},
 		};
 
-		ffi::jvalue {
-			data: data,
-		}
-	}
-
-	/// Converts a Java value into its equivalent Rust version.
-	fn from_jvalue<'b>(value: ffi::jvalue, kind: &Type, jvm: &'b JavaVM)
-			-> Value<'b> {
-		// Depending on the type of the jvalue
-		match kind {
-			&Type::Boolean => Value::Boolean(value.z() == ffi::JNI_TRUE),
-			&Type::Byte => Value::Byte(value.b()),
-			&Type::Char => Value::Char(unsafe {
-				char::from_u32_unchecked(value.c() as u32)
-			}),
-			&Type::Short => Value::Short(value.s()),
-			&Type::Int => Value::Int(value.i()),
-			&Type::Long => Value::Long(value.j()),
-			&Type::Float => Value::Float(value.f()),
-			&Type::Double => Value::Double(value.d()),
-			&Type::Void => Value::Void,
-			&Type::Object(_) => Value::Object(Object {
-				jvm: jvm,
-				raw: value.l(),
-			}),
-			&Type::Str => {
-				// Allocate a new string object and read from the Java string
-				let mut result = String::new();
-				convert_string(jvm, value.l() as ffi::jstring, &mut result);
-				Value::Str(result)
-			},
-		}
+		Ok(value)
 	}
 
 	expand!(as_bool, Boolean, bool);
@@ -1039,48 +2259,19 @@ impl<'a> Value<'a> {
 }
 
 impl<'a> Signature for Value<'a> {
-	fn signature(&self) -> String {
-		let mut result = String::from(self.static_signature());
+	fn signature(&self) -> Result<String> {
 		if let &Value::Object(ref obj) = self {
-			result.push_str(&obj.class_name().unwrap());
+			signature::class_type(obj).map(|kind| kind.descriptor())
+		} else {
+			Ok(String::from(self.static_signature()))
 		}
-		result
 	}
 }
 
-/// Returns the function signature as a string for a method with the given
-/// arguments and return type.
-fn function_signature(args: &[Value], return_type: &Type) -> String {
-	let mut sig = String::new();
-
-	// Push the opening bracket for the arguments type list
-	sig.push('(');
-
-	// Iterate over each argument
-	for arg in args {
-		// Each Java type has a 1 character type associated with it, which we
-		// push onto the signature to indicate another argument to the function
-		sig.push_str(arg.static_signature());
-
-		// We need to push the class name of an object to the string after the
-		// `L` character
-		if let &Value::Object(ref obj) = arg {
-			sig.push_str(&obj.class_name().unwrap());
-			sig.push(';');
-		}
-	}
-
-	// Push the closing bracket to the arguments list
-	sig.push(')');
-
-	// Push the return type's signature
-	sig.push_str(return_type.static_signature());
-	if let &Type::Object(name) = return_type {
-		sig.push_str(name);
-		sig.push(';');
-	}
-
-	sig
+/// Converts a list of arguments into an array of jvalues, suitable to pass to
+/// an FFI call.
+fn to_jvalues(args: &[Value], jvm: &JavaVM) -> Result<Vec<ffi::jvalue>> {
+	args.iter().map(|arg| arg.to_jvalue(jvm)).collect()
 }
 
 /// Convert the given Java string into the proper Rust version, and push it onto
@@ -1126,6 +2317,32 @@ pub enum Error {
 
 	/// An exception raised in Java code.
 	Exception(ExceptionInfo),
+
+	/// A malformed JNI type descriptor or signature.
+	InvalidSignature(DescriptorError),
+
+	/// Arguments that can't be passed to the method being called.
+	ArgumentMismatch(String),
+
+	/// A Java value that can't be converted into the requested Rust type.
+	Conversion(String),
+
+	/// A JVM library that couldn't be found or loaded, with the `dynamic`
+	/// feature.
+	JvmNotFound(String),
+
+	/// A JVM option that's invalid, or can't be used with the requested
+	/// version.
+	InvalidOption(String),
+
+	/// A Java application that couldn't be launched.
+	Launch(String),
+
+	/// A Maven artifact that couldn't be resolved from the local repository.
+	Maven(String),
+
+	/// A Java application that called `System.exit` with a non-zero status.
+	Exit(i32),
 }
 
 impl Error {
@@ -1144,12 +2361,16 @@ impl Error {
 		// Get the thrown exception
 		let obj = jvm.exception_obj();
 		jvm.clear_exception();
+		Error::from_throwable(&obj)
+	}
 
+	/// Create a new error from a throwable object, which must not be null.
+	fn from_throwable(obj: &Object) -> Error {
 		// Class name
 		let class_name = obj.class_name().unwrap();
 
 		// Get the message associated with the error
-		let msg = obj.call("toString", &[], Type::Str).unwrap().as_str();
+		let msg = obj.call("toString", &[]).unwrap();
 
 		// Create the exception object
 		Error::Exception(ExceptionInfo {
@@ -1159,12 +2380,21 @@ impl Error {
 	}
 }
 
-impl error::Error for Error {
-	fn description<'a>(&'a self) -> &'a str {
+impl Error {
+	/// Returns a short description of the error.
+	fn summary<'a>(&'a self) -> &'a str {
 		match self {
 			&Error::UnsupportedVersion => "Unsupported JVM version",
 			&Error::OutOfMemory => "Out of memory",
 			&Error::Exception(ref info) => info.message(),
+			&Error::InvalidSignature(ref err) => err.reason(),
+			&Error::ArgumentMismatch(ref msg) => msg,
+			&Error::Conversion(ref msg) => msg,
+			&Error::JvmNotFound(ref msg) => msg,
+			&Error::InvalidOption(ref msg) => msg,
+			&Error::Launch(ref msg) => msg,
+			&Error::Maven(ref msg) => msg,
+			&Error::Exit(_) => "Java application exited",
 			&Error::FFIError(code) => match code {
 				ffi::JNIError::JNI_OK => "Success?",
 				ffi::JNIError::JNI_ERR => "Unknown error",
@@ -1178,15 +2408,44 @@ impl error::Error for Error {
 	}
 }
 
+impl error::Error for Error {
+	fn description<'a>(&'a self) -> &'a str {
+		self.summary()
+	}
+}
+
 impl fmt::Display for Error {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match self {
 			&Error::Exception(ref info) => {
 				info.fmt(f)
 			},
+			&Error::InvalidSignature(ref err) => {
+				write!(f, "Invalid signature: {}", err)
+			},
+			&Error::ArgumentMismatch(ref msg) => {
+				write!(f, "Argument mismatch: {}", msg)
+			},
+			&Error::Conversion(ref msg) => {
+				write!(f, "Conversion failed: {}", msg)
+			},
+			&Error::JvmNotFound(ref msg) => {
+				write!(f, "JVM not found: {}", msg)
+			},
+			&Error::InvalidOption(ref msg) => {
+				write!(f, "Invalid JVM option: {}", msg)
+			},
+			&Error::Launch(ref msg) => {
+				write!(f, "Launch failed: {}", msg)
+			},
+			&Error::Maven(ref msg) => {
+				write!(f, "Maven resolution failed: {}", msg)
+			},
+			&Error::Exit(code) => {
+				write!(f, "Java application exited with status {}", code)
+			},
 			_ => {
-				use std::error::Error;
-				write!(f, "{}", self.description())
+				write!(f, "{}", self.summary())
 			},
 		}
 	}
@@ -1228,3 +2487,160 @@ impl fmt::Debug for ExceptionInfo {
 		write!(f, "{}", self)
 	}
 }
+
+
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use rjni_build;
+	use {JavaVM, Options, Version, GarbageCollector, Value, Error, Result, futures};
+
+	/// Returns the JVM shared by every test, creating it the first time, and
+	/// attaching the current thread to it.
+	pub fn jvm() -> JavaVM {
+		static VM: Mutex<usize> = Mutex::new(0);
+		let mut vm = VM.lock().unwrap();
+		if *vm == 0 {
+			let jvm = JavaVM::new(Options::new().version(Version::V18)).unwrap();
+			*vm = jvm.vm as usize;
+			return jvm;
+		}
+		futures::attach(*vm as *mut _).unwrap()
+	}
+
+	/// Converts a version into `rjni_build`'s copy of the enum. Along with
+	/// `from_build`, this stops compiling if either enum gains a variant.
+	fn to_build(version: Version) -> rjni_build::Version {
+		match version {
+			Version::V11 => rjni_build::Version::V11,
+			Version::V12 => rjni_build::Version::V12,
+			Version::V14 => rjni_build::Version::V14,
+			Version::V15 => rjni_build::Version::V15,
+			Version::V16 => rjni_build::Version::V16,
+			Version::V17 => rjni_build::Version::V17,
+			Version::V18 => rjni_build::Version::V18,
+			Version::V9 => rjni_build::Version::V9,
+			Version::V10 => rjni_build::Version::V10,
+			Version::V19 => rjni_build::Version::V19,
+			Version::V20 => rjni_build::Version::V20,
+			Version::V21 => rjni_build::Version::V21,
+		}
+	}
+
+	fn from_build(version: rjni_build::Version) -> Version {
+		match version {
+			rjni_build::Version::V11 => Version::V11,
+			rjni_build::Version::V12 => Version::V12,
+			rjni_build::Version::V14 => Version::V14,
+			rjni_build::Version::V15 => Version::V15,
+			rjni_build::Version::V16 => Version::V16,
+			rjni_build::Version::V17 => Version::V17,
+			rjni_build::Version::V18 => Version::V18,
+			rjni_build::Version::V9 => Version::V9,
+			rjni_build::Version::V10 => Version::V10,
+			rjni_build::Version::V19 => Version::V19,
+			rjni_build::Version::V20 => Version::V20,
+			rjni_build::Version::V21 => Version::V21,
+		}
+	}
+
+	#[test]
+	fn build_versions_match() {
+		for &version in Version::ALL.iter() {
+			let build = to_build(version);
+			assert_eq!(from_build(build), version);
+			assert_eq!(format!("{:?}", build), format!("{:?}", version));
+
+			// The release is the minor number of a 1.x version, and the major
+			// number of any later one
+			let number = version as u32;
+			let release = if number >> 16 == 1 { number & 0xffff } else { number >> 16 };
+			assert_eq!(build.release(), release);
+		}
+	}
+
+	#[test]
+	fn infers_return_types() {
+		let jvm = jvm();
+		let integer = jvm.class("java/lang/Integer").unwrap();
+		let args = [Value::Str(String::from("42"))];
+		assert_eq!(integer.call_static::<i32>("parseInt", &args).unwrap(), 42);
+		let args = [Value::Int(7)];
+		assert_eq!(integer.call_static::<String>("toString", &args).unwrap(), "7");
+
+		let builder = jvm.class("java/lang/StringBuilder").unwrap()
+			.instantiate(&[Value::Str(String::from("ab"))]).unwrap();
+		assert_eq!(builder.call::<i32>("length", &[]).unwrap(), 2);
+		builder.call::<()>("setLength", &[Value::Int(1)]).unwrap();
+		assert_eq!(builder.call::<String>("toString", &[]).unwrap(), "a");
+		assert!(builder.call::<bool>("length", &[]).is_err());
+	}
+
+	/// Builds the options, returning the option strings passed to the JVM
+	/// (without the hooks).
+	fn build(mut options: Options) -> Result<Vec<String>> {
+		unsafe { options.build()? };
+		Ok(options.sources.into_iter().map(|(option, _)| option).collect())
+	}
+
+	#[test]
+	fn builds_options() {
+		let options = Options::new()
+			.version(Version::V18)
+			.max_heap_size(1 << 20)
+			.thread_stack_size(4096)
+			.property("a.b", "c=d")
+			.garbage_collector(GarbageCollector::G1)
+			.check_jni(true)
+			.enable_assertions(true)
+			.custom("-Xint");
+		assert_eq!(build(options).unwrap(), [
+			"-Xmx1048576", "-Xss4096", "-Djava.class.path=", "-Da.b=c=d", "-XX:+UseG1GC",
+			"-Xcheck:jni", "-ea", "-Xint",
+		]);
+
+		let options = Options::new()
+			.version(Version::V9)
+			.add_opens("java.base", "java.lang", "ALL-UNNAMED");
+		assert_eq!(build(options).unwrap(), [
+			"-Djava.class.path=", "--add-opens=java.base/java.lang=ALL-UNNAMED",
+		]);
+	}
+
+	#[test]
+	fn rejects_invalid_property_names() {
+		for &name in ["", "a=b"].iter() {
+			let result = build(Options::new().property(name, "value"));
+			assert!(matches!(result, Err(Error::InvalidOption(_))), "{:?}", name);
+		}
+	}
+
+	#[test]
+	fn rejects_options_newer_than_version() {
+		let result = build(Options::new().version(Version::V18)
+			.garbage_collector(GarbageCollector::Z));
+		assert!(matches!(result, Err(Error::InvalidOption(_))));
+		let result = build(Options::new().garbage_collector(GarbageCollector::G1));
+		assert!(matches!(result, Err(Error::InvalidOption(_))));
+		let result = build(Options::new().version(Version::V18).module_path("mods"));
+		assert!(matches!(result, Err(Error::InvalidOption(_))));
+		let result = build(Options::new().version(Version::V18)
+			.add_exports("java.base", "sun.nio.ch", "ALL-UNNAMED"));
+		assert!(matches!(result, Err(Error::InvalidOption(_))));
+	}
+
+	#[test]
+	fn unlocks_experimental_collectors() {
+		for &collector in [GarbageCollector::Z, GarbageCollector::Shenandoah,
+				GarbageCollector::Epsilon].iter() {
+			let options = build(Options::new().version(Version::V10)
+				.garbage_collector(collector)).unwrap();
+			assert_eq!(options[1..], ["-XX:+UnlockExperimentalVMOptions", collector.option()]);
+		}
+		let options = build(Options::new().version(Version::V10)
+			.garbage_collector(GarbageCollector::Parallel)).unwrap();
+		assert_eq!(options[1..], ["-XX:+UseParallelGC"]);
+	}
+}
@@ -0,0 +1,218 @@
+//
+//  Library Loading
+//
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{fs, mem};
+
+use libc;
+use ffi;
+use installation::{JavaInstallation, VersionRequirement, Selection};
+use {Error, Result};
+
+
+type CreateJavaVM = unsafe extern "C" fn(vm: *mut *mut ffi::JavaVM,
+	env: *mut *mut ffi::JNIEnv, args: *mut ffi::JavaVMInitArgs) -> ffi::JNIError;
+type GetDefaultJavaVMInitArgs = unsafe extern "C" fn(args: *mut ffi::JavaVMInitArgs)
+	-> ffi::JNIError;
+type GetCreatedJavaVMs = unsafe extern "C" fn(vm: *mut *mut ffi::JavaVM,
+	buf_len: ffi::jsize, n_vms: *mut ffi::jsize) -> ffi::JNIError;
+
+/// The JNI invocation functions exported by a loaded JVM library.
+pub struct Library {
+	/// The canonical path to the library.
+	pub path: PathBuf,
+
+	pub create_java_vm: CreateJavaVM,
+	pub get_default_java_vm_init_args: GetDefaultJavaVMInitArgs,
+	pub get_created_java_vms: GetCreatedJavaVMs,
+}
+
+/// The loaded JVM library, which is never unloaded, since a JVM can't be
+/// created again once destroyed.
+static LIBRARY: Mutex<Option<&'static Library>> = Mutex::new(None);
+
+/// Loads the JVM library at the given path, instead of searching for one,
+/// when rjni is built with the `dynamic` feature.
+///
+/// The path can be the library itself (eg. `libjvm.so`), or a Java home
+/// directory to look for it in. This must be called before the first JVM is
+/// created, and fails if a different library has already been loaded.
+pub fn load_jvm<P: AsRef<Path>>(path: P) -> Result<()> {
+	let path = path.as_ref();
+	let path = if path.is_dir() {
+		JavaInstallation::from_home(path)?.library_path().to_path_buf()
+	} else {
+		path.to_path_buf()
+	};
+
+	let mut library = LIBRARY.lock().unwrap_or_else(|err| err.into_inner());
+	match *library {
+		Some(loaded) if fs::canonicalize(&path).ok().as_ref() == Some(&loaded.path) => Ok(()),
+		Some(loaded) => Err(Error::JvmNotFound(format!(
+			"can't load `{}`, since `{}` is already loaded", path.display(),
+			loaded.path.display()))),
+		None => {
+			*library = Some(load_from(&path)?);
+			Ok(())
+		},
+	}
+}
+
+/// Loads the JVM library for the installation selected in a JVM's options,
+/// or searches for one if there's no selection.
+///
+/// If a library has already been loaded, it must be from the selected
+/// installation, or one satisfying the selected version requirement.
+pub fn load(selection: Option<&Selection>) -> Result<()> {
+	let selection = match selection {
+		Some(selection) => selection,
+		None => return library().map(|_| ()),
+	};
+
+	let loaded = *LIBRARY.lock().unwrap_or_else(|err| err.into_inner());
+	match (selection, loaded) {
+		(&Selection::Installation(ref installation), _) => {
+			load_jvm(installation.library_path())
+		},
+		(&Selection::Version(ref requirement), None) => {
+			load_jvm(JavaInstallation::find(requirement)?.library_path())
+		},
+		(&Selection::Version(ref requirement), Some(loaded)) => {
+			let installation = JavaInstallation::of_library(&loaded.path);
+			if installation.is_some_and(|found| requirement.matches(found.major_version())) {
+				Ok(())
+			} else {
+				Err(Error::JvmNotFound(format!("`{}` is already loaded, which isn't {}",
+					loaded.path.display(), requirement)))
+			}
+		},
+	}
+}
+
+/// Returns the loaded JVM library, loading the one picked by
+/// `JavaInstallation::find` the first time.
+pub fn library() -> Result<&'static Library> {
+	let mut library = LIBRARY.lock().unwrap_or_else(|err| err.into_inner());
+	if let Some(library) = *library {
+		return Ok(library);
+	}
+
+	let installation = JavaInstallation::find(&VersionRequirement::any())?;
+	let loaded = load_from(installation.library_path())?;
+	*library = Some(loaded);
+	Ok(loaded)
+}
+
+/// Loads the JVM library from the given file, and resolves its invocation
+/// functions.
+fn load_from(path: &Path) -> Result<&'static Library> {
+	unsafe {
+		let handle = os::open(path)?;
+		let create = os::symbol(handle, path, "JNI_CreateJavaVM")?;
+		let default_args = os::symbol(handle, path, "JNI_GetDefaultJavaVMInitArgs")?;
+		let created = os::symbol(handle, path, "JNI_GetCreatedJavaVMs")?;
+		let library = Library {
+			path: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+			create_java_vm: mem::transmute::<*mut libc::c_void, CreateJavaVM>(create),
+			get_default_java_vm_init_args:
+				mem::transmute::<*mut libc::c_void, GetDefaultJavaVMInitArgs>(default_args),
+			get_created_java_vms: mem::transmute::<*mut libc::c_void, GetCreatedJavaVMs>(created),
+		};
+		Ok(Box::leak(Box::new(library)))
+	}
+}
+
+
+
+//
+//  Platform Specific
+//
+
+#[cfg(unix)]
+mod os {
+	use std::ffi::{CString, CStr};
+	use std::os::unix::ffi::OsStrExt;
+	use std::path::Path;
+
+	use libc;
+	use {Error, Result};
+
+	/// Opens a shared library.
+	pub unsafe fn open(path: &Path) -> Result<*mut libc::c_void> {
+		let cpath = CString::new(path.as_os_str().as_bytes()).unwrap();
+		let handle = libc::dlopen(cpath.as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL);
+		if handle.is_null() {
+			Err(Error::JvmNotFound(format!(
+				"couldn't load `{}`: {}", path.display(), last_error())))
+		} else {
+			Ok(handle)
+		}
+	}
+
+	/// Resolves a function exported by a shared library.
+	pub unsafe fn symbol(handle: *mut libc::c_void, path: &Path, name: &str)
+			-> Result<*mut libc::c_void> {
+		let cname = CString::new(name).unwrap();
+		let symbol = libc::dlsym(handle, cname.as_ptr());
+		if symbol.is_null() {
+			Err(Error::JvmNotFound(format!(
+				"`{}` doesn't export `{}`", path.display(), name)))
+		} else {
+			Ok(symbol)
+		}
+	}
+
+	/// Returns the message for the most recent `dlopen` error.
+	unsafe fn last_error() -> String {
+		let message = libc::dlerror();
+		if message.is_null() {
+			String::from("unknown error")
+		} else {
+			CStr::from_ptr(message).to_string_lossy().into_owned()
+		}
+	}
+}
+
+#[cfg(windows)]
+mod os {
+	use std::ffi::CString;
+	use std::os::windows::ffi::OsStrExt;
+	use std::path::Path;
+
+	use libc;
+	use {Error, Result};
+
+	extern "system" {
+		fn LoadLibraryW(name: *const u16) -> *mut libc::c_void;
+		fn GetProcAddress(module: *mut libc::c_void, name: *const libc::c_char)
+			-> *mut libc::c_void;
+		fn GetLastError() -> u32;
+	}
+
+	/// Opens a DLL.
+	pub unsafe fn open(path: &Path) -> Result<*mut libc::c_void> {
+		let wide = path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<_>>();
+		let handle = LoadLibraryW(wide.as_ptr());
+		if handle.is_null() {
+			Err(Error::JvmNotFound(format!(
+				"couldn't load `{}`: error {}", path.display(), GetLastError())))
+		} else {
+			Ok(handle)
+		}
+	}
+
+	/// Resolves a function exported by a DLL.
+	pub unsafe fn symbol(handle: *mut libc::c_void, path: &Path, name: &str)
+			-> Result<*mut libc::c_void> {
+		let cname = CString::new(name).unwrap();
+		let symbol = GetProcAddress(handle, cname.as_ptr());
+		if symbol.is_null() {
+			Err(Error::JvmNotFound(format!(
+				"`{}` doesn't export `{}`", path.display(), name)))
+		} else {
+			Ok(symbol)
+		}
+	}
+}
@@ -0,0 +1,777 @@
+//
+//  Maven Resolution
+//
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs};
+
+use xml::{self, Element};
+use {Classpath, Error, Result};
+
+
+/// How many parents (or imported BOMs) deep a POM can be, to catch cycles.
+const MAX_DEPTH: usize = 32;
+
+impl Classpath {
+	/// Set the local Maven repository that `add_maven` reads artifacts from.
+	///
+	/// Otherwise the `localRepository` in `~/.m2/settings.xml` is used, or
+	/// `~/.m2/repository`.
+	pub fn maven_repository<T: AsRef<Path>>(mut self, dir: T) -> Classpath {
+		self.maven_repository = Some(dir.as_ref().to_owned());
+		self
+	}
+
+	/// Add a Maven artifact and its dependencies from the local repository,
+	/// given its coordinates (`group:artifact:version`, or
+	/// `group:artifact:type[:classifier]:version`).
+	///
+	/// The artifact's POM (and its parents and imported BOMs) is read to follow
+	/// its `compile` and `runtime` dependencies transitively, skipping optional
+	/// and excluded ones. Like Maven, when several versions of an artifact are
+	/// depended on, the one nearest the root (and then declared first) wins,
+	/// including across calls to `add_maven`.
+	///
+	/// The root artifact's dependency management (including the BOMs it
+	/// imports) applies to its whole dependency graph, overriding the versions
+	/// and scopes of transitive dependencies, as in Maven.
+	///
+	/// Nothing is downloaded, so an artifact (or a parent POM) that isn't in
+	/// the local repository returns an error, which says which artifact
+	/// depended on it.
+	pub fn add_maven(mut self, coordinates: &str) -> Result<Classpath> {
+		let root = Artifact::parse(coordinates)?;
+		let repository = match self.maven_repository {
+			Some(ref repository) => repository.clone(),
+			None => default_repository()?,
+		};
+		let mut resolver = Resolver {
+			repository: repository,
+			models: HashMap::new(),
+		};
+
+		// Breadth first, so the nearest version of each artifact is found first
+		let mut root_managed = Vec::new();
+		let mut queue = VecDeque::new();
+		queue.push_back(Node {
+			artifact: root,
+			exclusions: Vec::new(),
+			path: Vec::new(),
+		});
+		while let Some(node) = queue.pop_front() {
+			let key = node.artifact.key();
+			if self.maven_artifacts.contains(&key) {
+				continue;
+			}
+			self.maven_artifacts.push(key);
+
+			let model = resolver.model(&node.artifact.group, &node.artifact.artifact,
+				&node.artifact.version, &node)?;
+			let kind = match (node.path.is_empty(), model.as_ref()) {
+				// The type of the root defaults to its packaging (eg. `pom`)
+				(true, Some(model)) if !node.artifact.explicit_kind => {
+					packaging_type(&model.packaging)
+				},
+				_ => node.artifact.kind.clone(),
+			};
+			if let Some(file) = node.artifact.file(&resolver.repository, &kind) {
+				if !file.is_file() {
+					return Err(missing(&node, &format!("no `{}`", file.display())));
+				}
+				self.paths.push(file);
+			}
+
+			let model = match model {
+				Some(model) => model,
+				None => continue,
+			};
+			if node.path.is_empty() {
+				root_managed = model.managed.clone();
+			}
+			for dependency in &model.dependencies {
+				let mut dependency = dependency.clone();
+				if !node.path.is_empty() {
+					dependency.manage(&root_managed, true);
+				}
+
+				let scope = dependency.scope.as_deref().unwrap_or("compile");
+				if scope != "compile" && scope != "runtime" || dependency.optional {
+					continue;
+				}
+				if node.exclusions.iter().any(|exclusion| exclusion.matches(&dependency)) {
+					continue;
+				}
+				let version = match dependency.version {
+					Some(ref version) => version.clone(),
+					None => return Err(Error::Maven(format!(
+						"no version for {}:{} in the POM of {}", dependency.group,
+						dependency.artifact, node.artifact))),
+				};
+				if version.starts_with('[') || version.starts_with('(') {
+					return Err(Error::Maven(format!(
+						"version range `{}` for {}:{} in the POM of {} isn't supported",
+						version, dependency.group, dependency.artifact, node.artifact)));
+				}
+
+				let mut path = node.path.clone();
+				path.push(node.artifact.to_string());
+				let mut exclusions = node.exclusions.clone();
+				exclusions.extend(dependency.exclusions.iter().cloned());
+				queue.push_back(Node {
+					artifact: Artifact {
+						group: dependency.group.clone(),
+						artifact: dependency.artifact.clone(),
+						version: version,
+						kind: dependency.kind.clone(),
+						classifier: dependency.classifier.clone(),
+						explicit_kind: true,
+					},
+					exclusions: exclusions,
+					path: path,
+				});
+			}
+		}
+		Ok(self)
+	}
+}
+
+/// Returns the local repository configured in `~/.m2/settings.xml`, or
+/// `~/.m2/repository`.
+fn default_repository() -> Result<PathBuf> {
+	let home = match env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+		Some(home) => PathBuf::from(home),
+		None => return Err(Error::Maven(String::from(
+			"no home directory to find the local repository in"))),
+	};
+	let m2 = home.join(".m2");
+	let configured = fs::read_to_string(m2.join("settings.xml")).ok()
+		.and_then(|settings| xml::parse(&settings).ok())
+		.and_then(|settings| settings.child_text("localRepository").map(String::from))
+		.filter(|dir| !dir.is_empty());
+	Ok(match configured {
+		Some(dir) => PathBuf::from(dir.replace("${user.home}", &home.to_string_lossy())),
+		None => m2.join("repository"),
+	})
+}
+
+/// Returns the error for an artifact missing from the local repository.
+fn missing(node: &Node, reason: &str) -> Error {
+	let required_by = if node.path.is_empty() {
+		String::new()
+	} else {
+		format!(" (required by {})", node.path.join(" -> "))
+	};
+	Error::Maven(format!("{}{} isn't in the local repository: {}", node.artifact, required_by,
+		reason))
+}
+
+/// Returns the dependency type of an artifact with the given packaging.
+fn packaging_type(packaging: &str) -> String {
+	match packaging {
+		"bundle" | "maven-plugin" | "ejb" => String::from("jar"),
+		packaging => packaging.to_string(),
+	}
+}
+
+
+
+//
+//  Artifacts
+//
+
+/// An artifact to add to the classpath.
+#[derive(Debug, Clone)]
+struct Artifact {
+	group: String,
+	artifact: String,
+	version: String,
+
+	/// The dependency type (eg. `jar` or `pom`).
+	kind: String,
+	classifier: Option<String>,
+
+	/// Whether the type was given, rather than defaulting to `jar`.
+	explicit_kind: bool,
+}
+
+impl Artifact {
+	/// Parses `group:artifact[:type[:classifier]]:version`.
+	fn parse(coordinates: &str) -> Result<Artifact> {
+		let parts = coordinates.split(':').map(str::trim).collect::<Vec<_>>();
+		if parts.iter().any(|part| part.is_empty()) {
+			return Err(invalid_coordinates(coordinates));
+		}
+		let (kind, classifier) = match parts.len() {
+			3 => (None, None),
+			4 => (Some(parts[2]), None),
+			5 => (Some(parts[2]), Some(parts[3].to_string())),
+			_ => return Err(invalid_coordinates(coordinates)),
+		};
+		Ok(Artifact {
+			group: parts[0].to_string(),
+			artifact: parts[1].to_string(),
+			version: parts[parts.len() - 1].to_string(),
+			kind: kind.unwrap_or("jar").to_string(),
+			classifier: classifier,
+			explicit_kind: kind.is_some(),
+		})
+	}
+
+	/// Returns the key that identifies the artifact regardless of its version.
+	fn key(&self) -> String {
+		format!("{}:{}:{}:{}", self.group, self.artifact, self.kind,
+			self.classifier.as_deref().unwrap_or(""))
+	}
+
+	/// Returns the path to the artifact's file for a dependency type, or None
+	/// for a POM.
+	fn file(&self, repository: &Path, kind: &str) -> Option<PathBuf> {
+		let (extension, classifier) = match kind {
+			"pom" => return None,
+			"test-jar" => ("jar", Some("tests")),
+			"jar" | "bundle" | "maven-plugin" | "ejb" | "ejb-client" => {
+				("jar", self.classifier.as_deref())
+			},
+			extension => (extension, self.classifier.as_deref()),
+		};
+		let name = match classifier {
+			Some(classifier) => format!("{}-{}-{}.{}", self.artifact, self.version, classifier,
+				extension),
+			None => format!("{}-{}.{}", self.artifact, self.version, extension),
+		};
+		Some(version_dir(repository, &self.group, &self.artifact, &self.version).join(name))
+	}
+}
+
+impl fmt::Display for Artifact {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}", self.group, self.artifact)?;
+		if self.explicit_kind && self.kind != "jar" || self.classifier.is_some() {
+			write!(f, ":{}", self.kind)?;
+		}
+		if let Some(ref classifier) = self.classifier {
+			write!(f, ":{}", classifier)?;
+		}
+		write!(f, ":{}", self.version)
+	}
+}
+
+fn invalid_coordinates(coordinates: &str) -> Error {
+	Error::Maven(format!("`{}` isn't group:artifact[:type[:classifier]]:version", coordinates))
+}
+
+/// Returns the directory containing a version of an artifact.
+fn version_dir(repository: &Path, group: &str, artifact: &str, version: &str) -> PathBuf {
+	let mut dir = repository.to_path_buf();
+	dir.extend(group.split('.'));
+	dir.push(artifact);
+	dir.push(version);
+	dir
+}
+
+/// An artifact waiting to be added, and how it was reached.
+struct Node {
+	artifact: Artifact,
+
+	/// The exclusions of every dependency on the path to the artifact.
+	exclusions: Vec<Exclusion>,
+
+	/// The artifacts that led to this one, from the root.
+	path: Vec<String>,
+}
+
+
+
+//
+//  POMs
+//
+
+/// A dependency declared in a POM, or managed by its `dependencyManagement`.
+#[derive(Debug, Clone)]
+struct Dependency {
+	group: String,
+	artifact: String,
+	version: Option<String>,
+	kind: String,
+	classifier: Option<String>,
+	scope: Option<String>,
+	optional: bool,
+	exclusions: Vec<Exclusion>,
+}
+
+impl Dependency {
+	/// Reads a `<dependency>` element, interpolating properties.
+	fn parse(element: &Element, properties: &HashMap<String, String>) -> Dependency {
+		let text = |name: &str| {
+			element.child_text(name)
+				.map(|text| interpolate(text, properties))
+				.filter(|text| !text.is_empty())
+		};
+		let exclusions = element.child("exclusions").into_iter()
+			.flat_map(|exclusions| exclusions.children("exclusion"))
+			.map(|exclusion| Exclusion {
+				group: exclusion.child_text("groupId").unwrap_or("*").to_string(),
+				artifact: exclusion.child_text("artifactId").unwrap_or("*").to_string(),
+			})
+			.collect();
+		Dependency {
+			group: text("groupId").unwrap_or_default(),
+			artifact: text("artifactId").unwrap_or_default(),
+			version: text("version"),
+			kind: text("type").unwrap_or_else(|| String::from("jar")),
+			classifier: text("classifier"),
+			scope: text("scope"),
+			optional: text("optional").is_some_and(|optional| optional == "true"),
+			exclusions: exclusions,
+		}
+	}
+
+	/// Applies the first entry in a dependency management that manages this
+	/// dependency, which fills in its version and scope if they're missing, or
+	/// replaces them if `force` is set (for the root's management of
+	/// transitive dependencies).
+	fn manage(&mut self, managed: &[Dependency], force: bool) {
+		let managed = match managed.iter().find(|managed| managed.manages(self)) {
+			Some(managed) => managed,
+			None => return,
+		};
+		if force && managed.version.is_some() || self.version.is_none() {
+			self.version = managed.version.clone();
+		}
+		if force && managed.scope.is_some() || self.scope.is_none() {
+			self.scope = managed.scope.clone();
+		}
+		self.exclusions.extend(managed.exclusions.iter().cloned());
+	}
+
+	/// Returns true if this imports the dependency management of a BOM.
+	fn is_import(&self) -> bool {
+		self.kind == "pom" && self.scope.as_ref().is_some_and(|scope| scope == "import")
+	}
+
+	/// Returns true if this manages the same artifact as another dependency.
+	fn manages(&self, other: &Dependency) -> bool {
+		self.group == other.group && self.artifact == other.artifact &&
+			self.kind == other.kind && self.classifier == other.classifier
+	}
+}
+
+/// An artifact excluded from a dependency's transitive dependencies.
+#[derive(Debug, Clone)]
+struct Exclusion {
+	group: String,
+	artifact: String,
+}
+
+impl Exclusion {
+	fn matches(&self, dependency: &Dependency) -> bool {
+		(self.group == "*" || self.group == dependency.group) &&
+			(self.artifact == "*" || self.artifact == dependency.artifact)
+	}
+}
+
+/// The parts of a POM needed for resolution, after inheriting from its
+/// parents.
+#[derive(Debug, Clone)]
+struct Model {
+	packaging: String,
+	dependencies: Vec<Dependency>,
+
+	/// The dependency management, including that of imported BOMs.
+	managed: Vec<Dependency>,
+}
+
+/// A POM and its parents, before properties are interpolated.
+struct RawModel {
+	group: String,
+	version: String,
+	packaging: String,
+	properties: HashMap<String, String>,
+
+	/// The `<dependency>` elements, from the furthest parent to the POM.
+	dependencies: Vec<Element>,
+
+	/// The managed `<dependency>` elements, from the POM to its furthest
+	/// parent.
+	managed: Vec<Element>,
+}
+
+/// Reads and caches POMs from a local repository.
+struct Resolver {
+	repository: PathBuf,
+	models: HashMap<String, Option<Model>>,
+}
+
+impl Resolver {
+	/// Returns the model of an artifact's POM, or None if it isn't in the
+	/// repository (in which case, like Maven, it has no dependencies).
+	fn model(&mut self, group: &str, artifact: &str, version: &str, node: &Node)
+			-> Result<Option<Model>> {
+		let key = format!("{}:{}:{}", group, artifact, version);
+		if let Some(model) = self.models.get(&key) {
+			return Ok(model.clone());
+		}
+		let model = self.effective(group, artifact, version, node, 0)?;
+		self.models.insert(key, model.clone());
+		Ok(model)
+	}
+
+	/// Builds a POM's model, by interpolating its properties and applying its
+	/// dependency management.
+	fn effective(&mut self, group: &str, artifact: &str, version: &str, node: &Node,
+	             depth: usize) -> Result<Option<Model>> {
+		let raw = match self.raw(group, artifact, version, node, depth)? {
+			Some(raw) => raw,
+			None => return Ok(None),
+		};
+
+		let managed = self.managed(&raw, node, depth)?;
+
+		// Later declarations of the same dependency replace earlier ones
+		let mut dependencies: Vec<Dependency> = Vec::new();
+		for element in &raw.dependencies {
+			let mut dependency = Dependency::parse(element, &raw.properties);
+			dependency.manage(&managed, false);
+			dependencies.retain(|existing| !dependency.manages(existing));
+			dependencies.push(dependency);
+		}
+
+		Ok(Some(Model {
+			packaging: raw.packaging,
+			dependencies: dependencies,
+			managed: managed,
+		}))
+	}
+
+	/// Returns a POM's dependency management, with the entries of the BOMs it
+	/// imports after its own.
+	fn managed(&mut self, raw: &RawModel, node: &Node, depth: usize) -> Result<Vec<Dependency>> {
+		let (imports, mut managed): (Vec<_>, Vec<_>) = raw.managed.iter()
+			.map(|element| Dependency::parse(element, &raw.properties))
+			.partition(Dependency::is_import);
+		for dependency in imports {
+			let version = dependency.version.clone().unwrap_or_default();
+			match self.raw(&dependency.group, &dependency.artifact, &version, node, depth + 1)? {
+				Some(bom) => managed.extend(self.managed(&bom, node, depth + 1)?),
+				None => return Err(missing(node, &format!(
+					"the BOM {}:{}:{} it imports isn't in the local repository",
+					dependency.group, dependency.artifact, version))),
+			}
+		}
+		Ok(managed)
+	}
+
+	/// Reads a POM and inherits from its parents, or returns None if it isn't
+	/// in the repository.
+	fn raw(&mut self, group: &str, artifact: &str, version: &str, node: &Node, depth: usize)
+			-> Result<Option<RawModel>> {
+		if depth > MAX_DEPTH {
+			return Err(Error::Maven(format!(
+				"{}:{}:{} has too many parents or imported BOMs, which might be a cycle",
+				group, artifact, version)));
+		}
+		let path = version_dir(&self.repository, group, artifact, version)
+			.join(format!("{}-{}.pom", artifact, version));
+		let text = match fs::read_to_string(&path) {
+			Ok(text) => text,
+			Err(_) => return Ok(None),
+		};
+		let project = match xml::parse(&text) {
+			Ok(project) => project,
+			Err(err) => return Err(Error::Maven(format!(
+				"couldn't parse `{}`: {}", path.display(), err))),
+		};
+
+		// Inherit from the parent, which must be in the repository
+		let parent = match project.child("parent") {
+			Some(parent) => {
+				let text = |name| parent.child_text(name).unwrap_or_default().to_string();
+				let (group, artifact, version) =
+					(text("groupId"), text("artifactId"), text("version"));
+				match self.raw(&group, &artifact, &version, node, depth + 1)? {
+					Some(raw) => Some(raw),
+					None => return Err(missing(node, &format!(
+						"its parent POM {}:{}:{} isn't in the local repository", group,
+						artifact, version))),
+				}
+			},
+			None => None,
+		};
+
+		let own = |name: &str| project.child_text(name).map(String::from);
+		let group = own("groupId")
+			.or_else(|| parent.as_ref().map(|parent| parent.group.clone()))
+			.unwrap_or_else(|| group.to_string());
+		let version = own("version")
+			.or_else(|| parent.as_ref().map(|parent| parent.version.clone()))
+			.unwrap_or_else(|| version.to_string());
+		let packaging = own("packaging").unwrap_or_else(|| String::from("jar"));
+
+		let mut properties = HashMap::new();
+		let mut dependencies = Vec::new();
+		let mut managed = Vec::new();
+		if let Some(parent) = parent {
+			properties = parent.properties;
+			properties.insert(String::from("project.parent.groupId"), parent.group.clone());
+			properties.insert(String::from("project.parent.version"), parent.version.clone());
+			properties.insert(String::from("parent.groupId"), parent.group);
+			properties.insert(String::from("parent.version"), parent.version);
+			dependencies = parent.dependencies;
+			managed = parent.managed;
+		}
+		let own_properties = project.child("properties").into_iter()
+			.flat_map(|properties| &properties.children);
+		for element in own_properties {
+			properties.insert(element.name.clone(), element.text.trim().to_string());
+		}
+		for prefix in &["project.", "pom.", ""] {
+			properties.insert(format!("{}groupId", prefix), group.clone());
+			properties.insert(format!("{}artifactId", prefix), artifact.to_string());
+			properties.insert(format!("{}version", prefix), version.clone());
+		}
+
+		dependencies.extend(project.child("dependencies").iter()
+			.flat_map(|dependencies| dependencies.children("dependency"))
+			.cloned());
+		let own_managed = project.child("dependencyManagement")
+			.and_then(|management| management.child("dependencies"))
+			.iter()
+			.flat_map(|dependencies| dependencies.children("dependency"))
+			.cloned()
+			.collect::<Vec<_>>();
+		managed.splice(0..0, own_managed);
+
+		Ok(Some(RawModel {
+			group: group,
+			version: version,
+			packaging: packaging,
+			properties: properties,
+			dependencies: dependencies,
+			managed: managed,
+		}))
+	}
+}
+
+/// Replaces each `${name}` in text with the property's value (or for `env.`
+/// properties, the environment variable's), leaving unknown properties as
+/// they are.
+fn interpolate(text: &str, properties: &HashMap<String, String>) -> String {
+	let mut result = text.to_string();
+
+	// Properties can refer to others, so keep replacing while anything changes
+	for _ in 0..MAX_DEPTH {
+		let mut replaced = String::with_capacity(result.len());
+		let mut rest = result.as_str();
+		let mut changed = false;
+		while let Some(start) = rest.find("${") {
+			replaced.push_str(&rest[..start]);
+			rest = &rest[start..];
+			let end = match rest.find('}') {
+				Some(end) => end,
+				None => break,
+			};
+			let name = &rest[2..end];
+			let value = match name.strip_prefix("env.") {
+				Some(var) => env::var(var).ok(),
+				None => properties.get(name).cloned(),
+			};
+			match value {
+				Some(value) => {
+					replaced.push_str(&value);
+					changed = true;
+				},
+				None => replaced.push_str(&rest[..end + 1]),
+			}
+			rest = &rest[end + 1..];
+		}
+		replaced.push_str(rest);
+		if !changed {
+			return replaced;
+		}
+		result = replaced;
+	}
+	result
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::process;
+
+	/// A local repository in a temporary directory, removed once the test
+	/// finishes.
+	struct Repository(PathBuf);
+
+	impl Repository {
+		fn new(name: &str) -> Repository {
+			let path = env::temp_dir().join(format!("rjni-maven-{}-{}", process::id(), name));
+			let _ = fs::remove_dir_all(&path);
+			fs::create_dir_all(&path).unwrap();
+			Repository(path)
+		}
+
+		/// Writes an artifact's POM with the given contents inside `<project>`,
+		/// and an empty Jar file for it.
+		fn artifact(&self, coordinates: &str, body: &str) {
+			let parts = coordinates.split(':').collect::<Vec<_>>();
+			let dir = version_dir(&self.0, parts[0], parts[1], parts[2]);
+			fs::create_dir_all(&dir).unwrap();
+			let pom = format!("<project><groupId>{}</groupId><artifactId>{}</artifactId><version>{}</version>{}</project>",
+				parts[0], parts[1], parts[2], body);
+			fs::write(dir.join(format!("{}-{}.pom", parts[1], parts[2])), pom).unwrap();
+			fs::write(dir.join(format!("{}-{}.jar", parts[1], parts[2])), "").unwrap();
+		}
+
+		/// Adds an artifact, returning the names of the files on the classpath.
+		fn resolve(&self, coordinates: &str) -> Result<Vec<String>> {
+			let classpath = Classpath::new().maven_repository(&self.0).add_maven(coordinates)?;
+			Ok(names(&classpath))
+		}
+	}
+
+	impl Drop for Repository {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn names(classpath: &Classpath) -> Vec<String> {
+		classpath.paths.iter()
+			.map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+			.collect()
+	}
+
+	/// Returns a `<dependencies>` element for the given coordinates, which
+	/// can be followed by extra elements inside each `<dependency>`.
+	fn dependencies(dependencies: &[&str]) -> String {
+		let mut xml = String::from("<dependencies>");
+		for dependency in dependencies {
+			let mut parts = dependency.splitn(4, ':');
+			let (group, artifact) = (parts.next().unwrap(), parts.next().unwrap());
+			xml.push_str(&format!("<dependency><groupId>{}</groupId><artifactId>{}</artifactId>",
+				group, artifact));
+			if let Some(version) = parts.next().filter(|version| !version.is_empty()) {
+				xml.push_str(&format!("<version>{}</version>", version));
+			}
+			xml.push_str(parts.next().unwrap_or(""));
+			xml.push_str("</dependency>");
+		}
+		xml.push_str("</dependencies>");
+		xml
+	}
+
+	fn managed(entries: &[&str]) -> String {
+		format!("<dependencyManagement>{}</dependencyManagement>", dependencies(entries))
+	}
+
+	#[test]
+	fn inherits_from_parents() {
+		let repo = Repository::new("parents");
+		repo.artifact("com.me:parent:1", &format!("<packaging>pom</packaging><properties><lib.version>2</lib.version></properties>{}{}",
+			dependencies(&["com.lib:base:${lib.version}"]), managed(&["com.lib:managed:3"])));
+		let dir = version_dir(&repo.0, "com.me", "app", "1");
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("app-1.pom"), format!(
+			"<project><parent><groupId>com.me</groupId><artifactId>parent</artifactId><version>1</version></parent><artifactId>app</artifactId>{}</project>",
+			dependencies(&["com.lib:managed"]))).unwrap();
+		fs::write(dir.join("app-1.jar"), "").unwrap();
+		repo.artifact("com.lib:base:2", "");
+		repo.artifact("com.lib:managed:3", "");
+
+		assert_eq!(repo.resolve("com.me:app:1").unwrap(), ["app-1.jar", "base-2.jar", "managed-3.jar"]);
+
+		// The root's type defaults to its packaging
+		assert_eq!(repo.resolve("com.me:parent:1").unwrap(), ["base-2.jar"]);
+	}
+
+	#[test]
+	fn interpolates_properties() {
+		let repo = Repository::new("properties");
+		repo.artifact("com.me:app:1", &format!("<properties><a>${{b}}</a><b>4</b></properties>{}",
+			dependencies(&["${project.groupId}:lib:${a}", "com.me:other:${project.version}"])));
+		repo.artifact("com.me:lib:4", "");
+		repo.artifact("com.me:other:1", "");
+		assert_eq!(repo.resolve("com.me:app:1").unwrap(), ["app-1.jar", "lib-4.jar", "other-1.jar"]);
+
+		let mut properties = HashMap::new();
+		properties.insert(String::from("a"), String::from("1"));
+		assert_eq!(interpolate("${a}.${unknown}", &properties), "1.${unknown}");
+	}
+
+	#[test]
+	fn imports_boms() {
+		let repo = Repository::new("boms");
+		repo.artifact("com.me:bom:1", &format!("<packaging>pom</packaging>{}",
+			managed(&["com.lib:a:5", "com.lib:b:6"])));
+		repo.artifact("com.me:app:1", &format!("{}{}",
+			managed(&["com.me:bom:1:<type>pom</type><scope>import</scope>", "com.lib:b:7"]),
+			dependencies(&["com.lib:a", "com.lib:b"])));
+		repo.artifact("com.lib:a:5", "");
+		repo.artifact("com.lib:b:7", "");
+
+		// The POM's own management comes before what it imports
+		assert_eq!(repo.resolve("com.me:app:1").unwrap(), ["app-1.jar", "a-5.jar", "b-7.jar"]);
+	}
+
+	#[test]
+	fn skips_exclusions_and_scopes() {
+		let repo = Repository::new("exclusions");
+		repo.artifact("com.me:app:1", &dependencies(&[
+			"com.lib:a:1:<exclusions><exclusion><groupId>com.lib</groupId><artifactId>c</artifactId></exclusion></exclusions>",
+			"com.lib:test:1:<scope>test</scope>",
+			"com.lib:optional:1:<optional>true</optional>",
+		]));
+		repo.artifact("com.lib:a:1", &dependencies(&["com.lib:b:1", "com.lib:provided:1:<scope>provided</scope>"]));
+		repo.artifact("com.lib:b:1", &dependencies(&["com.lib:c:1", "com.lib:d:1:<scope>runtime</scope>"]));
+		repo.artifact("com.lib:d:1", "");
+		assert_eq!(repo.resolve("com.me:app:1").unwrap(), ["app-1.jar", "a-1.jar", "b-1.jar", "d-1.jar"]);
+
+		// Wildcards exclude every transitive dependency
+		repo.artifact("com.me:wild:1", &dependencies(&[
+			"com.lib:a:1:<exclusions><exclusion><groupId>*</groupId><artifactId>*</artifactId></exclusion></exclusions>",
+		]));
+		assert_eq!(repo.resolve("com.me:wild:1").unwrap(), ["wild-1.jar", "a-1.jar"]);
+	}
+
+	#[test]
+	fn nearest_version_wins() {
+		let repo = Repository::new("nearest");
+		repo.artifact("com.me:app:1", &dependencies(&["com.lib:a:1", "com.lib:c:2"]));
+		repo.artifact("com.lib:a:1", &dependencies(&["com.lib:c:1"]));
+		repo.artifact("com.lib:c:1", "");
+		repo.artifact("com.lib:c:2", "");
+		repo.artifact("com.lib:c:3", "");
+		assert_eq!(repo.resolve("com.me:app:1").unwrap(), ["app-1.jar", "a-1.jar", "c-2.jar"]);
+
+		// Including across calls
+		let classpath = Classpath::new().maven_repository(&repo.0)
+			.add_maven("com.me:app:1").unwrap()
+			.add_maven("com.lib:c:3").unwrap();
+		assert_eq!(names(&classpath), ["app-1.jar", "a-1.jar", "c-2.jar"]);
+	}
+
+	#[test]
+	fn root_management_applies_transitively() {
+		let repo = Repository::new("management");
+		repo.artifact("com.me:app:1", &format!("{}{}",
+			managed(&["com.lib:c:5", "com.lib:d:1:<scope>test</scope>"]),
+			dependencies(&["com.lib:a:1"])));
+		repo.artifact("com.lib:a:1", &dependencies(&["com.lib:c:1", "com.lib:d:1"]));
+		repo.artifact("com.lib:c:5", "");
+		assert_eq!(repo.resolve("com.me:app:1").unwrap(), ["app-1.jar", "a-1.jar", "c-5.jar"]);
+	}
+
+	#[test]
+	fn reports_missing_artifacts() {
+		let repo = Repository::new("missing");
+		repo.artifact("com.me:app:1", &dependencies(&["com.lib:a:1"]));
+		repo.artifact("com.lib:a:1", &dependencies(&["com.lib:gone:1"]));
+		let err = repo.resolve("com.me:app:1").unwrap_err().to_string();
+		assert!(err.contains("com.lib:gone:1 (required by com.me:app:1 -> com.lib:a:1)"), "{}", err);
+
+		assert!(repo.resolve("com.me:app").is_err());
+	}
+}
@@ -0,0 +1,324 @@
+//
+//  Native Classes
+//
+
+use std::ffi::CString;
+use std::{panic, ptr};
+use std::sync::Mutex;
+
+use ffi;
+use {JavaVM, Class, Object, Field, GlobalRef, Type, Value};
+use {Error, Result};
+
+
+/// A class defined at runtime, whose methods are implemented by Rust
+/// functions.
+///
+/// Each instance holds a pointer to a Rust value in its `long handle` field,
+/// which its native methods operate on.
+pub struct NativeClass {
+	name: &'static str,
+	superclass: &'static str,
+	interfaces: &'static [&'static str],
+	natives: &'static [Native],
+	state: Mutex<Option<DefinedClass>>,
+}
+
+/// A native method, and the function implementing it.
+pub struct Native {
+	pub name: &'static str,
+	pub signature: &'static str,
+	pub function: *mut ffi::jvoid,
+}
+
+unsafe impl Sync for Native {}
+
+/// A native class that has been defined in the JVM.
+struct DefinedClass {
+	class: GlobalRef,
+
+	/// The field holding a pointer to the Rust value.
+	handle: Field,
+}
+
+impl NativeClass {
+	/// Create a native class with the given name, which extends the given
+	/// superclass and implements the given interfaces.
+	pub const fn new(name: &'static str, superclass: &'static str,
+	                 interfaces: &'static [&'static str], natives: &'static [Native])
+			-> NativeClass {
+		NativeClass {
+			name: name,
+			superclass: superclass,
+			interfaces: interfaces,
+			natives: natives,
+			state: Mutex::new(None),
+		}
+	}
+
+	/// Returns the class and its `handle` field, defining the class and
+	/// registering its native methods the first time it's used.
+	fn get<'a>(&self, jvm: &'a JavaVM) -> Result<(Class<'a>, Field)> {
+		let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+		if let Some(ref defined) = *state {
+			return Ok((defined.class.as_class(jvm), defined.handle.clone()));
+		}
+
+		// Define the class with the bootstrap class loader
+		let env = jvm.env;
+		let bytes = class_file(self.name, self.superclass, self.interfaces, self.natives);
+		let name = CString::new(self.name).unwrap();
+		let raw = unsafe {
+			((**env).DefineClass)(env, name.as_ptr(), ptr::null_mut(),
+				bytes.as_ptr() as *const ffi::jbyte, bytes.len() as ffi::jsize)
+		};
+		if raw.is_null() {
+			return Err(Error::from_exception(jvm));
+		}
+		let class = Class {
+			jvm: jvm,
+			raw: raw,
+		};
+
+		// Bind each native method to its Rust function
+		let names: Vec<_> = self.natives.iter()
+			.map(|native| (CString::new(native.name).unwrap(),
+				CString::new(native.signature).unwrap()))
+			.collect();
+		let methods: Vec<_> = self.natives.iter().zip(&names)
+			.map(|(native, &(ref name, ref signature))| ffi::JNINativeMethod {
+				name: name.as_ptr() as *mut _,
+				signature: signature.as_ptr() as *mut _,
+				fnPtr: native.function,
+			})
+			.collect();
+		let code = unsafe {
+			((**env).RegisterNatives)(env, raw, methods.as_ptr(), methods.len() as ffi::jint)
+		};
+		if code != ffi::JNIError::JNI_OK {
+			return Err(Error::from_exception(jvm));
+		}
+
+		let handle = class.field("handle", Type::Long)?;
+		*state = Some(DefinedClass {
+			class: class.global(),
+			handle: handle.clone(),
+		});
+		Ok((class, handle))
+	}
+
+	/// Creates an instance of this class holding the given Rust value.
+	pub fn instantiate<'a, T>(&self, jvm: &'a JavaVM, value: T) -> Result<Object<'a>> {
+		let (class, field) = self.get(jvm)?;
+		let object = class.instantiate(&[])?;
+		let handle = Box::into_raw(Box::new(value)) as ffi::jlong;
+		if let Err(err) = field.set(&object, Value::Long(handle)) {
+			unsafe { drop(Box::from_raw(handle as *mut T)) };
+			return Err(err);
+		}
+		Ok(object)
+	}
+
+	/// Returns the Rust value held by an instance of this class, or null if
+	/// it has been taken.
+	///
+	/// The value must be of the type the instance was created with.
+	pub unsafe fn value<T>(&self, env: *mut ffi::JNIEnv, this: ffi::jobject) -> *mut T {
+		match self.handle() {
+			Some(handle) => ((**env).GetLongField)(env, this, handle) as *mut T,
+			None => ptr::null_mut(),
+		}
+	}
+
+	/// Takes the Rust value held by an instance of this class, leaving it
+	/// empty.
+	///
+	/// The value must be of the type the instance was created with.
+	pub unsafe fn take<T>(&self, env: *mut ffi::JNIEnv, this: ffi::jobject)
+			-> Option<Box<T>> {
+		let value = self.value::<T>(env, this);
+		if value.is_null() {
+			return None;
+		}
+		((**env).SetLongField)(env, this, self.handle()?, 0);
+		Some(Box::from_raw(value))
+	}
+
+	/// Drops the Rust value held by an instance of this class, if it hasn't
+	/// been taken.
+	///
+	/// The instance's monitor is held while the value is taken, so it can't be
+	/// in use by one of the (`synchronized`) native methods. Panics while
+	/// dropping the value are caught, since this may be called from Java.
+	pub unsafe fn release<T>(&self, env: *mut ffi::JNIEnv, this: ffi::jobject) {
+		if ((**env).MonitorEnter)(env, this) != ffi::JNIError::JNI_OK {
+			return;
+		}
+		let value = self.take::<T>(env, this);
+		((**env).MonitorExit)(env, this);
+		let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| drop(value)));
+	}
+
+	/// Returns the ID of the `handle` field, if the class has been defined.
+	fn handle(&self) -> Option<ffi::jfieldID> {
+		let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+		state.as_ref().map(|defined| defined.handle.id)
+	}
+}
+
+/// Throws a new exception of the given class from a native method.
+pub unsafe fn throw(env: *mut ffi::JNIEnv, class: &str, message: &str) {
+	let class = CString::new(class).unwrap();
+	let message = CString::new(message.replace('\0', "")).unwrap();
+	let class = ((**env).FindClass)(env, class.as_ptr());
+	if !class.is_null() {
+		((**env).ThrowNew)(env, class, message.as_ptr());
+	}
+}
+
+
+
+//
+//  Class Files
+//
+
+/// The class file version native classes are defined with (Java 8).
+const CLASS_VERSION: u16 = 52;
+
+const ACC_PUBLIC: u16 = 0x0001;
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_FINAL: u16 = 0x0010;
+const ACC_SUPER: u16 = 0x0020;
+const ACC_SYNCHRONIZED: u16 = 0x0020;
+const ACC_NATIVE: u16 = 0x0100;
+
+const CONSTANT_UTF8: u8 = 1;
+const CONSTANT_CLASS: u8 = 7;
+const CONSTANT_METHODREF: u8 = 10;
+const CONSTANT_NAME_AND_TYPE: u8 = 12;
+
+/// Builds the class file for a final subclass of the given superclass, which
+/// implements the given interfaces, with a `long handle` field, a private
+/// no-argument constructor, and the given `synchronized` native methods.
+fn class_file(name: &str, superclass: &str, interfaces: &[&str], natives: &[Native])
+		-> Vec<u8> {
+	let mut pool = ConstantPool::default();
+	let this_class = pool.class(name);
+	let super_class = pool.class(superclass);
+	let interfaces: Vec<_> = interfaces.iter()
+		.map(|interface| pool.class(interface))
+		.collect();
+	let init = pool.utf8("<init>");
+	let no_args = pool.utf8("()V");
+	let super_init = pool.method_ref(super_class, init, no_args);
+	let code = pool.utf8("Code");
+	let handle = pool.utf8("handle");
+	let long = pool.utf8("J");
+	let methods: Vec<_> = natives.iter()
+		.map(|native| (pool.utf8(native.name), pool.utf8(native.signature)))
+		.collect();
+
+	let mut out = Vec::new();
+	out.extend_from_slice(&0xCAFEBABE_u32.to_be_bytes());
+	push_u16(&mut out, 0);
+	push_u16(&mut out, CLASS_VERSION);
+	push_u16(&mut out, pool.count);
+	out.extend_from_slice(&pool.bytes);
+	push_u16(&mut out, ACC_PUBLIC | ACC_FINAL | ACC_SUPER);
+	push_u16(&mut out, this_class);
+	push_u16(&mut out, super_class);
+	push_u16(&mut out, interfaces.len() as u16);
+	for &interface in &interfaces {
+		push_u16(&mut out, interface);
+	}
+
+	// The `handle` field
+	push_u16(&mut out, 1);
+	for &value in &[ACC_PRIVATE, handle, long, 0] {
+		push_u16(&mut out, value);
+	}
+
+	// The constructor, which calls the superclass' constructor
+	push_u16(&mut out, 1 + methods.len() as u16);
+	for &value in &[ACC_PRIVATE, init, no_args, 1, code] {
+		push_u16(&mut out, value);
+	}
+	let [index_high, index_low] = super_init.to_be_bytes();
+	let bytecode = [
+		0x2a, // aload_0
+		0xb7, index_high, index_low, // invokespecial
+		0xb1, // return
+	];
+	out.extend_from_slice(&(12 + bytecode.len() as u32).to_be_bytes());
+	push_u16(&mut out, 1); // max_stack
+	push_u16(&mut out, 1); // max_locals
+	out.extend_from_slice(&(bytecode.len() as u32).to_be_bytes());
+	out.extend_from_slice(&bytecode);
+	push_u16(&mut out, 0); // exception_table_length
+	push_u16(&mut out, 0); // attributes_count
+
+	// The native methods, which have no code
+	for &(name, signature) in &methods {
+		for &value in &[ACC_PUBLIC | ACC_SYNCHRONIZED | ACC_NATIVE, name, signature, 0] {
+			push_u16(&mut out, value);
+		}
+	}
+
+	push_u16(&mut out, 0);
+	out
+}
+
+/// A class file's constant pool, whose entries are numbered from 1.
+struct ConstantPool {
+	bytes: Vec<u8>,
+	count: u16,
+}
+
+impl Default for ConstantPool {
+	fn default() -> ConstantPool {
+		ConstantPool {
+			bytes: Vec::new(),
+			count: 1,
+		}
+	}
+}
+
+impl ConstantPool {
+	/// Adds an entry, returning its index.
+	fn push(&mut self, tag: u8, contents: &[u8]) -> u16 {
+		self.bytes.push(tag);
+		self.bytes.extend_from_slice(contents);
+		self.count += 1;
+		self.count - 1
+	}
+
+	/// Adds a string, which must be ASCII so its modified UTF-8 encoding is
+	/// the same as its UTF-8 one.
+	fn utf8(&mut self, value: &str) -> u16 {
+		let mut contents = Vec::new();
+		push_u16(&mut contents, value.len() as u16);
+		contents.extend_from_slice(value.as_bytes());
+		self.push(CONSTANT_UTF8, &contents)
+	}
+
+	fn class(&mut self, name: &str) -> u16 {
+		let name = self.utf8(name);
+		self.push(CONSTANT_CLASS, &name.to_be_bytes())
+	}
+
+	fn method_ref(&mut self, class: u16, name: u16, descriptor: u16) -> u16 {
+		let mut name_and_type = Vec::new();
+		push_u16(&mut name_and_type, name);
+		push_u16(&mut name_and_type, descriptor);
+		let name_and_type = self.push(CONSTANT_NAME_AND_TYPE, &name_and_type);
+
+		let mut method_ref = Vec::new();
+		push_u16(&mut method_ref, class);
+		push_u16(&mut method_ref, name_and_type);
+		self.push(CONSTANT_METHODREF, &method_ref)
+	}
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+	out.extend_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,741 @@
+
+//
+//  Type Signatures
+//
+
+use std::{error, fmt};
+use std::str::FromStr;
+
+use {JavaVM, Value, Type, Object, Error, Result, with_local_frame};
+
+
+/// The class names that a Java string can be passed as, other than
+/// `java/lang/String` itself.
+const STRING_SUPERTYPES: [&str; 6] = [
+	"java/lang/Object",
+	"java/lang/CharSequence",
+	"java/lang/Comparable",
+	"java/io/Serializable",
+	"java/lang/constant/Constable",
+	"java/lang/constant/ConstantDesc",
+];
+
+/// The most dimensions an array type can have, as limited by the JVM.
+const MAX_DIMENSIONS: usize = 255;
+
+
+/// A Java type, as described by a JNI field descriptor (eg. `I` or
+/// `[Ljava/lang/String;`).
+///
+/// Class names are stored in their internal form, using `/` to separate
+/// packages (eg. `java/lang/String`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JavaType {
+	Boolean,
+	Byte,
+	Char,
+	Short,
+	Int,
+	Long,
+	Float,
+	Double,
+
+	/// Only valid as the return type of a method.
+	Void,
+
+	/// An instance of the class with the given internal name.
+	Object(String),
+
+	/// An array of the given element type.
+	Array(Box<JavaType>),
+}
+
+impl JavaType {
+	/// Parse a type from its JNI field descriptor.
+	pub fn parse(descriptor: &str) -> Result<JavaType> {
+		let mut parser = Parser::new(descriptor);
+		let kind = parser.parse_type(false)?;
+		parser.finish()?;
+		Ok(kind)
+	}
+
+	/// Returns the type of the class with the given binary name, as returned
+	/// by `Class.getName` (eg. `java.lang.String`, `int`, or `[I`).
+	pub fn from_class_name(name: &str) -> Result<JavaType> {
+		match name {
+			"boolean" => Ok(JavaType::Boolean),
+			"byte" => Ok(JavaType::Byte),
+			"char" => Ok(JavaType::Char),
+			"short" => Ok(JavaType::Short),
+			"int" => Ok(JavaType::Int),
+			"long" => Ok(JavaType::Long),
+			"float" => Ok(JavaType::Float),
+			"double" => Ok(JavaType::Double),
+			"void" => Ok(JavaType::Void),
+			// The names of array classes are their descriptors, but with
+			// binary class names
+			_ if name.starts_with('[') => JavaType::parse(&internal_name(name)),
+			_ => JavaType::parse(&format!("L{};", internal_name(name))),
+		}
+	}
+
+	/// Create an array type with the given number of dimensions.
+	pub fn array(element: JavaType, dimensions: usize) -> JavaType {
+		(0..dimensions).fold(element, |kind, _| JavaType::Array(Box::new(kind)))
+	}
+
+	/// Returns the number of array dimensions of this type, which is 0 for
+	/// non-array types.
+	pub fn dimensions(&self) -> usize {
+		match self {
+			&JavaType::Array(ref element) => 1 + element.dimensions(),
+			_ => 0,
+		}
+	}
+
+	/// Returns the innermost element type of an array, or the type itself if
+	/// it isn't an array.
+	pub fn base_type<'a>(&'a self) -> &'a JavaType {
+		match self {
+			&JavaType::Array(ref element) => element.base_type(),
+			_ => self,
+		}
+	}
+
+	/// Returns true if this is a primitive type (including void).
+	pub fn is_primitive(&self) -> bool {
+		!matches!(self, &JavaType::Object(_) | &JavaType::Array(_))
+	}
+
+	/// Returns the JNI descriptor for this type.
+	pub fn descriptor(&self) -> String {
+		let mut result = String::new();
+		self.write_descriptor(&mut result);
+		result
+	}
+
+	/// Returns the binary name of this type, as returned by `Class.getName`
+	/// (eg. `java.lang.String`, `int`, or `[I`).
+	pub fn class_name(&self) -> String {
+		match self {
+			&JavaType::Boolean => String::from("boolean"),
+			&JavaType::Byte => String::from("byte"),
+			&JavaType::Char => String::from("char"),
+			&JavaType::Short => String::from("short"),
+			&JavaType::Int => String::from("int"),
+			&JavaType::Long => String::from("long"),
+			&JavaType::Float => String::from("float"),
+			&JavaType::Double => String::from("double"),
+			&JavaType::Void => String::from("void"),
+			&JavaType::Object(ref name) => binary_name(name),
+			&JavaType::Array(_) => binary_name(&self.descriptor()),
+		}
+	}
+
+	/// Pushes the JNI descriptor for this type onto the given string.
+	fn write_descriptor(&self, result: &mut String) {
+		match self {
+			&JavaType::Boolean => result.push('Z'),
+			&JavaType::Byte => result.push('B'),
+			&JavaType::Char => result.push('C'),
+			&JavaType::Short => result.push('S'),
+			&JavaType::Int => result.push('I'),
+			&JavaType::Long => result.push('J'),
+			&JavaType::Float => result.push('F'),
+			&JavaType::Double => result.push('D'),
+			&JavaType::Void => result.push('V'),
+			&JavaType::Object(ref name) => {
+				result.push('L');
+				result.push_str(name);
+				result.push(';');
+			},
+			&JavaType::Array(ref element) => {
+				result.push('[');
+				element.write_descriptor(result);
+			},
+		}
+	}
+
+	/// Returns the kind of value used to represent this type.
+	///
+	/// Only the kind of a type matters when calling a method with an already
+	/// resolved ID, so every object other than a string maps to a plain
+	/// `java/lang/Object`.
+	pub(crate) fn value_type(&self) -> Type {
+		match self {
+			&JavaType::Boolean => Type::Boolean,
+			&JavaType::Byte => Type::Byte,
+			&JavaType::Char => Type::Char,
+			&JavaType::Short => Type::Short,
+			&JavaType::Int => Type::Int,
+			&JavaType::Long => Type::Long,
+			&JavaType::Float => Type::Float,
+			&JavaType::Double => Type::Double,
+			&JavaType::Void => Type::Void,
+			&JavaType::Object(ref name) if name == "java/lang/String" => Type::Str,
+			_ => Type::Object("java/lang/Object"),
+		}
+	}
+
+	/// Returns true if the given value's kind can be passed as a parameter of
+	/// this type.
+	///
+	/// Primitive values must match exactly. Objects are accepted by any
+	/// reference type, and their class is checked by `accepts_class`. Strings
+	/// are accepted by `java/lang/String` and its supertypes.
+	pub(crate) fn accepts(&self, value: &Value) -> bool {
+		match (self, value) {
+			(&JavaType::Boolean, &Value::Boolean(_)) => true,
+			(&JavaType::Byte, &Value::Byte(_)) => true,
+			(&JavaType::Char, &Value::Char(_)) => true,
+			(&JavaType::Short, &Value::Short(_)) => true,
+			(&JavaType::Int, &Value::Int(_)) => true,
+			(&JavaType::Long, &Value::Long(_)) => true,
+			(&JavaType::Float, &Value::Float(_)) => true,
+			(&JavaType::Double, &Value::Double(_)) => true,
+			(&JavaType::Object(ref name), &Value::Str(_)) => {
+				name == "java/lang/String" || STRING_SUPERTYPES.contains(&&name[..])
+			},
+			(&JavaType::Object(_), &Value::Object(_)) => true,
+			(&JavaType::Array(_), &Value::Object(_)) => true,
+			_ => false,
+		}
+	}
+
+	/// Returns true unless the given value is a non-null object that isn't an
+	/// instance of this type's class (or array class).
+	///
+	/// The JNI doesn't check the class of objects passed to methods or stored
+	/// in fields, and the behaviour is undefined if it's wrong.
+	pub(crate) fn accepts_class(&self, jvm: &JavaVM, value: &Value) -> Result<bool> {
+		let obj = match value {
+			&Value::Object(ref obj) if !obj.is_null() => obj,
+			_ => return Ok(true),
+		};
+
+		// Array classes are found by their descriptors
+		let name = match self {
+			&JavaType::Object(ref name) if name == "java/lang/Object" => return Ok(true),
+			&JavaType::Object(ref name) => name.clone(),
+			&JavaType::Array(_) => self.descriptor(),
+			_ => return Ok(true),
+		};
+		with_local_frame(jvm, || Ok(obj.is_instance_of(jvm.class(&name)?)))
+	}
+}
+
+impl<'a> From<&'a Type> for JavaType {
+	fn from(kind: &'a Type) -> JavaType {
+		match kind {
+			&Type::Boolean => JavaType::Boolean,
+			&Type::Byte => JavaType::Byte,
+			&Type::Char => JavaType::Char,
+			&Type::Short => JavaType::Short,
+			&Type::Int => JavaType::Int,
+			&Type::Long => JavaType::Long,
+			&Type::Float => JavaType::Float,
+			&Type::Double => JavaType::Double,
+			&Type::Void => JavaType::Void,
+			&Type::Str => JavaType::Object(String::from("java/lang/String")),
+			// Array classes are named by their descriptors
+			&Type::Object(name) if name.starts_with('[') => JavaType::parse(name)
+				.unwrap_or_else(|_| JavaType::Object(String::from(name))),
+			&Type::Object(name) => JavaType::Object(String::from(name)),
+		}
+	}
+}
+
+impl FromStr for JavaType {
+	type Err = Error;
+
+	fn from_str(descriptor: &str) -> Result<JavaType> {
+		JavaType::parse(descriptor)
+	}
+}
+
+impl fmt::Display for JavaType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.descriptor())
+	}
+}
+
+
+/// Converts a binary class name (eg. `java.lang.String`) into its internal
+/// form (eg. `java/lang/String`), as required by the JNI.
+pub fn internal_name(binary_name: &str) -> String {
+	binary_name.replace('.', "/")
+}
+
+/// Converts an internal class name (eg. `java/lang/String`) into its binary
+/// form (eg. `java.lang.String`), as used by Java code.
+pub fn binary_name(internal_name: &str) -> String {
+	internal_name.replace('/', ".")
+}
+
+
+
+/// A JNI method type signature, such as `(Ljava/lang/Object;I)V`.
+///
+/// A signature can either be parsed from an explicit descriptor, or inferred
+/// from the values of the arguments passed to a method. Explicit signatures
+/// are required to call methods which declare a parameter as a supertype (eg.
+/// `Object` or an interface) of the argument actually passed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodSignature {
+	descriptor: String,
+	args: Vec<JavaType>,
+	return_type: JavaType,
+}
+
+impl MethodSignature {
+	/// Create a method signature from its argument and return types.
+	pub fn new(args: Vec<JavaType>, return_type: JavaType) -> MethodSignature {
+		let mut descriptor = String::from("(");
+		for arg in &args {
+			arg.write_descriptor(&mut descriptor);
+		}
+		descriptor.push(')');
+		return_type.write_descriptor(&mut descriptor);
+
+		MethodSignature {
+			descriptor: descriptor,
+			args: args,
+			return_type: return_type,
+		}
+	}
+
+	/// Parse a method signature from its JNI descriptor.
+	pub fn parse(descriptor: &str) -> Result<MethodSignature> {
+		let mut parser = Parser::new(descriptor);
+		parser.expect(b'(')?;
+
+		// Parse each argument type until we reach the closing bracket
+		let mut args = Vec::new();
+		while parser.peek() != Some(b')') {
+			if parser.peek().is_none() {
+				return Err(parser.error("expected `)`"));
+			}
+			args.push(parser.parse_type(false)?);
+		}
+		parser.expect(b')')?;
+
+		// The return type must take up the rest of the descriptor
+		let return_type = parser.parse_type(true)?;
+		parser.finish()?;
+
+		Ok(MethodSignature {
+			descriptor: descriptor.to_string(),
+			args: args,
+			return_type: return_type,
+		})
+	}
+
+	/// Infer a method signature from the values of the arguments passed to it,
+	/// and its return type.
+	///
+	/// Each object argument contributes its runtime class to the signature, so
+	/// the inferred signature only matches methods which declare that exact
+	/// class as the parameter type.
+	pub fn from_values(args: &[Value], return_type: &Type) -> Result<MethodSignature> {
+		Ok(MethodSignature::new(arg_types(args)?, JavaType::from(return_type)))
+	}
+
+	/// Returns the JNI descriptor for this signature.
+	pub fn descriptor<'a>(&'a self) -> &'a str {
+		&self.descriptor
+	}
+
+	/// Returns the types of the method's arguments.
+	pub fn args<'a>(&'a self) -> &'a [JavaType] {
+		&self.args
+	}
+
+	/// Returns the method's return type.
+	pub fn return_type<'a>(&'a self) -> &'a JavaType {
+		&self.return_type
+	}
+
+	/// Returns the number of arguments the method takes.
+	pub fn arg_count(&self) -> usize {
+		self.args.len()
+	}
+
+	/// Checks that the given values can be passed as arguments to a method
+	/// with this signature.
+	///
+	/// Primitive arguments must match their parameter's type exactly. Strings
+	/// are accepted by `java/lang/String` and its supertypes. Objects are
+	/// accepted by any reference type here, since checking their class needs
+	/// the JVM: calls made with a signature also check that each non-null
+	/// object is an instance of its parameter's class.
+	pub fn validate(&self, args: &[Value]) -> Result<()> {
+		if args.len() != self.args.len() {
+			return Err(Error::ArgumentMismatch(format!(
+				"`{}` takes {} arguments, but {} were given",
+				self.descriptor, self.args.len(), args.len())));
+		}
+
+		for (i, (param, arg)) in self.args.iter().zip(args).enumerate() {
+			if !param.accepts(arg) {
+				return Err(Error::ArgumentMismatch(format!(
+					"argument {} of `{}` has type `{}`, but {:?} was given",
+					i, self.descriptor, param, arg)));
+			}
+		}
+		Ok(())
+	}
+
+	/// Checks that each non-null object argument is an instance of its
+	/// parameter's class.
+	pub(crate) fn check_classes(&self, jvm: &JavaVM, args: &[Value]) -> Result<()> {
+		for (i, (param, arg)) in self.args.iter().zip(args).enumerate() {
+			if !param.accepts_class(jvm, arg)? {
+				return Err(Error::ArgumentMismatch(format!(
+					"argument {} of `{}` isn't an instance of `{}`",
+					i, self.descriptor, param)));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for MethodSignature {
+	type Err = Error;
+
+	fn from_str(descriptor: &str) -> Result<MethodSignature> {
+		MethodSignature::parse(descriptor)
+	}
+}
+
+impl fmt::Display for MethodSignature {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.descriptor)
+	}
+}
+
+
+/// Returns the types of each of the given arguments, as they contribute to a
+/// method's signature.
+pub(crate) fn arg_types(args: &[Value]) -> Result<Vec<JavaType>> {
+	let mut types = Vec::with_capacity(args.len());
+	for arg in args {
+		types.push(match arg {
+			&Value::Void => {
+				return Err(Error::ArgumentMismatch(
+					String::from("void can't be passed as an argument")));
+			},
+			&Value::Object(ref obj) => class_type(obj)?,
+			&Value::Str(_) => JavaType::from(&Type::Str),
+			&Value::Boolean(_) => JavaType::Boolean,
+			&Value::Byte(_) => JavaType::Byte,
+			&Value::Char(_) => JavaType::Char,
+			&Value::Short(_) => JavaType::Short,
+			&Value::Int(_) => JavaType::Int,
+			&Value::Long(_) => JavaType::Long,
+			&Value::Float(_) => JavaType::Float,
+			&Value::Double(_) => JavaType::Double,
+		});
+	}
+	Ok(types)
+}
+
+/// Returns the type of the runtime class of the given object.
+///
+/// A null object has no class, so its type can't be determined.
+pub(crate) fn class_type(obj: &Object) -> Result<JavaType> {
+	if obj.is_null() {
+		return Err(Error::ArgumentMismatch(String::from(
+			"the type of a null object can't be inferred")));
+	}
+	JavaType::from_class_name(&obj.class_name()?)
+}
+
+
+
+/// An error encountered while parsing a malformed descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorError {
+	descriptor: String,
+	position: usize,
+	reason: &'static str,
+}
+
+impl DescriptorError {
+	/// Returns the descriptor that failed to parse.
+	pub fn descriptor<'a>(&'a self) -> &'a str {
+		&self.descriptor
+	}
+
+	/// Returns the byte offset into the descriptor at which the error occurred.
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	/// Returns a short description of what was wrong at the error's position.
+	pub fn reason(&self) -> &'static str {
+		self.reason
+	}
+}
+
+impl error::Error for DescriptorError {}
+
+impl fmt::Display for DescriptorError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at position {} in `{}`", self.reason, self.position,
+			self.descriptor)
+	}
+}
+
+
+/// A recursive descent parser over a descriptor.
+struct Parser<'a> {
+	descriptor: &'a str,
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn new(descriptor: &'a str) -> Parser<'a> {
+		Parser {
+			descriptor: descriptor,
+			pos: 0,
+		}
+	}
+
+	/// Returns the byte at the current position.
+	fn peek(&self) -> Option<u8> {
+		self.descriptor.as_bytes().get(self.pos).cloned()
+	}
+
+	/// Creates an error at the current position.
+	fn error(&self, reason: &'static str) -> Error {
+		Error::InvalidSignature(DescriptorError {
+			descriptor: self.descriptor.to_string(),
+			position: self.pos,
+			reason: reason,
+		})
+	}
+
+	/// Consumes the given byte, or fails if it isn't next.
+	fn expect(&mut self, byte: u8) -> Result<()> {
+		if self.peek() == Some(byte) {
+			self.pos += 1;
+			Ok(())
+		} else if byte == b'(' {
+			Err(self.error("expected `(`"))
+		} else {
+			Err(self.error("expected `)`"))
+		}
+	}
+
+	/// Fails if there's anything left in the descriptor.
+	fn finish(&self) -> Result<()> {
+		if self.pos == self.descriptor.len() {
+			Ok(())
+		} else {
+			Err(self.error("unexpected trailing characters"))
+		}
+	}
+
+	/// Parses a single type, which may only be void if this is a return type.
+	fn parse_type(&mut self, allow_void: bool) -> Result<JavaType> {
+		let byte = match self.peek() {
+			Some(byte) => byte,
+			None => return Err(self.error("expected a type")),
+		};
+		self.pos += 1;
+
+		match byte {
+			b'Z' => Ok(JavaType::Boolean),
+			b'B' => Ok(JavaType::Byte),
+			b'C' => Ok(JavaType::Char),
+			b'S' => Ok(JavaType::Short),
+			b'I' => Ok(JavaType::Int),
+			b'J' => Ok(JavaType::Long),
+			b'F' => Ok(JavaType::Float),
+			b'D' => Ok(JavaType::Double),
+			b'V' if allow_void => Ok(JavaType::Void),
+			b'[' => {
+				let start = self.pos - 1;
+				while self.peek() == Some(b'[') {
+					self.pos += 1;
+				}
+				let dimensions = self.pos - start;
+				if dimensions > MAX_DIMENSIONS {
+					self.pos = start + MAX_DIMENSIONS;
+					return Err(self.error("array has more than 255 dimensions"));
+				}
+
+				// Arrays of void aren't allowed, even for return types
+				let element = self.parse_type(false)?;
+				Ok(JavaType::array(element, dimensions))
+			},
+			b'L' => self.parse_class_name().map(JavaType::Object),
+			_ => {
+				self.pos -= 1;
+				if byte == b'V' {
+					Err(self.error("void is only allowed as a return type"))
+				} else {
+					Err(self.error("invalid type"))
+				}
+			},
+		}
+	}
+
+	/// Parses an internal class name up to and including its terminating `;`.
+	fn parse_class_name(&mut self) -> Result<String> {
+		let start = self.pos;
+		let mut segment_start = start;
+		loop {
+			match self.peek() {
+				None => return Err(self.error("unterminated class name")),
+				Some(b';') | Some(b'/') if self.pos == segment_start => {
+					return Err(self.error("empty class name segment"));
+				},
+				Some(b';') => break,
+				Some(b'/') => segment_start = self.pos + 1,
+				Some(b'.') | Some(b'[') | Some(b'(') | Some(b')') => {
+					return Err(self.error("invalid character in class name"));
+				},
+				Some(_) => {},
+			}
+			self.pos += 1;
+		}
+
+		let name = self.descriptor[start..self.pos].to_string();
+		self.pos += 1;
+		Ok(name)
+	}
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Returns the position and reason of the error parsing a descriptor.
+	fn error(result: Result<JavaType>) -> (usize, &'static str) {
+		match result {
+			Err(Error::InvalidSignature(err)) => (err.position(), err.reason()),
+			other => panic!("expected a descriptor error, got {:?}", other),
+		}
+	}
+
+	fn method_error(descriptor: &str) -> (usize, &'static str) {
+		match MethodSignature::parse(descriptor) {
+			Err(Error::InvalidSignature(err)) => (err.position(), err.reason()),
+			other => panic!("expected a descriptor error, got {:?}", other),
+		}
+	}
+
+	fn object(name: &str) -> JavaType {
+		JavaType::Object(String::from(name))
+	}
+
+	#[test]
+	fn parse_types() {
+		assert_eq!(JavaType::parse("I").unwrap(), JavaType::Int);
+		assert_eq!(JavaType::parse("Z").unwrap(), JavaType::Boolean);
+		assert_eq!(JavaType::parse("Ljava/lang/String;").unwrap(), object("java/lang/String"));
+		assert_eq!(JavaType::parse("[J").unwrap(), JavaType::array(JavaType::Long, 1));
+		assert_eq!(JavaType::parse("[[Ljava/util/List;").unwrap(),
+			JavaType::array(object("java/util/List"), 2));
+		assert_eq!(JavaType::parse("LOuter$Inner;").unwrap(), object("Outer$Inner"));
+	}
+
+	#[test]
+	fn descriptors_round_trip() {
+		for descriptor in &["I", "[[D", "Ljava/lang/Object;", "[Ljava/lang/String;"] {
+			assert_eq!(JavaType::parse(descriptor).unwrap().descriptor(), *descriptor);
+		}
+	}
+
+	#[test]
+	fn invalid_types() {
+		assert_eq!(error(JavaType::parse("")), (0, "expected a type"));
+		assert_eq!(error(JavaType::parse("Q")), (0, "invalid type"));
+		assert_eq!(error(JavaType::parse("V")), (0, "void is only allowed as a return type"));
+		assert_eq!(error(JavaType::parse("[V")), (1, "void is only allowed as a return type"));
+		assert_eq!(error(JavaType::parse("II")), (1, "unexpected trailing characters"));
+		assert_eq!(error(JavaType::parse("Ljava/lang/String")), (17, "unterminated class name"));
+		assert_eq!(error(JavaType::parse("L;")), (1, "empty class name segment"));
+		assert_eq!(error(JavaType::parse("Ljava//String;")), (6, "empty class name segment"));
+		assert_eq!(error(JavaType::parse("Ljava.lang.String;")), (5, "invalid character in class name"));
+	}
+
+	#[test]
+	fn array_dimension_limit() {
+		let max = format!("{}I", "[".repeat(255));
+		assert_eq!(JavaType::parse(&max).unwrap().dimensions(), 255);
+
+		let over = format!("{}I", "[".repeat(256));
+		assert_eq!(error(JavaType::parse(&over)), (255, "array has more than 255 dimensions"));
+
+		// Deep enough to overflow the stack if the parser recursed per dimension
+		let deep = format!("{}I", "[".repeat(1_000_000));
+		assert_eq!(error(JavaType::parse(&deep)).0, 255);
+	}
+
+	#[test]
+	fn parse_method_signatures() {
+		let signature = MethodSignature::parse("(ILjava/lang/String;[J)V").unwrap();
+		assert_eq!(signature.args(), &[JavaType::Int, object("java/lang/String"),
+			JavaType::array(JavaType::Long, 1)][..]);
+		assert_eq!(signature.return_type(), &JavaType::Void);
+		assert_eq!(signature.arg_count(), 3);
+		assert_eq!(signature.descriptor(), "(ILjava/lang/String;[J)V");
+
+		let signature = MethodSignature::parse("()[Ljava/lang/Object;").unwrap();
+		assert!(signature.args().is_empty());
+		assert_eq!(signature.return_type(), &JavaType::array(object("java/lang/Object"), 1));
+	}
+
+	#[test]
+	fn invalid_method_signatures() {
+		assert_eq!(method_error(""), (0, "expected `(`"));
+		assert_eq!(method_error("I)V"), (0, "expected `(`"));
+		assert_eq!(method_error("(I"), (2, "expected `)`"));
+		assert_eq!(method_error("(V)V"), (1, "void is only allowed as a return type"));
+		assert_eq!(method_error("(I)"), (3, "expected a type"));
+		assert_eq!(method_error("(I)VV"), (4, "unexpected trailing characters"));
+		assert_eq!(method_error("(Ljava/lang/String)V"), (18, "invalid character in class name"));
+	}
+
+	#[test]
+	fn new_builds_descriptor() {
+		let signature = MethodSignature::new(vec![JavaType::Char, object("java/lang/String")],
+			JavaType::Boolean);
+		assert_eq!(signature.descriptor(), "(CLjava/lang/String;)Z");
+		assert_eq!(signature, MethodSignature::parse("(CLjava/lang/String;)Z").unwrap());
+	}
+
+	#[test]
+	fn validate_arguments() {
+		let signature = MethodSignature::parse("(ILjava/lang/CharSequence;)V").unwrap();
+		assert!(signature.validate(&[Value::Int(1), Value::Str(String::from("a"))]).is_ok());
+		assert!(signature.validate(&[Value::Long(1), Value::Str(String::from("a"))]).is_err());
+		assert!(signature.validate(&[Value::Int(1)]).is_err());
+
+		let signature = MethodSignature::parse("(Ljava/lang/Integer;)V").unwrap();
+		assert!(signature.validate(&[Value::Str(String::from("a"))]).is_err());
+	}
+
+	#[test]
+	fn binary_and_internal_names() {
+		assert_eq!(internal_name("java.lang.String"), "java/lang/String");
+		assert_eq!(binary_name("java/lang/String"), "java.lang.String");
+		assert_eq!(binary_name("java/util/Map$Entry"), "java.util.Map$Entry");
+
+		assert_eq!(JavaType::from_class_name("int").unwrap(), JavaType::Int);
+		assert_eq!(JavaType::from_class_name("java.lang.String").unwrap(),
+			object("java/lang/String"));
+		assert_eq!(JavaType::from_class_name("[Ljava.lang.String;").unwrap(),
+			JavaType::array(object("java/lang/String"), 1));
+		assert_eq!(JavaType::from_class_name("[[I").unwrap(), JavaType::array(JavaType::Int, 2));
+
+		assert_eq!(JavaType::Int.class_name(), "int");
+		assert_eq!(object("java/util/Map$Entry").class_name(), "java.util.Map$Entry");
+		assert_eq!(JavaType::array(object("java/lang/String"), 1).class_name(),
+			"[Ljava.lang.String;");
+	}
+}
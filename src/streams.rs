@@ -0,0 +1,514 @@
+//
+//  Streams
+//
+
+use std::{cmp, io, panic};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use ffi;
+use native::{NativeClass, Native, throw};
+use {JavaVM, Object, ClassCache, Value, FromJava};
+use {Error, Result};
+
+
+/// The default size of the `byte[]` used to transfer data to and from a Java
+/// stream.
+const DEFAULT_CAPACITY: usize = 8192;
+
+/// The most bytes a Rust stream transfers in a single native call.
+const MAX_TRANSFER: usize = 65536;
+
+static INPUT_STREAM: ClassCache = ClassCache::new("java/io/InputStream");
+static OUTPUT_STREAM: ClassCache = ClassCache::new("java/io/OutputStream");
+
+
+
+//
+//  Java Streams
+//
+
+/// A Java `InputStream`, read through `std::io::Read`.
+///
+/// Data is transferred in bulk through a `byte[]`, which is allocated once and
+/// reused for every read. Exceptions thrown by the stream are returned as
+/// `io::Error`s.
+#[derive(Debug)]
+pub struct JavaInputStream<'a> {
+	stream: Object<'a>,
+	buffer: Object<'a>,
+	capacity: usize,
+}
+
+impl<'a> JavaInputStream<'a> {
+	/// Wraps an object, which must be a non-null instance of
+	/// `java.io.InputStream`.
+	pub fn new(stream: Object<'a>) -> Result<JavaInputStream<'a>> {
+		JavaInputStream::with_capacity(stream, DEFAULT_CAPACITY)
+	}
+
+	/// Wraps an input stream, transferring at most `capacity` bytes in each
+	/// call to `read`.
+	pub fn with_capacity(stream: Object<'a>, capacity: usize)
+			-> Result<JavaInputStream<'a>> {
+		check_instance(&stream, &INPUT_STREAM)?;
+		Ok(JavaInputStream {
+			buffer: new_buffer(stream.jvm, capacity)?,
+			stream: stream,
+			capacity: capacity,
+		})
+	}
+
+	/// Returns the underlying stream object.
+	pub fn into_object(self) -> Object<'a> {
+		self.stream
+	}
+
+	/// Closes the underlying stream.
+	pub fn close(self) -> io::Result<()> {
+		let method = INPUT_STREAM.method(self.stream.jvm, "close", "()V")?;
+		method.call(&self.stream, &[])?;
+		Ok(())
+	}
+}
+
+impl<'a> Read for JavaInputStream<'a> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+
+		// Read into the Java buffer
+		let jvm = self.stream.jvm;
+		let length = cmp::min(buf.len(), self.capacity) as i32;
+		let method = INPUT_STREAM.method(jvm, "read", "([BII)I")?;
+		let args = [Value::Object(self.buffer.clone()), Value::Int(0), Value::Int(length)];
+
+		// A negative count marks the end of the stream. A stream that read
+		// nothing (which `InputStream` allows) is read a single byte at a time
+		// instead, which blocks until there's one or the stream ends
+		let count = i32::from_java(method.call(&self.stream, &args)?)?;
+		if count < 0 {
+			return Ok(0);
+		} else if count == 0 {
+			let method = INPUT_STREAM.method(jvm, "read", "()I")?;
+			let byte = i32::from_java(method.call(&self.stream, &[])?)?;
+			if byte < 0 {
+				return Ok(0);
+			}
+			buf[0] = byte as u8;
+			return Ok(1);
+		}
+		let count = cmp::min(count, length);
+		unsafe {
+			let env = jvm.env;
+			((**env).GetByteArrayRegion)(env, self.buffer.raw, 0, count,
+				buf.as_mut_ptr() as *mut ffi::jbyte);
+		}
+		Ok(count as usize)
+	}
+}
+
+
+/// A Java `OutputStream`, written through `std::io::Write`.
+///
+/// Data is transferred in the same way as a `JavaInputStream`.
+#[derive(Debug)]
+pub struct JavaOutputStream<'a> {
+	stream: Object<'a>,
+	buffer: Object<'a>,
+	capacity: usize,
+}
+
+impl<'a> JavaOutputStream<'a> {
+	/// Wraps an object, which must be a non-null instance of
+	/// `java.io.OutputStream`.
+	pub fn new(stream: Object<'a>) -> Result<JavaOutputStream<'a>> {
+		JavaOutputStream::with_capacity(stream, DEFAULT_CAPACITY)
+	}
+
+	/// Wraps an output stream, transferring at most `capacity` bytes in each
+	/// call to `write`.
+	pub fn with_capacity(stream: Object<'a>, capacity: usize)
+			-> Result<JavaOutputStream<'a>> {
+		check_instance(&stream, &OUTPUT_STREAM)?;
+		Ok(JavaOutputStream {
+			buffer: new_buffer(stream.jvm, capacity)?,
+			stream: stream,
+			capacity: capacity,
+		})
+	}
+
+	/// Returns the underlying stream object.
+	pub fn into_object(self) -> Object<'a> {
+		self.stream
+	}
+
+	/// Closes the underlying stream, which flushes it first.
+	pub fn close(self) -> io::Result<()> {
+		let method = OUTPUT_STREAM.method(self.stream.jvm, "close", "()V")?;
+		method.call(&self.stream, &[])?;
+		Ok(())
+	}
+}
+
+impl<'a> Write for JavaOutputStream<'a> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+
+		// Copy into the Java buffer, then write it
+		let jvm = self.stream.jvm;
+		let length = cmp::min(buf.len(), self.capacity) as i32;
+		unsafe {
+			let env = jvm.env;
+			((**env).SetByteArrayRegion)(env, self.buffer.raw, 0, length,
+				buf.as_ptr() as *const ffi::jbyte);
+		}
+		let method = OUTPUT_STREAM.method(jvm, "write", "([BII)V")?;
+		let args = [Value::Object(self.buffer.clone()), Value::Int(0), Value::Int(length)];
+		method.call(&self.stream, &args)?;
+		Ok(length as usize)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		let method = OUTPUT_STREAM.method(self.stream.jvm, "flush", "()V")?;
+		method.call(&self.stream, &[])?;
+		Ok(())
+	}
+}
+
+/// Allocates the `byte[]` a stream transfers data through.
+fn new_buffer<'a>(jvm: &'a JavaVM, capacity: usize) -> Result<Object<'a>> {
+	let length = match ffi::jsize::try_from(capacity) {
+		Ok(length) if length > 0 => length,
+		_ => return Err(Error::ArgumentMismatch(format!(
+			"a stream can't transfer {} bytes at a time", capacity))),
+	};
+	let env = jvm.env;
+	let raw = unsafe { ((**env).NewByteArray)(env, length) };
+	if raw.is_null() {
+		return Err(Error::from_exception(jvm));
+	}
+	Ok(Object {
+		jvm: jvm,
+		raw: raw,
+	})
+}
+
+/// Returns an error if the object isn't a non-null instance of the cached
+/// class.
+fn check_instance(object: &Object, class: &ClassCache) -> Result<()> {
+	if !object.is_null() && object.is_instance_of(class.class(object.jvm)?) {
+		Ok(())
+	} else {
+		Err(Error::ArgumentMismatch(format!(
+			"expected an instance of {}", class.name)))
+	}
+}
+
+
+impl From<Error> for io::Error {
+	fn from(err: Error) -> io::Error {
+		let kind = match &err {
+			&Error::Exception(ref info) => match info.name() {
+				"java.io.FileNotFoundException" | "java.nio.file.NoSuchFileException" => {
+					io::ErrorKind::NotFound
+				},
+				"java.nio.file.AccessDeniedException" => io::ErrorKind::PermissionDenied,
+				"java.nio.file.FileAlreadyExistsException" => io::ErrorKind::AlreadyExists,
+				"java.io.EOFException" => io::ErrorKind::UnexpectedEof,
+				"java.net.SocketTimeoutException" => io::ErrorKind::TimedOut,
+				_ => io::ErrorKind::Other,
+			},
+			&Error::ArgumentMismatch(_) | &Error::Conversion(_) => io::ErrorKind::InvalidInput,
+			&Error::OutOfMemory => io::ErrorKind::OutOfMemory,
+			_ => io::ErrorKind::Other,
+		};
+		io::Error::new(kind, err)
+	}
+}
+
+
+
+//
+//  Rust Streams
+//
+
+impl JavaVM {
+	/// Create a Java `InputStream` that reads from the given reader.
+	///
+	/// The stream is an instance of a class defined by this library, whose
+	/// native methods call into the reader. Errors returned by the reader are
+	/// thrown as `IOException`s. The reader is dropped when the stream is
+	/// closed. If it's never closed, the reader is dropped when the stream is
+	/// finalized, on the JVM's finalizer thread, or leaked if finalization is
+	/// disabled (eg. with `--finalization=disabled`).
+	pub fn input_stream<'a, R>(&'a self, reader: R) -> Result<Object<'a>>
+			where R: Read + Send + 'static {
+		let stream: Box<RustReader> = Box::new(reader);
+		RUST_INPUT_STREAM.instantiate(self, stream)
+	}
+
+	/// Create a Java `OutputStream` that writes to the given writer.
+	///
+	/// The stream behaves in the same way as an `input_stream`. The writer is
+	/// flushed and dropped when the stream is closed, or dropped without being
+	/// flushed when it's finalized.
+	pub fn output_stream<'a, W>(&'a self, writer: W) -> Result<Object<'a>>
+			where W: Write + Send + 'static {
+		let stream: Box<RustWriter> = Box::new(writer);
+		RUST_OUTPUT_STREAM.instantiate(self, stream)
+	}
+}
+
+
+/// The Rust stream behind a `rjni/RustInputStream`.
+type RustReader = dyn Read + Send + 'static;
+
+/// The Rust stream behind a `rjni/RustOutputStream`.
+type RustWriter = dyn Write + Send + 'static;
+
+static RUST_INPUT_STREAM: NativeClass = NativeClass::new(
+	"rjni/RustInputStream",
+	"java/io/InputStream",
+	&[],
+	&[
+		Native {
+			name: "read",
+			signature: "()I",
+			function: read_byte as *mut ffi::jvoid,
+		},
+		Native {
+			name: "read",
+			signature: "([BII)I",
+			function: read_bytes as *mut ffi::jvoid,
+		},
+		Native {
+			name: "close",
+			signature: "()V",
+			function: close_input as *mut ffi::jvoid,
+		},
+		Native {
+			name: "finalize",
+			signature: "()V",
+			function: finalize_input as *mut ffi::jvoid,
+		},
+	],
+);
+
+static RUST_OUTPUT_STREAM: NativeClass = NativeClass::new(
+	"rjni/RustOutputStream",
+	"java/io/OutputStream",
+	&[],
+	&[
+		Native {
+			name: "write",
+			signature: "(I)V",
+			function: write_byte as *mut ffi::jvoid,
+		},
+		Native {
+			name: "write",
+			signature: "([BII)V",
+			function: write_bytes as *mut ffi::jvoid,
+		},
+		Native {
+			name: "flush",
+			signature: "()V",
+			function: flush_output as *mut ffi::jvoid,
+		},
+		Native {
+			name: "close",
+			signature: "()V",
+			function: close_output as *mut ffi::jvoid,
+		},
+		Native {
+			name: "finalize",
+			signature: "()V",
+			function: finalize_output as *mut ffi::jvoid,
+		},
+	],
+);
+
+/// Runs a function on the Rust stream behind a stream object, throwing an
+/// `IOException` if it fails.
+///
+/// The native methods are `synchronized`, so only one runs on a stream at a
+/// time. If `close` is set, the stream is dropped afterwards.
+unsafe fn with_stream<T: ?Sized, R, F>(env: *mut ffi::JNIEnv, this: ffi::jobject,
+                                       class: &NativeClass, close: bool, f: F)
+		-> Option<R>
+		where F: FnOnce(&mut T) -> io::Result<R> {
+	let stream = class.value::<Box<T>>(env, this);
+	if stream.is_null() {
+		throw(env, "java/io/IOException", "Stream closed");
+		return None;
+	}
+
+	// Unwinding into the JVM is undefined, so panics are thrown too
+	let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+		if close {
+			let mut stream = class.take::<Box<T>>(env, this).unwrap();
+			f(&mut **stream)
+		} else {
+			f(&mut **stream)
+		}
+	}));
+	match result {
+		Ok(Ok(value)) => Some(value),
+		Ok(Err(err)) => {
+			throw(env, exception_class(&err), &err.to_string());
+			None
+		},
+		Err(_) => {
+			throw(env, "java/io/IOException", "Rust stream panicked");
+			None
+		},
+	}
+}
+
+/// Returns the Java exception thrown for an I/O error.
+fn exception_class(err: &io::Error) -> &'static str {
+	match err.kind() {
+		io::ErrorKind::NotFound => "java/io/FileNotFoundException",
+		io::ErrorKind::UnexpectedEof => "java/io/EOFException",
+		io::ErrorKind::TimedOut => "java/net/SocketTimeoutException",
+		_ => "java/io/IOException",
+	}
+}
+
+/// Returns the length of a region of a `byte[]`, throwing an exception if it's
+/// out of bounds.
+unsafe fn check_region(env: *mut ffi::JNIEnv, array: ffi::jbyteArray,
+                       offset: ffi::jint, length: ffi::jint) -> Option<usize> {
+	if array.is_null() {
+		throw(env, "java/lang/NullPointerException", "buffer is null");
+		return None;
+	}
+	let array_length = ((**env).GetArrayLength)(env, array);
+	if offset < 0 || length < 0 || offset > array_length - length {
+		throw(env, "java/lang/IndexOutOfBoundsException", &format!(
+			"range [{}, {} + {}) out of bounds for length {}",
+			offset, offset, length, array_length));
+		return None;
+	}
+	Some(length as usize)
+}
+
+/// Reads into a buffer, retrying if the read is interrupted.
+fn read_retrying(reader: &mut RustReader, buf: &mut [u8]) -> io::Result<usize> {
+	loop {
+		match reader.read(buf) {
+			Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+			result => return result,
+		}
+	}
+}
+
+/// Implements `InputStream.read()`.
+extern "C" fn read_byte(env: *mut ffi::JNIEnv, this: ffi::jobject) -> ffi::jint {
+	let result = unsafe {
+		with_stream(env, this, &RUST_INPUT_STREAM, false, |reader: &mut RustReader| {
+			let mut byte = [0];
+			let count = read_retrying(reader, &mut byte)?;
+			Ok(if count == 0 { -1 } else { byte[0] as ffi::jint })
+		})
+	};
+	result.unwrap_or(-1)
+}
+
+/// Implements `InputStream.read(byte[], int, int)`.
+extern "C" fn read_bytes(env: *mut ffi::JNIEnv, this: ffi::jobject,
+                         array: ffi::jbyteArray, offset: ffi::jint, length: ffi::jint)
+		-> ffi::jint {
+	unsafe {
+		let length = match check_region(env, array, offset, length) {
+			Some(0) => return 0,
+			Some(length) => cmp::min(length, MAX_TRANSFER),
+			None => return -1,
+		};
+		let result = with_stream(env, this, &RUST_INPUT_STREAM, false,
+				|reader: &mut RustReader| {
+			let mut buf = vec![0; length];
+			let count = read_retrying(reader, &mut buf)?;
+			((**env).SetByteArrayRegion)(env, array, offset, count as ffi::jsize,
+				buf.as_ptr() as *const ffi::jbyte);
+			Ok(if count == 0 { -1 } else { count as ffi::jint })
+		});
+		result.unwrap_or(-1)
+	}
+}
+
+/// Implements `InputStream.close()`.
+extern "C" fn close_input(env: *mut ffi::JNIEnv, this: ffi::jobject) {
+	unsafe {
+		if !is_closed(env, this, &RUST_INPUT_STREAM) {
+			with_stream(env, this, &RUST_INPUT_STREAM, true, |_: &mut RustReader| Ok(()));
+		}
+	}
+}
+
+/// Implements `Object.finalize()` for an input stream, dropping the reader if
+/// the stream wasn't closed.
+extern "C" fn finalize_input(env: *mut ffi::JNIEnv, this: ffi::jobject) {
+	unsafe { RUST_INPUT_STREAM.release::<Box<RustReader>>(env, this) }
+}
+
+/// Implements `OutputStream.write(int)`.
+extern "C" fn write_byte(env: *mut ffi::JNIEnv, this: ffi::jobject, byte: ffi::jint) {
+	unsafe {
+		with_stream(env, this, &RUST_OUTPUT_STREAM, false, |writer: &mut RustWriter| {
+			writer.write_all(&[byte as u8])
+		});
+	}
+}
+
+/// Implements `OutputStream.write(byte[], int, int)`.
+extern "C" fn write_bytes(env: *mut ffi::JNIEnv, this: ffi::jobject,
+                          array: ffi::jbyteArray, offset: ffi::jint, length: ffi::jint) {
+	unsafe {
+		let length = match check_region(env, array, offset, length) {
+			Some(length) => length,
+			None => return,
+		};
+		let mut buf = vec![0u8; length];
+		((**env).GetByteArrayRegion)(env, array, offset, length as ffi::jsize,
+			buf.as_mut_ptr() as *mut ffi::jbyte);
+		with_stream(env, this, &RUST_OUTPUT_STREAM, false, |writer: &mut RustWriter| {
+			writer.write_all(&buf)
+		});
+	}
+}
+
+/// Implements `OutputStream.flush()`.
+extern "C" fn flush_output(env: *mut ffi::JNIEnv, this: ffi::jobject) {
+	unsafe {
+		with_stream(env, this, &RUST_OUTPUT_STREAM, false, |writer: &mut RustWriter| {
+			writer.flush()
+		});
+	}
+}
+
+/// Implements `OutputStream.close()`.
+extern "C" fn close_output(env: *mut ffi::JNIEnv, this: ffi::jobject) {
+	unsafe {
+		if !is_closed(env, this, &RUST_OUTPUT_STREAM) {
+			with_stream(env, this, &RUST_OUTPUT_STREAM, true, |writer: &mut RustWriter| {
+				writer.flush()
+			});
+		}
+	}
+}
+
+/// Implements `Object.finalize()` for an output stream, dropping the writer if
+/// the stream wasn't closed.
+extern "C" fn finalize_output(env: *mut ffi::JNIEnv, this: ffi::jobject) {
+	unsafe { RUST_OUTPUT_STREAM.release::<Box<RustWriter>>(env, this) }
+}
+
+/// Returns true if the stream object has been closed, since closing a closed
+/// stream has no effect.
+unsafe fn is_closed(env: *mut ffi::JNIEnv, this: ffi::jobject, class: &NativeClass) -> bool {
+	class.value::<()>(env, this).is_null()
+}
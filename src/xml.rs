@@ -0,0 +1,231 @@
+//
+//  XML
+//
+
+/// An element of an XML document, with the text directly inside it.
+///
+/// This is only as much of XML as reading Maven POMs requires: attributes and
+/// namespaces are ignored, and the document isn't validated.
+#[derive(Debug, Clone, Default)]
+pub struct Element {
+	pub name: String,
+	pub children: Vec<Element>,
+	pub text: String,
+}
+
+impl Element {
+	/// Returns the first child element with the given name.
+	pub fn child(&self, name: &str) -> Option<&Element> {
+		self.children.iter().find(|child| child.name == name)
+	}
+
+	/// Returns every child element with the given name.
+	pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+		self.children.iter().filter(move |child| child.name == name)
+	}
+
+	/// Returns the trimmed text of the first child element with the given
+	/// name, if there is one.
+	pub fn child_text(&self, name: &str) -> Option<&str> {
+		self.child(name).map(|child| child.text.trim())
+	}
+}
+
+/// Parses a document, returning its root element.
+pub fn parse(text: &str) -> Result<Element, String> {
+	let mut parser = Parser {
+		text: text.trim_start_matches('\u{feff}'),
+		pos: 0,
+	};
+	parser.skip_misc()?;
+	let root = parser.element()?;
+	parser.skip_misc()?;
+	if parser.pos < parser.text.len() {
+		return Err(parser.error("content after the root element"));
+	}
+	Ok(root)
+}
+
+struct Parser<'a> {
+	text: &'a str,
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn rest(&self) -> &'a str {
+		&self.text[self.pos..]
+	}
+
+	/// Skips past the next occurrence of a string.
+	fn skip_past(&mut self, end: &str) -> Result<(), String> {
+		match self.rest().find(end) {
+			Some(index) => {
+				self.pos += index + end.len();
+				Ok(())
+			},
+			None => Err(self.error(&format!("missing `{}`", end))),
+		}
+	}
+
+	/// Skips whitespace, comments, processing instructions and doctypes.
+	fn skip_misc(&mut self) -> Result<(), String> {
+		loop {
+			let trimmed = self.rest().trim_start();
+			self.pos = self.text.len() - trimmed.len();
+			if trimmed.starts_with("<!--") {
+				self.skip_past("-->")?;
+			} else if trimmed.starts_with("<?") {
+				self.skip_past("?>")?;
+			} else if trimmed.starts_with("<!") {
+				self.skip_past(">")?;
+			} else {
+				return Ok(());
+			}
+		}
+	}
+
+	/// Parses an element, starting at its `<`.
+	fn element(&mut self) -> Result<Element, String> {
+		if !self.rest().starts_with('<') {
+			return Err(self.error("expected an element"));
+		}
+		self.pos += 1;
+		let name_len = self.rest()
+			.find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+			.unwrap_or(self.rest().len());
+		let mut element = Element {
+			name: self.rest()[..name_len].to_string(),
+			..Element::default()
+		};
+		self.pos += name_len;
+
+		// Skip the attributes, whose quoted values can contain `>`
+		let mut quote = None;
+		loop {
+			let c = match self.rest().chars().next() {
+				Some(c) => c,
+				None => return Err(self.error("unterminated start tag")),
+			};
+			self.pos += c.len_utf8();
+			match (c, quote) {
+				('"', None) | ('\'', None) => quote = Some(c),
+				(c, Some(open)) if c == open => quote = None,
+				('>', None) => break,
+				('/', None) if self.rest().starts_with('>') => {
+					self.pos += 1;
+					return Ok(element);
+				},
+				_ => {},
+			}
+		}
+
+		// Content, until the matching end tag
+		loop {
+			let next = match self.rest().find('<') {
+				Some(next) => next,
+				None => return Err(self.error(&format!("unterminated `<{}>`", element.name))),
+			};
+			element.text.push_str(&unescape(&self.rest()[..next]));
+			self.pos += next;
+
+			let rest = self.rest();
+			if let Some(end_tag) = rest.strip_prefix("</") {
+				let name = end_tag.split('>').next().unwrap_or("").trim_end();
+				if name != element.name {
+					return Err(self.error(&format!("expected `</{}>`, but found `</{}>`",
+						element.name, name)));
+				}
+				self.skip_past(">")?;
+				return Ok(element);
+			} else if rest.starts_with("<![CDATA[") {
+				let end = match rest.find("]]>") {
+					Some(end) => end,
+					None => return Err(self.error("unterminated CDATA section")),
+				};
+				element.text.push_str(&rest["<![CDATA[".len()..end]);
+				self.pos += end + "]]>".len();
+			} else if rest.starts_with("<!--") {
+				self.skip_past("-->")?;
+			} else if rest.starts_with("<?") {
+				self.skip_past("?>")?;
+			} else {
+				element.children.push(self.element()?);
+			}
+		}
+	}
+
+	fn error(&self, msg: &str) -> String {
+		let line = self.text[..self.pos].matches('\n').count() + 1;
+		format!("{} on line {}", msg, line)
+	}
+}
+
+/// Replaces the predefined and numeric character references in text.
+fn unescape(text: &str) -> String {
+	let mut result = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find('&') {
+		result.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let end = match rest.find(';') {
+			Some(end) => end,
+			None => break,
+		};
+		let reference = &rest[1..end];
+		let replacement = match reference {
+			"amp" => Some('&'),
+			"lt" => Some('<'),
+			"gt" => Some('>'),
+			"quot" => Some('"'),
+			"apos" => Some('\''),
+			_ => reference.strip_prefix("#x")
+				.map(|hex| u32::from_str_radix(hex, 16))
+				.or_else(|| reference.strip_prefix('#').map(|dec| dec.parse()))
+				.and_then(|code| code.ok())
+				.and_then(::std::char::from_u32),
+		};
+		match replacement {
+			Some(c) => {
+				result.push(c);
+				rest = &rest[end + 1..];
+			},
+			None => {
+				result.push('&');
+				rest = &rest[1..];
+			},
+		}
+	}
+	result.push_str(rest);
+	result
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_elements() {
+		let root = parse("<?xml version=\"1.0\"?>\n<!-- comment -->\n<project a=\"x>y\">\n\t<name>rjni</name>\n\t<empty/>\n\t<dep><id>1</id></dep>\n\t<dep><id>2</id></dep>\n</project>\n").unwrap();
+		assert_eq!(root.name, "project");
+		assert_eq!(root.child_text("name"), Some("rjni"));
+		assert!(root.child("empty").is_some());
+		assert_eq!(root.children("dep").filter_map(|dep| dep.child_text("id")).collect::<Vec<_>>(),
+			["1", "2"]);
+	}
+
+	#[test]
+	fn unescapes_text() {
+		let root = parse("<a>&lt;&amp;&gt; &#65;&#x42; &bogus; <![CDATA[<raw>&amp;]]></a>").unwrap();
+		assert_eq!(root.text, "<&> AB &bogus; <raw>&amp;");
+	}
+
+	#[test]
+	fn checks_end_tags() {
+		assert_eq!(parse("<a>\n<b></c>\n</a>").unwrap_err(), "expected `</b>`, but found `</c>` on line 2");
+		assert_eq!(parse("<a></a >").unwrap().name, "a");
+		assert!(parse("<a><b></b>").is_err());
+		assert!(parse("<a></a><b></b>").is_err());
+	}
+}